@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use futures_util::{SinkExt, StreamExt};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{header, HeaderValue};
+use tokio_tungstenite::tungstenite::Message;
+use crate::outgoing::Outgoing;
+use crate::remote::RemoteOptions;
+use crate::state::{now_ms, Packet, SessionInfo};
+
+/// Round trips per clock measurement; the one with the least delay counts.
+const CLOCK_SAMPLES: usize = 5;
+
+/// Port the agent's remote API listens on unless `--port` says otherwise.
+pub const DEFAULT_AGENT_PORT: u16 = 7400;
+
+/// Another wirescope instance, running as an agent, to attach to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AgentOptions {
+    /// The agent's remote API, e.g. "ws://bench-pi.local:7400".
+    pub url: String,
+    /// A token with the read scope, and the tx scope to send on its sessions.
+    pub token: Option<String>,
+    /// How often the clock offset is measured again, to follow drift.
+    pub clock_interval_s: u64,
+}
+
+impl Default for AgentOptions {
+    fn default() -> Self {
+        Self { url: String::new(), token: None, clock_interval_s: 30 }
+    }
+}
+
+impl AgentOptions {
+    /// `host:port` of the agent, which prefixes the ids of its sessions.
+    pub fn name(&self) -> String {
+        let rest = self.url.split_once("://").map_or(self.url.as_str(), |(_, rest)| rest);
+        rest.split(['/', '?']).next().unwrap_or(rest).to_string()
+    }
+}
+
+/// Remote API options from the command line when wirescope was started as
+/// an agent: `--agent [--bind ADDR] [--port N] [--token SECRET]`. The token
+/// can also come from `WIRESCOPE_AGENT_TOKEN`, keeping it out of process
+/// listings. The API listens on all interfaces with a token, unencrypted
+/// (see `RemoteOptions::bind`), and only on loopback without one unless
+/// `--bind` says otherwise, which then fails.
+/// A flag without its value, or a port that isn't a number, is an error.
+pub fn launch_options(args: impl IntoIterator<Item = String>) -> Result<Option<RemoteOptions>, String> {
+    let mut args = args.into_iter();
+    let mut agent = false;
+    let mut options = RemoteOptions { bind: String::new(), port: DEFAULT_AGENT_PORT, tokens: Vec::new() };
+    let mut secret = std::env::var("WIRESCOPE_AGENT_TOKEN").ok();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--agent" => agent = true,
+            "--bind" => options.bind = args.next().ok_or("--bind needs an address")?,
+            "--port" => {
+                let value = args.next().unwrap_or_default();
+                options.port = value.parse().map_err(|_| format!("invalid --port value {value:?}"))?;
+            }
+            "--token" => secret = Some(args.next().ok_or("--token needs a value")?),
+            _ => {}
+        }
+    }
+    options.tokens.extend(secret.filter(|s| !s.is_empty()).map(|secret| crate::remote::ApiToken {
+        name: "agent".into(),
+        secret,
+        scopes: vec![crate::remote::Scope::Read, crate::remote::Scope::Tx, crate::remote::Scope::Open],
+    }));
+    if options.bind.is_empty() {
+        options.bind = if options.tokens.is_empty() { "127.0.0.1" } else { "0.0.0.0" }.into();
+    }
+    Ok(agent.then_some(options))
+}
+
+/// The agent's clock minus ours, from the round trip with the least delay.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ClockSample {
+    pub offset_ms: f64,
+    pub rtt_ms: f64,
+}
+
+/// Something that happened on the agent.
+pub enum AgentEvent {
+    /// A packet of one of its sessions, with timestamps moved onto our
+    /// clock. TX packets we sent ourselves are left out.
+    Packet(Box<Packet>),
+    /// A session of the agent was closed by its peer.
+    Closed(String),
+    /// The clock offset was measured again.
+    Clock(ClockSample),
+    /// The agent sent events faster than we read them; this many are lost.
+    Lagged(u64),
+}
+
+type Call = (Value, oneshot::Sender<Result<Value, String>>);
+
+/// Shared between the link, its reader and the TX forwarders.
+#[derive(Default)]
+struct Shared {
+    /// Agent clock minus ours.
+    offset_ms: f64,
+    /// TX packets per agent session that echo our own sends and so are
+    /// already recorded here.
+    echoes: HashMap<String, usize>,
+}
+
+/// An attached agent; dropping it detaches.
+pub struct AgentLink {
+    pub options: AgentOptions,
+    calls: UnboundedSender<Call>,
+    shared: Arc<Mutex<Shared>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Drop for AgentLink {
+    fn drop(&mut self) {
+        self.tasks.iter().for_each(JoinHandle::abort);
+    }
+}
+
+/// Connect to the agent's remote API, measure the clock offset and stream
+/// its events into `on_event` until the link drops, then call `on_close`.
+pub async fn attach(
+    options: AgentOptions,
+    on_event: impl Fn(AgentEvent) + Send + Sync + 'static,
+    on_close: impl FnOnce() + Send + 'static,
+) -> Result<AgentLink, String> {
+    let mut request = options.url.as_str().into_client_request().map_err(|e| e.to_string())?;
+    if let Some(token) = &options.token {
+        let value = HeaderValue::from_str(&format!("Bearer {token}")).map_err(|e| e.to_string())?;
+        request.headers_mut().insert(header::AUTHORIZATION, value);
+    }
+    let (ws, _) = tokio_tungstenite::connect_async(request).await.map_err(|e| format!("{}: {e}", options.url))?;
+
+    let on_event = Arc::new(on_event);
+    let shared = Arc::new(Mutex::new(Shared::default()));
+    let (calls, calls_rx) = mpsc::unbounded_channel();
+    let reader = tokio::spawn(run(ws, calls_rx, Arc::clone(&shared), Arc::clone(&on_event), on_close));
+    let mut link = AgentLink { options, calls, shared, tasks: vec![reader] };
+
+    let sample = link.measure_clock().await?;
+    on_event(AgentEvent::Clock(sample));
+    link.call(json!({ "op": "subscribe" })).await?;
+
+    let interval = Duration::from_secs(link.options.clock_interval_s.max(1));
+    let (calls, shared) = (link.calls.clone(), Arc::clone(&link.shared));
+    link.tasks.push(tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            match measure(&calls, &shared).await {
+                Ok(sample) => on_event(AgentEvent::Clock(sample)),
+                Err(_) => break,
+            }
+        }
+    }));
+    Ok(link)
+}
+
+impl AgentLink {
+    /// Send a remote API request and wait for its result.
+    async fn call(&self, op: Value) -> Result<Value, String> {
+        call(&self.calls, op).await
+    }
+
+    pub async fn sessions(&self) -> Result<Vec<SessionInfo>, String> {
+        serde_json::from_value(self.call(json!({ "op": "sessions" })).await?).map_err(|e| e.to_string())
+    }
+
+    async fn measure_clock(&self) -> Result<ClockSample, String> {
+        measure(&self.calls, &self.shared).await
+    }
+
+    /// A writer for the agent's session `session_id`: each write is sent
+    /// with the `send` op and acknowledged with its result. The forwarder
+    /// ends once the writer is dropped.
+    pub fn session_tx(&self, session_id: &str) -> UnboundedSender<Outgoing> {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Outgoing>();
+        let (calls, shared, sid) = (self.calls.clone(), Arc::clone(&self.shared), session_id.to_string());
+        tokio::spawn(async move {
+            while let Some(out) = rx.recv().await {
+                *shared.lock().echoes.entry(sid.clone()).or_default() += 1;
+                let hex: String = out.data.iter().map(|b| format!("{b:02X}")).collect();
+                let result = call(&calls, json!({ "op": "send", "session_id": sid, "data": hex, "format": "hex" })).await;
+                if result.is_err() {
+                    if let Some(n) = shared.lock().echoes.get_mut(&sid) {
+                        *n = n.saturating_sub(1);
+                    }
+                }
+                out.done(result.map(|_| ()));
+            }
+        });
+        tx
+    }
+}
+
+async fn call(calls: &UnboundedSender<Call>, op: Value) -> Result<Value, String> {
+    let (reply, result) = oneshot::channel();
+    calls.send((op, reply)).map_err(|_| "Agent link is closed".to_string())?;
+    result.await.map_err(|_| "Agent link is closed".to_string())?
+}
+
+/// Ask for the agent's clock a few times and keep the sample with the
+/// shortest round trip, assuming the answer was sent halfway through it.
+async fn measure(calls: &UnboundedSender<Call>, shared: &Mutex<Shared>) -> Result<ClockSample, String> {
+    let mut best: Option<ClockSample> = None;
+    for _ in 0..CLOCK_SAMPLES {
+        let sent = now_ms();
+        let remote = call(calls, json!({ "op": "clock" })).await?.as_f64().ok_or("Agent sent no clock")?;
+        let received = now_ms();
+        let sample = ClockSample { offset_ms: remote - (sent + received) / 2.0, rtt_ms: received - sent };
+        if best.is_none_or(|b| sample.rtt_ms < b.rtt_ms) {
+            best = Some(sample);
+        }
+    }
+    let best = best.ok_or("No clock samples")?;
+    shared.lock().offset_ms = best.offset_ms;
+    Ok(best)
+}
+
+type Ws = tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Own the WebSocket: send calls, match replies to them by id and turn
+/// streamed events into `AgentEvent`s.
+async fn run(
+    mut ws: Ws,
+    mut calls: UnboundedReceiver<Call>,
+    shared: Arc<Mutex<Shared>>,
+    on_event: Arc<impl Fn(AgentEvent)>,
+    on_close: impl FnOnce(),
+) {
+    let mut pending: HashMap<u64, oneshot::Sender<Result<Value, String>>> = HashMap::new();
+    let mut next_id = 0u64;
+    loop {
+        tokio::select! {
+            call = calls.recv() => {
+                let Some((mut op, reply)) = call else { break };
+                next_id += 1;
+                op["id"] = next_id.into();
+                pending.insert(next_id, reply);
+                if ws.send(Message::text(op.to_string())).await.is_err() {
+                    break;
+                }
+            }
+            msg = ws.next() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    let Ok(frame) = serde_json::from_str::<Value>(text.as_str()) else { continue };
+                    if let Some(id) = frame.get("id").and_then(Value::as_u64) {
+                        if let Some(reply) = pending.remove(&id) {
+                            let result = match frame["ok"].as_bool() {
+                                Some(true) => Ok(frame["result"].clone()),
+                                _ => Err(frame["error"].as_str().unwrap_or("Agent refused the request").to_string()),
+                            };
+                            let _ = reply.send(result);
+                        }
+                        continue;
+                    }
+                    if let Some(event) = agent_event(&frame, &shared) {
+                        on_event(event);
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    on_close();
+}
+
+fn agent_event(frame: &Value, shared: &Mutex<Shared>) -> Option<AgentEvent> {
+    match frame.get("event")?.as_str()? {
+        "packet" => {
+            let mut pkt: Packet = serde_json::from_value(frame.get("payload")?.clone()).ok()?;
+            let mut shared = shared.lock();
+            if pkt.direction == "TX" {
+                if let Some(n) = shared.echoes.get_mut(&pkt.session_id).filter(|n| **n > 0) {
+                    *n -= 1;
+                    return None;
+                }
+            }
+            let offset = shared.offset_ms;
+            pkt.timestamp_ms -= offset;
+            pkt.corrected_ts_ms = pkt.corrected_ts_ms.map(|ts| ts - offset);
+            Some(AgentEvent::Packet(Box::new(pkt)))
+        }
+        "session_closed" => Some(AgentEvent::Closed(frame["payload"]["session_id"].as_str()?.to_string())),
+        "lagged" => Some(AgentEvent::Lagged(frame.get("missed")?.as_u64()?)),
+        _ => None,
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use serde::{Deserialize, Serialize};
+
+/// A metric condition checked once per interval on each watched session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Condition {
+    /// No RX for `seconds`.
+    Idle { seconds: f64 },
+    /// Mean RX rate over the last `window_s` seconds above `bytes_per_sec`.
+    RateAbove { bytes_per_sec: f64, window_s: f64 },
+    /// Mean RX rate over the last `window_s` seconds below `bytes_per_sec`.
+    RateBelow { bytes_per_sec: f64, window_s: f64 },
+    /// New bad checksums or serial line errors since the last check.
+    Errors,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub name: String,
+    /// Session the rule watches; `None` watches every connected session.
+    #[serde(default)]
+    pub session_id: Option<String>,
+    pub condition: Condition,
+    /// Also raise a system notification when the alert fires.
+    #[serde(default)]
+    pub notify: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AlertEvent {
+    pub rule: String,
+    pub session_id: String,
+    /// True when the condition started to hold, false when it stopped.
+    pub active: bool,
+    /// Idle seconds, bytes per second or new errors, by condition.
+    pub value: f64,
+    pub message: String,
+    pub timestamp_ms: f64,
+    #[serde(skip)]
+    pub notify: bool,
+}
+
+/// Counters of one session at the time of a check.
+pub struct Sample {
+    pub rx_bytes: u64,
+    pub errors: u64,
+}
+
+struct Track {
+    since_ms: f64,
+    last_rx_ms: Option<f64>,
+    rx_bytes: u64,
+    errors: u64,
+    /// `(timestamp, rx_bytes)` per check, as far back as the longest window.
+    history: VecDeque<(f64, u64)>,
+}
+
+/// Alert rules and what they have seen so far. Conditions are edge-triggered:
+/// one event when a condition starts to hold and one when it stops.
+#[derive(Default)]
+pub struct Alerts {
+    rules: Vec<AlertRule>,
+    tracks: HashMap<String, Track>,
+    /// Rule index and session of each condition that currently holds.
+    active: HashSet<(usize, String)>,
+    bad_checksums: HashMap<String, u64>,
+    /// Alerts raised per session.
+    fired: HashMap<String, u64>,
+}
+
+impl Alerts {
+    pub fn rules(&self) -> Vec<AlertRule> {
+        self.rules.clone()
+    }
+
+    pub fn set_rules(&mut self, rules: Vec<AlertRule>) -> Result<(), String> {
+        for rule in &rules {
+            let valid = match rule.condition {
+                Condition::Idle { seconds } => seconds > 0.0,
+                Condition::RateAbove { window_s, .. } | Condition::RateBelow { window_s, .. } => window_s > 0.0,
+                Condition::Errors => true,
+            };
+            if !valid {
+                return Err(format!("Alert {}: the period must be positive", rule.name));
+            }
+        }
+        self.rules = rules;
+        self.active.clear();
+        Ok(())
+    }
+
+    /// Count an RX packet that failed its checksum.
+    pub fn count_bad_checksum(&mut self, session_id: &str) {
+        *self.bad_checksums.entry(session_id.to_string()).or_default() += 1;
+    }
+
+    pub fn bad_checksums(&self, session_id: &str) -> u64 {
+        self.bad_checksums.get(session_id).copied().unwrap_or(0)
+    }
+
+    pub fn fired(&self, session_id: &str) -> u64 {
+        self.fired.get(session_id).copied().unwrap_or(0)
+    }
+
+    /// Evaluate every rule against the connected sessions' counters. Sessions
+    /// missing from `samples` are forgotten, so a reconnect starts afresh.
+    pub fn check(&mut self, now_ms: f64, samples: Vec<(String, Sample)>) -> Vec<AlertEvent> {
+        self.tracks.retain(|sid, _| samples.iter().any(|(s, _)| s == sid));
+        self.active.retain(|(_, sid)| self.tracks.contains_key(sid));
+        self.fired.retain(|sid, _| self.tracks.contains_key(sid));
+        let longest_ms = self.rules.iter()
+            .filter_map(|r| match r.condition {
+                Condition::RateAbove { window_s, .. } | Condition::RateBelow { window_s, .. } => Some(window_s * 1000.0),
+                _ => None,
+            })
+            .fold(0.0, f64::max);
+
+        let mut events = Vec::new();
+        for (sid, sample) in samples {
+            let track = self.tracks.entry(sid.clone()).or_insert_with(|| Track {
+                since_ms: now_ms,
+                last_rx_ms: None,
+                rx_bytes: sample.rx_bytes,
+                errors: sample.errors,
+                history: VecDeque::new(),
+            });
+            if sample.rx_bytes != track.rx_bytes {
+                track.last_rx_ms = Some(now_ms);
+            }
+            let new_errors = sample.errors.saturating_sub(track.errors);
+            (track.rx_bytes, track.errors) = (sample.rx_bytes, sample.errors);
+            track.history.push_back((now_ms, sample.rx_bytes));
+            while track.history.front().is_some_and(|&(t, _)| now_ms - t > longest_ms) {
+                track.history.pop_front();
+            }
+
+            for (i, rule) in self.rules.iter().enumerate() {
+                if rule.session_id.as_ref().is_some_and(|s| *s != sid) {
+                    continue;
+                }
+                let Some((holds, value)) = reading(&rule.condition, track, now_ms, new_errors) else {
+                    continue;
+                };
+                let key = (i, sid.clone());
+                if holds == self.active.contains(&key) {
+                    continue;
+                }
+                if holds {
+                    self.active.insert(key);
+                    *self.fired.entry(sid.clone()).or_default() += 1;
+                } else {
+                    self.active.remove(&key);
+                }
+                events.push(AlertEvent {
+                    rule: rule.name.clone(),
+                    session_id: sid.clone(),
+                    active: holds,
+                    value,
+                    message: describe(&rule.condition, holds, value),
+                    timestamp_ms: now_ms,
+                    notify: rule.notify,
+                });
+            }
+        }
+        events
+    }
+}
+
+/// Whether `condition` holds, and the value it was judged on. `None` while a
+/// rate window has not filled yet.
+fn reading(condition: &Condition, track: &Track, now_ms: f64, new_errors: u64) -> Option<(bool, f64)> {
+    let rate = |window_s: f64| {
+        if now_ms - track.since_ms < window_s * 1000.0 {
+            return None;
+        }
+        let &(t, bytes) = track.history.iter().find(|(t, _)| now_ms - t <= window_s * 1000.0)?;
+        (now_ms > t).then(|| (track.rx_bytes - bytes) as f64 * 1000.0 / (now_ms - t))
+    };
+    match *condition {
+        Condition::Idle { seconds } => {
+            let idle_s = (now_ms - track.last_rx_ms.unwrap_or(track.since_ms)) / 1000.0;
+            Some((idle_s >= seconds, idle_s))
+        }
+        Condition::RateAbove { bytes_per_sec, window_s } => rate(window_s).map(|r| (r > bytes_per_sec, r)),
+        Condition::RateBelow { bytes_per_sec, window_s } => rate(window_s).map(|r| (r < bytes_per_sec, r)),
+        Condition::Errors => Some((new_errors > 0, new_errors as f64)),
+    }
+}
+
+fn describe(condition: &Condition, holds: bool, value: f64) -> String {
+    match (condition, holds) {
+        (Condition::Idle { .. }, true) => format!("No data for {value:.0} s"),
+        (Condition::Idle { .. }, false) => "Data resumed".into(),
+        (Condition::RateAbove { bytes_per_sec, .. }, true) => format!("RX at {value:.0} B/s, above {bytes_per_sec} B/s"),
+        (Condition::RateBelow { bytes_per_sec, .. }, true) => format!("RX at {value:.0} B/s, below {bytes_per_sec} B/s"),
+        (Condition::RateAbove { .. } | Condition::RateBelow { .. }, false) => format!("RX back to {value:.0} B/s"),
+        (Condition::Errors, true) => format!("{value} new errors"),
+        (Condition::Errors, false) => "No new errors".into(),
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+
+/// Rates tried by the auto-baud probe, most common first.
+pub const COMMON_RATES: [u32; 8] = [115200, 9600, 57600, 38400, 19200, 230400, 460800, 921600];
+
+/// What to do once a serial session has been receiving garbage for a while.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MismatchAction {
+    /// Emit a "baud_mismatch" event suggesting another rate.
+    #[default]
+    Hint,
+    /// Probe the common rates and switch to the one that reads cleanly.
+    AutoBaud,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BaudWatchConfig {
+    pub enabled: bool,
+    pub window_ms: f64,
+    /// Windows with fewer bytes than this are not judged.
+    pub min_bytes: usize,
+    /// Fraction of framing-error bytes that makes a window bad.
+    pub threshold: f64,
+    /// Consecutive bad windows before acting.
+    pub windows: u32,
+    pub action: MismatchAction,
+}
+
+impl Default for BaudWatchConfig {
+    fn default() -> Self {
+        Self { enabled: false, window_ms: 1000.0, min_bytes: 32, threshold: 0.3, windows: 2, action: MismatchAction::Hint }
+    }
+}
+
+/// Bytes that a line at the wrong rate typically yields. Framing errors are
+/// read as NUL, and mis-sampled ASCII lands in the high half or the control
+/// range. Geared towards text consoles, hence opt-in.
+fn is_suspect(b: u8) -> bool {
+    b == 0 || b >= 0x80 || (b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x1B))
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sample {
+    pub bytes: usize,
+    pub suspect: usize,
+}
+
+impl Sample {
+    fn feed(&mut self, data: &[u8]) {
+        self.bytes += data.len();
+        self.suspect += data.iter().filter(|&&b| is_suspect(b)).count();
+    }
+
+    pub fn ratio(&self) -> f64 {
+        if self.bytes == 0 { 0.0 } else { self.suspect as f64 / self.bytes as f64 }
+    }
+}
+
+#[derive(Default)]
+struct Window {
+    started_ms: f64,
+    sample: Sample,
+    bad: u32,
+}
+
+#[derive(Default)]
+pub struct BaudWatch {
+    pub config: BaudWatchConfig,
+    windows: HashMap<String, Window>,
+    /// Sessions being probed; their data is sampled but not judged.
+    probing: HashMap<String, Sample>,
+    /// Sessions that already triggered and wait for the rate to change.
+    fired: HashSet<String>,
+}
+
+impl BaudWatch {
+    pub fn set_config(&mut self, config: BaudWatchConfig) {
+        self.config = config;
+        self.windows.clear();
+        self.fired.clear();
+    }
+
+    /// Account RX data. Returns the bad-byte ratio once the session has had
+    /// `windows` bad windows in a row; fires once until `reset`.
+    pub fn feed(&mut self, session_id: &str, data: &[u8], now_ms: f64) -> Option<f64> {
+        if let Some(sample) = self.probing.get_mut(session_id) {
+            sample.feed(data);
+            return None;
+        }
+        if !self.config.enabled || self.fired.contains(session_id) {
+            return None;
+        }
+        let w = self.windows.entry(session_id.to_string()).or_insert_with(|| Window { started_ms: now_ms, ..Default::default() });
+        w.sample.feed(data);
+        if now_ms - w.started_ms < self.config.window_ms {
+            return None;
+        }
+        let ratio = w.sample.ratio();
+        if w.sample.bytes >= self.config.min_bytes && ratio >= self.config.threshold {
+            w.bad += 1;
+        } else if w.sample.bytes >= self.config.min_bytes {
+            w.bad = 0;
+        }
+        w.started_ms = now_ms;
+        w.sample = Sample::default();
+        if w.bad < self.config.windows.max(1) {
+            return None;
+        }
+        self.windows.remove(session_id);
+        self.fired.insert(session_id.to_string());
+        Some(ratio)
+    }
+
+    /// Forget the session's history, e.g. after its rate changed.
+    pub fn reset(&mut self, session_id: &str) {
+        self.windows.remove(session_id);
+        self.fired.remove(session_id);
+    }
+
+    /// Returns false if the session is already being probed.
+    pub fn begin_probe(&mut self, session_id: &str) -> bool {
+        if self.probing.contains_key(session_id) {
+            return false;
+        }
+        self.probing.insert(session_id.to_string(), Sample::default());
+        true
+    }
+
+    /// What was received since the previous call; starts the next probe step.
+    pub fn take_sample(&mut self, session_id: &str) -> Sample {
+        self.probing.get_mut(session_id).map(std::mem::take).unwrap_or_default()
+    }
+
+    pub fn end_probe(&mut self, session_id: &str) {
+        self.probing.remove(session_id);
+        self.reset(session_id);
+    }
+}
+
+/// Rate to suggest when probing is not wanted: the most common one that is
+/// not the current rate.
+pub fn suggest(current: u32) -> u32 {
+    COMMON_RATES.into_iter().find(|&r| r != current).unwrap_or(115200)
+}
+
+/// Pick the probed rate that read the most clean bytes.
+pub fn best(samples: &[(u32, Sample)], threshold: f64) -> Option<u32> {
+    samples.iter()
+        .filter(|(_, s)| s.bytes > 0 && s.ratio() < threshold)
+        .max_by_key(|(_, s)| s.bytes - s.suspect)
+        .map(|(rate, _)| *rate)
+}
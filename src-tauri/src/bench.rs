@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use crate::expect::RxTap;
+use crate::histogram::Histogram;
+use crate::modem::Writer;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RoundtripSummary {
+    pub session_id: String,
+    pub payload_size: usize,
+    pub iterations: u32,
+    /// Payloads that came back before the timeout.
+    pub completed: u32,
+    pub lost: u32,
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub p50_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Printable test pattern, shifted per iteration so a late echo of the
+/// previous payload doesn't match.
+fn payload(iteration: u32, size: usize) -> Vec<u8> {
+    (0..size).map(|k| b'A' + ((iteration as usize + k) % 26) as u8).collect()
+}
+
+/// Send `iterations` payloads one at a time to an echoing peer and time how
+/// long each takes to come back.
+pub async fn roundtrip(
+    tap: &mut RxTap,
+    send: Writer<'_>,
+    session_id: &str,
+    payload_size: usize,
+    iterations: u32,
+    timeout: Duration,
+) -> Result<RoundtripSummary, String> {
+    let mut hist = Histogram::default();
+    let mut lost = 0;
+    for i in 0..iterations {
+        let data = payload(i, payload_size);
+        tap.clear();
+        let started = Instant::now();
+        send(data.clone())?;
+        match tap.expect(&[&data], Some(timeout)).await {
+            Ok(_) => hist.record_ms(started.elapsed().as_secs_f64() * 1000.0),
+            Err(e) if e.starts_with("Timed out") => lost += 1,
+            Err(e) => return Err(e),
+        }
+    }
+    let s = hist.summary(session_id);
+    Ok(RoundtripSummary {
+        session_id: session_id.to_string(),
+        payload_size,
+        iterations,
+        completed: iterations - lost,
+        lost,
+        min_ms: s.min_ms,
+        avg_ms: s.mean_ms,
+        p50_ms: s.p50_ms,
+        p99_ms: s.p99_ms,
+        max_ms: s.max_ms,
+    })
+}
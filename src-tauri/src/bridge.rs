@@ -0,0 +1,78 @@
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::watch;
+use crate::server::{Listener, ServerOptions};
+
+/// A session shared with TCP clients, like ser2net does for serial ports.
+pub struct Bridge {
+    pub listener: Listener,
+    stop: watch::Sender<bool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BridgeInfo {
+    pub session_id: String,
+    pub local_addr: String,
+    pub clients: usize,
+    pub options: ServerOptions,
+}
+
+impl Bridge {
+    pub fn new(listener: Listener) -> Self {
+        Self { listener, stop: watch::channel(false).0 }
+    }
+
+    /// Fires when the bridge is stopped, so its clients let go.
+    pub fn stopped(&self) -> watch::Receiver<bool> {
+        self.stop.subscribe()
+    }
+
+    pub fn info(&self, session_id: &str) -> BridgeInfo {
+        BridgeInfo {
+            session_id: session_id.to_string(),
+            local_addr: self.listener.local_addr.to_string(),
+            clients: self.listener.clients(),
+            options: self.listener.options.clone(),
+        }
+    }
+}
+
+impl Drop for Bridge {
+    /// Stops accepting and disconnects the clients.
+    fn drop(&mut self) {
+        let _ = self.stop.send(true);
+    }
+}
+
+/// Relay between a client and the session until the client disconnects,
+/// the session's data ends or the bridge stops: what the client sends goes
+/// to `on_data`, what arrives on `from_session` goes to the client.
+pub async fn serve_client(
+    stream: TcpStream,
+    mut from_session: UnboundedReceiver<Vec<u8>>,
+    mut stopped: watch::Receiver<bool>,
+    on_data: impl Fn(Vec<u8>),
+) {
+    let _ = stream.set_nodelay(true);
+    let (mut reader, mut writer) = stream.into_split();
+    let mut buf = vec![0u8; 4096];
+    loop {
+        tokio::select! {
+            read = reader.read(&mut buf) => match read {
+                Ok(0) | Err(_) => break,
+                Ok(n) => on_data(buf[..n].to_vec()),
+            },
+            data = from_session.recv() => match data {
+                Some(data) => {
+                    if writer.write_all(&data).await.is_err() {
+                        break;
+                    }
+                }
+                None => break,
+            },
+            _ = stopped.changed() => break,
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use serde::Serialize;
+use crate::checksum;
+use crate::decoder::Protocol;
+
+/// What this build supports on the running platform.
+#[derive(Debug, Clone, Serialize)]
+pub struct Capabilities {
+    pub version: &'static str,
+    /// `std::env::consts::OS`, e.g. "linux", "macos", "windows".
+    pub platform: &'static str,
+    pub transports: Vec<&'static str>,
+    pub decoders: Vec<Protocol>,
+    pub checksums: Vec<String>,
+    /// Kinds of RX pipeline stages.
+    pub rx_stages: Vec<&'static str>,
+    pub import_formats: Vec<&'static str>,
+    /// `None` when only OS resources limit the number of open sessions.
+    pub max_connections: Option<usize>,
+    pub tls: bool,
+    /// Serial parity/framing/overrun counters.
+    pub serial_line_errors: bool,
+    pub socketcan: bool,
+    pub ble: bool,
+}
+
+pub fn detect() -> Capabilities {
+    let mut transports = vec!["serial", "tcp", "tls", "ws", "udp", "udp_multicast", "tcp_server", "ntrip", "virtual_tcp", "telnet", "ssh", "mqtt", "agent", "rfc2217", "bridge"];
+    if cfg!(unix) {
+        transports.push("virtual_pty");
+        transports.push("unix_socket");
+    }
+    if cfg!(target_os = "linux") {
+        transports.push("serial_sniff");
+    }
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        platform: std::env::consts::OS,
+        transports,
+        decoders: vec![Protocol::ModbusRtu, Protocol::Nmea, Protocol::Mavlink, Protocol::OneWire, Protocol::Dali],
+        checksums: checksum::compute_all(&[]).into_iter().map(|c| c.algorithm).collect(),
+        rx_stages: vec!["strip_ansi", "cobs_decode", "verify_crc", "protobuf", "replace"],
+        import_formats: vec!["pcap", "pcapng", "text"],
+        max_connections: None,
+        tls: true,
+        serial_line_errors: cfg!(target_os = "linux"),
+        socketcan: false,
+        ble: false,
+    }
+}
@@ -0,0 +1,202 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::expect::RxTap;
+use crate::modem::Writer;
+use crate::payload;
+use crate::vars::{Capturer, VarCapture, Vars};
+
+/// One expect/send pair. An empty `expect` sends right away.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatStep {
+    pub expect: String,
+    /// Empty sends nothing; otherwise followed by a carriage return unless
+    /// it ends in `\c`. `\d` waits a second and `\p` a tenth of one.
+    /// `{{name}}` is replaced with the session variable.
+    pub send: String,
+    /// Overrides the script's timeout for this step.
+    pub timeout_ms: Option<u64>,
+    /// Set a session variable from what arrived up to `expect`.
+    pub capture: Option<VarCapture>,
+}
+
+/// A chat script in the style of pppd's `chat`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatScript {
+    pub steps: Vec<ChatStep>,
+    /// Responses that end the script as failed whenever they arrive.
+    pub abort: Vec<String>,
+    pub timeout_ms: u64,
+}
+
+impl Default for ChatScript {
+    fn default() -> Self {
+        Self {
+            steps: Vec::new(),
+            abort: ["BUSY", "NO CARRIER", "NO DIALTONE", "NO ANSWER", "ERROR"].map(String::from).to_vec(),
+            timeout_ms: 45_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatResult {
+    /// Every step completed.
+    pub ok: bool,
+    /// Step that failed, or the number of steps on success.
+    pub step: usize,
+    /// The abort string that matched, "timeout" or the I/O error.
+    pub reason: Option<String>,
+    /// The `CONNECT` line, e.g. "CONNECT 115200".
+    pub connect: Option<String>,
+    pub transcript: String,
+    /// Variables set by the steps' captures.
+    pub captured: Vars,
+}
+
+enum Part {
+    Bytes(Vec<u8>),
+    Pause(Duration),
+}
+
+pub fn unescape(s: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut b = [0; 4];
+            out.extend_from_slice(c.encode_utf8(&mut b).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('r') => out.push(b'\r'),
+            Some('n') => out.push(b'\n'),
+            Some('t') => out.push(b'\t'),
+            Some('s') => out.push(b' '),
+            Some(other) => out.extend_from_slice(other.to_string().as_bytes()),
+            None => out.push(b'\\'),
+        }
+    }
+    out
+}
+
+fn send_parts(s: &str) -> Vec<Part> {
+    let (body, cr) = match s.strip_suffix("\\c") {
+        Some(body) => (body, false),
+        None => (s, true),
+    };
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut rest = body;
+    while let Some(pos) = rest.find('\\') {
+        let pause = match rest[pos + 1..].chars().next() {
+            Some('d') => Some(Duration::from_secs(1)),
+            Some('p') => Some(Duration::from_millis(100)),
+            _ => None,
+        };
+        match pause {
+            Some(d) => {
+                text.push_str(&rest[..pos]);
+                parts.push(Part::Bytes(unescape(&std::mem::take(&mut text))));
+                parts.push(Part::Pause(d));
+                rest = &rest[pos + 2..];
+            }
+            None => {
+                // Keep the escape and the character after it for unescape
+                let end = pos + 1 + rest[pos + 1..].chars().next().map_or(0, char::len_utf8);
+                text.push_str(&rest[..end]);
+                rest = &rest[end..];
+            }
+        }
+    }
+    text.push_str(rest);
+    let mut last = unescape(&text);
+    if cr {
+        last.push(b'\r');
+    }
+    parts.push(Part::Bytes(last));
+    parts
+}
+
+/// Run `script` against the session behind `tap`/`send`, with the session's
+/// `vars` for the sends.
+pub async fn run(tap: &mut RxTap, send: Writer<'_>, script: &ChatScript, vars: &Vars) -> ChatResult {
+    let aborts: Vec<Vec<u8>> = script.abort.iter().map(|a| unescape(a)).collect();
+    let mut transcript = Vec::new();
+    let mut connect = None;
+    let mut vars = vars.clone();
+    let mut captured = Vars::new();
+    let fail = |step: usize, reason: String, transcript: &[u8], connect: Option<String>, captured: Vars| ChatResult {
+        ok: false,
+        step,
+        reason: Some(reason),
+        connect,
+        transcript: String::from_utf8_lossy(transcript).into_owned(),
+        captured,
+    };
+
+    tap.clear();
+    for (i, step) in script.steps.iter().enumerate() {
+        if !step.expect.is_empty() {
+            let wanted = unescape(&step.expect);
+            let mut needles: Vec<&[u8]> = vec![&wanted];
+            needles.extend(aborts.iter().map(Vec::as_slice));
+            let timeout = Duration::from_millis(step.timeout_ms.unwrap_or(script.timeout_ms));
+            match tap.expect(&needles, Some(timeout)).await {
+                Ok((0, data)) => {
+                    transcript.extend_from_slice(&data);
+                    if let Some(capture) = &step.capture {
+                        let capturer = match Capturer::new(capture.clone()) {
+                            Ok(capturer) => capturer,
+                            Err(e) => return fail(i, e, &transcript, connect, captured),
+                        };
+                        if let Some(value) = capturer.extract(&data) {
+                            vars.insert(capture.name.clone(), value.clone());
+                            captured.insert(capture.name.clone(), value);
+                        }
+                    }
+                    if wanted.starts_with(b"CONNECT") {
+                        // The rest of the line carries the link rate
+                        let tail = tap.expect(&[b"\r"], Some(Duration::from_millis(500))).await.map(|(_, t)| t).unwrap_or_default();
+                        transcript.extend_from_slice(&tail);
+                        let line = [wanted.as_slice(), &tail].concat();
+                        connect = Some(String::from_utf8_lossy(&line).trim().to_string());
+                    }
+                }
+                Ok((idx, data)) => {
+                    transcript.extend_from_slice(&data);
+                    return fail(i, script.abort[idx - 1].clone(), &transcript, connect, captured);
+                }
+                Err(e) if e.starts_with("Timed out") => return fail(i, "timeout".into(), &transcript, connect, captured),
+                Err(e) => return fail(i, e, &transcript, connect, captured),
+            }
+        }
+        if step.send.is_empty() {
+            continue;
+        }
+        let line = match payload::expand(&step.send, &vars) {
+            Ok(line) => line,
+            Err(e) => return fail(i, e.to_string(), &transcript, connect, captured),
+        };
+        for part in send_parts(&line) {
+            match part {
+                Part::Bytes(bytes) if bytes.is_empty() => {}
+                Part::Bytes(bytes) => {
+                    if let Err(e) = send(bytes) {
+                        return fail(i, e, &transcript, connect, captured);
+                    }
+                }
+                Part::Pause(d) => tokio::time::sleep(d).await,
+            }
+        }
+    }
+    ChatResult {
+        ok: true,
+        step: script.steps.len(),
+        reason: None,
+        connect,
+        transcript: String::from_utf8_lossy(&transcript).into_owned(),
+        captured,
+    }
+}
@@ -0,0 +1,76 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Tags packets whose text matches `pattern`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassRule {
+    pub pattern: String,
+    /// e.g. "error", "warn", "info". The first matching rule with a severity wins.
+    #[serde(default)]
+    pub severity: Option<String>,
+    /// e.g. "boot", "user". Every matching rule contributes its category.
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub case_insensitive: bool,
+    /// Times to play the alert sound when an RX packet matches; 0 is silent.
+    #[serde(default)]
+    pub sound: u32,
+}
+
+/// Compiled rule set, evaluated once per packet as it is created.
+#[derive(Default)]
+pub struct Classifier {
+    rules: Vec<(ClassRule, Regex)>,
+}
+
+impl Classifier {
+    pub fn new(rules: Vec<ClassRule>) -> Result<Self, String> {
+        let rules = rules
+            .into_iter()
+            .enumerate()
+            .map(|(i, rule)| {
+                let re = RegexBuilder::new(&rule.pattern)
+                    .case_insensitive(rule.case_insensitive)
+                    .build()
+                    .map_err(|e| format!("Rule {}: {e}", i + 1))?;
+                Ok((rule, re))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn rules(&self) -> Vec<ClassRule> {
+        self.rules.iter().map(|(r, _)| r.clone()).collect()
+    }
+
+    /// Severity and categories for a packet payload, matched as lossy UTF-8.
+    pub fn classify(&self, bytes: &[u8]) -> (Option<String>, Vec<String>) {
+        let (severity, tags, _) = self.classify_rx(bytes);
+        (severity, tags)
+    }
+
+    /// Like `classify`, plus the most sound repeats of any matching rule.
+    pub fn classify_rx(&self, bytes: &[u8]) -> (Option<String>, Vec<String>, u32) {
+        if self.rules.is_empty() {
+            return (None, Vec::new(), 0);
+        }
+        let text = String::from_utf8_lossy(bytes);
+        let mut severity = None;
+        let mut tags: Vec<String> = Vec::new();
+        let mut sound = 0;
+        for (rule, re) in &self.rules {
+            if !re.is_match(&text) {
+                continue;
+            }
+            if severity.is_none() {
+                severity = rule.severity.clone();
+            }
+            if let Some(cat) = rule.category.as_ref().filter(|c| !tags.contains(c)) {
+                tags.push(cat.clone());
+            }
+            sound = sound.max(rule.sound);
+        }
+        (severity, tags, sound)
+    }
+}
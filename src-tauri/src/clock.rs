@@ -0,0 +1,158 @@
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use time::format_description::BorrowedFormatItem;
+use time::macros::format_description;
+use time::{OffsetDateTime, UtcOffset};
+use crate::state::now_ms;
+
+/// Seconds between the NTP epoch (1900) and the Unix epoch (1970).
+const NTP_UNIX_DELTA: f64 = 2_208_988_800.0;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClockInfo {
+    pub ntp_server: Option<String>,
+    /// Host clock correction in ms (true time = host time + offset).
+    pub ntp_offset_ms: Option<f64>,
+    pub round_trip_ms: Option<f64>,
+    pub synced_at_ms: Option<f64>,
+}
+
+/// Query an SNTP server once and return `(offset_ms, round_trip_ms)`.
+pub fn query_ntp(server: &str) -> Result<(f64, f64), String> {
+    let addr = crate::dns::server_addr(server, 123)?;
+    let bind = if addr.starts_with('[') { "[::]:0" } else { "0.0.0.0:0" };
+    let sock = UdpSocket::bind(bind).map_err(|e| e.to_string())?;
+    sock.set_read_timeout(Some(Duration::from_secs(3))).map_err(|e| e.to_string())?;
+
+    let mut req = [0u8; 48];
+    req[0] = 0x23; // LI = 0, VN = 4, Mode = 3 (client)
+    let t0 = now_ms();
+    sock.send_to(&req, &addr).map_err(|e| e.to_string())?;
+
+    let mut resp = [0u8; 48];
+    let n = sock.recv(&mut resp).map_err(|e| e.to_string())?;
+    let t3 = now_ms();
+    if n < 48 || resp[0] & 0x07 != 4 {
+        return Err("Invalid NTP response".into());
+    }
+
+    let t1 = ntp_ts_ms(&resp[32..40]);
+    let t2 = ntp_ts_ms(&resp[40..48]);
+    let offset = ((t1 - t0) + (t2 - t3)) / 2.0;
+    let round_trip = (t3 - t0) - (t2 - t1);
+    Ok((offset, round_trip))
+}
+
+/// Convert a 64-bit NTP timestamp to Unix milliseconds.
+fn ntp_ts_ms(b: &[u8]) -> f64 {
+    let secs = u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64;
+    let frac = u32::from_be_bytes([b[4], b[5], b[6], b[7]]) as f64 / 4_294_967_296.0;
+    (secs - NTP_UNIX_DELTA + frac) * 1000.0
+}
+
+/// How timestamps are read and written in logs and packet events.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeSource {
+    /// RFC 3339 in the local offset resolved at startup.
+    #[default]
+    Local,
+    /// RFC 3339 in UTC.
+    Utc,
+    /// Seconds since startup, counted on a monotonic clock so that steps of
+    /// the wall clock don't show.
+    Monotonic,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeConfig {
+    pub source: TimeSource,
+    /// Local UTC offset in seconds, resolved once at startup.
+    pub local_offset_s: i32,
+    /// False when the offset could not be determined; local time is then UTC.
+    pub local_offset_known: bool,
+}
+
+struct Startup {
+    instant: Instant,
+    wall_ms: f64,
+    offset: Option<UtcOffset>,
+}
+
+static STARTUP: OnceLock<Startup> = OnceLock::new();
+static SOURCE: AtomicU8 = AtomicU8::new(0);
+
+const RFC3339_MS: &[BorrowedFormatItem<'static>] = format_description!(
+    "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3][offset_hour sign:mandatory]:[offset_minute]"
+);
+
+fn startup() -> &'static Startup {
+    STARTUP.get_or_init(|| Startup {
+        instant: Instant::now(),
+        wall_ms: wall_ms(),
+        offset: UtcOffset::current_local_offset().ok(),
+    })
+}
+
+/// Resolve the local offset. Call before any other thread starts: on Unix the
+/// offset can only be read safely while the process is single-threaded.
+pub fn init() {
+    startup();
+}
+
+fn wall_ms() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+        * 1000.0
+}
+
+/// Current Unix time in ms; in monotonic mode, startup time plus elapsed time.
+pub fn current_ms() -> f64 {
+    match source() {
+        TimeSource::Monotonic => {
+            let start = startup();
+            start.wall_ms + start.instant.elapsed().as_secs_f64() * 1000.0
+        }
+        _ => wall_ms(),
+    }
+}
+
+pub fn source() -> TimeSource {
+    match SOURCE.load(Ordering::Relaxed) {
+        1 => TimeSource::Utc,
+        2 => TimeSource::Monotonic,
+        _ => TimeSource::Local,
+    }
+}
+
+pub fn set_source(source: TimeSource) {
+    SOURCE.store(source as u8, Ordering::Relaxed);
+}
+
+pub fn config() -> TimeConfig {
+    let offset = startup().offset;
+    TimeConfig {
+        source: source(),
+        local_offset_s: offset.map_or(0, UtcOffset::whole_seconds),
+        local_offset_known: offset.is_some(),
+    }
+}
+
+/// `ms` (Unix time) as text for the current time source.
+pub fn format_ts(ms: f64) -> String {
+    let start = startup();
+    let offset = match source() {
+        TimeSource::Local => start.offset.unwrap_or(UtcOffset::UTC),
+        TimeSource::Utc => UtcOffset::UTC,
+        TimeSource::Monotonic => return format!("+{:.3}", (ms - start.wall_ms) / 1000.0),
+    };
+    OffsetDateTime::from_unix_timestamp_nanos((ms * 1e6) as i128)
+        .ok()
+        .and_then(|t| t.to_offset(offset).format(RFC3339_MS).ok())
+        .unwrap_or_else(|| format!("{ms:.3}"))
+}
@@ -1,12 +1,78 @@
-use tauri::{AppHandle, Emitter, State};
+use tauri::{AppHandle, Emitter, Manager, State};
 use tauri_plugin_dialog::DialogExt;
+use tauri_plugin_notification::NotificationExt;
 use tauri_plugin_updater::UpdaterExt;
-use crate::state::{SharedState, SplitterConfig, TimingStats, SessionInfo, now_ms};
+use crate::state::{AppState, SharedState, SplitterConfig, TimingStats, SessionInfo, SessionMeta, now_ms};
+use crate::agent::{self, AgentEvent, AgentOptions};
+use crate::alerts::{AlertRule, Sample};
+use crate::bench::{self, RoundtripSummary};
+use crate::capabilities::{self, Capabilities};
+use crate::baud::{self, BaudWatchConfig, MismatchAction};
+use crate::bridge::{self, Bridge, BridgeInfo};
+use crate::chat::{self, ChatResult, ChatScript};
+use crate::classify::{ClassRule, Classifier};
 use crate::checksum::{self, ChecksumResult};
+use crate::clock::{self, ClockInfo, TimeConfig, TimeSource};
+use crate::compare::{CompareOptions, CompareStatus, Comparator};
+use crate::rack::{self, RackBoard, RackBoardStatus, RackMember};
+use crate::session_log::{self, LogMode, SessionLog};
+use crate::expect::RxTap;
+use crate::datagram::{DatagramCounter, DatagramStats, SeqRule};
+use crate::decoder::{MessageCounter, Protocol, ProtocolStats};
+use crate::detect::{self, Detection};
+use crate::diagnostics::{self, Finding};
+use crate::device::{self, DeviceScript, DeviceTransport};
+use crate::entropy::{self, ByteDistribution};
+use crate::eol::TxAppend;
+use crate::filter::{CaptureFilter, CaptureFilterInfo};
+use crate::fixture::{self, Fixture, FixtureReport, Recorder};
+use crate::fuzz::{self, FuzzOptions};
+use crate::histogram::{self, HistogramSummary};
+use crate::identify::{self, IdentifyProbe};
+use crate::journal::{self, Resync};
+use crate::history::{HistoryEntry, HistoryQuery, SessionHistory};
+use crate::import;
+use crate::library::{self, PayloadLibrary, SavedPayload};
+use crate::manifest::{self, ManifestReport};
+use crate::mdns::{self, Service};
+use crate::mqtt::{self, MqttClient, MqttOptions};
+use crate::mirror::{self, MirrorDirection, MirrorInfo, MirrorTarget};
+use crate::notify::NotifyConfig;
+use crate::outgoing::Outgoing;
+use crate::ntrip::{self, Mountpoint, NtripOptions};
+use crate::payload::{self, hex_to_bytes, PayloadFormat};
+use crate::pps::{Correlator, Pps, PpsOptions};
+use crate::presets::{FramingPreset, PresetStore};
+use crate::quota::{Quota, QuotaAction, QuotaEvent, QuotaState};
+use crate::profile::{self, BenchConnection, BenchConnectionResult, BenchOpenResult, BenchProfile};
+use crate::reconnect::{ReconnectPolicy, Reopen};
+use crate::rfc2217::{self, ComPortOptions, Notification};
+use crate::remote::{self, AuditEntry, RemoteOp, RemoteOptions, TokenInfo};
+use crate::segment::{Segment, SegmentConfig, Segmenter};
+use crate::sms::{self, SmsEntry, SmsMessage, SmsPdu};
+use crate::session_file::{CaptureSettings, RawCapture, SessionFile};
+use crate::sniff::{self, Direction};
+use crate::server::{self, Admission, ServerOptions};
+use crate::serial_port::{Flow, FlowChange, LineErrors, SerialControl, SerialSettings, WaitForPort};
 use crate::splitter::Splitter;
-use crate::{serial_port, socket};
+use crate::summary::{self, CloseReason, SessionSummary};
+use crate::sweep::{self, SweepAttempt, SweepPlan, SweepReport};
+use crate::sys_events::{self, SysEvent};
+use crate::socket::{ConnectError, ErrorCategory, OpenProgress, SocketMode, SocketOpenArgs, SocketProto, SocketOptionsReport, UdpOptions};
+use crate::sound;
+use crate::ssh::{self, SshOptions};
+use crate::telnet;
+use crate::transaction::{PairingConfig, TransactionStats};
+use crate::transform::{Pipeline, Stage};
+use crate::timeline::{self, TimeBase, TimelineFormat};
+use crate::tofu::{TofuPin, TofuStore};
+use crate::vars::{VarCapture, Vars};
+use crate::ws::FrameKind;
+use crate::{dns, modem, serial_port, socket, tls};
 use crate::PendingUpdate;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
 
 #[tauri::command]
 pub fn list_serial_ports() -> Vec<String> {
@@ -19,256 +85,3896 @@ pub async fn connect_serial(
     state: State<'_, SharedState>,
     port: String,
     baud: u32,
+    flow: Option<Flow>,
+    wait: Option<WaitForPort>,
+    settings: Option<SerialSettings>,
 ) -> Result<SessionInfo, String> {
-    let state_arc = Arc::clone(&state);
+    let mut settings = settings.unwrap_or_default();
+    if let Some(flow) = flow {
+        settings.flow = flow;
+    }
+    match wait {
+        Some(wait) => wait_and_open(&app, &state, port, baud, settings, wait).await,
+        None => open_serial_session(&app, &state, port.clone(), baud, settings, port),
+    }
+}
+
+/// Progress of a connect waiting for its port to appear.
+#[derive(serde::Serialize, Clone)]
+pub struct PortWaitEvent {
+    /// Requested port name.
+    pub port: String,
+    pub attempt: u32,
+    pub elapsed_ms: f64,
+    /// "waiting", "opened" or "timeout".
+    pub status: &'static str,
+    /// Why the last open failed, when the port was present.
+    pub error: Option<String>,
+}
+
+/// Poll until the port enumerates and opens, so a late USB CDC port is
+/// caught from its first bytes.
+async fn wait_and_open(
+    app: &AppHandle,
+    state: &SharedState,
+    port: String,
+    baud: u32,
+    settings: SerialSettings,
+    wait: WaitForPort,
+) -> Result<SessionInfo, String> {
+    let started = std::time::Instant::now();
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        // A freshly enumerated port may still refuse to open until udev has set it up
+        let error = match serial_port::find_port(&port, &wait) {
+            Some(name) => match open_serial_session(app, state, name.clone(), baud, settings, name) {
+                Ok(session) => {
+                    journal::emit(app, "port_wait", PortWaitEvent {
+                        port: port.clone(),
+                        attempt,
+                        elapsed_ms: started.elapsed().as_secs_f64() * 1000.0,
+                        status: "opened",
+                        error: None,
+                    });
+                    return Ok(session);
+                }
+                Err(e) => Some(e),
+            },
+            None => None,
+        };
+        let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+        let timed_out = elapsed_ms >= wait.timeout_ms as f64;
+        let status = if timed_out { "timeout" } else { "waiting" };
+        sys_events::emit(app, "port_wait", format!("{port}\n{status}\n{error:?}"), PortWaitEvent {
+            port: port.clone(),
+            attempt,
+            elapsed_ms,
+            status,
+            error: error.clone(),
+        });
+        if timed_out {
+            return Err(error.unwrap_or_else(|| format!("{port} did not appear within {} ms", wait.timeout_ms)));
+        }
+        tokio::time::sleep(Duration::from_millis(wait.interval_ms.max(10))).await;
+    }
+}
+
+/// Emitted when the peer pauses or resumes our transmission.
+#[derive(serde::Serialize, Clone)]
+pub struct FlowControlEvent {
+    pub session_id: String,
+    #[serde(flatten)]
+    pub change: FlowChange,
+}
+
+/// Open a serial port as a session named `name`, keyed by the port name.
+fn open_serial_session(
+    app: &AppHandle,
+    state: &SharedState,
+    port: String,
+    baud: u32,
+    settings: SerialSettings,
+    name: String,
+) -> Result<SessionInfo, String> {
+    let on_data = rx_handler(app.clone(), Arc::clone(state), port.clone());
+    let (app2, state2, sid) = (app.clone(), Arc::clone(state), port.clone());
+    let on_flow = move |change: FlowChange| {
+        let event = match change.paused_ms {
+            Some(ms) => SysEvent::new(&sid, "flow", "resumed").with("paused_ms", ms),
+            None => SysEvent::new(&sid, "flow", "paused"),
+        };
+        sys_event(&app2, &mut state2.lock(), event.with("signal", change.signal));
+        journal::emit(&app2, "flow_control", FlowControlEvent { session_id: sid.clone(), change });
+    };
+    let (app3, state3, sid3) = (app.clone(), Arc::clone(state), port.clone());
+    let on_close = move || session_closed(&app3, &state3, &sid3);
+    let conn = serial_port::open(port.clone(), baud, settings, on_data, on_flow, on_close)?;
+
+    let session = SessionInfo {
+        id: port.clone(),
+        name,
+        kind: "serial".into(),
+        connected: true,
+        tx_bytes: 0,
+        rx_bytes: 0,
+        clock_offset_ms: 0.0,
+        line_ending: None,
+        tx_append: TxAppend::None,
+        device_time_offset_ms: None,
+        meta: SessionMeta::default(),
+        identity: None,
+    };
+    let supported = conn.control.line_errors().supported;
+    let mut st = state.lock();
+    st.reopen.insert(port.clone(), Reopen::Serial { port: port.clone(), baud, settings, name: session.name.clone() });
+    st.connections.insert(port.clone(), conn.tx);
+    st.serial_controls.insert(port.clone(), conn.control);
+    let session = st.insert_session(session);
+    drop(st);
+    spawn_identify(app, state, &port);
+    if supported {
+        watch_line_errors(app.clone(), Arc::clone(state), port);
+    }
+    Ok(session)
+}
+
+/// Poll interval for serial line error counts.
+const LINE_ERRORS_INTERVAL_MS: u64 = 1000;
+
+#[derive(serde::Serialize, Clone)]
+pub struct LineErrorsEvent {
+    pub session_id: String,
+    #[serde(flatten)]
+    pub errors: LineErrors,
+}
+
+/// Emit "line_errors" whenever the session's counts change, until it closes.
+fn watch_line_errors(app: AppHandle, state: SharedState, session_id: String) {
+    tokio::spawn(async move {
+        let mut last = LineErrors::default();
+        loop {
+            tokio::time::sleep(Duration::from_millis(LINE_ERRORS_INTERVAL_MS)).await;
+            let Some(errors) = state.lock().serial_controls.get(&session_id).map(|c| c.line_errors()) else {
+                break;
+            };
+            if errors != last {
+                last = errors;
+                journal::emit(&app, "line_errors", LineErrorsEvent { session_id: session_id.clone(), errors });
+            }
+        }
+    });
+}
+
+/// Parity, framing, break and overrun counts since the port was opened.
+#[tauri::command]
+pub fn get_line_errors(state: State<'_, SharedState>, session_id: String) -> Result<LineErrors, String> {
+    let st = state.lock();
+    Ok(st.serial_controls.get(&session_id).ok_or("Not an open serial session")?.line_errors())
+}
+
+/// Change flow control, loopback or read timeout of an open serial session
+/// without reopening the port.
+#[tauri::command]
+pub fn set_serial_settings(
+    state: State<'_, SharedState>,
+    session_id: String,
+    settings: SerialSettings,
+) -> Result<(), String> {
+    let st = state.lock();
+    let control = st.serial_controls.get(&session_id).ok_or("Not an open serial session")?;
+    control.apply(settings)
+}
+
+#[tauri::command]
+pub fn get_serial_settings(state: State<'_, SharedState>, session_id: String) -> Result<SerialSettings, String> {
+    let st = state.lock();
+    let control = st.serial_controls.get(&session_id).ok_or("Not an open serial session")?;
+    Ok(control.settings())
+}
+
+#[tauri::command]
+pub async fn connect_tcp(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    mut args: SocketOpenArgs,
+) -> Result<SessionInfo, String> {
+    let session_id = args.session_id();
+    if let Some(tls) = args.tls.as_mut().filter(|t| t.trust_on_first_use && t.pinned_fingerprint.is_none()) {
+        tls.pinned_fingerprint = tofu_store(&app)?.get(&session_id).cloned();
+    }
+    let on_data = socket_handler(app.clone(), Arc::clone(&state), session_id.clone());
+    let (app2, state2, sid) = (app.clone(), Arc::clone(&state), session_id.clone());
+    let on_close = move || session_closed(&app2, &state2, &sid);
+    let (app3, sid) = (app.clone(), session_id.clone());
+    let on_progress = move |progress: OpenProgress| {
+        let key = format!("{sid}\n{progress:?}");
+        sys_events::emit(&app3, "connect_progress", key, ConnectProgressEvent { session_id: sid.clone(), progress });
+    };
+    let (app4, state4, sid) = (app.clone(), Arc::clone(&state), session_id.clone());
+    let on_telnet = move |event| {
+        let ev = match event {
+            telnet::Event::Option(opt) => {
+                let code = if opt.enabled { "enabled" } else { "disabled" };
+                SysEvent::new(&sid, "telnet", code).with("option", opt.name()).with("side", opt.side)
+            }
+            telnet::Event::ComPort(Notification::Setting { name, value }) => {
+                SysEvent::new(&sid, "com_port", "setting").with("name", name).with("value", value)
+            }
+            telnet::Event::ComPort(Notification::LineState(errors)) => SysEvent::new(&sid, "com_port", "line_state").with("errors", errors),
+            telnet::Event::ComPort(Notification::ModemState { cts, dsr, ri, cd }) => SysEvent::new(&sid, "com_port", "modem_state")
+                .with("cts", cts)
+                .with("dsr", dsr)
+                .with("ri", ri)
+                .with("cd", cd),
+        };
+        sys_event(&app4, &mut state4.lock(), ev);
+    };
+    let connect = socket::connect_tcp(&args, on_progress, on_data, on_telnet, on_close);
+    let result = match args.mode {
+        SocketMode::Server => {
+            // Without an accept timeout this waits for good, until `disconnect`
+            let (cancel, cancelled) = tokio::sync::oneshot::channel();
+            state.lock().pending_accepts.insert(session_id.clone(), cancel);
+            let result = tokio::select! {
+                result = connect => result,
+                cancel = cancelled => match cancel {
+                    Ok(()) => Err(ConnectError::new(ErrorCategory::Network, "Stopped waiting for a client")),
+                    Err(_) => Err(ConnectError::new(ErrorCategory::Network, "Session closed while waiting for a client")),
+                },
+            };
+            state.lock().pending_accepts.remove(&session_id);
+            result
+        }
+        SocketMode::Client => connect.await,
+    };
+    let conn = result.map_err(|e| {
+        if let Some(fp) = &e.peer_fingerprint {
+            let expected = args.tls.as_ref().and_then(|t| t.pinned_fingerprint.clone());
+            journal::emit(&app, "tls_fingerprint", TlsFingerprintEvent {
+                session_id: session_id.clone(),
+                fingerprint: fp.clone(),
+                expected,
+            });
+        }
+        let key = format!("{session_id}\n{}", e.message);
+        let sys = SysEvent::new(&session_id, "connect", "failed")
+            .with("stage", serde_json::to_value(e.category).unwrap_or_default())
+            .with("message", e.message.clone());
+        sys_events::emit(&app, "sys_event", key.clone(), sys);
+        sys_events::emit(&app, "connect_error", key, ConnectErrorEvent { session_id: session_id.clone(), error: e.clone() });
+        e.to_string()
+    })?;
+
+    let name = match args.mode {
+        SocketMode::Client => session_id.clone(),
+        SocketMode::Server => conn.peer.map_or(session_id.clone(), |peer| peer.to_string()),
+    };
+    let session = SessionInfo {
+        id: session_id.clone(),
+        name,
+        kind: if args.proto == SocketProto::Unix {
+            "unix"
+        } else if args.ws.is_some() {
+            "ws"
+        } else if args.telnet.as_ref().is_some_and(|t| t.com_port.is_some()) {
+            "rfc2217"
+        } else if args.telnet.is_some() {
+            "telnet"
+        } else if args.tls.is_some() {
+            "tls"
+        } else {
+            "tcp"
+        }
+        .into(),
+        connected: true,
+        tx_bytes: 0,
+        rx_bytes: 0,
+        clock_offset_ms: 0.0,
+        line_ending: None,
+        tx_append: TxAppend::None,
+        device_time_offset_ms: None,
+        meta: SessionMeta::default(),
+        identity: None,
+    };
+    journal::emit(&app, "socket_status", SocketStatusEvent {
+        session_id: session_id.clone(),
+        options: conn.options,
+        negotiated_alpn: conn.alpn,
+        ws_protocol: conn.ws_protocol,
+    });
+    let mut st = state.lock();
+    if let Some(control) = conn.control.filter(|_| args.telnet.as_ref().is_some_and(|t| t.com_port.is_some())) {
+        st.com_ports.insert(session_id.clone(), control);
+    }
+    st.reopen.insert(session_id.clone(), Reopen::Tcp(Box::new(args)));
+    st.connections.insert(session_id.clone(), conn.tx);
+    st.tcp_shutdown.insert(session_id.clone(), conn.shutdown);
+    st.readers.insert(session_id.clone(), conn.reader);
+    let session = st.insert_session(session);
+    drop(st);
+    spawn_identify(&app, &state, &session_id);
+    Ok(session)
+}
+
+/// Emitted when opening a socket fails, so the UI can tell certificate
+/// problems apart from network problems.
+#[derive(serde::Serialize, Clone)]
+pub struct ConnectErrorEvent {
+    pub session_id: String,
+    #[serde(flatten)]
+    pub error: ConnectError,
+}
+
+/// Listening, accepting and TLS handshake steps of a socket being opened.
+#[derive(serde::Serialize, Clone)]
+pub struct ConnectProgressEvent {
+    pub session_id: String,
+    #[serde(flatten)]
+    pub progress: OpenProgress,
+}
+
+/// An untrusted (`expected` is None) or changed (`expected` differs) certificate.
+#[derive(serde::Serialize, Clone)]
+pub struct TlsFingerprintEvent {
+    pub session_id: String,
+    pub fingerprint: String,
+    pub expected: Option<String>,
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct SocketStatusEvent {
+    pub session_id: String,
+    pub options: SocketOptionsReport,
+    pub negotiated_alpn: Option<String>,
+    pub ws_protocol: Option<String>,
+}
+
+// ── SSH ─────────────────────────────────────────────────────────────────────
+
+/// Open a shell on an SSH server as a session. An unknown or changed host
+/// key fails the connect and is emitted as "ssh_host_key", to be pinned
+/// with `tofu_accept`.
+#[tauri::command]
+pub async fn connect_ssh(app: AppHandle, state: State<'_, SharedState>, mut opts: SshOptions) -> Result<SessionInfo, String> {
+    let session_id = opts.session_id();
+    let store = tofu_store(&app)?;
+    if opts.pinned_fingerprint.is_none() {
+        opts.pinned_fingerprint = store.get(&opts.endpoint()).cloned();
+    }
+    let shell = ssh::connect(&opts).await.map_err(|e| {
+        if let Some(fp) = &e.peer_fingerprint {
+            journal::emit(&app, "ssh_host_key", TlsFingerprintEvent {
+                session_id: session_id.clone(),
+                fingerprint: fp.clone(),
+                expected: opts.pinned_fingerprint.clone(),
+            });
+        }
+        let key = format!("{session_id}\n{}", e.message);
+        let sys = SysEvent::new(&session_id, "connect", "failed")
+            .with("stage", serde_json::to_value(e.category).unwrap_or_default())
+            .with("message", e.message.clone());
+        sys_events::emit(&app, "sys_event", key.clone(), sys);
+        sys_events::emit(&app, "connect_error", key, ConnectErrorEvent { session_id: session_id.clone(), error: e.clone() });
+        e.to_string()
+    })?;
+    if opts.pinned_fingerprint.is_none() {
+        let mut store = store;
+        store.pin(opts.endpoint(), shell.fingerprint.clone())?;
+    }
+
+    let session = SessionInfo {
+        id: session_id.clone(),
+        name: session_id.clone(),
+        kind: "ssh".into(),
+        connected: true,
+        tx_bytes: 0,
+        rx_bytes: 0,
+        clock_offset_ms: 0.0,
+        line_ending: None,
+        tx_append: TxAppend::None,
+        device_time_offset_ms: None,
+        meta: SessionMeta::default(),
+        identity: None,
+    };
+    let on_data = socket_handler(app.clone(), Arc::clone(&state), session_id.clone());
+    let (app2, state2, sid) = (app.clone(), Arc::clone(&state), session_id.clone());
+    let on_close = move || session_closed(&app2, &state2, &sid);
+    // Inserted before the I/O starts, so an early close finds the session
+    let mut st = state.lock();
+    st.reopen.insert(session_id.clone(), Reopen::Ssh(Box::new(opts)));
+    let session = st.insert_session(session);
+    let conn = shell.start(move |data| on_data(data, None), on_close);
+    st.connections.insert(session_id.clone(), conn.tx);
+    st.tcp_shutdown.insert(session_id.clone(), conn.shutdown);
+    drop(st);
+    spawn_identify(&app, &state, &session_id);
+    Ok(session)
+}
+
+// ── RFC 2217 ────────────────────────────────────────────────────────────────
+
+/// Send raw telnet commands on an RFC 2217 session.
+fn com_port_send(st: &AppState, session_id: &str, commands: Vec<u8>) -> Result<(), String> {
+    let control = st.com_ports.get(session_id).ok_or("Not an open RFC 2217 session")?;
+    control.send(commands).map_err(|_| "Connection closed".to_string())
+}
+
+/// The port settings a reconnect of `session_id` applies.
+fn reopen_com_port<'a>(st: &'a mut AppState, session_id: &str) -> Option<&'a mut ComPortOptions> {
+    match st.reopen.get_mut(session_id)? {
+        Reopen::Tcp(args) => args.telnet.as_mut()?.com_port.as_mut(),
+        _ => None,
+    }
+}
+
+/// Reconfigure the serial port behind an RFC 2217 session. The server
+/// confirms each value it applied with a "com_port" "setting" event; a
+/// reconnect applies the new settings.
+#[tauri::command]
+pub fn com_port_configure(state: State<'_, SharedState>, session_id: String, options: ComPortOptions) -> Result<(), String> {
+    options.validate()?;
+    let mut st = state.lock();
+    com_port_send(&st, &session_id, options.commands())?;
+    if let Some(saved) = reopen_com_port(&mut st, &session_id) {
+        *saved = options;
+    }
+    Ok(())
+}
+
+/// Drive DTR and RTS of an RFC 2217 session's port; unset lines stay as
+/// they are.
+#[tauri::command]
+pub fn com_port_set_lines(state: State<'_, SharedState>, session_id: String, dtr: Option<bool>, rts: Option<bool>) -> Result<(), String> {
+    com_port_send(&state.lock(), &session_id, rfc2217::set_lines(dtr, rts))
+}
+
+/// Start or end a break on an RFC 2217 session's port.
+#[tauri::command]
+pub fn com_port_break(state: State<'_, SharedState>, session_id: String, on: bool) -> Result<(), String> {
+    com_port_send(&state.lock(), &session_id, rfc2217::set_break(on))
+}
+
+/// Drop the data the terminal server buffered for an RFC 2217 session.
+#[tauri::command]
+pub fn com_port_purge(state: State<'_, SharedState>, session_id: String) -> Result<(), String> {
+    com_port_send(&state.lock(), &session_id, rfc2217::purge())
+}
+
+// ── MQTT ────────────────────────────────────────────────────────────────────
+
+/// Connect to an MQTT broker as a session. Messages on subscribed topics
+/// arrive as packets tagged with their topic; plain sends publish to the
+/// options' `publish_topic`.
+#[tauri::command]
+pub async fn mqtt_connect(app: AppHandle, state: State<'_, SharedState>, opts: MqttOptions) -> Result<SessionInfo, String> {
+    let session_id = opts.session_id();
+    let (app2, state2, sid) = (app.clone(), Arc::clone(&state), session_id.clone());
+    let on_message = move |topic, data| receive(&app2, &state2, &sid, data, Framing::Message { topic });
+    let (app2, state2, sid) = (app.clone(), Arc::clone(&state), session_id.clone());
+    let on_close = move || session_closed(&app2, &state2, &sid);
+    let conn = mqtt::connect(&opts, on_message, on_close).await.map_err(|e| {
+        let key = format!("{session_id}\n{}", e.message);
+        let sys = SysEvent::new(&session_id, "connect", "failed")
+            .with("stage", serde_json::to_value(e.category).unwrap_or_default())
+            .with("message", e.message.clone());
+        sys_events::emit(&app, "sys_event", key.clone(), sys);
+        sys_events::emit(&app, "connect_error", key, ConnectErrorEvent { session_id: session_id.clone(), error: e.clone() });
+        e.to_string()
+    })?;
+
+    let session = SessionInfo {
+        id: session_id.clone(),
+        name: session_id.clone(),
+        kind: "mqtt".into(),
+        connected: true,
+        tx_bytes: 0,
+        rx_bytes: 0,
+        clock_offset_ms: 0.0,
+        line_ending: None,
+        tx_append: TxAppend::None,
+        device_time_offset_ms: None,
+        meta: SessionMeta::default(),
+        identity: None,
+    };
+    let mut st = state.lock();
+    st.connections.insert(session_id.clone(), conn.tx);
+    st.tcp_shutdown.insert(session_id.clone(), conn.shutdown);
+    st.mqtt.insert(session_id, conn.client);
+    Ok(st.insert_session(session))
+}
+
+fn mqtt_client(state: &SharedState, session_id: &str) -> Result<MqttClient, String> {
+    state.lock().mqtt.get(session_id).cloned().ok_or_else(|| format!("Not an MQTT session: {session_id}"))
+}
+
+/// Subscribe to a topic filter and return the QoS the broker granted.
+#[tauri::command]
+pub async fn mqtt_subscribe(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    session_id: String,
+    filter: String,
+    qos: Option<u8>,
+) -> Result<u8, String> {
+    let granted = mqtt_client(&state, &session_id)?.subscribe(&filter, qos.unwrap_or(0)).await?;
+    let ev = SysEvent::new(&session_id, "mqtt", "subscribed").with("filter", filter).with("qos", granted);
+    sys_event(&app, &mut state.lock(), ev);
+    Ok(granted)
+}
+
+#[tauri::command]
+pub async fn mqtt_unsubscribe(app: AppHandle, state: State<'_, SharedState>, session_id: String, filter: String) -> Result<(), String> {
+    mqtt_client(&state, &session_id)?.unsubscribe(&filter).await?;
+    sys_event(&app, &mut state.lock(), SysEvent::new(&session_id, "mqtt", "unsubscribed").with("filter", filter));
+    Ok(())
+}
+
+/// Publish hex bytes on `topic` and record them as a TX packet. Returns its
+/// id; "tx_status" follows once the message was written (QoS 0) or the
+/// broker acknowledged it (QoS 1).
+#[tauri::command]
+pub fn mqtt_tx(
+    state: State<'_, SharedState>,
+    app: AppHandle,
+    session_id: String,
+    topic: String,
+    hex: String,
+    qos: Option<u8>,
+    retain: Option<bool>,
+) -> Result<u64, String> {
+    let qos = qos.unwrap_or(0);
+    mqtt::check_topic(&topic)?;
+    mqtt::check_qos(qos)?;
+    let bytes = prepare(&state, &session_id, &hex, PayloadFormat::Hex, false, None)?;
+    let client = mqtt_client(&state, &session_id)?;
+    let publish = |out| client.publish(&topic, qos, retain.unwrap_or(false), out);
+    queue_via(&app, &mut state.lock(), &session_id, bytes, None, publish)
+}
+
+// ── Service discovery ───────────────────────────────────────────────────────
+
+/// Browse mDNS for instances of a service type such as `_telnet._tcp` for
+/// `timeout_ms` (default 2 s) and return their hosts, ports and addresses.
+#[tauri::command]
+pub async fn discover_services(service_type: String, timeout_ms: Option<u64>) -> Result<Vec<Service>, String> {
+    mdns::browse(&service_type, Duration::from_millis(timeout_ms.unwrap_or(2000))).await
+}
+
+// ── Agents ──────────────────────────────────────────────────────────────────
+
+/// The clock offset to an attached agent, emitted as "agent_clock" each time
+/// it is measured.
+#[derive(serde::Serialize, Clone)]
+pub struct AgentClockEvent {
+    pub agent: String,
+    pub offset_ms: f64,
+    pub rtt_ms: f64,
+}
+
+/// Attach to a wirescope instance started with `--agent`, e.g. on a
+/// Raspberry Pi wired to the device. Each of its sessions appears here as
+/// `agent:<host:port>/<session id>`, with its packets shifted onto this
+/// machine's clock; sending on one sends on the agent's connection.
+/// Sessions the agent opens later appear with their first packet.
+#[tauri::command]
+pub async fn agent_attach(app: AppHandle, state: State<'_, SharedState>, options: AgentOptions) -> Result<Vec<SessionInfo>, String> {
+    let agent = options.name();
+    if state.lock().agents.contains_key(&agent) {
+        return Err(format!("Already attached to {agent}"));
+    }
+    let (app2, state2, name) = (app.clone(), Arc::clone(&state), agent.clone());
+    let on_event = move |ev| agent_event(&app2, &state2, &name, ev);
+    let (app2, state2, name) = (app.clone(), Arc::clone(&state), agent.clone());
+    let on_close = move || agent_closed(&app2, &state2, &name);
+    let link = agent::attach(options, on_event, on_close).await?;
+    let remote = link.sessions().await?;
+
+    let mut st = state.lock();
+    st.agents.insert(agent.clone(), link);
+    Ok(remote.iter().filter(|s| s.connected).map(|s| agent_session(&mut st, &agent, &s.id, Some(&s.name))).collect())
+}
+
+/// Detach from an agent, disconnecting its sessions here. The agent keeps
+/// its connections open.
+#[tauri::command]
+pub fn agent_detach(app: AppHandle, state: State<'_, SharedState>, agent: String) -> Result<(), String> {
+    let mut st = state.lock();
+    st.agents.remove(&agent).ok_or_else(|| format!("Not attached to {agent}"))?;
+    for sid in agent_sessions(&st, &agent) {
+        if let Some(summary) = close_session(&mut st, &sid) {
+            closed_summary(&app, summary);
+        }
+    }
+    Ok(())
+}
+
+/// Names of the attached agents, as `host:port`.
+#[tauri::command]
+pub fn list_agents(state: State<'_, SharedState>) -> Vec<String> {
+    state.lock().agents.keys().cloned().collect()
+}
+
+/// Serve this instance's connections to attached desktops, for a launch
+/// with `--agent`. The window is hidden; the process exits if the API can't
+/// be started.
+pub fn start_agent(app: &AppHandle, options: RemoteOptions) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.hide();
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = remote_start(app.clone(), app.state::<SharedState>(), options).await {
+            agent_failed(&app, e);
+        }
+    });
+}
+
+/// Report that the agent can't run, on stderr for whoever launched it and
+/// as a system event, and exit.
+pub fn agent_failed(app: &AppHandle, message: String) {
+    eprintln!("wirescope agent: {message}");
+    sys_events::emit(app, "sys_event", format!("agent\n{message}"), SysEvent::new("", "agent", "failed").with("message", message));
+    app.exit(1);
+}
+
+/// Local sessions mirroring those of `agent`.
+fn agent_sessions(st: &AppState, agent: &str) -> Vec<String> {
+    let prefix = format!("agent:{agent}/");
+    st.sessions.keys().filter(|id| id.starts_with(&prefix)).cloned().collect()
+}
+
+/// The local session mirroring `remote_id` on `agent`, (re)connected and
+/// with a writer forwarding to the agent.
+fn agent_session(st: &mut AppState, agent: &str, remote_id: &str, name: Option<&str>) -> SessionInfo {
+    let session_id = format!("agent:{agent}/{remote_id}");
+    if let Some(tx) = st.agents.get(agent).filter(|_| !st.connections.contains_key(&session_id)).map(|l| l.session_tx(remote_id)) {
+        st.connections.insert(session_id.clone(), tx);
+    }
+    if let Some(sess) = st.sessions.get_mut(&session_id).filter(|s| s.connected) {
+        return sess.clone();
+    }
+    st.insert_session(SessionInfo {
+        id: session_id.clone(),
+        name: format!("{} @ {agent}", name.unwrap_or(remote_id)),
+        kind: "agent".into(),
+        connected: true,
+        tx_bytes: 0,
+        rx_bytes: 0,
+        clock_offset_ms: 0.0,
+        line_ending: None,
+        // The agent applies its own session's append mode
+        tx_append: TxAppend::None,
+        device_time_offset_ms: None,
+        meta: SessionMeta::default(),
+        identity: None,
+    })
+}
+
+fn agent_event(app: &AppHandle, state: &SharedState, agent: &str, ev: AgentEvent) {
+    match ev {
+        AgentEvent::Packet(pkt) => {
+            let pkt = *pkt;
+            let mut st = state.lock();
+            // Packets racing the attach are dropped; their sessions follow
+            if !st.agents.contains_key(agent) {
+                return;
+            }
+            let session_id = agent_session(&mut st, agent, &pkt.session_id, None).id;
+            if pkt.direction == "TX" {
+                record_tx_at(app, &mut st, &session_id, pkt.bytes, pkt.timestamp_ms);
+                return;
+            }
+            drop(st);
+            let framing = Framing::Remote { timestamp_ms: pkt.timestamp_ms, source: pkt.source };
+            receive(app, state, &session_id, pkt.bytes, framing);
+        }
+        AgentEvent::Closed(remote_id) => session_closed(app, state, &format!("agent:{agent}/{remote_id}")),
+        AgentEvent::Clock(sample) => {
+            journal::emit(app, "agent_clock", AgentClockEvent { agent: agent.to_string(), offset_ms: sample.offset_ms, rtt_ms: sample.rtt_ms });
+        }
+        AgentEvent::Lagged(missed) => {
+            let mut st = state.lock();
+            for sid in agent_sessions(&st, agent) {
+                sys_event(app, &mut st, SysEvent::new(&sid, "agent", "lagged").with("missed", missed));
+            }
+        }
+    }
+}
+
+/// The link to `agent` dropped: its sessions close as if their peers had.
+fn agent_closed(app: &AppHandle, state: &SharedState, agent: &str) {
+    let sessions = {
+        let mut st = state.lock();
+        st.agents.remove(agent);
+        agent_sessions(&st, agent)
+    };
+    for sid in sessions {
+        session_closed(app, state, &sid);
+    }
+}
+
+// ── Notifications ───────────────────────────────────────────────────────────
+
+#[derive(serde::Serialize, Clone)]
+pub struct SessionClosedEvent {
+    pub session_id: String,
+}
+
+/// Longest packet text shown in a notification.
+const NOTIFY_BODY_CHARS: usize = 200;
+
+/// Show a system notification; failures (e.g. permission denied) are ignored.
+fn notify(app: &AppHandle, title: &str, body: &str) {
+    let _ = app.notification().builder().title(title).body(body).show();
+}
+
+fn session_label(st: &AppState, session_id: &str) -> String {
+    st.sessions.get(session_id)
+        .map(|s| s.meta.label.clone().unwrap_or_else(|| s.name.clone()))
+        .unwrap_or_else(|| session_id.to_string())
+}
+
+/// Transport of `session_id` ended without `disconnect`: the peer closed or
+/// the device went away. Emits "session_closed" and notifies.
+fn session_closed(app: &AppHandle, state: &SharedState, session_id: &str) {
+    let mut st = state.lock();
+    let Some(sess) = st.sessions.get_mut(session_id).filter(|s| s.connected) else {
+        return; // Already disconnected on purpose
+    };
+    sess.connected = false;
+    let summary = summary::summarize(&st, session_id, CloseReason::Remote, now_ms());
+    st.bench_profiles.remove(session_id);
+    st.quotas.remove(session_id);
+    st.connections.remove(session_id);
+    st.tcp_shutdown.remove(session_id);
+    if let Some(reader) = st.readers.remove(session_id) {
+        reader.abort();
+    }
+    if let Some(cancel) = st.pending_accepts.remove(session_id) {
+        let _ = cancel.send(());
+    }
+    st.mqtt.remove(session_id);
+    st.com_ports.remove(session_id);
+    st.serial_controls.remove(session_id);
+    st.sniffers.remove(session_id);
+    sys_event(app, &mut st, SysEvent::new(session_id, "connection", "closed").with("reason", "remote"));
+    if let Some(summary) = &summary {
+        write_log(app, &mut st, session_id, |log| log.write_summary(summary));
+    }
+    journal::emit(app, "session_closed", SessionClosedEvent { session_id: session_id.to_string() });
+    if let Some(summary) = summary {
+        closed_summary(app, summary);
+    }
+    if st.notifier.on_disconnect(session_id, now_ms()) {
+        notify(app, "Disconnected", &format!("{} closed unexpectedly", session_label(&st, session_id)));
+    }
+    if let (Some(policy), Some(reopen)) = (st.auto_reconnect.get(session_id).copied(), st.reopen.get(session_id).cloned()) {
+        let task = spawn_reconnect(app, state, session_id, policy, reopen);
+        st.reconnecting.insert(session_id.to_string(), task);
+    }
+}
+
+/// Note `event` in the session's log, if it has one, and emit it as "sys_event".
+fn sys_event(app: &AppHandle, st: &mut AppState, event: SysEvent) {
+    write_log(app, st, &event.session_id.clone(), |log| log.write_note(event.timestamp_ms, &event.render()));
+    journal::emit(app, "sys_event", event);
+}
+
+/// Write to the session's log, if it has one. A log that fails to write is
+/// closed and the failure emitted, rather than the session carrying on as if
+/// it were still logged.
+fn write_log(app: &AppHandle, st: &mut AppState, session_id: &str, write: impl FnOnce(&mut SessionLog) -> std::io::Result<()>) {
+    let Some(log) = st.logs.get_mut(session_id) else { return };
+    if let Err(e) = write(log) {
+        let path = log.path.to_string_lossy().into_owned();
+        st.logs.remove(session_id);
+        sys_event(app, st, SysEvent::new(session_id, "log", "failed").with("path", path).with("message", e.to_string()));
+    }
+}
+
+/// Emit a closed session's summary and add it to the session history.
+fn closed_summary(app: &AppHandle, summary: SessionSummary) {
+    journal::emit(app, "session_summary", &summary);
+    journal::trim(app, &summary.session_id, journal::CLOSED_CAPACITY);
+    if let Ok(dir) = app.path().app_data_dir() {
+        // Rewriting the index shouldn't hold up whoever closed the session
+        std::thread::spawn(move || {
+            let _ = SessionHistory::record(&dir, summary);
+        });
+    }
+}
+
+#[tauri::command]
+pub fn set_notifications(state: State<'_, SharedState>, config: NotifyConfig) {
+    state.lock().notifier.config = config;
+}
+
+/// Play the rule alert sound, e.g. to check the volume before a test.
+#[tauri::command]
+pub fn play_alert_sound(times: Option<u32>) {
+    sound::play(times.unwrap_or(1));
+}
+
+#[tauri::command]
+pub fn get_notifications(state: State<'_, SharedState>) -> NotifyConfig {
+    state.lock().notifier.config.clone()
+}
+
+// ── Alerts ──────────────────────────────────────────────────────────────────
+
+/// How often alert rules are checked.
+const ALERT_CHECK_INTERVAL_MS: u64 = 1000;
+
+/// Check the alert rules every interval until they are cleared, emitting
+/// "alert" as conditions start and stop holding.
+fn watch_alerts(app: AppHandle, state: SharedState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_millis(ALERT_CHECK_INTERVAL_MS)).await;
+            let mut guard = state.lock();
+            let st = &mut *guard;
+            let now = now_ms();
+            let samples = st.sessions.values()
+                .filter(|s| s.connected)
+                .map(|s| {
+                    let line = st.serial_controls.get(&s.id).map_or(0, |c| {
+                        let e = c.line_errors();
+                        e.framing + e.parity + e.breaks + e.overruns + e.buffer_overruns
+                    });
+                    (s.id.clone(), Sample { rx_bytes: s.rx_bytes, errors: st.alerts.bad_checksums(&s.id) + line })
+                })
+                .collect();
+            for event in st.alerts.check(now, samples) {
+                let sys = SysEvent::new(&event.session_id, "alert", if event.active { "raised" } else { "cleared" })
+                    .at(now)
+                    .with("rule", event.rule.clone())
+                    .with("value", event.value)
+                    .with("message", event.message.clone());
+                sys_event(&app, st, sys);
+                if event.active && event.notify && st.notifier.on_alert(&event.session_id, &event.rule, now) {
+                    notify(&app, &format!("{} · {}", session_label(st, &event.session_id), event.rule), &event.message);
+                }
+                journal::emit(&app, "alert", event);
+            }
+        }
+    })
+}
+
+/// Replace the metric alert rules: inactivity, RX rate thresholds and error
+/// counts. An empty list stops checking.
+#[tauri::command]
+pub async fn set_alert_rules(app: AppHandle, state: State<'_, SharedState>, rules: Vec<AlertRule>) -> Result<(), String> {
+    let mut st = state.lock();
+    st.alerts.set_rules(rules)?;
+    if st.alerts.rules().is_empty() {
+        if let Some(watch) = st.alert_watch.take() {
+            watch.abort();
+        }
+    } else if st.alert_watch.is_none() {
+        st.alert_watch = Some(watch_alerts(app, Arc::clone(&state)));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_alert_rules(state: State<'_, SharedState>) -> Vec<AlertRule> {
+    state.lock().alerts.rules()
+}
+
+// ── TLS trust store ─────────────────────────────────────────────────────────
+
+fn tofu_store(app: &AppHandle) -> Result<TofuStore, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(TofuStore::load(&dir))
+}
+
+/// Connect once with certificate checks disabled and report the peer's fingerprint.
+#[tauri::command]
+pub async fn probe_tls_fingerprint(host: String, port: u16) -> Result<String, String> {
+    let args = SocketOpenArgs { host: host.clone(), port, ..Default::default() };
+    let stream = socket::open_stream(&args).await.map_err(|e| e.to_string())?;
+    tls::probe_fingerprint(stream, dns::bare_host(&host)).await.map_err(|e| e.to_string())
+}
+
+/// Pin a certificate fingerprint for `host:port`, replacing any previous pin.
+#[tauri::command]
+pub fn tofu_accept(app: AppHandle, host: String, port: u16, fingerprint: String) -> Result<(), String> {
+    tofu_store(&app)?.pin(dns::host_port(&host, port), fingerprint)
+}
+
+#[tauri::command]
+pub fn tofu_forget(app: AppHandle, host: String, port: u16) -> Result<(), String> {
+    tofu_store(&app)?.forget(&dns::host_port(&host, port))
+}
+
+#[tauri::command]
+pub fn tofu_list(app: AppHandle) -> Result<Vec<TofuPin>, String> {
+    Ok(tofu_store(&app)?.list())
+}
+
+/// Half-close a TCP session: send FIN once queued data is written, but keep
+/// receiving until the peer closes its side. Logged as a "half_closed"
+/// connection event, so the device's response lines up after it.
+#[tauri::command]
+pub fn socket_shutdown(app: AppHandle, state: State<'_, SharedState>, session_id: String) -> Result<(), String> {
+    let mut st = state.lock();
+    // An MQTT session's trigger disconnects from the broker instead
+    if st.mqtt.contains_key(&session_id) {
+        return Err("MQTT sessions can't be half-closed".into());
+    }
+    let trigger = st.tcp_shutdown.remove(&session_id).ok_or("Not an open TCP session")?;
+    let _ = trigger.send(());
+    sys_event(&app, &mut st, SysEvent::new(&session_id, "connection", "half_closed"));
+    Ok(())
+}
+
+/// Open a UDP session. With `local_port` the socket listens on a fixed port;
+/// with `reply_to_sender` outgoing datagrams go to whoever sent last.
+#[tauri::command]
+pub async fn connect_udp(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    host: String,
+    port: u16,
+    local_port: Option<u16>,
+    reply_to_sender: Option<bool>,
+    options: Option<UdpOptions>,
+) -> Result<SessionInfo, String> {
+    let remote = (!host.is_empty()).then_some((host.as_str(), port));
+    if remote.is_none() && local_port.is_none() {
+        return Err("UDP needs a remote host or a local port".into());
+    }
+    let options = options.unwrap_or_default();
+    if !(1..=socket::UDP_MAX_BUFFER).contains(&options.recv_buffer) {
+        return Err(format!("Receive buffer must be 1 to {} bytes", socket::UDP_MAX_BUFFER));
+    }
+    let mut counter = DatagramCounter::default();
+    counter.set_rule(options.sequence)?;
+    let session_id = profile::udp_session_id(&host, port, local_port);
+    let on_data = datagram_handler(app.clone(), Arc::clone(&state), session_id.clone());
+    let (app2, sid2) = (app.clone(), session_id.clone());
+    let on_peer = move |peer: std::net::SocketAddr| {
+        journal::emit(&app2, "udp_peer", UdpPeerEvent { session_id: sid2.clone(), peer: peer.to_string() });
+    };
+    let conn = socket::open_udp(remote, &options, local_port, reply_to_sender.unwrap_or(false), on_data, on_peer).await?;
+
+    let session = SessionInfo {
+        id: session_id.clone(),
+        name: session_id.clone(),
+        kind: "udp".into(),
+        connected: true,
+        tx_bytes: 0,
+        rx_bytes: 0,
+        clock_offset_ms: 0.0,
+        line_ending: None,
+        tx_append: TxAppend::None,
+        device_time_offset_ms: None,
+        meta: SessionMeta::default(),
+        identity: None,
+    };
+    let mut st = state.lock();
+    st.connections.insert(session_id.clone(), conn.tx);
+    st.udp_peers.insert(session_id.clone(), conn.peer);
+    st.readers.insert(session_id.clone(), conn.reader);
+    st.datagrams.insert(session_id.clone(), counter);
+    let session = st.insert_session(session);
+    drop(st);
+    spawn_identify(&app, &state, &session_id);
+    Ok(session)
+}
+
+#[tauri::command]
+pub fn get_udp_stats(state: State<'_, SharedState>, session_id: String) -> Result<DatagramStats, String> {
+    let st = state.lock();
+    Ok(st.datagrams.get(&session_id).ok_or("Not a UDP session")?.stats(&session_id))
+}
+
+/// Set or clear where datagrams carry a sequence number.
+#[tauri::command]
+pub fn set_udp_sequence(state: State<'_, SharedState>, session_id: String, rule: Option<SeqRule>) -> Result<(), String> {
+    let mut st = state.lock();
+    st.datagrams.get_mut(&session_id).ok_or("Not a UDP session")?.set_rule(rule)
+}
+
+#[tauri::command]
+pub fn reset_udp_stats(state: State<'_, SharedState>, session_id: String) -> Result<(), String> {
+    let mut st = state.lock();
+    st.datagrams.get_mut(&session_id).ok_or("Not a UDP session")?.reset();
+    Ok(())
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct UdpPeerEvent {
+    pub session_id: String,
+    pub peer: String,
+}
+
+/// Point a UDP session's transmissions at a specific peer.
+#[tauri::command]
+pub fn set_udp_peer(app: AppHandle, state: State<'_, SharedState>, session_id: String, peer: String) -> Result<(), String> {
+    let addr: std::net::SocketAddr = peer.parse().map_err(|e: std::net::AddrParseError| e.to_string())?;
+    let st = state.lock();
+    let slot = st.udp_peers.get(&session_id).ok_or("Not a UDP session")?;
+    *slot.lock() = Some(addr);
+    journal::emit(&app, "udp_peer", UdpPeerEvent { session_id, peer: addr.to_string() });
+    Ok(())
+}
+
+/// Minimum spacing of "protocol_stats" events per session.
+const STATS_EMIT_INTERVAL_MS: f64 = 1000.0;
+
+/// Fields a bus decoder (1-Wire, DALI) took out of a received packet.
+#[derive(serde::Serialize, Clone)]
+pub struct DecodedEvent {
+    pub session_id: String,
+    pub packet_id: u64,
+    pub protocol: Protocol,
+    pub message: String,
+    pub ok: bool,
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+/// How incoming bytes become packets.
+enum Framing {
+    /// Byte stream cut up by the splitter.
+    Stream,
+    /// One packet per datagram from `from`, when the OS reported it;
+    /// `truncated` if it filled the receive buffer.
+    Datagram { from: Option<std::net::SocketAddr>, truncated: bool },
+    /// One packet per WebSocket message.
+    Frame(FrameKind),
+    /// One packet per MQTT message, tagged with its topic.
+    Message { topic: String },
+    /// A packet an agent already cut, stamped on its clock shifted onto
+    /// ours.
+    Remote { timestamp_ms: f64, source: Option<String> },
+}
+
+/// Build the RX callback shared by all transports: split incoming bytes into
+/// packets, update counters, log and emit them.
+fn rx_handler(app: AppHandle, state: SharedState, session_id: String) -> impl Fn(Vec<u8>) + Send + 'static {
+    move |data| receive(&app, &state, &session_id, data, Framing::Stream)
+}
+
+/// RX callback for datagram transports, keeping datagram boundaries.
+fn datagram_handler(
+    app: AppHandle,
+    state: SharedState,
+    session_id: String,
+) -> impl Fn(Vec<u8>, Option<std::net::SocketAddr>, bool) + Send + 'static {
+    move |data, from, truncated| receive(&app, &state, &session_id, data, Framing::Datagram { from, truncated })
+}
+
+/// RX callback for TCP sessions, keeping message boundaries on WebSocket.
+fn socket_handler(app: AppHandle, state: SharedState, session_id: String) -> impl Fn(Vec<u8>, Option<FrameKind>) + Send + 'static {
+    move |data, frame| {
+        let framing = frame.map_or(Framing::Stream, Framing::Frame);
+        receive(&app, &state, &session_id, data, framing)
+    }
+}
+
+fn receive(app: &AppHandle, state: &SharedState, session_id: &str, data: Vec<u8>, framing: Framing) {
+    let mut st = state.lock();
+    // Bytes still in flight when the session was closed
+    if st.sessions.get(session_id).is_some_and(|s| !s.connected) {
+        return;
+    }
+    if let Some(taps) = st.rx_taps.get_mut(session_id) {
+        taps.retain(|tap| tap.send(data.clone()).is_ok());
+    }
+    let ts = match &framing {
+        Framing::Remote { timestamp_ms, .. } => *timestamp_ms,
+        _ => now_ms(),
+    };
+    let prev_ts = st.packets.last().map(|p| p.timestamp_ms);
+    if let Some(rec) = st.fixture_recordings.get_mut(session_id) {
+        rec.push(ts, &data);
+    }
+
+    let eol = st.eol_counters.entry(session_id.to_string()).or_default();
+    eol.feed(&data);
+    let detected = eol.detected();
+    if let Some(ratio) = st.baud_watch.feed(session_id, &data, ts) {
+        on_baud_mismatch(app, state, &st, session_id, ratio);
+    }
+    if let Some(sess) = st.sessions.get_mut(session_id) {
+        sess.line_ending = detected;
+    }
+    let mut pkts = match &framing {
+        Framing::Stream => {
+            // Swap out the persisted splitter state so we don't recreate it every call
+            let ss = st.splitter_states.remove(session_id).unwrap_or_default();
+            let mut splitter = Splitter::with_state(st.splitter.clone(), ss.buf, ss.in_packet)
+                .detected_line_ending(detected);
+            let pkts = splitter.feed(&data, "RX", ts, session_id, &mut st.next_id);
+            let (buf, in_packet) = splitter.into_state();
+            st.splitter_states.insert(session_id.to_string(), crate::state::SessionSplitterState { buf, in_packet });
+            pkts
+        }
+        Framing::Datagram { from, truncated } => {
+            if let Some(counter) = st.datagrams.get_mut(session_id) {
+                counter.record(&data, *from, *truncated);
+            }
+            let splitter = Splitter::with_state(st.splitter.clone(), Vec::new(), false);
+            let mut pkt = splitter.whole(data, "RX", ts, session_id, &mut st.next_id);
+            pkt.source = from.map(|a| a.to_string());
+            vec![pkt]
+        }
+        Framing::Frame(kind) => {
+            let splitter = Splitter::with_state(st.splitter.clone(), Vec::new(), false);
+            let mut pkt = splitter.whole(data, "RX", ts, session_id, &mut st.next_id);
+            pkt.frame = Some(*kind);
+            vec![pkt]
+        }
+        Framing::Message { topic } => {
+            let splitter = Splitter::with_state(st.splitter.clone(), Vec::new(), false);
+            let mut pkt = splitter.whole(data, "RX", ts, session_id, &mut st.next_id);
+            pkt.source = Some(topic.clone());
+            vec![pkt]
+        }
+        Framing::Remote { source, .. } => {
+            let splitter = Splitter::with_state(st.splitter.clone(), Vec::new(), false);
+            let mut pkt = splitter.whole(data, "RX", ts, session_id, &mut st.next_id);
+            pkt.source = source.clone();
+            vec![pkt]
+        }
+    };
+
+    let corrected = st.corrected_ts(session_id, ts);
+    let device_ts = st.device_ts(session_id, ts);
+    for t in st.pairer.expire(ts) {
+        journal::emit(app, "transaction", t);
+    }
+    for pkt in &mut pkts {
+        let raw_len = pkt.bytes.len() as u64;
+        let (mut dropped, mut stage_errors) = (false, Vec::new());
+        if let Some(pipeline) = st.pipelines.get(session_id) {
+            let out = pipeline.apply(std::mem::take(&mut pkt.bytes));
+            pkt.bytes = out.bytes;
+            pkt.checksum_ok = out.checksum_ok.or(pkt.checksum_ok);
+            (dropped, stage_errors) = (out.dropped, out.errors);
+        }
+        pkt.gap_ms = prev_ts.map(|pt| ts - pt);
+        pkt.corrected_ts_ms = corrected;
+        pkt.device_ts_ms = device_ts;
+        pkt.time = clock::format_ts(corrected.unwrap_or(ts));
+        let sound_times;
+        (pkt.severity, pkt.tags, sound_times) = st.classifier.classify_rx(&pkt.bytes);
+        match &framing {
+            Framing::Datagram { truncated: true, .. } => pkt.tags.push("truncated".into()),
+            Framing::Message { topic } => pkt.tags.push(topic.clone()),
+            _ => {}
+        }
+        pkt.tags.extend(stage_errors);
+        if pkt.checksum_ok == Some(false) {
+            st.alerts.count_bad_checksum(session_id);
+        }
+        if let Some(sess) = st.sessions.get_mut(session_id) {
+            sess.rx_bytes += raw_len;
+        }
+        // Dropped and filtered packets only count towards the byte total
+        if dropped || st.capture_filters.get_mut(session_id).is_some_and(|f| !f.admit(&pkt.bytes)) {
+            continue;
+        }
+        sound::play(sound_times);
+        if let Some(vars) = st.session_vars.get_mut(session_id) {
+            for (name, value) in vars.capture(&pkt.bytes) {
+                let ev = SessionVarEvent { session_id: session_id.to_string(), name, value: Some(value), source: "rx" };
+                journal::emit(app, "session_var", ev);
+            }
+        }
+        if let Some(mark) = st.pps.get_mut(session_id).and_then(|p| p.correlator.packet(session_id, &pkt.bytes, ts)) {
+            journal::emit(app, "time_mark", mark);
+        }
+        if let Some(sev) = pkt.severity.as_deref().filter(|s| st.notifier.on_severity(session_id, s, ts)) {
+            let text: String = String::from_utf8_lossy(&pkt.bytes).trim().chars().take(NOTIFY_BODY_CHARS).collect();
+            notify(app, &format!("{} · {sev}", session_label(&st, session_id)), &text);
+        }
+        if let Some(t) = st.pairer.on_rx(pkt) {
+            journal::emit(app, "transaction", t);
+        }
+        write_log(app, &mut st, session_id, |log| log.write_packet(pkt));
+        st.packets.push(pkt.clone());
+        segment_packet(app, &mut st, pkt);
+        journal::emit(app, "packet", pkt.clone());
+        if let Some(cmp) = st.compare.as_mut() {
+            for divergence in cmp.push(pkt) {
+                journal::emit(app, "compare_divergence", divergence);
+            }
+        }
+        if let Some(counter) = st.decoders.get_mut(session_id) {
+            let decoded = counter.record(&pkt.bytes, ts);
+            if !decoded.fields.is_empty() {
+                journal::emit(app, "decoded", DecodedEvent {
+                    session_id: session_id.to_string(),
+                    packet_id: pkt.id,
+                    protocol: counter.protocol,
+                    message: decoded.message,
+                    ok: decoded.ok,
+                    fields: decoded.fields,
+                });
+            }
+        }
+    }
+    if let Some(counter) = st.decoders.get_mut(session_id).filter(|c| ts - c.last_emit_ms >= STATS_EMIT_INTERVAL_MS) {
+        counter.last_emit_ms = ts;
+        journal::emit(app, "protocol_stats", counter.stats(session_id));
+    }
+    check_quota(app, &mut st, session_id);
+}
+
+#[tauri::command]
+pub fn disconnect(app: AppHandle, state: State<'_, SharedState>, session_id: String) {
+    let summary = close_session(&mut state.lock(), &session_id);
+    if let Some(summary) = summary {
+        closed_summary(&app, summary);
+    }
+}
+
+/// Close a session's connection and log. Returns its summary, which also
+/// ends the log, when it was still connected.
+fn close_session(st: &mut AppState, session_id: &str) -> Option<SessionSummary> {
+    let mut summary = None;
+    if let Some(sess) = st.sessions.get_mut(session_id).filter(|s| s.connected) {
+        sess.connected = false;
+        summary = summary::summarize(st, session_id, CloseReason::Disconnect, now_ms());
+    }
+    st.bench_profiles.remove(session_id);
+    st.quotas.remove(session_id);
+    if let Some(task) = st.reconnecting.remove(session_id) {
+        task.abort();
+    }
+    if let (Some(log), Some(summary)) = (st.logs.get_mut(session_id), &summary) {
+        // Closing anyway; the summary still goes out as an event
+        let _ = log.write_summary(summary);
+    }
+    st.connections.remove(session_id);
+    st.logs.remove(session_id);
+    st.udp_peers.remove(session_id);
+    // FIN after what is already queued, and no more RX from the peer
+    if let Some(shutdown) = st.tcp_shutdown.remove(session_id) {
+        let _ = shutdown.send(());
+    }
+    if let Some(reader) = st.readers.remove(session_id) {
+        reader.abort();
+    }
+    if let Some(cancel) = st.pending_accepts.remove(session_id) {
+        let _ = cancel.send(());
+    }
+    st.mqtt.remove(session_id);
+    st.com_ports.remove(session_id);
+    st.bridges.remove(session_id);
+    st.serial_controls.remove(session_id);
+    st.sniffers.remove(session_id);
+    st.rx_taps.remove(session_id);
+    st.tx_taps.remove(session_id);
+    st.eol_counters.remove(session_id);
+    st.session_vars.remove(session_id);
+    st.pps.remove(session_id);
+    if let Some(watcher) = st.sms_watchers.remove(session_id) {
+        watcher.abort();
+    }
+    summary
+}
+
+/// Queue bytes on a session, given as hex unless `format` says otherwise;
+/// `escape` resolves C-style escapes in text. Returns the TX packet id; a
+/// "tx_status" event with that id follows once the bytes were written or
+/// failed.
+#[tauri::command]
+pub fn send_bytes(
+    state: State<'_, SharedState>,
+    app: AppHandle,
+    hex: String,
+    session_id: String,
+    format: Option<PayloadFormat>,
+    escape: Option<bool>,
+) -> Result<u64, String> {
+    let format = format.unwrap_or(PayloadFormat::Hex);
+    let bytes = prepare(&state, &session_id, &hex, format, escape.unwrap_or(false), None)?;
+    queue(&app, &state, &session_id, bytes, None)
+}
+
+/// Like `send_bytes`, but resolves only once the bytes were written, failing
+/// if the write fails or takes longer than `timeout_ms` (default 5 s).
+#[tauri::command]
+pub async fn send_bytes_confirmed(
+    state: State<'_, SharedState>,
+    app: AppHandle,
+    hex: String,
+    session_id: String,
+    timeout_ms: Option<u64>,
+    format: Option<PayloadFormat>,
+    escape: Option<bool>,
+) -> Result<u64, String> {
+    let format = format.unwrap_or(PayloadFormat::Hex);
+    let bytes = prepare(&state, &session_id, &hex, format, escape.unwrap_or(false), None)?;
+    let (done, written) = tokio::sync::oneshot::channel();
+    let id = queue(&app, &state, &session_id, bytes, Some(done))?;
+    match tokio::time::timeout(Duration::from_millis(timeout_ms.unwrap_or(5000)), written).await {
+        Ok(Ok(result)) => result.map(|()| id),
+        Ok(Err(_)) => Err("Connection closed before the data was written".into()),
+        Err(_) => Err("Timed out waiting for the write".into()),
+    }
+}
+
+/// Send keystroke bytes (e.g. 0x03 for Ctrl+C, 0x1B for ESC) exactly as given,
+/// for interactive terminals. Skips hex parsing and the TX append mode.
+#[tauri::command]
+pub fn tx_raw_key(state: State<'_, SharedState>, app: AppHandle, session_id: String, bytes: Vec<u8>) -> Result<(), String> {
+    transmit(&app, &state, &session_id, bytes)
+}
+
+/// Choose the terminator appended to every `send_bytes` on a session.
+#[tauri::command]
+pub fn set_tx_append(state: State<'_, SharedState>, session_id: String, mode: TxAppend) -> Result<(), String> {
+    let mut st = state.lock();
+    let sess = st.sessions.get_mut(&session_id).ok_or("Unknown session")?;
+    sess.tx_append = mode;
+    Ok(())
+}
+
+/// Emitted once the bytes of a TX packet were written, or failed to be.
+#[derive(serde::Serialize, Clone)]
+pub struct TxStatusEvent {
+    pub session_id: String,
+    pub packet_id: u64,
+    pub ok: bool,
+    pub error: Option<String>,
+    /// From queueing to the write completing.
+    pub elapsed_ms: f64,
+}
+
+/// The bytes of a payload for `session_id`, with its variables filled in and
+/// its terminator appended.
+fn prepare(
+    state: &SharedState,
+    session_id: &str,
+    data: &str,
+    format: PayloadFormat,
+    escape: bool,
+    mode: Option<&TxAppend>,
+) -> Result<Vec<u8>, String> {
+    let st = state.lock();
+    payload::apply_append_mode(&st.sessions, &st.vars_of(session_id), session_id, data, format, escape, mode).map_err(|e| e.to_string())
+}
+
+/// Queue `bytes` on a session and record them as a TX packet.
+fn transmit(app: &AppHandle, state: &SharedState, session_id: &str, bytes: Vec<u8>) -> Result<(), String> {
+    queue(app, state, session_id, bytes, None).map(|_| ())
+}
+
+/// `transmit`, returning the packet id. The write outcome is emitted as
+/// "tx_status" and also sent to `done` when given.
+fn queue(
+    app: &AppHandle,
+    state: &SharedState,
+    session_id: &str,
+    bytes: Vec<u8>,
+    done: Option<tokio::sync::oneshot::Sender<Result<(), String>>>,
+) -> Result<u64, String> {
+    let mut st = state.lock();
+    if st.sniffers.contains_key(session_id) {
+        return Err("Sniffed sessions are read-only".into());
+    }
+    let tx = st.connections.get(session_id).ok_or("Not connected")?.clone();
+    queue_via(app, &mut st, session_id, bytes, done, |out| tx.send(out).map_err(|e| e.0))
+}
+
+/// `queue` through `send`, which hands back a write it couldn't take.
+fn queue_via(
+    app: &AppHandle,
+    st: &mut AppState,
+    session_id: &str,
+    bytes: Vec<u8>,
+    done: Option<tokio::sync::oneshot::Sender<Result<(), String>>>,
+    send: impl FnOnce(Outgoing) -> Result<(), Outgoing>,
+) -> Result<u64, String> {
+    let id = st.next_id;
+    let (app2, sid, queued) = (app.clone(), session_id.to_string(), std::time::Instant::now());
+    let ack = move |result: Result<(), String>| {
+        journal::emit(&app2, "tx_status", TxStatusEvent {
+            session_id: sid,
+            packet_id: id,
+            ok: result.is_ok(),
+            error: result.clone().err(),
+            elapsed_ms: queued.elapsed().as_secs_f64() * 1000.0,
+        });
+        if let Some(done) = done {
+            let _ = done.send(result);
+        }
+    };
+    send(Outgoing::with_ack(bytes.clone(), Box::new(ack))).map_err(|out| {
+        out.cancel();
+        "Connection closed".to_string()
+    })?;
+    Ok(record_tx(app, st, session_id, bytes))
+}
+
+/// Add bytes sent on `session_id` as a TX packet and return its id.
+fn record_tx(app: &AppHandle, st: &mut AppState, session_id: &str, bytes: Vec<u8>) -> u64 {
+    record_tx_at(app, st, session_id, bytes, now_ms())
+}
+
+/// `record_tx` for bytes sent at `ts`, e.g. by another client of an agent.
+fn record_tx_at(app: &AppHandle, st: &mut AppState, session_id: &str, bytes: Vec<u8>, ts: f64) -> u64 {
+    let prev_ts = st.packets.last().map(|p| p.timestamp_ms);
+    let id = st.next_id;
+    st.next_id += 1;
+    if let Some(taps) = st.tx_taps.get_mut(session_id) {
+        taps.retain(|tap| tap.send(bytes.clone()).is_ok());
+    }
+    let (severity, tags) = st.classifier.classify(&bytes);
+    let mut pkt = crate::state::Packet {
+        id,
+        timestamp_ms: ts,
+        gap_ms: prev_ts.map(|pt| ts - pt),
+        direction: "TX".into(),
+        bytes: bytes.clone(),
+        checksum_ok: None,
+        session_id: session_id.to_string(),
+        corrected_ts_ms: st.corrected_ts(session_id, ts),
+        severity,
+        tags,
+        transaction_id: None,
+        device_ts_ms: st.device_ts(session_id, ts),
+        time: String::new(),
+        source: None,
+        frame: None,
+    };
+    pkt.time = clock::format_ts(pkt.corrected_ts_ms.unwrap_or(ts));
+    for t in st.pairer.expire(ts) {
+        journal::emit(app, "transaction", t);
+    }
+    if let Some(t) = st.pairer.on_tx(&mut pkt) {
+        journal::emit(app, "transaction", t);
+    }
+    if let Some(sess) = st.sessions.get_mut(session_id) {
+        sess.tx_bytes += bytes.len() as u64;
+    }
+    write_log(app, st, session_id, |log| log.write_packet(&pkt));
+    st.packets.push(pkt.clone());
+    segment_packet(app, st, &pkt);
+    journal::emit(app, "packet", &pkt);
+    check_quota(app, st, session_id);
+    id
+}
+
+/// Start copying a session's RX bytes into a new channel. The copy stops when
+/// the receiver is dropped or the session disconnects.
+fn subscribe(state: &SharedState, session_id: &str) -> Result<UnboundedReceiver<Vec<u8>>, String> {
+    let mut st = state.lock();
+    if !st.connections.contains_key(session_id) {
+        return Err("Not connected".into());
+    }
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    st.rx_taps.entry(session_id.to_string()).or_default().push(tx);
+    Ok(rx)
+}
+
+fn tap(state: &SharedState, session_id: &str) -> Result<RxTap, String> {
+    subscribe(state, session_id).map(RxTap::new)
+}
+
+#[tauri::command]
+pub fn get_packets(state: State<'_, SharedState>) -> Vec<crate::state::Packet> {
+    state.lock().packets.clone()
+}
+
+#[tauri::command]
+pub fn clear_packets(app: AppHandle, state: State<'_, SharedState>) {
+    let mut st = state.lock();
+    st.packets.clear();
+    st.next_id = 1;
+    journal::clear_sessions(&app);
+}
+
+#[tauri::command]
+pub fn get_sessions(state: State<'_, SharedState>) -> Vec<SessionInfo> {
+    state.lock().sessions.values().cloned().collect()
+}
+
+/// Events of `conn_id` (a session id, or "" for events of no session) emitted
+/// after `last_seq`, for a webview that reloaded or missed events.
+#[tauri::command]
+pub fn resync(app: AppHandle, conn_id: String, last_seq: u64) -> Resync {
+    let mut resync = journal::resync(&app, &conn_id, last_seq);
+    let schema = journal::schema(&app);
+    for entry in &mut resync.events {
+        entry.payload = journal::shape(schema, &entry.event, std::mem::take(&mut entry.payload));
+    }
+    resync
+}
+
+/// Agree on the event schema with the webview, which passes the newest one
+/// it understands. Returns the schema emitted from now on; a frontend that
+/// never calls this keeps getting the legacy shape.
+#[tauri::command]
+pub fn negotiate_schema(app: AppHandle, supported: u32) -> u32 {
+    journal::negotiate(&app, supported)
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct SessionMetaEvent {
+    pub session_id: String,
+    pub meta: SessionMeta,
+}
+
+/// Set a session's label, colour and notes. The change is emitted as
+/// "session_meta" and noted in the session's log, if it has one.
+#[tauri::command]
+pub fn set_session_meta(app: AppHandle, state: State<'_, SharedState>, session_id: String, meta: SessionMeta) -> Result<(), String> {
+    let mut st = state.lock();
+    let sess = st.sessions.get_mut(&session_id).ok_or("Unknown session")?;
+    sess.meta = meta.clone();
+    if st.logs.contains_key(&session_id) {
+        let json = serde_json::to_string(&meta).map_err(|e| e.to_string())?;
+        write_log(&app, &mut st, &session_id, |log| log.write_note(now_ms(), &format!("meta {json}")));
+    }
+    journal::emit(&app, "session_meta", SessionMetaEvent { session_id, meta });
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_splitter(state: State<'_, SharedState>, config: SplitterConfig) {
+    let mut st = state.lock();
+    st.splitter = config;
+    st.splitter_states.clear(); // reset per-session buffers on config change
+}
+
+#[tauri::command]
+pub fn get_splitter(state: State<'_, SharedState>) -> SplitterConfig {
+    state.lock().splitter.clone()
+}
+
+fn preset_store(app: &AppHandle) -> Result<PresetStore, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(PresetStore::load(&dir))
+}
+
+#[tauri::command]
+pub fn framing_presets(app: AppHandle) -> Result<Vec<FramingPreset>, String> {
+    Ok(preset_store(&app)?.list())
+}
+
+/// Switch the splitter to a named preset. Returns the new configuration.
+#[tauri::command]
+pub fn apply_framing_preset(app: AppHandle, state: State<'_, SharedState>, name: String) -> Result<SplitterConfig, String> {
+    let preset = preset_store(&app)?.get(&name)?;
+    set_splitter(state, preset.config.clone());
+    Ok(preset.config)
+}
+
+/// Save a user preset, from the current splitter unless `config` is given.
+#[tauri::command]
+pub fn save_framing_preset(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    name: String,
+    description: Option<String>,
+    config: Option<SplitterConfig>,
+) -> Result<(), String> {
+    let config = config.unwrap_or_else(|| state.lock().splitter.clone());
+    let preset = FramingPreset { name, description: description.unwrap_or_default(), config, builtin: false };
+    preset_store(&app)?.save_preset(preset)
+}
+
+#[tauri::command]
+pub fn delete_framing_preset(app: AppHandle, name: String) -> Result<(), String> {
+    preset_store(&app)?.delete(&name)
+}
+
+/// Replace the classification rules applied to new packets. Nothing changes
+/// if any pattern fails to compile.
+#[tauri::command]
+pub fn set_class_rules(state: State<'_, SharedState>, rules: Vec<ClassRule>) -> Result<(), String> {
+    let classifier = Classifier::new(rules)?;
+    state.lock().classifier = classifier;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_class_rules(state: State<'_, SharedState>) -> Vec<ClassRule> {
+    state.lock().classifier.rules()
+}
+
+#[tauri::command]
+pub fn compute_checksum(algo: String, hex: String) -> Result<ChecksumResult, String> {
+    let data = hex_to_bytes(&hex)?;
+    let v = checksum::compute(&algo, &data);
+    let width = match algo.as_str() {
+        "crc32" => 8,
+        "sum8" | "xor" => 2,
+        _ => 4,
+    };
+    Ok(ChecksumResult {
+        algorithm: algo,
+        value: v,
+        hex: format!("{v:0>width$X}"),
+    })
+}
+
+#[tauri::command]
+pub fn compute_all_checksums(hex: String) -> Result<Vec<ChecksumResult>, String> {
+    let data = hex_to_bytes(&hex)?;
+    Ok(checksum::compute_all(&data))
+}
+
+// ── Capture filters ─────────────────────────────────────────────────────────
+
+/// Set the session's capture filter; RX packets it rejects are neither
+/// logged, stored nor emitted. An empty expression removes the filter.
+#[tauri::command]
+pub fn set_capture_filter(state: State<'_, SharedState>, session_id: String, expr: String) -> Result<(), String> {
+    let mut st = state.lock();
+    if expr.trim().is_empty() {
+        st.capture_filters.remove(&session_id);
+    } else {
+        st.capture_filters.insert(session_id, CaptureFilter::new(&expr)?);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_capture_filters(state: State<'_, SharedState>) -> Vec<CaptureFilterInfo> {
+    let st = state.lock();
+    let mut list: Vec<_> = st.capture_filters.iter().map(|(sid, f)| f.info(sid)).collect();
+    list.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+    list
+}
+
+// ── RX pipeline ─────────────────────────────────────────────────────────────
+
+/// Set the stages each RX packet of the session passes through after
+/// framing, before classification and capture filters. An empty list
+/// removes the pipeline.
+#[tauri::command]
+pub fn set_rx_pipeline(state: State<'_, SharedState>, session_id: String, stages: Vec<Stage>) -> Result<(), String> {
+    let mut st = state.lock();
+    if stages.is_empty() {
+        st.pipelines.remove(&session_id);
+    } else {
+        st.pipelines.insert(session_id, Pipeline::new(stages)?);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_rx_pipeline(state: State<'_, SharedState>, session_id: String) -> Vec<Stage> {
+    state.lock().pipelines.get(&session_id).map(Pipeline::stages).unwrap_or_default()
+}
+
+// ── Protocol statistics ─────────────────────────────────────────────────────
+
+/// Select the protocol used to count message types on a session's RX
+/// packets; `None` stops counting. Counters restart on every change.
+#[tauri::command]
+pub fn set_decoder(state: State<'_, SharedState>, session_id: String, protocol: Option<Protocol>) {
+    let mut st = state.lock();
+    match protocol {
+        Some(p) => { st.decoders.insert(session_id, MessageCounter::new(p)); }
+        None => { st.decoders.remove(&session_id); }
+    }
+}
+
+/// Default RX sample for protocol detection.
+const DETECT_SAMPLE_MS: u64 = 3000;
+
+/// Sample a session's RX and rank the protocols it could be carrying. With
+/// `auto_enable`, the best guess's decoder is selected when it is confident
+/// enough.
+#[tauri::command]
+pub async fn detect_protocol(
+    state: State<'_, SharedState>,
+    session_id: String,
+    sample_ms: Option<u64>,
+    auto_enable: Option<bool>,
+) -> Result<Detection, String> {
+    let mut tap = tap(&state, &session_id)?;
+    let sample = tap.collect(Duration::from_millis(sample_ms.unwrap_or(DETECT_SAMPLE_MS))).await?;
+    let mut detection = detect::detect(&session_id, &sample);
+    if auto_enable.unwrap_or(false) {
+        let best = detection.candidates.first()
+            .filter(|c| c.confidence >= detect::AUTO_ENABLE_CONFIDENCE)
+            .and_then(|c| c.protocol);
+        if let Some(p) = best {
+            state.lock().decoders.insert(session_id, MessageCounter::new(p));
+            detection.enabled = Some(p);
+        }
+    }
+    Ok(detection)
+}
+
+#[tauri::command]
+pub fn get_protocol_stats(state: State<'_, SharedState>) -> Vec<ProtocolStats> {
+    let st = state.lock();
+    let mut list: Vec<_> = st.decoders.iter().map(|(sid, c)| c.stats(sid)).collect();
+    list.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+    list
+}
+
+#[tauri::command]
+pub fn reset_protocol_stats(state: State<'_, SharedState>) {
+    for counter in state.lock().decoders.values_mut() {
+        counter.reset();
+    }
+}
+
+// ── Transactions ────────────────────────────────────────────────────────────
+
+/// Configure how RX packets are grouped with the TX that preceded them.
+#[tauri::command]
+pub fn set_pairing(state: State<'_, SharedState>, config: PairingConfig) {
+    state.lock().pairer.set_config(config);
+}
+
+#[tauri::command]
+pub fn get_pairing(state: State<'_, SharedState>) -> PairingConfig {
+    state.lock().pairer.config.clone()
+}
+
+/// Per-session transaction counts and response latency.
+#[tauri::command]
+pub fn get_transaction_stats(app: AppHandle, state: State<'_, SharedState>) -> Vec<TransactionStats> {
+    let mut st = state.lock();
+    // Settle transactions that timed out with no traffic since
+    for t in st.pairer.expire(now_ms()) {
+        journal::emit(&app, "transaction", t);
+    }
+    st.pairer.stats()
+}
+
+#[tauri::command]
+pub fn get_latency_histograms(state: State<'_, SharedState>) -> Vec<HistogramSummary> {
+    state.lock().pairer.histograms()
+}
+
+/// Save the response latency histograms as "csv" or "json".
+#[tauri::command]
+pub async fn export_latency_histograms(app: AppHandle, state: State<'_, SharedState>, format: String) -> Result<String, String> {
+    let summaries = state.lock().pairer.histograms();
+    let contents = match format.as_str() {
+        "csv" => histogram::to_csv(&summaries),
+        "json" => serde_json::to_string_pretty(&summaries).map_err(|e| e.to_string())?,
+        other => return Err(format!("Unknown format: {other}")),
+    };
+    save_with_dialog(&app, contents, &format).await
+}
+
+#[tauri::command]
+pub fn reset_transaction_stats(state: State<'_, SharedState>) {
+    state.lock().pairer.reset_stats();
+}
+
+#[tauri::command]
+pub fn get_timing_stats(state: State<'_, SharedState>) -> TimingStats {
+    let st = state.lock();
+    let packets = &st.packets;
+    let total_packets = packets.len() as u64;
+    let total_bytes: u64 = packets.iter().map(|p| p.bytes.len() as u64).sum();
+
+    let gaps: Vec<f64> = packets.iter().filter_map(|p| p.gap_ms).collect();
+    let n = gaps.len() as f64;
+    let avg_gap_ms = if n > 0.0 { gaps.iter().sum::<f64>() / n } else { 0.0 };
+    let min_gap_ms = gaps.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max_gap_ms = gaps.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let variance = if n > 1.0 {
+        gaps.iter().map(|g| (g - avg_gap_ms).powi(2)).sum::<f64>() / n
+    } else { 0.0 };
+    let std_gap_ms = variance.sqrt();
+
+    let checksum_pass = packets.iter().filter(|p| p.checksum_ok == Some(true)).count() as u64;
+    let checksum_fail = packets.iter().filter(|p| p.checksum_ok == Some(false)).count() as u64;
+
+    TimingStats {
+        total_packets,
+        total_bytes,
+        avg_gap_ms,
+        min_gap_ms: if min_gap_ms.is_infinite() { 0.0 } else { min_gap_ms },
+        max_gap_ms: if max_gap_ms.is_infinite() { 0.0 } else { max_gap_ms },
+        std_gap_ms,
+        cycle_count: 0,
+        avg_cycle_ms: 0.0,
+        avg_idle_ms: 0.0,
+        checksum_pass,
+        checksum_fail,
+    }
+}
+
+/// Byte histogram, entropy and likely encoding of one session's captured
+/// packets in one direction ("RX" by default), optionally limited to a time
+/// window.
+#[tauri::command]
+pub fn analyze_bytes(
+    state: State<'_, SharedState>,
+    session_id: String,
+    direction: Option<String>,
+    from_ms: Option<f64>,
+    to_ms: Option<f64>,
+) -> ByteDistribution {
+    let direction = direction.unwrap_or_else(|| "RX".into());
+    let st = state.lock();
+    let window = st.packets.iter()
+        .filter(|p| p.session_id == session_id && p.direction == direction)
+        .filter(|p| from_ms.is_none_or(|t| p.timestamp_ms >= t) && to_ms.is_none_or(|t| p.timestamp_ms <= t))
+        .map(|p| p.bytes.as_slice());
+    entropy::analyze(&session_id, window)
+}
+
+/// Export the packets of several sessions interleaved into one timeline,
+/// labelled with the session names.
+#[tauri::command]
+pub async fn export_timeline(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    session_ids: Vec<String>,
+    format: Option<TimelineFormat>,
+    time_base: Option<TimeBase>,
+) -> Result<String, String> {
+    let format = format.unwrap_or_default();
+    let text = {
+        let st = state.lock();
+        let labels = session_ids.iter()
+            .map(|sid| {
+                let sess = st.sessions.get(sid).ok_or_else(|| format!("Unknown session: {sid}"))?;
+                Ok((sid.as_str(), sess.meta.label.as_deref().unwrap_or(&sess.name)))
+            })
+            .collect::<Result<std::collections::HashMap<_, _>, String>>()?;
+        timeline::render(&st.packets, &labels, format, time_base.unwrap_or_default())
+    };
+    save_with_dialog(&app, text, format.extension()).await
+}
+
+#[tauri::command]
+pub async fn export_packets(app: AppHandle, json: String, ext: Option<String>) -> Result<String, String> {
+    save_with_dialog(&app, json, ext.as_deref().unwrap_or("json")).await
+}
+
+/// Ask the user for a destination file and write `contents` to it.
+async fn save_with_dialog(app: &AppHandle, contents: String, ext: &str) -> Result<String, String> {
+    let ts = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let filter_label = match ext {
+        "csv" => "CSV",
+        "txt" => "Text",
+        "log" => "Log",
+        "wsession" => "Wirescope session",
+        _     => "JSON",
+    };
+
+    // Use async save_file + oneshot to avoid blocking the main thread event loop
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .add_filter(filter_label, &[ext])
+        .set_file_name(&format!("wirescope-{ts}.{ext}"))
+        .save_file(move |path| { let _ = tx.send(path); });
+
+    let file_path = rx.await
+        .map_err(|_| "다이얼로그 오류".to_string())?
+        .ok_or_else(|| "취소됨".to_string())?;
+
+    let path = file_path.as_path()
+        .ok_or_else(|| "잘못된 경로".to_string())?
+        .to_path_buf();
+    std::fs::write(&path, contents.as_bytes()).map_err(|e| e.to_string())?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+// ── Import ──────────────────────────────────────────────────────────────────
+
+#[derive(serde::Serialize, Clone)]
+pub struct ImportSummary {
+    /// One per TCP/UDP conversation or log label, disconnected.
+    pub sessions: Vec<SessionInfo>,
+    pub packets: usize,
+    /// Capture frames without a TCP or UDP payload.
+    pub skipped: usize,
+}
+
+/// Import a pcap/pcapng capture or a timestamped text log. Each conversation
+/// becomes a session whose packets are framed with the current splitter (TCP
+/// payloads) or kept whole (datagrams, log lines) and classified, so they
+/// show up in the packet list like live traffic.
+#[tauri::command]
+pub fn import_capture(state: State<'_, SharedState>, path: String) -> Result<ImportSummary, String> {
+    let data = std::fs::read(&path).map_err(|e| e.to_string())?;
+    let name = std::path::Path::new(&path).file_name().map_or_else(|| path.clone(), |n| n.to_string_lossy().into_owned());
+    let capture = import::parse(&data, &name)?;
+    let prefix = format!("import:{}", now_ms() as u64);
+
+    let mut st = state.lock();
+    let mut summary = ImportSummary { sessions: Vec::new(), packets: 0, skipped: capture.skipped };
+    for (n, stream) in capture.streams.into_iter().enumerate() {
+        let session_id = format!("{prefix}/{n}");
+        // Stream framing state per direction
+        let mut framing: [(Vec<u8>, bool); 2] = Default::default();
+        let (mut rx_bytes, mut tx_bytes, mut prev_ts) = (0, 0, None);
+        for chunk in stream.chunks {
+            let side = usize::from(chunk.direction == "RX");
+            match side {
+                1 => rx_bytes += chunk.bytes.len() as u64,
+                _ => tx_bytes += chunk.bytes.len() as u64,
+            }
+            let mut pkts = if stream.kind == "tcp" {
+                let (buf, in_packet) = std::mem::take(&mut framing[side]);
+                let mut splitter = Splitter::with_state(st.splitter.clone(), buf, in_packet);
+                let pkts = splitter.feed(&chunk.bytes, chunk.direction, chunk.ts_ms, &session_id, &mut st.next_id);
+                framing[side] = splitter.into_state();
+                pkts
+            } else {
+                let splitter = Splitter::with_state(st.splitter.clone(), Vec::new(), false);
+                vec![splitter.whole(chunk.bytes, chunk.direction, chunk.ts_ms, &session_id, &mut st.next_id)]
+            };
+            for pkt in &mut pkts {
+                pkt.gap_ms = prev_ts.map(|pt| pkt.timestamp_ms - pt);
+                prev_ts = Some(pkt.timestamp_ms);
+                pkt.time = clock::format_ts(pkt.timestamp_ms);
+                (pkt.severity, pkt.tags) = st.classifier.classify(&pkt.bytes);
+            }
+            summary.packets += pkts.len();
+            st.packets.extend(pkts);
+        }
+        let session = SessionInfo {
+            id: session_id,
+            name: stream.name,
+            kind: "import".into(),
+            connected: false,
+            tx_bytes,
+            rx_bytes,
+            clock_offset_ms: 0.0,
+            line_ending: None,
+            tx_append: TxAppend::None,
+            device_time_offset_ms: None,
+            meta: SessionMeta::default(),
+            identity: None,
+        };
+        summary.sessions.push(st.insert_session(session));
+    }
+    Ok(summary)
+}
+
+// ── Session files ───────────────────────────────────────────────────────────
+
+/// Save a session with its settings, packets, events and any raw recording
+/// as a self-contained file. Returns the saved path.
+#[tauri::command]
+pub async fn session_export(app: AppHandle, state: State<'_, SharedState>, session_id: String) -> Result<String, String> {
+    let mut file = {
+        let st = state.lock();
+        let session = st.sessions.get(&session_id).cloned().ok_or("Unknown session")?;
+        let settings = CaptureSettings {
+            splitter: st.splitter.clone(),
+            rules: st.classifier.rules(),
+            protocol: st.decoders.get(&session_id).map(|c| c.protocol),
+            stages: st.pipelines.get(&session_id).map(Pipeline::stages).unwrap_or_default(),
+        };
+        let packets = st.packets.iter().filter(|p| p.session_id == session_id).cloned().collect();
+        let mut file = SessionFile::new(session, settings, packets, now_ms());
+        file.raw = st.fixture_recordings.get(&session_id).map(|rec| {
+            let (started_ms, chunks) = rec.raw();
+            RawCapture { started_ms, chunks }
+        });
+        file
+    };
+    file.events = journal::resync(&app, &session_id, 0).events;
+    let json = serde_json::to_string(&file).map_err(|e| e.to_string())?;
+    save_with_dialog(&app, json, "wsession").await
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct SessionImport {
+    /// The session under a new id, disconnected.
+    pub session: SessionInfo,
+    pub packets: usize,
+    /// Events as emitted on the machine that exported the session.
+    pub events: Vec<crate::journal::Entry>,
+    pub settings: CaptureSettings,
+    pub raw: Option<RawCapture>,
+    pub app_version: String,
+}
+
+/// Open a session file exported on this or another machine. The session's
+/// RX pipeline and decoder are restored with it; the splitter and rules it
+/// was captured with are returned rather than replacing the current ones.
+#[tauri::command]
+pub fn session_import(state: State<'_, SharedState>, path: String) -> Result<SessionImport, String> {
+    let data = std::fs::read(&path).map_err(|e| e.to_string())?;
+    let file = SessionFile::parse(&data)?;
+    let pipeline = match file.settings.stages.is_empty() {
+        true => None,
+        false => Some(Pipeline::new(file.settings.stages.clone())?),
+    };
+    let session_id = format!("import:{}/{}", now_ms() as u64, file.session.id);
+
+    let mut st = state.lock();
+    let packets = file.packets.len();
+    for mut pkt in file.packets {
+        pkt.id = st.next_id;
+        st.next_id += 1;
+        pkt.session_id = session_id.clone();
+        pkt.transaction_id = None;
+        st.packets.push(pkt);
+    }
+    if let Some(pipeline) = pipeline {
+        st.pipelines.insert(session_id.clone(), pipeline);
+    }
+    if let Some(protocol) = file.settings.protocol {
+        st.decoders.insert(session_id.clone(), MessageCounter::new(protocol));
+    }
+    let session = st.insert_session(SessionInfo { id: session_id, connected: false, ..file.session });
+    Ok(SessionImport {
+        session,
+        packets,
+        events: file.events,
+        settings: file.settings,
+        raw: file.raw,
+        app_version: file.app_version,
+    })
+}
+
+// ── Segmentation ────────────────────────────────────────────────────────────
+
+/// Count `pkt` towards its session's segment; emits "segment" when that
+/// completes one.
+fn segment_packet(app: &AppHandle, st: &mut AppState, pkt: &crate::state::Packet) {
+    let Some(seg) = st.segmenters.get_mut(&pkt.session_id).and_then(|s| s.record(pkt)) else {
+        return;
+    };
+    let event = SysEvent::new(&pkt.session_id, "segment", "ended").at(pkt.timestamp_ms).with("index", seg.index);
+    sys_event(app, st, event);
+    journal::emit(app, "segment", seg);
+}
+
+/// Cut the session into segments every N packets or M bytes, restarting the
+/// count. `None` stops segmenting and forgets the segments.
+#[tauri::command]
+pub fn set_segmentation(state: State<'_, SharedState>, session_id: String, config: Option<SegmentConfig>) -> Result<(), String> {
+    let mut st = state.lock();
+    match config {
+        Some(config) => { st.segmenters.insert(session_id, Segmenter::new(config)?); }
+        None => { st.segmenters.remove(&session_id); }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_segments(state: State<'_, SharedState>, session_id: String) -> Vec<Segment> {
+    state.lock().segmenters.get(&session_id).map(Segmenter::segments).unwrap_or_default()
+}
+
+/// Export the packets of one segment.
+#[tauri::command]
+pub async fn export_segment(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    session_id: String,
+    index: u64,
+    format: Option<TimelineFormat>,
+    time_base: Option<TimeBase>,
+) -> Result<String, String> {
+    let format = format.unwrap_or_default();
+    let text = {
+        let st = state.lock();
+        let seg = st.segmenters.get(&session_id)
+            .and_then(|s| s.get(index))
+            .ok_or_else(|| format!("No segment {index} on {session_id}"))?;
+        let sess = st.sessions.get(&session_id).ok_or_else(|| format!("Unknown session: {session_id}"))?;
+        let labels = std::collections::HashMap::from([(session_id.as_str(), sess.meta.label.as_deref().unwrap_or(&sess.name))]);
+        let packets: Vec<_> = st.packets.iter()
+            .filter(|p| (seg.first_packet_id..=seg.last_packet_id).contains(&p.id))
+            .cloned()
+            .collect();
+        timeline::render(&packets, &labels, format, time_base.unwrap_or_default())
+    };
+    save_with_dialog(&app, text, format.extension()).await
+}
+
+/// One session's packets within a time range as text, for the clipboard.
+/// `last_ms` selects the range ending now instead of `from_ms`/`to_ms`.
+#[tauri::command]
+pub fn copy_range(
+    state: State<'_, SharedState>,
+    session_id: String,
+    from_ms: Option<f64>,
+    to_ms: Option<f64>,
+    last_ms: Option<f64>,
+    format: Option<TimelineFormat>,
+    time_base: Option<TimeBase>,
+) -> Result<String, String> {
+    let base = time_base.unwrap_or_default();
+    let (from_ms, to_ms) = match last_ms {
+        Some(last) => (Some(now_ms() - last), None),
+        None => (from_ms, to_ms),
+    };
+    let st = state.lock();
+    let sess = st.sessions.get(&session_id).ok_or_else(|| format!("Unknown session: {session_id}"))?;
+    let labels = std::collections::HashMap::from([(session_id.as_str(), sess.meta.label.as_deref().unwrap_or(&sess.name))]);
+    let packets: Vec<_> = timeline::in_range(&st.packets, &session_id, from_ms, to_ms, base).cloned().collect();
+    Ok(timeline::render(&packets, &labels, format.unwrap_or_default(), base))
+}
+
+// ── Quotas ──────────────────────────────────────────────────────────────────
+
+/// Limit how many bytes or how long a connected session may run before its
+/// logging stops or it is disconnected. `None` removes the limit.
+#[tauri::command]
+pub async fn set_quota(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    session_id: String,
+    quota: Option<Quota>,
+) -> Result<(), String> {
+    let mut st = state.lock();
+    let Some(quota) = quota else {
+        st.quotas.remove(&session_id);
+        return Ok(());
+    };
+    quota.validate()?;
+    if !st.sessions.get(&session_id).is_some_and(|s| s.connected) {
+        return Err("Not connected".into());
+    }
+    let timer = quota.max_duration_s.map(|limit_s| {
+        let elapsed_s = st.opened_at.get(&session_id).map_or(0.0, |t| (now_ms() - t) / 1000.0);
+        let (app, state, sid) = (app.clone(), Arc::clone(&state), session_id.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs_f64((limit_s - elapsed_s).max(0.0))).await;
+            quota_reached(&app, &mut state.lock(), &sid, "duration");
+        })
+    });
+    st.quotas.insert(session_id.clone(), QuotaState { quota, reached: false, timer });
+    check_quota(&app, &mut st, &session_id);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_quota(state: State<'_, SharedState>, session_id: String) -> Option<Quota> {
+    state.lock().quotas.get(&session_id).map(|q| q.quota.clone())
+}
+
+fn check_quota(app: &AppHandle, st: &mut AppState, session_id: &str) {
+    let Some(max) = st.quotas.get(session_id).filter(|q| !q.reached).and_then(|q| q.quota.max_bytes) else {
+        return;
+    };
+    if st.sessions.get(session_id).is_some_and(|s| s.tx_bytes + s.rx_bytes >= max) {
+        quota_reached(app, st, session_id, "bytes");
+    }
+}
+
+fn quota_reached(app: &AppHandle, st: &mut AppState, session_id: &str, limit: &'static str) {
+    let Some(q) = st.quotas.get_mut(session_id).filter(|q| !q.reached) else {
+        return;
+    };
+    q.reached = true;
+    let action = q.quota.action;
+    let now = now_ms();
+    let event = QuotaEvent {
+        session_id: session_id.to_string(),
+        limit,
+        action,
+        bytes: st.sessions.get(session_id).map_or(0, |s| s.tx_bytes + s.rx_bytes),
+        elapsed_s: st.opened_at.get(session_id).map_or(0.0, |t| (now - t) / 1000.0),
+    };
+    let action_name = match action {
+        QuotaAction::StopLogging => "stop_logging",
+        QuotaAction::Disconnect => "disconnect",
+    };
+    let sys = SysEvent::new(session_id, "quota", "reached")
+        .at(now)
+        .with("limit", limit)
+        .with("action", action_name)
+        .with("bytes", event.bytes)
+        .with("elapsed_s", event.elapsed_s);
+    sys_event(app, st, sys);
+    journal::emit(app, "quota_reached", event);
+    match action {
+        QuotaAction::StopLogging => {
+            st.logs.remove(session_id);
+        }
+        QuotaAction::Disconnect => {
+            if let Some(summary) = close_session(st, session_id) {
+                closed_summary(app, summary);
+            }
+        }
+    }
+}
+
+// ── Device identification ───────────────────────────────────────────────────
+
+/// Send `probe` whenever `session_id` connects and keep the response as the
+/// session's identity. `None` stops probing. Can be set before the session
+/// first connects.
+#[tauri::command]
+pub fn set_identify_probe(state: State<'_, SharedState>, session_id: String, probe: Option<IdentifyProbe>) -> Result<(), String> {
+    let mut st = state.lock();
+    match probe {
+        Some(probe) => {
+            payload::encode(&probe.data, probe.format, probe.escape)?;
+            st.identify_probes.insert(session_id, probe);
+        }
+        None => {
+            st.identify_probes.remove(&session_id);
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_identify_probe(state: State<'_, SharedState>, session_id: String) -> Option<IdentifyProbe> {
+    state.lock().identify_probes.get(&session_id).cloned()
+}
+
+/// Outcome of the identification probe of a session that just connected.
+#[derive(serde::Serialize, Clone)]
+pub struct SessionIdentityEvent {
+    pub session_id: String,
+    pub identity: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Run the session's identification probe, if one is set, in the background.
+/// The response is stored on the session, noted in its log and emitted as
+/// "session_identity".
+fn spawn_identify(app: &AppHandle, state: &SharedState, session_id: &str) {
+    let Some(probe) = state.lock().identify_probes.get(session_id).cloned() else {
+        return;
+    };
+    let (app, state, session_id) = (app.clone(), Arc::clone(state), session_id.to_string());
+    tokio::spawn(async move {
+        let result = async {
+            let request = prepare(&state, &session_id, &probe.data, probe.format, probe.escape, probe.append.as_ref())?;
+            let mut tap = tap(&state, &session_id)?;
+            let send = |bytes| transmit(&app, &state, &session_id, bytes);
+            identify::probe(&mut tap, &send, request, &probe).await
+        }
+        .await;
+        let mut st = state.lock();
+        let Some(sess) = st.sessions.get_mut(&session_id).filter(|s| s.connected) else {
+            return;
+        };
+        sess.identity = result.as_ref().ok().cloned();
+        let sys = match &result {
+            Ok(identity) => SysEvent::new(&session_id, "identity", "received").with("identity", identity.clone()),
+            Err(e) => SysEvent::new(&session_id, "identity", "failed").with("error", e.clone()),
+        };
+        sys_event(&app, &mut st, sys);
+        let (identity, error) = match result {
+            Ok(identity) => (Some(identity), None),
+            Err(e) => (None, Some(e)),
+        };
+        journal::emit(&app, "session_identity", SessionIdentityEvent { session_id, identity, error });
+    });
+}
+
+// ── Auto reconnect ──────────────────────────────────────────────────────────
+
+/// Reopen `session_id` the way it was last opened whenever its device or peer
+/// goes away. `None` turns it off. Applies to serial and TCP-based sessions.
+#[tauri::command]
+pub fn set_auto_reconnect(state: State<'_, SharedState>, session_id: String, policy: Option<ReconnectPolicy>) {
+    let mut st = state.lock();
+    match policy {
+        Some(policy) => {
+            st.auto_reconnect.insert(session_id, policy);
+        }
+        None => {
+            st.auto_reconnect.remove(&session_id);
+            if let Some(task) = st.reconnecting.remove(&session_id) {
+                task.abort();
+            }
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_auto_reconnect(state: State<'_, SharedState>, session_id: String) -> Option<ReconnectPolicy> {
+    state.lock().auto_reconnect.get(&session_id).copied()
+}
+
+/// Try to reopen a session that closed unexpectedly until it opens, the
+/// policy runs out of attempts or it is disconnected by hand. Every attempt
+/// is a "reconnect" system event.
+fn spawn_reconnect(
+    app: &AppHandle,
+    state: &SharedState,
+    session_id: &str,
+    policy: ReconnectPolicy,
+    reopen: Reopen,
+) -> tokio::task::JoinHandle<()> {
+    let (app, state, session_id) = (app.clone(), Arc::clone(state), session_id.to_string());
+    tokio::spawn(async move {
+        let mut attempt = 1;
+        loop {
+            if policy.exhausted(attempt) {
+                sys_event(&app, &mut state.lock(), SysEvent::new(&session_id, "reconnect", "gave_up").with("attempts", attempt - 1));
+                break;
+            }
+            let delay = policy.delay(attempt);
+            tokio::time::sleep(delay).await;
+            if state.lock().sessions.get(&session_id).is_some_and(|s| s.connected) {
+                break; // Reopened by hand meanwhile
+            }
+            let sys = SysEvent::new(&session_id, "reconnect", "attempt")
+                .with("attempt", attempt)
+                .with("delay_ms", delay.as_millis() as u64);
+            sys_event(&app, &mut state.lock(), sys);
+            let opened = match &reopen {
+                Reopen::Serial { port, baud, settings, name } => {
+                    open_serial_session(&app, &state, port.clone(), *baud, *settings, name.clone())
+                }
+                Reopen::Tcp(args) => connect_tcp(app.clone(), app.state::<SharedState>(), (**args).clone()).await,
+                Reopen::Ssh(opts) => connect_ssh(app.clone(), app.state::<SharedState>(), (**opts).clone()).await,
+            };
+            match opened {
+                // A later drop starts over with a task of its own
+                Ok(_) => {
+                    sys_event(&app, &mut state.lock(), SysEvent::new(&session_id, "reconnect", "succeeded").with("attempt", attempt));
+                    return;
+                }
+                Err(e) => {
+                    let sys = SysEvent::new(&session_id, "reconnect", "failed").with("attempt", attempt).with("error", e);
+                    sys_event(&app, &mut state.lock(), sys);
+                }
+            }
+            attempt += 1;
+        }
+        state.lock().reconnecting.remove(&session_id);
+    })
+}
+
+// ── Serial sniffing ─────────────────────────────────────────────────────────
+
+/// Watch the traffic between another program and the USB serial adapter
+/// behind `port` without opening it (Linux usbmon). The read-only session
+/// shows what the program wrote as TX and what the device sent as RX.
+#[tauri::command]
+pub async fn sniff_serial(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    port: String,
+    name: Option<String>,
+) -> Result<SessionInfo, String> {
+    let session_id = format!("sniff:{port}");
+    if state.lock().sniffers.contains_key(&session_id) {
+        return Err(format!("{port} is already being sniffed"));
+    }
+    let on_rx = rx_handler(app.clone(), Arc::clone(&state), session_id.clone());
+    let (app2, state2, sid) = (app.clone(), Arc::clone(&state), session_id.clone());
+    let on_data = move |dir, data| match dir {
+        Direction::ToDevice => {
+            record_tx(&app2, &mut state2.lock(), &sid, data);
+        }
+        Direction::FromDevice => on_rx(data),
+    };
+    let (app3, state3, sid3) = (app.clone(), Arc::clone(&state), session_id.clone());
+    let on_close = move || session_closed(&app3, &state3, &sid3);
+    let sniffer = sniff::start(&port, on_data, on_close)?;
+
+    let session = SessionInfo {
+        id: session_id.clone(),
+        name: name.unwrap_or_else(|| format!("{port} (sniff)")),
+        kind: "sniff".into(),
+        connected: true,
+        tx_bytes: 0,
+        rx_bytes: 0,
+        clock_offset_ms: 0.0,
+        line_ending: None,
+        tx_append: TxAppend::None,
+        device_time_offset_ms: None,
+        meta: SessionMeta::default(),
+        identity: None,
+    };
+    let mut st = state.lock();
+    st.sniffers.insert(session_id, sniffer);
+    Ok(st.insert_session(session))
+}
+
+// ── TCP server ──────────────────────────────────────────────────────────────
+
+#[derive(serde::Serialize, Clone)]
+pub struct ServerInfo {
+    pub id: String,
+    pub local_addr: String,
+    pub clients: usize,
+    pub options: ServerOptions,
+}
+
+/// A client connecting to, being refused by, or leaving a server.
+#[derive(serde::Serialize, Clone)]
+pub struct ServerClientEvent {
+    pub server_id: String,
+    pub peer: String,
+    /// "connected", "rejected" or "disconnected".
+    pub status: &'static str,
+    pub reason: Option<String>,
+    /// The new session, on "connected".
+    pub session: Option<SessionInfo>,
+}
+
+fn server_id(local_addr: std::net::SocketAddr) -> String {
+    format!("server:{local_addr}")
+}
+
+/// Listen for TCP clients. Each admitted client becomes its own session.
+#[tauri::command]
+pub async fn server_start(app: AppHandle, state: State<'_, SharedState>, options: ServerOptions) -> Result<ServerInfo, String> {
+    let (app2, state2) = (app.clone(), Arc::clone(&state));
+    let listener = server::start(options, move |local_addr, admission| {
+        admit_client(&app2, &state2, &server_id(local_addr), admission);
+    })
+    .await?;
+
+    let id = server_id(listener.local_addr);
+    let info = ServerInfo {
+        id: id.clone(),
+        local_addr: listener.local_addr.to_string(),
+        clients: 0,
+        options: listener.options.clone(),
+    };
+    state.lock().servers.insert(id, listener);
+    Ok(info)
+}
+
+fn admit_client(app: &AppHandle, state: &SharedState, server_id: &str, admission: Admission) {
+    let (stream, peer, pending, slot) = match admission {
+        Admission::Accepted { stream, peer, pending, slot } => (stream, peer, pending, slot),
+        Admission::Rejected { peer, reason } => {
+            // Keyed without the port, which changes with every attempt
+            let key = format!("{server_id}\n{}\n{reason}", peer.ip());
+            sys_events::emit(app, "server_client", key, ServerClientEvent {
+                server_id: server_id.to_string(),
+                peer: peer.to_string(),
+                status: "rejected",
+                reason: Some(reason),
+                session: None,
+            });
+            return;
+        }
+    };
+    let session_id = format!("{server_id}/{peer}");
+    let session = SessionInfo {
+        id: session_id.clone(),
+        name: peer.to_string(),
+        kind: "server".into(),
+        connected: true,
+        tx_bytes: 0,
+        rx_bytes: 0,
+        clock_offset_ms: 0.0,
+        line_ending: None,
+        tx_append: TxAppend::None,
+        device_time_offset_ms: None,
+        meta: SessionMeta::default(),
+        identity: None,
+    };
+    // The session exists before any of its bytes or its close are handled
+    let session = state.lock().insert_session(session);
+    journal::emit(app, "server_client", ServerClientEvent {
+        server_id: server_id.to_string(),
+        peer: peer.to_string(),
+        status: "connected",
+        reason: None,
+        session: Some(session),
+    });
+    let on_data = rx_handler(app.clone(), Arc::clone(state), session_id.clone());
+    if !pending.is_empty() {
+        on_data(pending);
+    }
+    let (app2, state2, sid, server) = (app.clone(), Arc::clone(state), session_id.clone(), server_id.to_string());
+    let on_close = move || {
+        drop(slot);
+        let mut st = state2.lock();
+        if let Some(sess) = st.sessions.get_mut(&sid) {
+            sess.connected = false;
+        }
+        st.connections.remove(&sid);
+        st.tcp_shutdown.remove(&sid);
+        st.readers.remove(&sid);
+        journal::emit(&app2, "server_client", ServerClientEvent {
+            server_id: server,
+            peer: peer.to_string(),
+            status: "disconnected",
+            reason: None,
+            session: None,
+        });
+    };
+    // Spawned under the lock, so a close right away waits for the inserts
+    let mut st = state.lock();
+    let (tx, shutdown, reader) = socket::spawn_io_notify(stream, on_data, on_close);
+    st.connections.insert(session_id.clone(), tx);
+    st.tcp_shutdown.insert(session_id.clone(), shutdown);
+    st.readers.insert(session_id, reader);
+}
+
+/// Stop accepting clients. Connected clients stay open.
+#[tauri::command]
+pub fn server_stop(state: State<'_, SharedState>, server_id: String) -> Result<(), String> {
+    state.lock().servers.remove(&server_id).map(|_| ()).ok_or("No such server".into())
+}
+
+#[tauri::command]
+pub fn server_list(state: State<'_, SharedState>) -> Vec<ServerInfo> {
+    let st = state.lock();
+    let mut list: Vec<_> = st.servers.iter()
+        .map(|(id, l)| ServerInfo {
+            id: id.clone(),
+            local_addr: l.local_addr.to_string(),
+            clients: l.clients(),
+            options: l.options.clone(),
+        })
+        .collect();
+    list.sort_by(|a, b| a.id.cmp(&b.id));
+    list
+}
+
+/// Send hex bytes to every connected client of a server, each recorded as a
+/// TX packet of the client's session; send on a session to reach one
+/// client. Returns the packet ids in client order.
+#[tauri::command]
+pub fn server_broadcast(state: State<'_, SharedState>, app: AppHandle, server_id: String, hex: String) -> Result<Vec<u64>, String> {
+    let prefix = format!("{server_id}/");
+    let mut clients: Vec<String> = state.lock().connections.keys().filter(|id| id.starts_with(&prefix)).cloned().collect();
+    if clients.is_empty() {
+        return Err(format!("No clients connected to {server_id}"));
+    }
+    clients.sort();
+    clients
+        .iter()
+        .map(|sid| {
+            let bytes = prepare(&state, sid, &hex, PayloadFormat::Hex, false, None)?;
+            queue(&app, &state, sid, bytes, None)
+        })
+        .collect()
+}
+
+// ── Remote API ──────────────────────────────────────────────────────────────
+
+#[derive(serde::Serialize, Clone)]
+pub struct RemoteInfo {
+    pub local_addr: String,
+    pub clients: usize,
+    pub tokens: Vec<TokenInfo>,
+}
+
+fn remote_info_of(server: &remote::RemoteServer) -> RemoteInfo {
+    RemoteInfo { local_addr: server.local_addr.to_string(), clients: server.clients(), tokens: server.tokens() }
+}
+
+/// Carry out a remote client's request the way the matching command would.
+fn remote_dispatch(app: AppHandle) -> remote::Dispatch {
+    Arc::new(move |op| {
+        let app = app.clone();
+        Box::pin(async move {
+            let state = app.state::<SharedState>();
+            match op {
+                RemoteOp::Sessions => {
+                    let sessions: Vec<SessionInfo> = state.lock().sessions.values().cloned().collect();
+                    serde_json::to_value(sessions).map_err(|e| e.to_string())
+                }
+                RemoteOp::Clock => Ok(now_ms().into()),
+                RemoteOp::Send { session_id, data, format, escape } => {
+                    let bytes = prepare(&state, &session_id, &data, format, escape, None)?;
+                    queue(&app, &state, &session_id, bytes, None).map(Into::into)
+                }
+                RemoteOp::Open { connection } => {
+                    let session = open_connection(&app, &state, connection).await?;
+                    serde_json::to_value(session).map_err(|e| e.to_string())
+                }
+                RemoteOp::Close { session_id } => {
+                    if let Some(summary) = close_session(&mut state.lock(), &session_id) {
+                        closed_summary(&app, summary);
+                    }
+                    Ok(serde_json::Value::Null)
+                }
+                // Handled by the client's connection
+                RemoteOp::Subscribe { .. } | RemoteOp::Unsubscribe => Ok(serde_json::Value::Null),
+            }
+        })
+    })
+}
+
+/// Serve the WebSocket control API, replacing a running one. Every request
+/// but successful clock polls is appended to the audit log and emitted as
+/// "remote_audit".
+#[tauri::command]
+pub async fn remote_start(app: AppHandle, state: State<'_, SharedState>, options: RemoteOptions) -> Result<RemoteInfo, String> {
+    state.lock().remote_api = None;
     let app2 = app.clone();
-    let session_id = port.clone();
-
-    let conn = serial_port::open(port.clone(), baud, move |data| {
-        let mut st = state_arc.lock();
-        let ts = now_ms();
-        let prev_ts = st.packets.last().map(|p| p.timestamp_ms);
-
-        // Swap out the persisted splitter state so we don't recreate it every call
-        let ss = st.splitter_states.remove(&session_id).unwrap_or_default();
-        let mut splitter = Splitter::with_state(st.splitter.clone(), ss.buf, ss.in_packet);
-        let mut pkts = splitter.feed(&data, "RX", ts, &session_id, &mut st.next_id);
-        let (buf, in_packet) = splitter.into_state();
-        st.splitter_states.insert(session_id.clone(), crate::state::SessionSplitterState { buf, in_packet });
-
-        for pkt in &mut pkts {
-            pkt.gap_ms = prev_ts.map(|pt| ts - pt);
-            if let Some(sess) = st.sessions.get_mut(&session_id) {
-                sess.rx_bytes += pkt.bytes.len() as u64;
+    let audit_log = app.path().app_data_dir().ok().map(remote::audit_writer).transpose()?;
+    let on_audit = move |entry: AuditEntry| {
+        if let Some(log) = &audit_log {
+            let _ = log.send(entry.clone());
+        }
+        journal::emit(&app2, "remote_audit", entry);
+    };
+    let server = remote::start(app.clone(), options, remote_dispatch(app), on_audit).await?;
+    let info = remote_info_of(&server);
+    state.lock().remote_api = Some(server);
+    Ok(info)
+}
+
+/// Stop the control API and disconnect its clients.
+#[tauri::command]
+pub fn remote_stop(state: State<'_, SharedState>) {
+    state.lock().remote_api = None;
+}
+
+#[tauri::command]
+pub fn remote_info(state: State<'_, SharedState>) -> Option<RemoteInfo> {
+    state.lock().remote_api.as_ref().map(remote_info_of)
+}
+
+/// The latest `limit` (default 200) entries of the audit log, oldest first.
+#[tauri::command]
+pub fn remote_audit(app: AppHandle, limit: Option<usize>) -> Result<Vec<AuditEntry>, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    remote::read_audit(&dir, limit.unwrap_or(200))
+}
+
+// ── Virtual devices ─────────────────────────────────────────────────────────
+
+#[derive(serde::Serialize, Clone)]
+pub struct DeviceInfo {
+    pub id: String,
+    /// Address to connect to, or the pty to open as a serial port.
+    pub endpoint: String,
+    pub transport: DeviceTransport,
+}
+
+/// Host a scripted fake device for external tools to talk to. Traffic and
+/// state changes are emitted as "device_event".
+#[tauri::command]
+pub async fn device_start(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    script: DeviceScript,
+    transport: DeviceTransport,
+) -> Result<DeviceInfo, String> {
+    let device = device::start(script, transport, move |event| {
+        journal::emit(&app, "device_event", event);
+    })
+    .await?;
+    let info = DeviceInfo { id: device.id.clone(), endpoint: device.endpoint.clone(), transport: device.transport.clone() };
+    state.lock().devices.insert(device.id.clone(), device);
+    Ok(info)
+}
+
+#[tauri::command]
+pub fn device_stop(state: State<'_, SharedState>, device_id: String) -> Result<(), String> {
+    state.lock().devices.remove(&device_id).map(|_| ()).ok_or("No such device".into())
+}
+
+#[tauri::command]
+pub fn device_list(state: State<'_, SharedState>) -> Vec<DeviceInfo> {
+    let st = state.lock();
+    let mut list: Vec<_> = st.devices.values()
+        .map(|d| DeviceInfo { id: d.id.clone(), endpoint: d.endpoint.clone(), transport: d.transport.clone() })
+        .collect();
+    list.sort_by(|a, b| a.id.cmp(&b.id));
+    list
+}
+
+// ── Rack ────────────────────────────────────────────────────────────────────
+
+/// Open every board of a rack definition. Boards that fail to open are
+/// reported in the returned status instead of aborting the whole rack.
+#[tauri::command]
+pub async fn open_rack(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    boards: Vec<RackBoard>,
+    log_dir: Option<String>,
+    log_mode: Option<LogMode>,
+) -> Result<Vec<RackBoardStatus>, String> {
+    let mut names = std::collections::HashSet::new();
+    if let Some(dup) = boards.iter().find(|b| !names.insert(b.name.as_str())) {
+        return Err(format!("Duplicate board name: {}", dup.name));
+    }
+    // Boards of a previous rack would otherwise stay open, untracked
+    close_rack_sessions(&app, &mut state.lock());
+
+    let mut members = Vec::with_capacity(boards.len());
+    for board in boards {
+        let mut member = RackMember { board, session_id: None, error: None, log_path: None };
+        let opened = rack::resolve_port(&member.board).and_then(|port| {
+            open_serial_session(&app, &state, port, member.board.baud, SerialSettings::default(), member.board.name.clone())
+        });
+        match opened {
+            Ok(session) => {
+                if let Some(dir) = &log_dir {
+                    let dir = std::path::Path::new(dir).join(rack::dir_name(&member.board.name));
+                    match SessionLog::create(&dir, &session.id, log_mode.unwrap_or_default()) {
+                        Ok(mut log) => {
+                            if session.meta != SessionMeta::default() {
+                                if let Err(e) = serde_json::to_string(&session.meta)
+                                    .map_err(std::io::Error::from)
+                                    .and_then(|json| log.write_note(now_ms(), &format!("meta {json}")))
+                                {
+                                    member.error = Some(e.to_string());
+                                }
+                            }
+                            member.log_path = Some(log.path.to_string_lossy().into_owned());
+                            state.lock().logs.insert(session.id.clone(), log);
+                        }
+                        Err(e) => member.error = Some(e),
+                    }
+                }
+                member.session_id = Some(session.id);
+            }
+            Err(e) => member.error = Some(e),
+        }
+        members.push(member);
+    }
+
+    let mut st = state.lock();
+    st.rack = members;
+    Ok(rack_status(&st))
+}
+
+#[tauri::command]
+pub fn get_rack_status(state: State<'_, SharedState>) -> Vec<RackBoardStatus> {
+    rack_status(&state.lock())
+}
+
+/// Disconnect every board of the current rack and forget the rack.
+#[tauri::command]
+pub fn close_rack(app: AppHandle, state: State<'_, SharedState>) {
+    close_rack_sessions(&app, &mut state.lock());
+}
+
+fn close_rack_sessions(app: &AppHandle, st: &mut AppState) {
+    let rack = std::mem::take(&mut st.rack);
+    for sid in rack.iter().filter_map(|m| m.session_id.as_ref()) {
+        if let Some(summary) = close_session(st, sid) {
+            closed_summary(app, summary);
+        }
+    }
+}
+
+/// Check every log listed in the manifest of `dir` against its recorded
+/// hash, and the manifest's own hash chain.
+#[tauri::command]
+pub fn verify_log_manifest(dir: String) -> Result<ManifestReport, String> {
+    manifest::verify(std::path::Path::new(&dir))
+}
+
+/// Export all rack boards' packets as one timeline ordered by timestamp.
+#[tauri::command]
+pub async fn export_rack_timeline(app: AppHandle, state: State<'_, SharedState>) -> Result<String, String> {
+    let text = {
+        let st = state.lock();
+        let labels: std::collections::HashMap<&str, &str> = st.rack.iter()
+            .filter_map(|m| m.session_id.as_deref().map(|sid| (sid, m.board.name.as_str())))
+            .collect();
+        timeline::render(&st.packets, &labels, TimelineFormat::Text, TimeBase::Host)
+    };
+    save_with_dialog(&app, text, "log").await
+}
+
+fn rack_status(st: &AppState) -> Vec<RackBoardStatus> {
+    st.rack.iter().map(|m| {
+        let sess = m.session_id.as_ref().and_then(|sid| st.sessions.get(sid));
+        RackBoardStatus {
+            name: m.board.name.clone(),
+            session_id: m.session_id.clone(),
+            connected: sess.is_some_and(|s| s.connected),
+            error: m.error.clone(),
+            log_path: m.log_path.clone(),
+            tx_bytes: sess.map_or(0, |s| s.tx_bytes),
+            rx_bytes: sess.map_or(0, |s| s.rx_bytes),
+        }
+    }).collect()
+}
+
+// ── Session history ─────────────────────────────────────────────────────────
+
+fn history_dir(app: &AppHandle) -> Result<std::path::PathBuf, String> {
+    app.path().app_data_dir().map_err(|e| e.to_string())
+}
+
+/// Every closed session, most recent first.
+#[tauri::command]
+pub fn history_list(app: AppHandle) -> Result<Vec<HistoryEntry>, String> {
+    Ok(SessionHistory::load(&history_dir(&app)?).search(&HistoryQuery::default()))
+}
+
+#[tauri::command]
+pub fn history_search(app: AppHandle, query: HistoryQuery) -> Result<Vec<HistoryEntry>, String> {
+    Ok(SessionHistory::load(&history_dir(&app)?).search(&query))
+}
+
+/// Replace the tags of a history entry.
+#[tauri::command]
+pub fn history_tag(app: AppHandle, id: u64, tags: Vec<String>) -> Result<HistoryEntry, String> {
+    SessionHistory::tag(&history_dir(&app)?, id, tags)
+}
+
+/// Drop an entry from the history; its log file is kept.
+#[tauri::command]
+pub fn history_forget(app: AppHandle, id: u64) -> Result<(), String> {
+    SessionHistory::forget(&history_dir(&app)?, id)
+}
+
+// ── Bench profiles ──────────────────────────────────────────────────────────
+
+/// Open every connection of a bench profile, or none: connections are opened
+/// in order and, at the first failure, the ones already open are closed again.
+#[tauri::command]
+pub async fn open_bench(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    profile: BenchProfile,
+) -> Result<BenchOpenResult, String> {
+    let ids: Vec<String> = profile.connections.iter().map(BenchConnection::session_id).collect();
+    {
+        let mut seen = std::collections::HashSet::new();
+        if let Some(dup) = ids.iter().find(|id| !seen.insert(id.as_str())) {
+            return Err(format!("{dup} appears twice in the profile"));
+        }
+        let st = state.lock();
+        if let Some(open) = ids.iter().find(|id| st.sessions.get(id.as_str()).is_some_and(|s| s.connected)) {
+            return Err(format!("{open} is already connected"));
+        }
+    }
+
+    let mut results = Vec::with_capacity(ids.len());
+    let mut prior = Vec::new();
+    let mut failed = false;
+    for (conn, session_id) in profile.connections.into_iter().zip(ids) {
+        if failed {
+            results.push(BenchConnectionResult { session_id, status: "skipped", session: None, error: None });
+            continue;
+        }
+        prior.push(state.lock().sessions.get(&session_id).cloned());
+        results.push(match open_connection(&app, &state, conn).await {
+            Ok(session) => BenchConnectionResult { session_id, status: "opened", session: Some(session), error: None },
+            Err(e) => {
+                failed = true;
+                prior.pop();
+                BenchConnectionResult { session_id, status: "failed", session: None, error: Some(e) }
+            }
+        });
+    }
+
+    let mut st = state.lock();
+    if failed {
+        for (result, prior) in results.iter_mut().filter(|r| r.status == "opened").zip(prior) {
+            close_session(&mut st, &result.session_id);
+            // Leave no trace of a session that did not exist before
+            match prior {
+                Some(prior) => st.sessions.insert(result.session_id.clone(), prior),
+                None => st.sessions.remove(&result.session_id),
+            };
+            result.status = "rolled_back";
+            result.session = None;
+        }
+    } else {
+        for result in &results {
+            st.bench_profiles.insert(result.session_id.clone(), profile.name.clone());
+        }
+    }
+    Ok(BenchOpenResult { profile: profile.name, ok: !failed, connections: results })
+}
+
+async fn open_connection(app: &AppHandle, state: &State<'_, SharedState>, conn: BenchConnection) -> Result<SessionInfo, String> {
+    match conn {
+        BenchConnection::Serial { port, baud, flow, name, worker } => {
+            let settings = SerialSettings { flow, worker, ..Default::default() };
+            open_serial_session(app, state, port.clone(), baud, settings, name.unwrap_or(port))
+        }
+        BenchConnection::Tcp(args) => connect_tcp(app.clone(), state.clone(), *args).await,
+        BenchConnection::Udp { host, port, local_port, reply_to_sender, options } => {
+            connect_udp(app.clone(), state.clone(), host, port, local_port, Some(reply_to_sender), Some(options)).await
+        }
+    }
+}
+
+// ── Parameter sweep ─────────────────────────────────────────────────────────
+
+/// Try each setting of a sweep in turn: open the connection, run the script
+/// and close it again. Each attempt is emitted as "sweep_attempt" when it
+/// ends. Sessions the sweep opened are removed again afterwards.
+#[tauri::command]
+pub async fn sweep_run(app: AppHandle, state: State<'_, SharedState>, plan: SweepPlan) -> Result<SweepReport, String> {
+    let connections = plan.space.connections();
+    if connections.is_empty() {
+        return Err("The sweep has no settings to try".into());
+    }
+    {
+        let st = state.lock();
+        if let Some(open) = connections.iter().map(BenchConnection::session_id).find(|id| st.sessions.get(id).is_some_and(|s| s.connected)) {
+            return Err(format!("{open} is already connected"));
+        }
+    }
+
+    let total = connections.len();
+    let mut attempts = Vec::with_capacity(total);
+    for (index, conn) in connections.into_iter().enumerate() {
+        let started = std::time::Instant::now();
+        let (label, session_id) = (sweep::label(&conn), conn.session_id());
+        let prior = state.lock().sessions.get(&session_id).cloned();
+        let (chat, error) = match open_connection(&app, &state, conn.clone()).await {
+            Ok(_) => {
+                let chat = match tap(&state, &session_id) {
+                    Ok(mut tap) => {
+                        tokio::time::sleep(Duration::from_millis(plan.settle_ms)).await;
+                        let send = |bytes| transmit(&app, &state, &session_id, bytes);
+                        Ok(chat::run(&mut tap, &send, &plan.script, &Default::default()).await)
+                    }
+                    Err(e) => Err(e),
+                };
+                let mut st = state.lock();
+                close_session(&mut st, &session_id);
+                match prior {
+                    Some(prior) => st.sessions.insert(session_id.clone(), prior),
+                    None => st.sessions.remove(&session_id),
+                };
+                match chat {
+                    Ok(chat) => (Some(chat), None),
+                    Err(e) => (None, Some(e)),
+                }
             }
-            st.packets.push(pkt.clone());
-            let _ = app2.emit("packet", pkt.clone());
+            Err(e) => (None, Some(e)),
+        };
+        let attempt = SweepAttempt {
+            index,
+            label,
+            connection: conn,
+            ok: chat.as_ref().is_some_and(|c| c.ok),
+            error,
+            chat,
+            elapsed_ms: started.elapsed().as_secs_f64() * 1000.0,
+        };
+        journal::emit(&app, "sweep_attempt", &attempt);
+        let stop = attempt.ok && plan.stop_at_first;
+        attempts.push(attempt);
+        if stop {
+            break;
+        }
+    }
+    let working = attempts.iter().filter(|a| a.ok).map(|a| a.label.clone()).collect();
+    Ok(SweepReport { skipped: total - attempts.len(), attempts, working })
+}
+
+// ── Clock ───────────────────────────────────────────────────────────────────
+
+/// Measure the host clock offset against an NTP server and apply it to all
+/// subsequent packet timestamps.
+#[tauri::command]
+pub async fn sync_ntp(state: State<'_, SharedState>, server: Option<String>) -> Result<ClockInfo, String> {
+    let server = server.unwrap_or_else(|| "pool.ntp.org".into());
+    let srv = server.clone();
+    let (offset, rtt) = tokio::task::spawn_blocking(move || clock::query_ntp(&srv))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let mut st = state.lock();
+    st.clock = ClockInfo {
+        ntp_server: Some(server),
+        ntp_offset_ms: Some(offset),
+        round_trip_ms: Some(rtt),
+        synced_at_ms: Some(now_ms()),
+    };
+    Ok(st.clock.clone())
+}
+
+#[tauri::command]
+pub fn get_clock_info(state: State<'_, SharedState>) -> ClockInfo {
+    state.lock().clock.clone()
+}
+
+#[tauri::command]
+pub fn clear_ntp_offset(state: State<'_, SharedState>) {
+    state.lock().clock = ClockInfo::default();
+}
+
+/// Choose how timestamps are taken and written in logs and packet events.
+#[tauri::command]
+pub fn set_time_source(source: TimeSource) -> TimeConfig {
+    clock::set_source(source);
+    clock::config()
+}
+
+#[tauri::command]
+pub fn get_time_config() -> TimeConfig {
+    clock::config()
+}
+
+/// Set a manual clock correction for one session, added on top of the NTP offset.
+#[tauri::command]
+pub fn set_clock_offset(state: State<'_, SharedState>, session_id: String, offset_ms: f64) -> Result<(), String> {
+    let mut st = state.lock();
+    let sess = st.sessions.get_mut(&session_id).ok_or("Unknown session")?;
+    sess.clock_offset_ms = offset_ms;
+    Ok(())
+}
+
+/// Set how far a session's device clock is ahead of the host (negative if
+/// behind). Packets then carry a device timestamp; `None` removes it.
+#[tauri::command]
+pub fn set_device_time_offset(state: State<'_, SharedState>, session_id: String, offset_ms: Option<f64>) -> Result<(), String> {
+    let mut st = state.lock();
+    let sess = st.sessions.get_mut(&session_id).ok_or("Unknown session")?;
+    sess.device_time_offset_ms = offset_ms;
+    Ok(())
+}
+
+// ── PPS time marks ──────────────────────────────────────────────────────────
+
+/// Watch a serial session's modem line for a GNSS receiver's PPS and pair
+/// each pulse with the NMEA or UBX time sentence that follows it. Pairs are
+/// emitted as "time_mark"; replaces any PPS watch already running.
+#[tauri::command]
+pub fn pps_start(state: State<'_, SharedState>, session_id: String, options: PpsOptions) -> Result<(), String> {
+    let mut st = state.lock();
+    let control = st.serial_controls.get(&session_id).ok_or("Not an open serial session")?;
+    let (state2, sid) = (Arc::clone(&state), session_id.clone());
+    let watch = control.watch_line(options.line, options.poll_us.max(50), move |ts, asserted| {
+        if let Some(pps) = state2.lock().pps.get_mut(&sid) {
+            pps.correlator.edge(ts, asserted);
         }
     })?;
+    st.pps.insert(session_id, Pps { correlator: Correlator::new(options), _watch: watch });
+    Ok(())
+}
+
+#[tauri::command]
+pub fn pps_stop(state: State<'_, SharedState>, session_id: String) {
+    state.lock().pps.remove(&session_id);
+}
+
+// ── SMS (GSM modem, PDU mode) ───────────────────────────────────────────────
+
+const AT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Submitting to the network can take much longer than a local command.
+const CMGS_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(serde::Serialize, Clone)]
+pub struct SmsEvent {
+    pub session_id: String,
+    pub message: SmsMessage,
+}
+
+#[tauri::command]
+pub fn sms_encode_pdu(number: String, text: String) -> Result<SmsPdu, String> {
+    sms::encode_submit(&number, &text)
+}
+
+#[tauri::command]
+pub fn sms_decode_pdu(pdu: String) -> Result<SmsMessage, String> {
+    sms::decode(&pdu)
+}
+
+/// Send a text message through the modem on `session_id`. Returns the
+/// message reference reported by `+CMGS`.
+#[tauri::command]
+pub async fn sms_send(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    session_id: String,
+    number: String,
+    text: String,
+) -> Result<Option<u8>, String> {
+    let pdu = sms::encode_submit(&number, &text)?;
+    let mut tap = tap(&state, &session_id)?;
+    let send = |bytes| transmit(&app, &state, &session_id, bytes);
+
+    modem::at_command(&mut tap, &send, "AT+CMGF=0", AT_TIMEOUT).await?;
+    let cmd = format!("AT+CMGS={}", pdu.tpdu_len);
+    tap.clear();
+    send(format!("{cmd}\r").into_bytes())?;
+    tap.expect(&[b"> "], Some(AT_TIMEOUT)).await?;
+    send(format!("{}\x1a", pdu.pdu).into_bytes())?;
+    let resp = modem::final_result(&mut tap, &cmd, CMGS_TIMEOUT).await?;
+    Ok(resp.lines()
+        .find_map(|l| l.trim().strip_prefix("+CMGS:"))
+        .and_then(|mr| mr.trim().parse().ok()))
+}
+
+/// List every message stored on the modem's preferred storage.
+#[tauri::command]
+pub async fn sms_list(app: AppHandle, state: State<'_, SharedState>, session_id: String) -> Result<Vec<SmsEntry>, String> {
+    let mut tap = tap(&state, &session_id)?;
+    let send = |bytes| transmit(&app, &state, &session_id, bytes);
+    modem::at_command(&mut tap, &send, "AT+CMGF=0", AT_TIMEOUT).await?;
+    let resp = modem::at_command(&mut tap, &send, "AT+CMGL=4", AT_TIMEOUT).await?;
+    Ok(sms::parse_listing(&resp))
+}
+
+#[tauri::command]
+pub async fn sms_read(app: AppHandle, state: State<'_, SharedState>, session_id: String, index: u32) -> Result<SmsEntry, String> {
+    let mut tap = tap(&state, &session_id)?;
+    let send = |bytes| transmit(&app, &state, &session_id, bytes);
+    modem::at_command(&mut tap, &send, "AT+CMGF=0", AT_TIMEOUT).await?;
+    let resp = modem::at_command(&mut tap, &send, &format!("AT+CMGR={index}"), AT_TIMEOUT).await?;
+    let mut entry = sms::parse_listing(&resp).into_iter().next().ok_or("Empty message slot")?;
+    entry.index = Some(index);
+    Ok(entry)
+}
+
+/// Route new messages straight to the terminal (`+CMT`) and emit each one as
+/// an "sms" event. Disabling only stops listening; the modem keeps the
+/// routing until it is reconfigured or reset.
+#[tauri::command]
+pub async fn sms_watch(app: AppHandle, state: State<'_, SharedState>, session_id: String, enabled: bool) -> Result<(), String> {
+    if let Some(watcher) = state.lock().sms_watchers.remove(&session_id) {
+        watcher.abort();
+    }
+    if !enabled {
+        return Ok(());
+    }
+
+    let mut tap = tap(&state, &session_id)?;
+    {
+        let send = |bytes| transmit(&app, &state, &session_id, bytes);
+        modem::at_command(&mut tap, &send, "AT+CMGF=0", AT_TIMEOUT).await?;
+        modem::at_command(&mut tap, &send, "AT+CNMI=2,2,0,0,0", AT_TIMEOUT).await?;
+    }
+
+    let sid = session_id.clone();
+    let app2 = app.clone();
+    let watcher = tokio::spawn(async move {
+        // +CMT: [<alpha>],<length><CR><LF><pdu><CR><LF>
+        while tap.expect(&[b"+CMT:"], None).await.is_ok() {
+            let line_timeout = Some(Duration::from_secs(2));
+            if tap.expect(&[b"\r\n"], line_timeout).await.is_err() {
+                continue;
+            }
+            let Ok((_, pdu)) = tap.expect(&[b"\r\n"], line_timeout).await else { continue };
+            if let Ok(message) = sms::decode(&String::from_utf8_lossy(&pdu)) {
+                journal::emit(&app2, "sms", SmsEvent { session_id: sid.clone(), message });
+            }
+        }
+    });
+    state.lock().sms_watchers.insert(session_id, watcher);
+    Ok(())
+}
+
+// ── Payload library ─────────────────────────────────────────────────────────
+
+fn payload_library(app: &AppHandle) -> Result<PayloadLibrary, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(PayloadLibrary::load(&dir))
+}
+
+#[tauri::command]
+pub fn payload_list(app: AppHandle) -> Result<Vec<SavedPayload>, String> {
+    Ok(payload_library(&app)?.list())
+}
+
+#[tauri::command]
+pub fn payload_add(app: AppHandle, payload: SavedPayload) -> Result<(), String> {
+    payload::encode(&payload.data, payload.format, payload.escape)?;
+    payload_library(&app)?.add(payload)
+}
+
+/// Replace the saved payload `name`, renaming it if `payload.name` differs.
+#[tauri::command]
+pub fn payload_update(app: AppHandle, name: String, payload: SavedPayload) -> Result<(), String> {
+    payload::encode(&payload.data, payload.format, payload.escape)?;
+    payload_library(&app)?.update(&name, payload)
+}
+
+#[tauri::command]
+pub fn payload_delete(app: AppHandle, name: String) -> Result<(), String> {
+    payload_library(&app)?.delete(&name)
+}
+
+/// Send a saved payload, appending its terminator or else the session's.
+#[tauri::command]
+pub fn payload_send(app: AppHandle, state: State<'_, SharedState>, session_id: String, name: String) -> Result<(), String> {
+    let saved = payload_library(&app)?.get(&name)?.clone();
+    let bytes = prepare(&state, &session_id, &saved.data, saved.format, saved.escape, saved.append.as_ref())?;
+    transmit(&app, &state, &session_id, bytes)
+}
+
+/// Merge payloads from an exported library or a text file of commands, one
+/// per line. Returns how many were added.
+#[tauri::command]
+pub fn payload_import(app: AppHandle, path: String, overwrite: Option<bool>) -> Result<usize, String> {
+    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let payloads = library::parse_import(&contents)?;
+    for p in &payloads {
+        payload::encode(&p.data, p.format, p.escape).map_err(|e| format!("{}: {e}", p.name))?;
+    }
+    payload_library(&app)?.import(payloads, overwrite.unwrap_or(false))
+}
+
+#[tauri::command]
+pub async fn payload_export(app: AppHandle) -> Result<String, String> {
+    let json = serde_json::to_string_pretty(&payload_library(&app)?.list()).map_err(|e| e.to_string())?;
+    save_with_dialog(&app, json, "json").await
+}
+
+// ── Chat scripts ────────────────────────────────────────────────────────────
+
+#[derive(serde::Serialize, Clone)]
+pub struct ChatResultEvent {
+    pub session_id: String,
+    #[serde(flatten)]
+    pub result: ChatResult,
+}
+
+/// Run an expect/send chat script, e.g. to dial a data call. The outcome is
+/// returned and also emitted as "chat_result".
+#[tauri::command]
+pub async fn chat_run(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    session_id: String,
+    script: ChatScript,
+) -> Result<ChatResult, String> {
+    let mut tap = tap(&state, &session_id)?;
+    let vars = state.lock().vars_of(&session_id);
+    let send = |bytes| transmit(&app, &state, &session_id, bytes);
+    let result = chat::run(&mut tap, &send, &script, &vars).await;
+    for (name, value) in &result.captured {
+        set_var(&app, &mut state.lock(), &session_id, name.clone(), Some(value.clone()), "script");
+    }
+    journal::emit(&app, "chat_result", ChatResultEvent { session_id: session_id.clone(), result: result.clone() });
+    Ok(result)
+}
+
+// ── Session variables ───────────────────────────────────────────────────────
+
+/// A session variable set or removed (`value` None) by an RX capture
+/// ("rx"), a chat script ("script") or a command ("command").
+#[derive(serde::Serialize, Clone)]
+pub struct SessionVarEvent {
+    pub session_id: String,
+    pub name: String,
+    pub value: Option<String>,
+    pub source: &'static str,
+}
+
+fn set_var(app: &AppHandle, st: &mut AppState, session_id: &str, name: String, value: Option<String>, source: &'static str) {
+    let vars = &mut st.session_vars.entry(session_id.to_string()).or_default().values;
+    match &value {
+        Some(value) => vars.insert(name.clone(), value.clone()),
+        None => vars.remove(&name),
+    };
+    journal::emit(app, "session_var", SessionVarEvent { session_id: session_id.to_string(), name, value, source });
+}
+
+/// Variables of a session, used as `{{name}}` in payloads and chat scripts.
+#[tauri::command]
+pub fn vars_get(state: State<'_, SharedState>, session_id: String) -> Vars {
+    state.lock().vars_of(&session_id)
+}
+
+/// Set a session variable, or remove it when `value` is None.
+#[tauri::command]
+pub fn vars_set(app: AppHandle, state: State<'_, SharedState>, session_id: String, name: String, value: Option<String>) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Variable name is empty".into());
+    }
+    let mut st = state.lock();
+    if !st.sessions.contains_key(&session_id) {
+        return Err(format!("Unknown session: {session_id}"));
+    }
+    set_var(&app, &mut st, &session_id, name, value, "command");
+    Ok(())
+}
+
+#[tauri::command]
+pub fn vars_clear(app: AppHandle, state: State<'_, SharedState>, session_id: String) {
+    let mut st = state.lock();
+    let names: Vec<String> = st.vars_of(&session_id).into_keys().collect();
+    for name in names {
+        set_var(&app, &mut st, &session_id, name, None, "command");
+    }
+}
+
+/// Set variables from received packets: each capture that matches a packet
+/// stores its value, replacing the previous one.
+#[tauri::command]
+pub fn set_var_captures(state: State<'_, SharedState>, session_id: String, captures: Vec<VarCapture>) -> Result<(), String> {
+    let mut st = state.lock();
+    st.session_vars.entry(session_id).or_default().set_captures(captures)
+}
+
+#[tauri::command]
+pub fn get_var_captures(state: State<'_, SharedState>, session_id: String) -> Vec<VarCapture> {
+    state.lock().session_vars.get(&session_id).map(|v| v.captures()).unwrap_or_default()
+}
+
+// ── Benchmark ───────────────────────────────────────────────────────────────
+
+/// Per-iteration wait when no timeout is given.
+const ROUNDTRIP_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Measure round-trip time against a peer that echoes what it receives.
+#[tauri::command]
+pub async fn benchmark_roundtrip(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    session_id: String,
+    payload_size: usize,
+    iterations: u32,
+    timeout_ms: Option<u64>,
+) -> Result<RoundtripSummary, String> {
+    if payload_size == 0 || iterations == 0 {
+        return Err("Payload size and iterations must be at least 1".into());
+    }
+    let timeout = timeout_ms.map_or(ROUNDTRIP_TIMEOUT, Duration::from_millis);
+    let mut tap = tap(&state, &session_id)?;
+    let send = |bytes| transmit(&app, &state, &session_id, bytes);
+    bench::roundtrip(&mut tap, &send, &session_id, payload_size, iterations, timeout).await
+}
 
-    let session = SessionInfo {
-        id: port.clone(),
-        name: port.clone(),
-        kind: "serial".into(),
-        connected: true,
-        tx_bytes: 0,
-        rx_bytes: 0,
-    };
+// ── Fixtures ────────────────────────────────────────────────────────────────
+
+/// Start recording a session's raw RX together with the current splitter,
+/// classification rules and decoder, for replay as a regression fixture.
+#[tauri::command]
+pub fn fixture_record_start(state: State<'_, SharedState>, session_id: String) -> Result<(), String> {
     let mut st = state.lock();
-    st.serial_tx = Some(conn.tx);
-    st.sessions.insert(port.clone(), session.clone());
-    Ok(session)
+    let sess = st.sessions.get(&session_id).ok_or_else(|| format!("Unknown session: {session_id}"))?;
+    let fixture = Fixture {
+        datagrams: sess.kind == "udp",
+        session_id: session_id.clone(),
+        splitter: st.splitter.clone(),
+        rules: st.classifier.rules(),
+        protocol: st.decoders.get(&session_id).map(|c| c.protocol),
+        stages: st.pipelines.get(&session_id).map(Pipeline::stages).unwrap_or_default(),
+        chunks: Vec::new(),
+    };
+    st.fixture_recordings.insert(session_id, Recorder::new(fixture, now_ms()));
+    Ok(())
 }
 
+/// Stop recording and save the fixture. Returns the saved path.
 #[tauri::command]
-pub async fn connect_tcp(
+pub async fn fixture_record_stop(app: AppHandle, state: State<'_, SharedState>, session_id: String) -> Result<String, String> {
+    let rec = state.lock().fixture_recordings.remove(&session_id)
+        .ok_or_else(|| format!("Not recording: {session_id}"))?;
+    let json = serde_json::to_string_pretty(&rec.finish()).map_err(|e| e.to_string())?;
+    save_with_dialog(&app, json, "json").await
+}
+
+/// Replay a fixture and compare the resulting packets against its stored
+/// expectation (`<fixture>.expected.json` unless `expected_path` is given).
+/// The expectation is written when missing or when `update` is set.
+#[tauri::command]
+pub fn fixture_test(fixture_path: String, expected_path: Option<String>, update: Option<bool>) -> Result<FixtureReport, String> {
+    fixture::test(
+        std::path::Path::new(&fixture_path),
+        expected_path.as_deref().map(std::path::Path::new),
+        update.unwrap_or(false),
+    )
+}
+
+// ── Fuzzing ─────────────────────────────────────────────────────────────────
+
+/// Send mutated copies of a seed payload to probe the device's parser. Each
+/// case is emitted as "fuzz_case" with the response collected during its
+/// interval, and "fuzz_done" follows when the run completes or fails. Returns
+/// the generator seed, which replays the same cases when passed back in
+/// `options`.
+#[tauri::command]
+pub async fn fuzz_start(
     app: AppHandle,
     state: State<'_, SharedState>,
-    host: String,
-    port: u16,
-) -> Result<SessionInfo, String> {
-    let state_arc = Arc::clone(&state);
-    let app2 = app.clone();
-    let session_id = format!("{host}:{port}");
-    let sid2 = session_id.clone();
-
-    let conn = socket::connect_tcp(host.clone(), port, move |data| {
-        let mut st = state_arc.lock();
-        let ts = now_ms();
-        let prev_ts = st.packets.last().map(|p| p.timestamp_ms);
-
-        let ss = st.splitter_states.remove(&sid2).unwrap_or_default();
-        let mut splitter = Splitter::with_state(st.splitter.clone(), ss.buf, ss.in_packet);
-        let mut pkts = splitter.feed(&data, "RX", ts, &sid2, &mut st.next_id);
-        let (buf, in_packet) = splitter.into_state();
-        st.splitter_states.insert(sid2.clone(), crate::state::SessionSplitterState { buf, in_packet });
-
-        for pkt in &mut pkts {
-            pkt.gap_ms = prev_ts.map(|pt| ts - pt);
-            if let Some(sess) = st.sessions.get_mut(&sid2) {
-                sess.rx_bytes += pkt.bytes.len() as u64;
-            }
-            st.packets.push(pkt.clone());
-            let _ = app2.emit("packet", pkt.clone());
+    session_id: String,
+    options: FuzzOptions,
+) -> Result<u64, String> {
+    if options.seed.is_empty() || options.mutations.is_empty() {
+        return Err("A seed payload and at least one mutation are required".into());
+    }
+    let rng_seed = options.rng_seed.unwrap_or_else(fuzz::random_seed);
+    let options = FuzzOptions { rng_seed: Some(rng_seed), ..options };
+    let mut tap = tap(&state, &session_id)?;
+    let checksum = {
+        let mut st = state.lock();
+        if let Some(run) = st.fuzzers.remove(&session_id) {
+            run.abort();
         }
-    }).await?;
+        (st.splitter.checksum_offset, st.splitter.checksum_size)
+    };
+
+    let (app2, state2, sid) = (app.clone(), Arc::clone(&state), session_id.clone());
+    let run = tokio::spawn(async move {
+        let send = |bytes| transmit(&app2, &state2, &sid, bytes);
+        let on_case = |case| { journal::emit(&app2, "fuzz_case", case); };
+        let summary = fuzz::run(&mut tap, &send, &sid, &options, checksum, on_case).await;
+        journal::emit(&app2, "fuzz_done", summary);
+    });
+    state.lock().fuzzers.insert(session_id, run);
+    Ok(rng_seed)
+}
+
+#[tauri::command]
+pub fn fuzz_stop(state: State<'_, SharedState>, session_id: String) {
+    if let Some(run) = state.lock().fuzzers.remove(&session_id) {
+        run.abort();
+    }
+}
+
+// ── NTRIP ───────────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub async fn ntrip_source_table(caster: NtripOptions) -> Result<Vec<Mountpoint>, String> {
+    ntrip::source_table(&caster).await
+}
+
+/// Stream RTCM corrections from an NTRIP caster into `target_session` (the
+/// GNSS receiver). The caster stream is a session of its own, so both sides
+/// are logged. With `gga_interval_s`, GGA sentences from the receiver are sent
+/// back to the caster at most that often, as VRS mountpoints require.
+#[tauri::command]
+pub async fn ntrip_connect(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    caster: NtripOptions,
+    target_session: String,
+    gga_interval_s: Option<u64>,
+) -> Result<SessionInfo, String> {
+    if !state.lock().connections.contains_key(&target_session) {
+        return Err(format!("Not connected: {target_session}"));
+    }
+    let session_id = format!("ntrip:{}:{}/{}", caster.host, caster.port, caster.mountpoint);
+    let stream = ntrip::open(&caster).await?;
+
+    let log_rx = rx_handler(app.clone(), Arc::clone(&state), session_id.clone());
+    let (app2, state2, target) = (app.clone(), Arc::clone(&state), target_session.clone());
+    let on_data = move |data: Vec<u8>| {
+        log_rx(data.clone());
+        // Corrections keep flowing into the log even if the receiver went away
+        let _ = transmit(&app2, &state2, &target, data);
+    };
+    let (tx, shutdown, reader) = socket::spawn_io(stream, on_data);
 
     let session = SessionInfo {
         id: session_id.clone(),
-        name: session_id.clone(),
-        kind: "tcp".into(),
+        name: format!("NTRIP {}", caster.mountpoint),
+        kind: "ntrip".into(),
         connected: true,
         tx_bytes: 0,
         rx_bytes: 0,
+        clock_offset_ms: 0.0,
+        line_ending: None,
+        tx_append: TxAppend::None,
+        device_time_offset_ms: None,
+        meta: SessionMeta::default(),
+        identity: None,
     };
-    let mut st = state.lock();
-    st.socket_tx = Some(conn.tx);
-    st.sessions.insert(session_id.clone(), session.clone());
+    let session = {
+        let mut st = state.lock();
+        st.connections.insert(session_id.clone(), tx);
+        st.tcp_shutdown.insert(session_id.clone(), shutdown);
+        st.readers.insert(session_id.clone(), reader);
+        st.insert_session(session)
+    };
+
+    if let Some(interval) = gga_interval_s.filter(|&s| s > 0).map(Duration::from_secs) {
+        let mut tap = tap(&state, &target_session)?;
+        let (app, state) = (app.clone(), Arc::clone(&state));
+        tokio::spawn(async move {
+            let mut last_sent: Option<tokio::time::Instant> = None;
+            // Ends when the receiver disconnects (tap closes) or the caster
+            // session is gone (transmit fails)
+            while let Ok((_, line)) = tap.expect(&[b"\n"], None).await {
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim();
+                if !ntrip::is_gga(line) || last_sent.is_some_and(|t| t.elapsed() < interval) {
+                    continue;
+                }
+                if transmit(&app, &state, &session_id, format!("{line}\r\n").into_bytes()).is_err() {
+                    break;
+                }
+                last_sent = Some(tokio::time::Instant::now());
+            }
+        });
+    }
     Ok(session)
 }
 
+// ── Mirroring ───────────────────────────────────────────────────────────────
+
+#[derive(serde::Serialize, Clone)]
+pub struct MirrorDataEvent {
+    pub mirror_id: u64,
+    pub session_id: String,
+    pub bytes: Vec<u8>,
+}
+
+/// A line a command mirror's program printed.
+#[derive(serde::Serialize, Clone)]
+pub struct MirrorAnnotationEvent {
+    pub mirror_id: u64,
+    pub session_id: String,
+    pub timestamp_ms: f64,
+    pub text: String,
+}
+
+/// Copy a session's RX and/or TX stream (RX by default) to a file, a UDP
+/// endpoint, another window or an external program alongside the normal
+/// view. Lines an annotating program prints are noted in the session log
+/// and emitted as "mirror_annotation".
 #[tauri::command]
-pub fn disconnect(state: State<'_, SharedState>, session_id: String) {
-    let mut st = state.lock();
-    if let Some(sess) = st.sessions.get_mut(&session_id) {
-        sess.connected = false;
+pub async fn mirror_attach(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    session_id: String,
+    target: MirrorTarget,
+    direction: Option<MirrorDirection>,
+) -> Result<MirrorInfo, String> {
+    let direction = direction.unwrap_or_default();
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    let id = {
+        let mut st = state.lock();
+        if !st.connections.contains_key(&session_id) {
+            return Err("Not connected".into());
+        }
+        if direction.rx() {
+            st.rx_taps.entry(session_id.clone()).or_default().push(tx.clone());
+        }
+        if direction.tx() {
+            st.tx_taps.entry(session_id.clone()).or_default().push(tx);
+        }
+        st.next_mirror_id += 1;
+        st.next_mirror_id - 1
+    };
+    let on_annotation = {
+        let program = match &target {
+            MirrorTarget::Command { program, .. } => program.clone(),
+            _ => String::new(),
+        };
+        let (app, state, sid) = (app.clone(), Arc::clone(&state), session_id.clone());
+        move |text: String| {
+            let timestamp_ms = now_ms();
+            write_log(&app, &mut state.lock(), &sid, |log| log.write_note(timestamp_ms, &format!("{program}: {text}")));
+            journal::emit(&app, "mirror_annotation", MirrorAnnotationEvent { mirror_id: id, session_id: sid.clone(), timestamp_ms, text });
+        }
+    };
+    let to_window = {
+        let label = match &target {
+            MirrorTarget::Window { label } => label.clone(),
+            _ => String::new(),
+        };
+        let sid = session_id.clone();
+        move |bytes: Vec<u8>| {
+            let _ = app.emit_to(label.as_str(), "mirror_data", MirrorDataEvent { mirror_id: id, session_id: sid.clone(), bytes });
+        }
+    };
+    let mirror = mirror::start(session_id, target, direction, rx, to_window, on_annotation).await?;
+    let info = mirror.info(id);
+    state.lock().mirrors.insert(id, mirror);
+    Ok(info)
+}
+
+#[tauri::command]
+pub fn mirror_detach(state: State<'_, SharedState>, id: u64) -> Result<(), String> {
+    let mirror = state.lock().mirrors.remove(&id).ok_or("Unknown mirror")?;
+    // Aborting drops the receiver, which detaches it from the session
+    mirror.task.abort();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn mirror_list(state: State<'_, SharedState>) -> Vec<MirrorInfo> {
+    let st = state.lock();
+    let mut list: Vec<_> = st.mirrors.iter().map(|(id, m)| m.info(*id)).collect();
+    list.sort_by_key(|m| m.id);
+    list
+}
+
+// ── Bridge ──────────────────────────────────────────────────────────────────
+
+/// Share a serial port with TCP clients on the network, like ser2net: open
+/// `port` at `baud` (default 115200) unless it is already open, then listen
+/// with `options`. What clients send is written to the port and what the
+/// port receives goes to every client; both directions are logged on the
+/// port's session, and clients coming and going as "bridge" events.
+#[tauri::command]
+pub async fn bridge_start(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    port: String,
+    baud: Option<u32>,
+    options: ServerOptions,
+) -> Result<BridgeInfo, String> {
+    let open = {
+        let st = state.lock();
+        if st.bridges.contains_key(&port) {
+            return Err(format!("{port} is already bridged"));
+        }
+        st.connections.contains_key(&port)
+    };
+    if !open {
+        open_serial_session(&app, &state, port.clone(), baud.unwrap_or(115_200), SerialSettings::default(), port.clone())?;
     }
-    st.serial_tx = None;
-    st.socket_tx = None;
+    let (app2, state2, sid) = (app.clone(), Arc::clone(&state), port.clone());
+    let listener = match server::start(options, move |_, admission| bridge_client(&app2, &state2, &sid, admission)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            // Leave the port as it was found
+            if !open {
+                disconnect(app, state, port);
+            }
+            return Err(e);
+        }
+    };
+
+    let bridge = Bridge::new(listener);
+    let info = bridge.info(&port);
+    let mut st = state.lock();
+    sys_event(&app, &mut st, SysEvent::new(&port, "bridge", "listening").with("local_addr", info.local_addr.clone()));
+    st.bridges.insert(port, bridge);
+    Ok(info)
+}
+
+fn bridge_client(app: &AppHandle, state: &SharedState, session_id: &str, admission: Admission) {
+    let (stream, peer, pending, slot) = match admission {
+        Admission::Accepted { stream, peer, pending, slot } => (stream, peer, pending, slot),
+        Admission::Rejected { peer, reason } => {
+            let ev = SysEvent::new(session_id, "bridge", "rejected").with("peer", peer.to_string()).with("reason", reason);
+            sys_event(app, &mut state.lock(), ev);
+            return;
+        }
+    };
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    let stopped = {
+        let mut st = state.lock();
+        let Some(stopped) = st.bridges.get(session_id).map(Bridge::stopped) else { return };
+        st.rx_taps.entry(session_id.to_string()).or_default().push(tx);
+        sys_event(app, &mut st, SysEvent::new(session_id, "bridge", "connected").with("peer", peer.to_string()));
+        stopped
+    };
+    let (app, state, sid) = (app.clone(), Arc::clone(state), session_id.to_string());
+    tokio::spawn(async move {
+        let forward = |data: Vec<u8>| {
+            let _ = queue(&app, &state, &sid, data, None);
+        };
+        if !pending.is_empty() {
+            forward(pending);
+        }
+        bridge::serve_client(stream, rx, stopped, forward).await;
+        drop(slot);
+        sys_event(&app, &mut state.lock(), SysEvent::new(&sid, "bridge", "disconnected").with("peer", peer.to_string()));
+    });
 }
 
+/// Stop sharing a session and disconnect its clients; the session stays open.
 #[tauri::command]
-pub fn send_bytes(state: State<'_, SharedState>, app: AppHandle, hex: String, session_id: String) -> Result<(), String> {
-    let bytes = hex_to_bytes(&hex)?;
-    let ts = now_ms();
+pub fn bridge_stop(app: AppHandle, state: State<'_, SharedState>, session_id: String) -> Result<(), String> {
     let mut st = state.lock();
-    let prev_ts = st.packets.last().map(|p| p.timestamp_ms);
+    st.bridges.remove(&session_id).ok_or("Not bridged")?;
+    sys_event(&app, &mut st, SysEvent::new(&session_id, "bridge", "stopped"));
+    Ok(())
+}
 
-    if let Some(tx) = &st.serial_tx {
-        tx.send(bytes.clone()).map_err(|e| e.to_string())?;
-    } else if let Some(tx) = &st.socket_tx {
-        tx.send(bytes.clone()).map_err(|e| e.to_string())?;
-    } else {
-        return Err("Not connected".into());
+#[tauri::command]
+pub fn bridge_list(state: State<'_, SharedState>) -> Vec<BridgeInfo> {
+    let st = state.lock();
+    let mut list: Vec<_> = st.bridges.iter().map(|(sid, b)| b.info(sid)).collect();
+    list.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+    list
+}
+
+// ── Baud detection ──────────────────────────────────────────────────────────
+
+/// How long each rate is listened to while probing.
+const PROBE_STEP_MS: u64 = 400;
+
+/// Emitted when a serial session keeps receiving what looks like framing errors.
+#[derive(serde::Serialize, Clone)]
+pub struct BaudMismatchEvent {
+    pub session_id: String,
+    pub baud: u32,
+    /// Fraction of bad bytes in the last window.
+    pub bad_ratio: f64,
+    pub suggested_baud: u32,
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct BaudChangedEvent {
+    pub session_id: String,
+    pub from: u32,
+    pub to: u32,
+}
+
+/// Called from the RX path when the baud watch fires.
+fn on_baud_mismatch(app: &AppHandle, state: &SharedState, st: &AppState, session_id: &str, bad_ratio: f64) {
+    let Some(baud) = st.serial_controls.get(session_id).and_then(|c| c.baud_rate().ok()) else { return };
+    match st.baud_watch.config.action {
+        MismatchAction::Hint => {
+            sys_events::emit(app, "baud_mismatch", format!("{session_id}\n{baud}"), BaudMismatchEvent {
+                session_id: session_id.to_string(),
+                baud,
+                bad_ratio,
+                suggested_baud: baud::suggest(baud),
+            });
+        }
+        MismatchAction::AutoBaud => {
+            let (app, state, sid) = (app.clone(), Arc::clone(state), session_id.to_string());
+            tokio::spawn(async move {
+                let _ = run_auto_baud(&app, &state, &sid).await;
+            });
+        }
     }
+}
 
-    let id = st.next_id;
-    st.next_id += 1;
-    let pkt = crate::state::Packet {
-        id,
-        timestamp_ms: ts,
-        gap_ms: prev_ts.map(|pt| ts - pt),
-        direction: "TX".into(),
-        bytes: bytes.clone(),
-        checksum_ok: None,
-        session_id: session_id.clone(),
+/// Listen at each common rate in turn and settle on the one that reads
+/// cleanly. Needs the device to be transmitting meanwhile.
+async fn run_auto_baud(app: &AppHandle, state: &SharedState, session_id: &str) -> Result<u32, String> {
+    let set_rate = |rate: u32| -> Result<(), String> {
+        let st = state.lock();
+        st.serial_controls.get(session_id).ok_or("Not an open serial session")?.set_baud_rate(rate)
     };
-    if let Some(sess) = st.sessions.get_mut(&session_id) {
-        sess.tx_bytes += bytes.len() as u64;
+    let original = {
+        let mut st = state.lock();
+        let baud = st.serial_controls.get(session_id).ok_or("Not an open serial session")?.baud_rate()?;
+        if !st.baud_watch.begin_probe(session_id) {
+            return Err("Auto-baud is already running on this session".into());
+        }
+        baud
+    };
+
+    let mut samples = Vec::new();
+    let mut probed = Ok(());
+    for rate in baud::COMMON_RATES {
+        if let Err(e) = set_rate(rate) {
+            probed = Err(e);
+            break;
+        }
+        state.lock().baud_watch.take_sample(session_id);
+        tokio::time::sleep(Duration::from_millis(PROBE_STEP_MS)).await;
+        samples.push((rate, state.lock().baud_watch.take_sample(session_id)));
     }
-    st.packets.push(pkt.clone());
-    let _ = app.emit("packet", &pkt);
-    Ok(())
+
+    let threshold = state.lock().baud_watch.config.threshold;
+    let picked = probed.and_then(|_| baud::best(&samples, threshold).ok_or_else(|| "No rate produced readable data".to_string()));
+    let applied = set_rate(*picked.as_ref().unwrap_or(&original));
+    state.lock().baud_watch.end_probe(session_id);
+    let baud = picked?;
+    applied?;
+    if baud != original {
+        journal::emit(app, "baud_changed", BaudChangedEvent { session_id: session_id.to_string(), from: original, to: baud });
+    }
+    Ok(baud)
 }
 
 #[tauri::command]
-pub fn get_packets(state: State<'_, SharedState>) -> Vec<crate::state::Packet> {
-    state.lock().packets.clone()
+pub async fn auto_baud(app: AppHandle, state: State<'_, SharedState>, session_id: String) -> Result<u32, String> {
+    run_auto_baud(&app, &state, &session_id).await
 }
 
+/// Change the rate of an open serial session, local or behind an RFC 2217
+/// terminal server.
 #[tauri::command]
-pub fn clear_packets(state: State<'_, SharedState>) {
+pub fn set_baud_rate(state: State<'_, SharedState>, session_id: String, baud: u32) -> Result<(), String> {
     let mut st = state.lock();
-    st.packets.clear();
-    st.next_id = 1;
+    if st.com_ports.contains_key(&session_id) {
+        com_port_send(&st, &session_id, rfc2217::set_baud(baud))?;
+        if let Some(saved) = reopen_com_port(&mut st, &session_id) {
+            saved.baud = baud;
+        }
+    } else {
+        st.serial_controls.get(&session_id).ok_or("Not an open serial session")?.set_baud_rate(baud)?;
+    }
+    st.baud_watch.reset(&session_id);
+    Ok(())
 }
 
 #[tauri::command]
-pub fn get_sessions(state: State<'_, SharedState>) -> Vec<SessionInfo> {
-    state.lock().sessions.values().cloned().collect()
+pub fn set_baud_watch(state: State<'_, SharedState>, config: BaudWatchConfig) {
+    state.lock().baud_watch.set_config(config);
 }
 
 #[tauri::command]
-pub fn set_splitter(state: State<'_, SharedState>, config: SplitterConfig) {
-    let mut st = state.lock();
-    st.splitter = config;
-    st.splitter_states.clear(); // reset per-session buffers on config change
+pub fn get_baud_watch(state: State<'_, SharedState>) -> BaudWatchConfig {
+    state.lock().baud_watch.config.clone()
 }
 
+// ── Compare ─────────────────────────────────────────────────────────────────
+
+/// Align the RX packets of two sessions and emit "compare_divergence" events
+/// where they differ. Replaces any comparison already running.
 #[tauri::command]
-pub fn get_splitter(state: State<'_, SharedState>) -> SplitterConfig {
-    state.lock().splitter.clone()
+pub fn compare_start(state: State<'_, SharedState>, options: CompareOptions) -> Result<(), String> {
+    let mut st = state.lock();
+    if options.a == options.b {
+        return Err("Pick two different sessions".into());
+    }
+    for id in [&options.a, &options.b] {
+        if !st.sessions.contains_key(id) {
+            return Err(format!("Unknown session: {id}"));
+        }
+    }
+    st.compare = Some(Comparator::new(options));
+    Ok(())
 }
 
 #[tauri::command]
-pub fn compute_checksum(algo: String, hex: String) -> Result<ChecksumResult, String> {
-    let data = hex_to_bytes(&hex)?;
-    let v = checksum::compute(&algo, &data);
-    let width = match algo.as_str() {
-        "crc32" => 8,
-        "sum8" | "xor" => 2,
-        _ => 4,
-    };
-    Ok(ChecksumResult {
-        algorithm: algo,
-        value: v,
-        hex: format!("{v:0>width$X}"),
-    })
+pub fn compare_stop(state: State<'_, SharedState>) -> Option<CompareStatus> {
+    state.lock().compare.take().map(|c| c.status())
 }
 
 #[tauri::command]
-pub fn compute_all_checksums(hex: String) -> Result<Vec<ChecksumResult>, String> {
-    let data = hex_to_bytes(&hex)?;
-    Ok(checksum::compute_all(&data))
+pub fn compare_status(state: State<'_, SharedState>) -> Option<CompareStatus> {
+    state.lock().compare.as_ref().map(Comparator::status)
 }
 
-#[tauri::command]
-pub fn get_timing_stats(state: State<'_, SharedState>) -> TimingStats {
-    let st = state.lock();
-    let packets = &st.packets;
-    let total_packets = packets.len() as u64;
-    let total_bytes: u64 = packets.iter().map(|p| p.bytes.len() as u64).sum();
+// ── Capabilities ────────────────────────────────────────────────────────────
 
-    let gaps: Vec<f64> = packets.iter().filter_map(|p| p.gap_ms).collect();
-    let n = gaps.len() as f64;
-    let avg_gap_ms = if n > 0.0 { gaps.iter().sum::<f64>() / n } else { 0.0 };
-    let min_gap_ms = gaps.iter().cloned().fold(f64::INFINITY, f64::min);
-    let max_gap_ms = gaps.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
-    let variance = if n > 1.0 {
-        gaps.iter().map(|g| (g - avg_gap_ms).powi(2)).sum::<f64>() / n
-    } else { 0.0 };
-    let std_gap_ms = variance.sqrt();
+/// Transports, decoders and platform features available in this build, so
+/// clients can hide what would only fail at runtime.
+#[tauri::command]
+pub fn get_capabilities() -> Capabilities {
+    capabilities::detect()
+}
 
-    let checksum_pass = packets.iter().filter(|p| p.checksum_ok == Some(true)).count() as u64;
-    let checksum_fail = packets.iter().filter(|p| p.checksum_ok == Some(false)).count() as u64;
+// ── Diagnostics ─────────────────────────────────────────────────────────────
 
-    TimingStats {
-        total_packets,
-        total_bytes,
-        avg_gap_ms,
-        min_gap_ms: if min_gap_ms.is_infinite() { 0.0 } else { min_gap_ms },
-        max_gap_ms: if max_gap_ms.is_infinite() { 0.0 } else { max_gap_ms },
-        std_gap_ms,
-        cycle_count: 0,
-        avg_cycle_ms: 0.0,
-        avg_idle_ms: 0.0,
-        checksum_pass,
-        checksum_fail,
+/// Check serial driver and permission problems, writability of the app data
+/// directory and `log_dir`, and firewall hints for running TCP servers.
+#[tauri::command]
+pub async fn diagnostics(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    log_dir: Option<String>,
+) -> Result<Vec<Finding>, String> {
+    let mut dirs = Vec::new();
+    if let Ok(dir) = app.path().app_data_dir() {
+        dirs.push(dir);
     }
+    dirs.extend(log_dir.map(std::path::PathBuf::from));
+    let listeners: Vec<_> = state.lock().servers.iter()
+        .map(|(id, l)| (id.clone(), l.local_addr))
+        .collect();
+    tokio::task::spawn_blocking(move || {
+        let dirs: Vec<&std::path::Path> = dirs.iter().map(|d| d.as_path()).collect();
+        diagnostics::run(&dirs, &listeners)
+    })
+    .await
+    .map_err(|e| e.to_string())
 }
 
-#[tauri::command]
-pub async fn export_packets(app: AppHandle, json: String, ext: Option<String>) -> Result<String, String> {
-    let ts = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-
-    let ext = ext.as_deref().unwrap_or("json");
-    let filter_label = match ext {
-        "csv" => "CSV",
-        "txt" => "Text",
-        "log" => "Log",
-        _     => "JSON",
-    };
-
-    // Use async save_file + oneshot to avoid blocking the main thread event loop
-    let (tx, rx) = tokio::sync::oneshot::channel();
-    app.dialog()
-        .file()
-        .add_filter(filter_label, &[ext])
-        .set_file_name(&format!("wirescope-{ts}.{ext}"))
-        .save_file(move |path| { let _ = tx.send(path); });
+// ── Shutdown ────────────────────────────────────────────────────────────────
 
-    let file_path = rx.await
-        .map_err(|_| "다이얼로그 오류".to_string())?
-        .ok_or_else(|| "취소됨".to_string())?;
+/// How long exiting waits for the serial I/O threads to let go of their ports.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
 
-    let path = file_path.as_path()
-        .ok_or_else(|| "잘못된 경로".to_string())?
-        .to_path_buf();
-    std::fs::write(&path, json.as_bytes()).map_err(|e| e.to_string())?;
-    Ok(path.to_string_lossy().into_owned())
+/// Close everything before the process exits: end each session's log with its
+/// summary and record it in the history, stop servers, devices and background
+/// tasks, and wait for serial ports and log manifests to be released.
+pub fn shutdown(app: &AppHandle) {
+    let state = app.state::<SharedState>();
+    let (summaries, workers) = {
+        let mut st = state.lock();
+        let workers: Vec<_> = st.serial_controls.values_mut().flat_map(SerialControl::take_workers).collect();
+        let ids: Vec<String> = st.sessions.keys().cloned().collect();
+        let summaries: Vec<_> = ids.iter().filter_map(|id| close_session(&mut st, id)).collect();
+        st.logs.clear();
+        st.servers.clear();
+        st.devices.clear();
+        for (_, mirror) in st.mirrors.drain() {
+            mirror.task.abort();
+        }
+        for (_, fuzzer) in st.fuzzers.drain() {
+            fuzzer.abort();
+        }
+        if let Some(watch) = st.alert_watch.take() {
+            watch.abort();
+        }
+        (summaries, workers)
+    };
+    // Not on a background thread like `closed_summary`: the process is about to go
+    if let Ok(dir) = app.path().app_data_dir() {
+        for summary in summaries {
+            let _ = SessionHistory::record(&dir, summary);
+        }
+    }
+    let _ = tauri::async_runtime::block_on(tokio::time::timeout(SHUTDOWN_TIMEOUT, async {
+        for worker in workers {
+            let _ = worker.await;
+        }
+    }));
+    session_log::wait_for_manifests();
 }
 
 // ── OTA Update ──────────────────────────────────────────────────────────────
@@ -342,14 +4048,3 @@ pub async fn install_update(
 
     app.restart();
 }
-
-fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, String> {
-    let hex = hex.replace([' ', ':', '-'], "");
-    if hex.len() % 2 != 0 {
-        return Err("Odd-length hex string".into());
-    }
-    (0..hex.len())
-        .step_by(2)
-        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| e.to_string()))
-        .collect()
-}
@@ -0,0 +1,168 @@
+use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use crate::state::Packet;
+
+/// Compare the RX packets of a reference session against a device under test.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompareOptions {
+    /// Reference (golden) session.
+    pub a: String,
+    /// Session being checked against `a`.
+    pub b: String,
+    /// How long one side may wait for its counterpart before it counts as missing.
+    #[serde(default = "default_max_skew_ms")]
+    pub max_skew_ms: f64,
+    /// Matching packets preceding a divergence to include in the event.
+    #[serde(default = "default_context")]
+    pub context: usize,
+}
+
+fn default_max_skew_ms() -> f64 { 1000.0 }
+fn default_context() -> usize { 3 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DivergenceKind {
+    /// Both sides produced a packet but the bytes differ.
+    Mismatch,
+    /// `a` produced a packet with no counterpart on `b` in time.
+    MissingB,
+    /// `b` produced a packet with no counterpart on `a` in time.
+    MissingA,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Divergence {
+    pub kind: DivergenceKind,
+    /// Position in the aligned stream.
+    pub index: u64,
+    pub a: Option<Packet>,
+    pub b: Option<Packet>,
+    /// b timestamp minus a timestamp.
+    pub skew_ms: Option<f64>,
+    /// Offset of the first differing byte (mismatch only).
+    pub first_diff: Option<usize>,
+    /// Last matching packets before the divergence, oldest first.
+    pub context: Vec<Packet>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompareStatus {
+    pub a: String,
+    pub b: String,
+    pub matched: u64,
+    pub mismatched: u64,
+    pub missing_a: u64,
+    pub missing_b: u64,
+    pub avg_skew_ms: f64,
+    pub max_skew_ms: f64,
+}
+
+pub struct Comparator {
+    opts: CompareOptions,
+    pending_a: VecDeque<Packet>,
+    pending_b: VecDeque<Packet>,
+    recent: VecDeque<Packet>,
+    index: u64,
+    matched: u64,
+    mismatched: u64,
+    missing_a: u64,
+    missing_b: u64,
+    skew_sum: f64,
+    skew_max: f64,
+}
+
+impl Comparator {
+    pub fn new(opts: CompareOptions) -> Self {
+        Self {
+            opts,
+            pending_a: VecDeque::new(),
+            pending_b: VecDeque::new(),
+            recent: VecDeque::new(),
+            index: 0,
+            matched: 0,
+            mismatched: 0,
+            missing_a: 0,
+            missing_b: 0,
+            skew_sum: 0.0,
+            skew_max: 0.0,
+        }
+    }
+
+    /// Feed one RX packet; packets from other sessions are ignored.
+    pub fn push(&mut self, pkt: &Packet) -> Vec<Divergence> {
+        if pkt.session_id == self.opts.a {
+            self.pending_a.push_back(pkt.clone());
+        } else if pkt.session_id == self.opts.b {
+            self.pending_b.push_back(pkt.clone());
+        } else {
+            return Vec::new();
+        }
+        let now = pkt.timestamp_ms;
+        let mut out = Vec::new();
+
+        loop {
+            match (self.pending_a.front(), self.pending_b.front()) {
+                (Some(_), Some(_)) => {
+                    let (a, b) = (self.pending_a.pop_front().unwrap(), self.pending_b.pop_front().unwrap());
+                    let skew = b.timestamp_ms - a.timestamp_ms;
+                    if a.bytes == b.bytes {
+                        self.matched += 1;
+                        self.skew_sum += skew;
+                        self.skew_max = self.skew_max.max(skew.abs());
+                        self.recent.push_back(a);
+                        while self.recent.len() > self.opts.context {
+                            self.recent.pop_front();
+                        }
+                    } else {
+                        self.mismatched += 1;
+                        let first_diff = a.bytes.iter().zip(&b.bytes).position(|(x, y)| x != y)
+                            .unwrap_or(a.bytes.len().min(b.bytes.len()));
+                        out.push(self.divergence(DivergenceKind::Mismatch, Some(a), Some(b), Some(skew), Some(first_diff)));
+                    }
+                    self.index += 1;
+                }
+                // One side is ahead: give the other until max_skew_ms to catch up
+                (Some(a), None) if now - a.timestamp_ms > self.opts.max_skew_ms => {
+                    let a = self.pending_a.pop_front();
+                    self.missing_b += 1;
+                    out.push(self.divergence(DivergenceKind::MissingB, a, None, None, None));
+                    self.index += 1;
+                }
+                (None, Some(b)) if now - b.timestamp_ms > self.opts.max_skew_ms => {
+                    let b = self.pending_b.pop_front();
+                    self.missing_a += 1;
+                    out.push(self.divergence(DivergenceKind::MissingA, None, b, None, None));
+                    self.index += 1;
+                }
+                _ => break,
+            }
+        }
+        out
+    }
+
+    fn divergence(
+        &mut self,
+        kind: DivergenceKind,
+        a: Option<Packet>,
+        b: Option<Packet>,
+        skew_ms: Option<f64>,
+        first_diff: Option<usize>,
+    ) -> Divergence {
+        let context = self.recent.drain(..).collect();
+        Divergence { kind, index: self.index, a, b, skew_ms, first_diff, context }
+    }
+
+    pub fn status(&self) -> CompareStatus {
+        CompareStatus {
+            a: self.opts.a.clone(),
+            b: self.opts.b.clone(),
+            matched: self.matched,
+            mismatched: self.mismatched,
+            missing_a: self.missing_a,
+            missing_b: self.missing_b,
+            avg_skew_ms: if self.matched > 0 { self.skew_sum / self.matched as f64 } else { 0.0 },
+            max_skew_ms: self.skew_max,
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::decoder::{self, Decoded};
+
+/// Decode a DALI frame as a serial interface relays it: two bytes for a
+/// forward frame to control gear, one for a backward frame (a reply) and
+/// three for a 24-bit DALI-2 frame to control devices. DALI carries no
+/// checksum, so only the length is checked.
+pub fn decode(bytes: &[u8]) -> Decoded {
+    match *bytes {
+        [answer] => Decoded { message: "Answer".into(), ok: true, ..Default::default() }
+            .with("frame", "backward")
+            .with("value", answer),
+        [address, opcode] => forward(address, opcode),
+        [address, instance, opcode] => {
+            let (target, selector) = target(address);
+            // Without the selector bit the frame is an input device's event
+            let message = if selector { format!("Device command {opcode:02X}") } else { "Event".into() };
+            Decoded { message, ok: true, ..Default::default() }
+                .with("frame", "forward24")
+                .with("address", target)
+                .with("instance", instance)
+                .with("opcode", format!("{opcode:02X}"))
+        }
+        _ => decoder::malformed(),
+    }
+}
+
+/// Where an address byte points and whether its selector bit marks a
+/// command rather than a level.
+fn target(address: u8) -> (String, bool) {
+    let selector = address & 0x01 != 0;
+    let target = match address >> 1 {
+        a @ 0..=63 => format!("short {a}"),
+        g @ 64..=79 => format!("group {}", g - 64),
+        0x7E => "broadcast unaddressed".into(),
+        0x7F => "broadcast".into(),
+        _ => "special".into(),
+    };
+    (target, selector)
+}
+
+/// A 16-bit forward frame: direct arc power, a command or, for addresses
+/// 0xA1-0xFB, a special command carrying data.
+fn forward(address: u8, opcode: u8) -> Decoded {
+    let out = Decoded { ok: true, ..Default::default() }.with("frame", "forward");
+    if (0xA1..=0xFB).contains(&address) && address & 0x01 != 0 {
+        let message = special_name(address).map_or_else(|| format!("Reserved {address:02X}"), String::from);
+        return Decoded { message, ..out }.with("data", opcode);
+    }
+    let (target, selector) = target(address);
+    let out = out.with("address", target);
+    if !selector {
+        // Direct arc power control; 0xFF stops a fade instead
+        let message = if opcode == 0xFF { "MASK" } else { "DAPC" };
+        return Decoded { message: message.into(), ..out }.with("level", opcode);
+    }
+    let (name, index) = command_name(opcode);
+    let out = Decoded { message: name.into(), ..out }.with("opcode", format!("{opcode:02X}"));
+    match index {
+        Some(index) => out.with("index", index),
+        None => out,
+    }
+}
+
+/// Name of a command to control gear, and the scene or group number of the
+/// ranges that carry one.
+fn command_name(opcode: u8) -> (&'static str, Option<u8>) {
+    let name = match opcode {
+        0x00 => "OFF",
+        0x01 => "UP",
+        0x02 => "DOWN",
+        0x03 => "STEP UP",
+        0x04 => "STEP DOWN",
+        0x05 => "RECALL MAX LEVEL",
+        0x06 => "RECALL MIN LEVEL",
+        0x07 => "STEP DOWN AND OFF",
+        0x08 => "ON AND STEP UP",
+        0x09 => "ENABLE DAPC SEQUENCE",
+        0x0A => "GO TO LAST ACTIVE LEVEL",
+        0x10..=0x1F => return ("GO TO SCENE", Some(opcode & 0x0F)),
+        0x20 => "RESET",
+        0x21 => "STORE ACTUAL LEVEL IN DTR0",
+        0x22 => "SAVE PERSISTENT VARIABLES",
+        0x23 => "SET OPERATING MODE",
+        0x24 => "RESET MEMORY BANK",
+        0x25 => "IDENTIFY DEVICE",
+        0x2A => "SET MAX LEVEL",
+        0x2B => "SET MIN LEVEL",
+        0x2C => "SET SYSTEM FAILURE LEVEL",
+        0x2D => "SET POWER ON LEVEL",
+        0x2E => "SET FADE TIME",
+        0x2F => "SET FADE RATE",
+        0x30 => "SET EXTENDED FADE TIME",
+        0x40..=0x4F => return ("SET SCENE", Some(opcode & 0x0F)),
+        0x50..=0x5F => return ("REMOVE FROM SCENE", Some(opcode & 0x0F)),
+        0x60..=0x6F => return ("ADD TO GROUP", Some(opcode & 0x0F)),
+        0x70..=0x7F => return ("REMOVE FROM GROUP", Some(opcode & 0x0F)),
+        0x80 => "SET SHORT ADDRESS",
+        0x81 => "ENABLE WRITE MEMORY",
+        0x90 => "QUERY STATUS",
+        0x91 => "QUERY CONTROL GEAR PRESENT",
+        0x92 => "QUERY LAMP FAILURE",
+        0x93 => "QUERY LAMP POWER ON",
+        0x94 => "QUERY LIMIT ERROR",
+        0x95 => "QUERY RESET STATE",
+        0x96 => "QUERY MISSING SHORT ADDRESS",
+        0x97 => "QUERY VERSION NUMBER",
+        0x98 => "QUERY CONTENT DTR0",
+        0x99 => "QUERY DEVICE TYPE",
+        0x9A => "QUERY PHYSICAL MINIMUM",
+        0x9B => "QUERY POWER FAILURE",
+        0x9C => "QUERY CONTENT DTR1",
+        0x9D => "QUERY CONTENT DTR2",
+        0x9E => "QUERY OPERATING MODE",
+        0x9F => "QUERY LIGHT SOURCE TYPE",
+        0xA0 => "QUERY ACTUAL LEVEL",
+        0xA1 => "QUERY MAX LEVEL",
+        0xA2 => "QUERY MIN LEVEL",
+        0xA3 => "QUERY POWER ON LEVEL",
+        0xA4 => "QUERY SYSTEM FAILURE LEVEL",
+        0xA5 => "QUERY FADE TIME/FADE RATE",
+        0xA6 => "QUERY MANUFACTURER SPECIFIC MODE",
+        0xA7 => "QUERY NEXT DEVICE TYPE",
+        0xA8 => "QUERY EXTENDED FADE TIME",
+        0xAA => "QUERY CONTROL GEAR FAILURE",
+        0xB0..=0xBF => return ("QUERY SCENE LEVEL", Some(opcode & 0x0F)),
+        0xC0 => "QUERY GROUPS 0-7",
+        0xC1 => "QUERY GROUPS 8-15",
+        0xC2 => "QUERY RANDOM ADDRESS (H)",
+        0xC3 => "QUERY RANDOM ADDRESS (M)",
+        0xC4 => "QUERY RANDOM ADDRESS (L)",
+        0xC5 => "READ MEMORY LOCATION",
+        0xE0..=0xFE => "APPLICATION EXTENDED COMMAND",
+        0xFF => "QUERY EXTENDED VERSION NUMBER",
+        _ => "RESERVED",
+    };
+    (name, None)
+}
+
+/// Special commands, addressed by their first byte and broadcast to all
+/// control gear.
+fn special_name(address: u8) -> Option<&'static str> {
+    Some(match address {
+        0xA1 => "TERMINATE",
+        0xA3 => "DTR0",
+        0xA5 => "INITIALISE",
+        0xA7 => "RANDOMISE",
+        0xA9 => "COMPARE",
+        0xAB => "WITHDRAW",
+        0xAD => "PING",
+        0xB1 => "SEARCHADDRH",
+        0xB3 => "SEARCHADDRM",
+        0xB5 => "SEARCHADDRL",
+        0xB7 => "PROGRAM SHORT ADDRESS",
+        0xB9 => "VERIFY SHORT ADDRESS",
+        0xBB => "QUERY SHORT ADDRESS",
+        0xC1 => "ENABLE DEVICE TYPE",
+        0xC3 => "DTR1",
+        0xC5 => "DTR2",
+        0xC7 => "WRITE MEMORY LOCATION",
+        0xC9 => "WRITE MEMORY LOCATION - NO REPLY",
+        _ => return None,
+    })
+}
@@ -0,0 +1,132 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::net::SocketAddr;
+use serde::{Deserialize, Serialize};
+
+/// Sequence numbers remembered for duplicate detection.
+const RECENT: usize = 1024;
+
+/// Where a datagram carries its sequence number.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SeqRule {
+    pub offset: usize,
+    /// Field width in bytes, 1 to 8. The counter is assumed to wrap at this width.
+    pub width: usize,
+    pub big_endian: bool,
+}
+
+impl Default for SeqRule {
+    fn default() -> Self {
+        Self { offset: 0, width: 2, big_endian: true }
+    }
+}
+
+impl SeqRule {
+    fn read(&self, data: &[u8]) -> Option<u64> {
+        let field = data.get(self.offset..self.offset.checked_add(self.width)?)?;
+        let fold = |acc: u64, &b: &u8| (acc << 8) | b as u64;
+        Some(if self.big_endian {
+            field.iter().fold(0, fold)
+        } else {
+            field.iter().rev().fold(0, fold)
+        })
+    }
+
+    fn mask(&self) -> u64 {
+        if self.width >= 8 { u64::MAX } else { (1 << (self.width * 8)) - 1 }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DatagramStats {
+    pub session_id: String,
+    pub datagrams: u64,
+    pub bytes: u64,
+    pub min_size: usize,
+    pub max_size: usize,
+    pub avg_size: f64,
+    /// Datagrams that filled the whole receive buffer.
+    pub truncated: u64,
+    /// The sequence fields below stay 0 without a rule.
+    pub out_of_order: u64,
+    pub duplicates: u64,
+    /// Sequence numbers skipped and not seen since.
+    pub missing: u64,
+    /// Datagrams per sender address.
+    pub senders: BTreeMap<String, u64>,
+}
+
+#[derive(Default)]
+pub struct DatagramCounter {
+    rule: Option<SeqRule>,
+    stats: DatagramStats,
+    highest: Option<u64>,
+    recent: VecDeque<u64>,
+}
+
+impl DatagramCounter {
+    /// Change the sequencing rule; sequence tracking starts over.
+    pub fn set_rule(&mut self, rule: Option<SeqRule>) -> Result<(), String> {
+        if rule.is_some_and(|r| !(1..=8).contains(&r.width)) {
+            return Err("Sequence width must be 1 to 8 bytes".into());
+        }
+        self.rule = rule;
+        self.highest = None;
+        self.recent.clear();
+        Ok(())
+    }
+
+    pub fn record(&mut self, data: &[u8], from: Option<SocketAddr>, truncated: bool) {
+        let s = &mut self.stats;
+        if let Some(from) = from {
+            *s.senders.entry(from.to_string()).or_default() += 1;
+        }
+        s.min_size = if s.datagrams == 0 { data.len() } else { s.min_size.min(data.len()) };
+        s.max_size = s.max_size.max(data.len());
+        s.datagrams += 1;
+        s.bytes += data.len() as u64;
+        s.avg_size = s.bytes as f64 / s.datagrams as f64;
+        if truncated {
+            s.truncated += 1;
+        }
+        if let Some((seq, mask)) = self.rule.and_then(|r| Some((r.read(data)?, r.mask()))) {
+            self.sequence(seq, mask);
+        }
+    }
+
+    fn sequence(&mut self, seq: u64, mask: u64) {
+        if self.recent.contains(&seq) {
+            self.stats.duplicates += 1;
+            return;
+        }
+        if self.recent.len() == RECENT {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(seq);
+        let Some(highest) = self.highest else {
+            self.highest = Some(seq);
+            return;
+        };
+        // Distances of less than half the counter range count as forward
+        let ahead = seq.wrapping_sub(highest) & mask;
+        if ahead == 0 {
+            self.stats.duplicates += 1;
+        } else if ahead <= mask / 2 {
+            self.stats.missing += ahead - 1;
+            self.highest = Some(seq);
+        } else {
+            self.stats.out_of_order += 1;
+            self.stats.missing = self.stats.missing.saturating_sub(1);
+        }
+    }
+
+    pub fn stats(&self, session_id: &str) -> DatagramStats {
+        DatagramStats { session_id: session_id.to_string(), ..self.stats.clone() }
+    }
+
+    pub fn reset(&mut self) {
+        self.stats = DatagramStats::default();
+        self.highest = None;
+        self.recent.clear();
+    }
+}
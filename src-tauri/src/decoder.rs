@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use crate::{checksum, dali, onewire};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Protocol {
+    ModbusRtu,
+    Nmea,
+    Mavlink,
+    /// 1-Wire driven through a UART: resets at 9600 baud, one byte per bit
+    /// slot at 115200.
+    OneWire,
+    /// DALI frames as relayed by a serial interface.
+    Dali,
+}
+
+/// Message type of one packet and whether it passed the protocol's checks.
+#[derive(Debug, Default)]
+pub struct Decoded {
+    pub message: String,
+    pub ok: bool,
+    /// Address, command and data of bus protocols; empty for the others.
+    pub fields: Map<String, Value>,
+}
+
+impl Decoded {
+    pub fn with(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.fields.insert(key.to_string(), value.into());
+        self
+    }
+}
+
+/// Identify the message type of a packet that was already split into frames.
+pub fn identify(proto: Protocol, bytes: &[u8]) -> Decoded {
+    match proto {
+        Protocol::ModbusRtu => modbus_rtu(bytes),
+        Protocol::Nmea => nmea(bytes),
+        Protocol::Mavlink => mavlink(bytes),
+        Protocol::OneWire => onewire::decode(bytes),
+        Protocol::Dali => dali::decode(bytes),
+    }
+}
+
+pub fn malformed() -> Decoded {
+    Decoded { message: "malformed".into(), ..Default::default() }
+}
+
+fn modbus_rtu(b: &[u8]) -> Decoded {
+    if b.len() < 4 {
+        return malformed();
+    }
+    let (body, crc) = b.split_at(b.len() - 2);
+    let crc_ok = checksum::compute("crc16-modbus", body) == u16::from_le_bytes([crc[0], crc[1]]) as u64;
+    let fc = body[1];
+    let name = match fc & 0x7F {
+        0x01 => "Read Coils",
+        0x02 => "Read Discrete Inputs",
+        0x03 => "Read Holding Registers",
+        0x04 => "Read Input Registers",
+        0x05 => "Write Single Coil",
+        0x06 => "Write Single Register",
+        0x08 => "Diagnostics",
+        0x0F => "Write Multiple Coils",
+        0x10 => "Write Multiple Registers",
+        0x16 => "Mask Write Register",
+        0x17 => "Read/Write Multiple Registers",
+        0x2B => "Encapsulated Interface",
+        _ => "Unknown",
+    };
+    // Exception responses set the high bit of the function code
+    let exception = fc & 0x80 != 0;
+    let message = if exception { format!("{:02X} {name} (exception)", fc & 0x7F) } else { format!("{fc:02X} {name}") };
+    Decoded { message, ok: crc_ok && !exception, ..Default::default() }
+}
+
+fn nmea(b: &[u8]) -> Decoded {
+    let text = String::from_utf8_lossy(b);
+    let line = text.trim_end();
+    let Some(body) = line.strip_prefix('$').or_else(|| line.strip_prefix('!')) else {
+        return malformed();
+    };
+    let (sentence, sum) = match body.rsplit_once('*') {
+        Some((s, sum)) => (s, Some(sum)),
+        None => (body, None),
+    };
+    let message = sentence.split(',').next().unwrap_or_default().to_string();
+    let calc = sentence.bytes().fold(0u8, |acc, c| acc ^ c);
+    let ok = sum.and_then(|s| u8::from_str_radix(s, 16).ok()) == Some(calc);
+    Decoded { message, ok, ..Default::default() }
+}
+
+/// MAVLink v1/v2 framing check; the CRC needs per-dialect seeds, so only
+/// the frame length is validated.
+fn mavlink(b: &[u8]) -> Decoded {
+    let (msgid, expected_len) = match b.first() {
+        Some(0xFE) if b.len() >= 8 => (b[5] as u32, 8 + b[1] as usize),
+        Some(0xFD) if b.len() >= 12 => {
+            let signed = b[2] & 0x01 != 0;
+            let id = u32::from_le_bytes([b[7], b[8], b[9], 0]);
+            (id, 12 + b[1] as usize + if signed { 13 } else { 0 })
+        }
+        _ => return malformed(),
+    };
+    let name = match msgid {
+        0 => "HEARTBEAT",
+        1 => "SYS_STATUS",
+        2 => "SYSTEM_TIME",
+        4 => "PING",
+        22 => "PARAM_VALUE",
+        24 => "GPS_RAW_INT",
+        27 => "RAW_IMU",
+        30 => "ATTITUDE",
+        33 => "GLOBAL_POSITION_INT",
+        42 => "MISSION_CURRENT",
+        62 => "NAV_CONTROLLER_OUTPUT",
+        65 => "RC_CHANNELS",
+        74 => "VFR_HUD",
+        76 => "COMMAND_LONG",
+        77 => "COMMAND_ACK",
+        147 => "BATTERY_STATUS",
+        253 => "STATUSTEXT",
+        _ => "",
+    };
+    let message = if name.is_empty() { format!("#{msgid}") } else { format!("#{msgid} {name}") };
+    Decoded { message, ok: b.len() == expected_len, ..Default::default() }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MessageStats {
+    pub message: String,
+    pub count: u64,
+    pub errors: u64,
+    pub bytes: u64,
+    pub last_seen_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProtocolStats {
+    pub session_id: String,
+    pub protocol: Protocol,
+    pub total: u64,
+    pub errors: u64,
+    pub messages: Vec<MessageStats>,
+}
+
+/// Per-message-type counters for one session's decoder.
+pub struct MessageCounter {
+    pub protocol: Protocol,
+    by_type: BTreeMap<String, MessageStats>,
+    pub last_emit_ms: f64,
+}
+
+impl MessageCounter {
+    pub fn new(protocol: Protocol) -> Self {
+        Self { protocol, by_type: BTreeMap::new(), last_emit_ms: 0.0 }
+    }
+
+    /// Count a packet and return what it decoded to.
+    pub fn record(&mut self, bytes: &[u8], ts: f64) -> Decoded {
+        let d = identify(self.protocol, bytes);
+        let s = self.by_type.entry(d.message.clone()).or_insert_with(|| MessageStats { message: d.message.clone(), ..Default::default() });
+        s.count += 1;
+        s.bytes += bytes.len() as u64;
+        s.last_seen_ms = ts;
+        if !d.ok {
+            s.errors += 1;
+        }
+        d
+    }
+
+    pub fn stats(&self, session_id: &str) -> ProtocolStats {
+        let messages: Vec<MessageStats> = self.by_type.values().cloned().collect();
+        ProtocolStats {
+            session_id: session_id.to_string(),
+            protocol: self.protocol,
+            total: messages.iter().map(|m| m.count).sum(),
+            errors: messages.iter().map(|m| m.errors).sum(),
+            messages,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.by_type.clear();
+    }
+}
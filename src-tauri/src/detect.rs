@@ -0,0 +1,178 @@
+use serde::Serialize;
+use crate::checksum;
+use crate::decoder::{self, Protocol};
+
+/// Confidence a guess needs before its decoder is enabled automatically.
+pub const AUTO_ENABLE_CONFIDENCE: f64 = 0.8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Guess {
+    Nmea,
+    ModbusRtu,
+    Mavlink,
+    Ubx,
+    JsonLines,
+    Ascii,
+}
+
+impl Guess {
+    /// Decoder that handles this protocol, where there is one.
+    pub fn protocol(self) -> Option<Protocol> {
+        match self {
+            Guess::Nmea => Some(Protocol::Nmea),
+            Guess::ModbusRtu => Some(Protocol::ModbusRtu),
+            Guess::Mavlink => Some(Protocol::Mavlink),
+            _ => None,
+        }
+    }
+
+    /// Splitter method that frames this protocol.
+    fn framing(self) -> &'static str {
+        match self {
+            Guess::Nmea | Guess::JsonLines | Guess::Ascii => "line",
+            Guess::ModbusRtu => "gap",
+            Guess::Mavlink | Guess::Ubx => "delimiter",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Candidate {
+    pub guess: Guess,
+    /// 0-1: share of lines, or of bytes for binary protocols, that parsed.
+    pub confidence: f64,
+    /// Valid lines or frames seen.
+    pub frames: usize,
+    pub protocol: Option<Protocol>,
+    pub framing: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Detection {
+    pub session_id: String,
+    pub bytes: usize,
+    /// Best guess first.
+    pub candidates: Vec<Candidate>,
+    /// Decoder that was switched on, with auto-enable.
+    pub enabled: Option<Protocol>,
+}
+
+/// Complete lines of the sample. The first may have been joined mid-way and
+/// the last may be unfinished, so both are dropped.
+fn lines(data: &[u8]) -> Vec<&[u8]> {
+    let mut lines: Vec<&[u8]> = data.split(|&b| b == b'\n').collect();
+    lines.pop();
+    if !lines.is_empty() {
+        lines.remove(0);
+    }
+    lines.into_iter()
+        .map(|l| l.strip_suffix(b"\r").unwrap_or(l))
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+/// Walk `data`, skipping bytes until `frame` recognises one at the current
+/// position. Returns the frame count and the bytes they cover.
+fn coverage(data: &[u8], frame: impl Fn(&[u8]) -> Option<usize>) -> (usize, usize) {
+    let (mut i, mut frames, mut covered) = (0, 0, 0);
+    while i < data.len() {
+        match frame(&data[i..]) {
+            Some(len) => {
+                frames += 1;
+                covered += len;
+                i += len;
+            }
+            None => i += 1,
+        }
+    }
+    (frames, covered)
+}
+
+fn ubx_frame(b: &[u8]) -> Option<usize> {
+    if !b.starts_with(&[0xB5, 0x62]) || b.len() < 8 {
+        return None;
+    }
+    let len = 8 + u16::from_le_bytes([b[4], b[5]]) as usize;
+    let frame = b.get(..len)?;
+    let (a, c) = frame[2..len - 2].iter().fold((0u8, 0u8), |(a, c), &x| {
+        let a = a.wrapping_add(x);
+        (a, c.wrapping_add(a))
+    });
+    (frame[len - 2..] == [a, c]).then_some(len)
+}
+
+/// MAVLink CRCs need the dialect's seeds, so a frame only counts when the
+/// next one starts right after it.
+fn mavlink_frame(b: &[u8]) -> Option<usize> {
+    let len = match b.first()? {
+        0xFE => 8 + *b.get(1)? as usize,
+        0xFD => 12 + *b.get(1)? as usize + if b.get(2)? & 0x01 != 0 { 13 } else { 0 },
+        _ => return None,
+    };
+    match b.get(len) {
+        Some(0xFE | 0xFD) => Some(len),
+        None if b.len() == len => Some(len),
+        _ => None,
+    }
+}
+
+/// Modbus RTU frame at the start of `b`. Lengths follow from the function
+/// code (request or response layout), and the CRC must match one of them.
+fn modbus_frame(b: &[u8]) -> Option<usize> {
+    if b.len() < 4 || b[0] > 247 {
+        return None;
+    }
+    let byte_at = |i: usize| b.get(i).map(|&n| n as usize);
+    let lengths = match b[1] {
+        0x01..=0x04 => [Some(8), byte_at(2).map(|n| 5 + n)],
+        0x05 | 0x06 | 0x08 => [Some(8), None],
+        0x0F | 0x10 => [Some(8), byte_at(6).map(|n| 9 + n)],
+        0x16 => [Some(10), None],
+        0x17 => [byte_at(2).map(|n| 5 + n), byte_at(10).map(|n| 13 + n)],
+        fc if fc & 0x80 != 0 => [Some(5), None],
+        _ => return None,
+    };
+    lengths.into_iter().flatten().find(|&len| {
+        len <= b.len() && checksum::compute("crc16-modbus", &b[..len - 2]) == u16::from_le_bytes([b[len - 2], b[len - 1]]) as u64
+    })
+}
+
+fn candidate(guess: Guess, confidence: f64, frames: usize) -> Candidate {
+    Candidate { guess, confidence, frames, protocol: guess.protocol(), framing: guess.framing().into() }
+}
+
+/// Score each known protocol against a sample of RX.
+pub fn detect(session_id: &str, data: &[u8]) -> Detection {
+    let mut candidates = Vec::new();
+    let lines = lines(data);
+    if !lines.is_empty() {
+        let share = |n: usize| n as f64 / lines.len() as f64;
+        let nmea = lines.iter().filter(|l| decoder::identify(Protocol::Nmea, l).ok).count();
+        candidates.push(candidate(Guess::Nmea, share(nmea), nmea));
+        let json = lines.iter()
+            .filter(|l| serde_json::from_slice::<serde_json::Value>(l).is_ok_and(|v| v.is_object() || v.is_array()))
+            .count();
+        candidates.push(candidate(Guess::JsonLines, share(json), json));
+    }
+    if !data.is_empty() {
+        let printable = data.iter().filter(|&&b| matches!(b, 0x20..=0x7E | b'\t' | b'\r' | b'\n')).count();
+        let ratio = printable as f64 / data.len() as f64;
+        // Any text protocol is also ASCII, so this only wins when nothing more specific does
+        if ratio >= 0.9 {
+            candidates.push(candidate(Guess::Ascii, ratio * 0.6, lines.len().max(1)));
+        }
+        let share = |n: usize| n as f64 / data.len() as f64;
+        for (guess, frame) in [
+            (Guess::Ubx, ubx_frame as fn(&[u8]) -> Option<usize>),
+            (Guess::Mavlink, mavlink_frame),
+            (Guess::ModbusRtu, modbus_frame),
+        ] {
+            let (frames, covered) = coverage(data, frame);
+            candidates.push(candidate(guess, share(covered), frames));
+        }
+    }
+    candidates.retain(|c| c.frames > 0 && c.confidence > 0.0);
+    candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    Detection { session_id: session_id.to_string(), bytes: data.len(), candidates, enabled: None }
+}
@@ -0,0 +1,364 @@
+use std::sync::Arc;
+use std::time::Duration;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::{JoinHandle, JoinSet};
+use crate::chat::unescape;
+
+/// Longest unterminated line kept while waiting for its end.
+const MAX_LINE: usize = 4096;
+
+/// Responds to one command line while the device is in one of `states`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceRule {
+    /// States the rule applies in; empty applies in all of them.
+    #[serde(default)]
+    pub states: Vec<String>,
+    /// Regex the whole line must match.
+    pub pattern: String,
+    /// Chat-style escapes (`\r`, `\n`); `$1` etc. insert groups of the match.
+    /// Empty sends nothing.
+    #[serde(default)]
+    pub reply: String,
+    /// State to move to after replying.
+    #[serde(default)]
+    pub next: Option<String>,
+    #[serde(default)]
+    pub delay_ms: u64,
+}
+
+/// A line-oriented fake device: a state machine whose rules map commands to
+/// replies.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceScript {
+    pub initial_state: String,
+    /// Sent when a client connects.
+    pub greeting: String,
+    /// First matching rule wins.
+    pub rules: Vec<DeviceRule>,
+    /// Reply when no rule matches, e.g. "ERROR". Empty stays silent.
+    pub unknown: String,
+    /// Appended to every reply.
+    pub eol: String,
+    /// Echo received bytes back, like a modem in ATE1.
+    pub echo: bool,
+}
+
+impl Default for DeviceScript {
+    fn default() -> Self {
+        Self {
+            initial_state: "idle".into(),
+            greeting: String::new(),
+            rules: Vec::new(),
+            unknown: String::new(),
+            eol: "\\r\\n".into(),
+            echo: false,
+        }
+    }
+}
+
+/// Where external tools reach the device.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DeviceTransport {
+    /// Each client gets its own instance of the state machine.
+    Tcp {
+        #[serde(default = "localhost")]
+        bind: String,
+        /// 0 picks a free port.
+        #[serde(default)]
+        port: u16,
+    },
+    /// A pseudo-terminal that tools open like a serial port (Unix only).
+    Pty,
+}
+
+fn localhost() -> String {
+    "127.0.0.1".into()
+}
+
+pub struct Reply {
+    pub bytes: Vec<u8>,
+    pub delay: Duration,
+    /// State once this reply is sent.
+    pub state: String,
+}
+
+/// One running instance of a script.
+#[derive(Clone)]
+pub struct Device {
+    script: Arc<DeviceScript>,
+    rules: Arc<Vec<Regex>>,
+    state: String,
+    line: Vec<u8>,
+}
+
+impl Device {
+    pub fn new(script: DeviceScript) -> Result<Self, String> {
+        let rules = script.rules.iter()
+            .enumerate()
+            .map(|(i, r)| Regex::new(&format!("^(?:{})$", r.pattern)).map_err(|e| format!("Rule {}: {e}", i + 1)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { state: script.initial_state.clone(), script: Arc::new(script), rules: Arc::new(rules), line: Vec::new() })
+    }
+
+    pub fn state(&self) -> &str {
+        &self.state
+    }
+
+    pub fn greeting(&self) -> Option<Vec<u8>> {
+        (!self.script.greeting.is_empty()).then(|| self.reply(&self.script.greeting))
+    }
+
+    fn reply(&self, text: &str) -> Vec<u8> {
+        [unescape(text), unescape(&self.script.eol)].concat()
+    }
+
+    /// Take received bytes; returns the replies to send, in order. Lines end
+    /// at CR or LF, and empty lines are ignored.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<Reply> {
+        let mut replies = Vec::new();
+        if self.script.echo {
+            replies.push(Reply { bytes: data.to_vec(), delay: Duration::ZERO, state: self.state.clone() });
+        }
+        for &b in data {
+            if b != b'\r' && b != b'\n' {
+                if self.line.len() < MAX_LINE {
+                    self.line.push(b);
+                }
+                continue;
+            }
+            if self.line.is_empty() {
+                continue;
+            }
+            let line = String::from_utf8_lossy(&std::mem::take(&mut self.line)).into_owned();
+            replies.extend(self.respond(&line));
+        }
+        replies
+    }
+
+    fn respond(&mut self, line: &str) -> Option<Reply> {
+        let script = Arc::clone(&self.script);
+        let hit = script.rules.iter().zip(self.rules.iter()).find_map(|(rule, re)| {
+            let applies = rule.states.is_empty() || rule.states.contains(&self.state);
+            applies.then(|| re.captures(line)).flatten().map(|caps| (rule, caps))
+        });
+        let Some((rule, caps)) = hit else {
+            let bytes = self.reply(&script.unknown);
+            return (!script.unknown.is_empty()).then(|| Reply { bytes, delay: Duration::ZERO, state: self.state.clone() });
+        };
+        if let Some(next) = &rule.next {
+            self.state = next.clone();
+        }
+        if rule.reply.is_empty() {
+            return None;
+        }
+        let mut text = String::new();
+        caps.expand(&rule.reply, &mut text);
+        Some(Reply { bytes: self.reply(&text), delay: Duration::from_millis(rule.delay_ms), state: self.state.clone() })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceEvent {
+    pub device_id: String,
+    /// Peer address, or the pty path.
+    pub client: String,
+    /// "connected", "rx", "tx" or "disconnected".
+    pub kind: &'static str,
+    pub bytes: Vec<u8>,
+    /// State of the device once the event happened.
+    pub state: String,
+}
+
+type OnEvent = Arc<dyn Fn(DeviceEvent) + Send + Sync>;
+
+/// Talk to one client until it disconnects.
+async fn serve(mut device: Device, io: impl AsyncRead + AsyncWrite, device_id: String, client: String, on_event: OnEvent) {
+    let (mut rd, mut wr) = tokio::io::split(io);
+    let event = |kind, bytes: &[u8], state: &str| on_event(DeviceEvent {
+        device_id: device_id.clone(),
+        client: client.clone(),
+        kind,
+        bytes: bytes.to_vec(),
+        state: state.to_string(),
+    });
+    event("connected", &[], device.state());
+    if let Some(greeting) = device.greeting() {
+        if wr.write_all(&greeting).await.is_ok() {
+            event("tx", &greeting, device.state());
+        }
+    }
+    let mut buf = vec![0u8; 4096];
+    'read: loop {
+        let n = match rd.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        event("rx", &buf[..n], device.state());
+        for reply in device.feed(&buf[..n]) {
+            tokio::time::sleep(reply.delay).await;
+            if wr.write_all(&reply.bytes).await.is_err() {
+                break 'read;
+            }
+            event("tx", &reply.bytes, &reply.state);
+        }
+    }
+    event("disconnected", &[], device.state());
+}
+
+/// A running virtual device; dropping it closes the listener or pty and
+/// disconnects every client.
+pub struct VirtualDevice {
+    pub id: String,
+    /// Listening address, or the pty path to open.
+    pub endpoint: String,
+    pub transport: DeviceTransport,
+    task: JoinHandle<()>,
+}
+
+impl Drop for VirtualDevice {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+pub async fn start(
+    script: DeviceScript,
+    transport: DeviceTransport,
+    on_event: impl Fn(DeviceEvent) + Send + Sync + 'static,
+) -> Result<VirtualDevice, String> {
+    let device = Device::new(script)?;
+    let on_event: OnEvent = Arc::new(on_event);
+    match &transport {
+        DeviceTransport::Tcp { bind, port } => {
+            let listener = TcpListener::bind((bind.as_str(), *port)).await.map_err(|e| e.to_string())?;
+            let endpoint = listener.local_addr().map_err(|e| e.to_string())?.to_string();
+            let id = format!("device:{endpoint}");
+            let device_id = id.clone();
+            let task = tokio::spawn(async move {
+                // Client tasks are aborted along with the set when the device stops
+                let mut clients = JoinSet::new();
+                loop {
+                    tokio::select! {
+                        accepted = listener.accept() => match accepted {
+                            Ok((stream, peer)) => {
+                                clients.spawn(serve(device.clone(), stream, device_id.clone(), peer.to_string(), Arc::clone(&on_event)));
+                            }
+                            Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+                        },
+                        Some(_) = clients.join_next() => {}
+                    }
+                }
+            });
+            Ok(VirtualDevice { id, endpoint, transport, task })
+        }
+        #[cfg(unix)]
+        DeviceTransport::Pty => {
+            let (master, path) = pty::open()?;
+            let id = format!("device:{path}");
+            let task = tokio::spawn(serve(device, master, id.clone(), path.clone(), on_event));
+            Ok(VirtualDevice { id, endpoint: path, transport, task })
+        }
+        #[cfg(not(unix))]
+        DeviceTransport::Pty => Err("Virtual serial ports need a Unix pty".into()),
+    }
+}
+
+#[cfg(unix)]
+mod pty {
+    use std::ffi::CStr;
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::pin::Pin;
+    use std::task::{ready, Context, Poll};
+    use tokio::io::unix::AsyncFd;
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    /// Master side of a pty. The slave stays open here too, so reads don't
+    /// fail with EIO while no tool has the port open.
+    pub struct Master {
+        fd: AsyncFd<OwnedFd>,
+        _slave: OwnedFd,
+    }
+
+    fn check(rc: libc::c_int) -> io::Result<libc::c_int> {
+        if rc < 0 { Err(io::Error::last_os_error()) } else { Ok(rc) }
+    }
+
+    /// Open a pty in raw mode. Returns the master and the slave's path.
+    pub fn open() -> Result<(Master, String), String> {
+        let (mut master, mut slave) = (0, 0);
+        let mut name = [0 as libc::c_char; 256];
+        // SAFETY: all pointers are valid; `name` is larger than any pty path
+        check(unsafe { libc::openpty(&mut master, &mut slave, name.as_mut_ptr(), std::ptr::null(), std::ptr::null()) })
+            .map_err(|e| e.to_string())?;
+        // SAFETY: openpty succeeded, so both fds are open and ours
+        let (master, slave) = unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+        // SAFETY: openpty wrote a NUL-terminated path into `name`
+        let path = unsafe { CStr::from_ptr(name.as_ptr()) }.to_string_lossy().into_owned();
+
+        // Raw mode, so replies aren't echoed back to us or have their line endings translated
+        // SAFETY: termios is plain data and the slave fd is open
+        unsafe {
+            let mut tio: libc::termios = std::mem::zeroed();
+            check(libc::tcgetattr(slave.as_raw_fd(), &mut tio)).map_err(|e| e.to_string())?;
+            libc::cfmakeraw(&mut tio);
+            check(libc::tcsetattr(slave.as_raw_fd(), libc::TCSANOW, &tio)).map_err(|e| e.to_string())?;
+            let flags = check(libc::fcntl(master.as_raw_fd(), libc::F_GETFL)).map_err(|e| e.to_string())?;
+            check(libc::fcntl(master.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK)).map_err(|e| e.to_string())?;
+        }
+        let fd = AsyncFd::new(master).map_err(|e| e.to_string())?;
+        Ok((Master { fd, _slave: slave }, path))
+    }
+
+    impl AsyncRead for Master {
+        fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+            loop {
+                let mut guard = ready!(self.fd.poll_read_ready(cx))?;
+                let unfilled = buf.initialize_unfilled();
+                // SAFETY: `unfilled` is valid for writes of its length
+                let read = guard.try_io(|fd| {
+                    let n = unsafe { libc::read(fd.as_raw_fd(), unfilled.as_mut_ptr().cast(), unfilled.len()) };
+                    if n < 0 { Err(io::Error::last_os_error()) } else { Ok(n as usize) }
+                });
+                match read {
+                    Ok(Ok(n)) => {
+                        buf.advance(n);
+                        return Poll::Ready(Ok(()));
+                    }
+                    Ok(Err(e)) => return Poll::Ready(Err(e)),
+                    Err(_would_block) => continue,
+                }
+            }
+        }
+    }
+
+    impl AsyncWrite for Master {
+        fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, data: &[u8]) -> Poll<io::Result<usize>> {
+            loop {
+                let mut guard = ready!(self.fd.poll_write_ready(cx))?;
+                // SAFETY: `data` is valid for reads of its length
+                let written = guard.try_io(|fd| {
+                    let n = unsafe { libc::write(fd.as_raw_fd(), data.as_ptr().cast(), data.len()) };
+                    if n < 0 { Err(io::Error::last_os_error()) } else { Ok(n as usize) }
+                });
+                match written {
+                    Ok(result) => return Poll::Ready(result),
+                    Err(_would_block) => continue,
+                }
+            }
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+}
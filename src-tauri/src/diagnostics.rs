@@ -0,0 +1,167 @@
+use std::net::SocketAddr;
+use std::path::Path;
+use serde::Serialize;
+
+/// One result of the environment check.
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    /// "serial_ports", "serial_permissions", "port_grabber", "log_dir" or "firewall".
+    pub check: &'static str,
+    /// "ok", "info", "warning" or "error".
+    pub status: &'static str,
+    pub message: String,
+    /// What to do about it.
+    pub hint: Option<String>,
+}
+
+impl Finding {
+    fn new(check: &'static str, status: &'static str, message: impl Into<String>, hint: Option<String>) -> Self {
+        Self { check, status, message: message.into(), hint }
+    }
+}
+
+/// Check serial access, the writability of `log_dirs` and the reachability of
+/// `listeners` (server id and bound address).
+pub fn run(log_dirs: &[&Path], listeners: &[(String, SocketAddr)]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    serial(&mut findings);
+    for dir in log_dirs {
+        findings.push(log_dir(dir));
+    }
+    for (id, addr) in listeners {
+        findings.push(firewall(id, *addr));
+    }
+    findings
+}
+
+fn serial(findings: &mut Vec<Finding>) {
+    match serialport::available_ports() {
+        Ok(ports) if ports.is_empty() => findings.push(Finding::new(
+            "serial_ports",
+            "warning",
+            "No serial ports found",
+            Some("Check the cable and that the USB-serial driver (CP210x, CH340, FTDI) is installed".into()),
+        )),
+        Ok(ports) => {
+            findings.push(Finding::new("serial_ports", "ok", format!("{} serial port(s) found", ports.len()), None));
+            #[cfg(target_os = "linux")]
+            linux::permissions(&ports, findings);
+        }
+        Err(e) => findings.push(Finding::new(
+            "serial_ports",
+            "error",
+            format!("Cannot enumerate serial ports: {e}"),
+            None,
+        )),
+    }
+    #[cfg(target_os = "linux")]
+    linux::port_grabbers(findings);
+}
+
+fn log_dir(dir: &Path) -> Finding {
+    let probe = dir.join(".wirescope-write-test");
+    let written = std::fs::create_dir_all(dir).and_then(|()| std::fs::write(&probe, b"ok"));
+    let _ = std::fs::remove_file(&probe);
+    match written {
+        Ok(()) => Finding::new("log_dir", "ok", format!("{} is writable", dir.display()), None),
+        Err(e) => Finding::new(
+            "log_dir",
+            "error",
+            format!("Cannot write to {}: {e}", dir.display()),
+            Some("Choose another log directory or fix its permissions".into()),
+        ),
+    }
+}
+
+fn firewall(id: &str, addr: SocketAddr) -> Finding {
+    if addr.ip().is_loopback() {
+        return Finding::new(
+            "firewall",
+            "info",
+            format!("{id} listens on {addr}, reachable from this machine only"),
+            Some("Bind to 0.0.0.0 to accept devices on the network".into()),
+        );
+    }
+    let port = addr.port();
+    let hint = match std::env::consts::OS {
+        "linux" => format!("If devices cannot connect, allow the port, e.g. `sudo ufw allow {port}/tcp`"),
+        "macos" => "If devices cannot connect, allow incoming connections for wirescope in System Settings → Network → Firewall".into(),
+        "windows" => format!("If devices cannot connect, allow wirescope or TCP port {port} in Windows Defender Firewall"),
+        _ => format!("If devices cannot connect, allow inbound TCP port {port} in the firewall"),
+    };
+    Finding::new("firewall", "info", format!("{id} listens on {addr}"), Some(hint))
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::os::unix::fs::MetadataExt;
+    use serialport::SerialPortInfo;
+    use super::Finding;
+
+    /// Ids from a `/proc/self/status` line such as `Groups:\t20 27 1000`.
+    fn status_ids(key: &str) -> Vec<u32> {
+        std::fs::read_to_string("/proc/self/status")
+            .ok()
+            .and_then(|s| {
+                let line = s.lines().find(|l| l.starts_with(key))?;
+                Some(line[key.len()..].split_whitespace().filter_map(|v| v.parse().ok()).collect())
+            })
+            .unwrap_or_default()
+    }
+
+    fn group_name(gid: u32) -> Option<String> {
+        std::fs::read_to_string("/etc/group").ok()?.lines().find_map(|l| {
+            let mut fields = l.split(':');
+            let name = fields.next()?;
+            (fields.nth(1)?.parse() == Ok(gid)).then(|| name.to_string())
+        })
+    }
+
+    pub fn permissions(ports: &[SerialPortInfo], findings: &mut Vec<Finding>) {
+        // Effective uid is the second field of "Uid:"
+        let uid = status_ids("Uid:").get(1).copied().unwrap_or(u32::MAX);
+        let groups = status_ids("Groups:");
+        for port in ports {
+            let Ok(meta) = std::fs::metadata(&port.port_name) else { continue };
+            let mode = meta.mode();
+            let allowed = uid == 0
+                || (meta.uid() == uid && mode & 0o600 == 0o600)
+                || (groups.contains(&meta.gid()) && mode & 0o060 == 0o060)
+                || mode & 0o006 == 0o006;
+            if allowed {
+                continue;
+            }
+            let group = group_name(meta.gid()).unwrap_or_else(|| meta.gid().to_string());
+            findings.push(Finding::new(
+                "serial_permissions",
+                "error",
+                format!("No read/write access to {} (group {group})", port.port_name),
+                Some(format!("Run `sudo usermod -aG {group} $USER`, then log out and back in, or add a udev rule for the device")),
+            ));
+        }
+    }
+
+    /// Services known to open new serial ports before wirescope can.
+    pub fn port_grabbers(findings: &mut Vec<Finding>) {
+        let Ok(procs) = std::fs::read_dir("/proc") else { return };
+        let running: Vec<String> = procs
+            .flatten()
+            .filter_map(|p| std::fs::read_to_string(p.path().join("comm")).ok())
+            .map(|c| c.trim().to_string())
+            .collect();
+        let known = [
+            ("ModemManager", "probes new ttyACM/ttyUSB ports with AT commands", "sudo systemctl disable --now ModemManager"),
+            ("brltty", "claims CH340/CP210x adapters as braille displays", "sudo apt remove brltty"),
+        ];
+        for (name, what, fix) in known {
+            if running.iter().any(|c| c == name) {
+                findings.push(Finding::new(
+                    "port_grabber",
+                    "warning",
+                    format!("{name} is running and {what}"),
+                    Some(format!("If ports are busy or devices receive stray bytes: `{fix}`, or exclude the device with a udev rule")),
+                ));
+            }
+        }
+    }
+}
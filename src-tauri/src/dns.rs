@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tokio::net::UdpSocket;
+
+/// How host names are turned into addresses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolverMode {
+    /// The OS resolver (subject to its caching).
+    #[default]
+    System,
+    /// Query `dns_server` directly on every connect, bypassing OS caches.
+    Direct,
+}
+
+/// Which address family is tried first when a host has both.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressFamily {
+    /// In the order the resolver returned them.
+    #[default]
+    Any,
+    PreferIpv4,
+    PreferIpv6,
+}
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+
+/// Resolve `host:port`, honouring overrides first and IP literals as-is.
+/// Addresses of the preferred family come first.
+pub async fn resolve(
+    host: &str,
+    port: u16,
+    overrides: &HashMap<String, IpAddr>,
+    mode: ResolverMode,
+    dns_server: Option<&str>,
+    family: AddressFamily,
+) -> Result<Vec<SocketAddr>, String> {
+    if let Some(ip) = overrides.get(host) {
+        return Ok(vec![SocketAddr::new(*ip, port)]);
+    }
+    if let Some(addr) = ip_literal(host, port)? {
+        return Ok(vec![addr]);
+    }
+    let mut addrs: Vec<SocketAddr> = match mode {
+        ResolverMode::System => tokio::net::lookup_host((host, port))
+            .await
+            .map(|it| it.collect())
+            .map_err(|e| format!("Cannot resolve {host}: {e}"))?,
+        ResolverMode::Direct => {
+            let server = dns_server.ok_or("Direct resolver needs a DNS server")?;
+            let server = server_addr(server, 53)?;
+            let order = match family {
+                AddressFamily::PreferIpv6 => [TYPE_AAAA, TYPE_A],
+                _ => [TYPE_A, TYPE_AAAA],
+            };
+            let mut ips = query(&server, host, order[0]).await?;
+            if ips.is_empty() {
+                ips = query(&server, host, order[1]).await?;
+            }
+            if ips.is_empty() {
+                return Err(format!("{server} has no address for {host}"));
+            }
+            ips.into_iter().map(|ip| SocketAddr::new(ip, port)).collect()
+        }
+    };
+    if addrs.is_empty() {
+        return Err(format!("Cannot resolve {host}"));
+    }
+    match family {
+        AddressFamily::Any => {}
+        AddressFamily::PreferIpv4 => addrs.sort_by_key(|a| !a.is_ipv4()),
+        AddressFamily::PreferIpv6 => addrs.sort_by_key(|a| !a.is_ipv6()),
+    }
+    Ok(addrs)
+}
+
+/// Parse an IP literal, with or without brackets (`[::1]`), and with an
+/// IPv6 scope given as interface name or index (`fe80::1%eth0`). `None` for
+/// host names.
+pub fn ip_literal(host: &str, port: u16) -> Result<Option<SocketAddr>, String> {
+    let bare = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or(host);
+    let (addr, scope) = match bare.split_once('%') {
+        Some((addr, scope)) => (addr, Some(scope)),
+        None => (bare, None),
+    };
+    let Ok(ip) = addr.parse::<IpAddr>() else {
+        return Ok(None);
+    };
+    match (ip, scope) {
+        (IpAddr::V6(ip), Some(scope)) => Ok(Some(SocketAddrV6::new(ip, port, 0, scope_id(scope)?).into())),
+        (IpAddr::V4(_), Some(_)) => Err(format!("IPv4 addresses have no scope: {host}")),
+        (ip, None) => Ok(Some(SocketAddr::new(ip, port))),
+    }
+}
+
+/// `host` without brackets or IPv6 scope, e.g. for TLS name checks.
+pub fn bare_host(host: &str) -> &str {
+    let bare = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or(host);
+    match bare.split_once('%') {
+        Some((addr, _)) if addr.parse::<Ipv6Addr>().is_ok() => addr,
+        _ => bare,
+    }
+}
+
+/// `host:port`, with IPv6 literals bracketed so the port stays unambiguous.
+pub fn host_port(host: &str, port: u16) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{host}]:{port}")
+    } else {
+        format!("{host}:{port}")
+    }
+}
+
+/// `server` as `host:port` for a UDP query, adding `default_port` unless it
+/// has one. IPv6 literals come out bracketed, with or without a port.
+pub fn server_addr(server: &str, default_port: u16) -> Result<String, String> {
+    if server.parse::<SocketAddr>().is_ok() {
+        return Ok(server.to_string());
+    }
+    if let Some(addr) = ip_literal(server, default_port)? {
+        return Ok(addr.to_string());
+    }
+    match server.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(server.to_string()),
+        _ => Ok(host_port(server, default_port)),
+    }
+}
+
+fn scope_id(scope: &str) -> Result<u32, String> {
+    match scope.parse() {
+        Ok(index) => Ok(index),
+        Err(_) => interface_index(scope).ok_or_else(|| format!("Unknown network interface: {scope}")),
+    }
+}
+
+#[cfg(unix)]
+fn interface_index(name: &str) -> Option<u32> {
+    let name = std::ffi::CString::new(name).ok()?;
+    // SAFETY: `name` is a valid NUL-terminated string
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    (index != 0).then_some(index)
+}
+
+/// Windows scopes must be given as the interface index.
+#[cfg(not(unix))]
+fn interface_index(_name: &str) -> Option<u32> {
+    None
+}
+
+/// Send one recursive query for `name` and collect the A/AAAA answers.
+async fn query(server: &str, name: &str, qtype: u16) -> Result<Vec<IpAddr>, String> {
+    let id = (crate::state::now_ms() as u64 & 0xFFFF) as u16;
+    let mut msg = Vec::with_capacity(64);
+    msg.extend_from_slice(&id.to_be_bytes());
+    msg.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]); // RD, QDCOUNT = 1
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(format!("Invalid host name: {name}"));
+        }
+        msg.push(label.len() as u8);
+        msg.extend_from_slice(label.as_bytes());
+    }
+    msg.push(0);
+    msg.extend_from_slice(&qtype.to_be_bytes());
+    msg.extend_from_slice(&1u16.to_be_bytes()); // IN
+
+    let bind = if server.starts_with('[') { "[::]:0" } else { "0.0.0.0:0" };
+    let sock = UdpSocket::bind(bind).await.map_err(|e| e.to_string())?;
+    sock.send_to(&msg, server).await.map_err(|e| e.to_string())?;
+    let mut buf = [0u8; 1500];
+    let n = tokio::time::timeout(Duration::from_secs(3), sock.recv(&mut buf))
+        .await
+        .map_err(|_| format!("DNS server {server} did not answer"))?
+        .map_err(|e| e.to_string())?;
+    parse_answers(&buf[..n], id)
+}
+
+fn parse_answers(resp: &[u8], id: u16) -> Result<Vec<IpAddr>, String> {
+    let bad = || "Malformed DNS response".to_string();
+    if resp.len() < 12 || u16::from_be_bytes([resp[0], resp[1]]) != id {
+        return Err(bad());
+    }
+    match resp[3] & 0x0F {
+        0 => {}
+        3 => return Ok(Vec::new()), // NXDOMAIN
+        rcode => return Err(format!("DNS error (rcode {rcode})")),
+    }
+    let qdcount = u16::from_be_bytes([resp[4], resp[5]]);
+    let ancount = u16::from_be_bytes([resp[6], resp[7]]);
+
+    let mut pos = 12;
+    for _ in 0..qdcount {
+        pos = skip_name(resp, pos).ok_or_else(bad)? + 4;
+    }
+    let mut ips = Vec::new();
+    for _ in 0..ancount {
+        pos = skip_name(resp, pos).ok_or_else(bad)?;
+        let hdr = resp.get(pos..pos + 10).ok_or_else(bad)?;
+        let rtype = u16::from_be_bytes([hdr[0], hdr[1]]);
+        let rdlen = u16::from_be_bytes([hdr[8], hdr[9]]) as usize;
+        let rdata = resp.get(pos + 10..pos + 10 + rdlen).ok_or_else(bad)?;
+        match (rtype, rdlen) {
+            (TYPE_A, 4) => ips.push(IpAddr::V4(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]))),
+            (TYPE_AAAA, 16) => {
+                let mut b = [0u8; 16];
+                b.copy_from_slice(rdata);
+                ips.push(IpAddr::V6(Ipv6Addr::from(b)));
+            }
+            _ => {} // CNAME and friends: the resolver already followed them
+        }
+        pos += 10 + rdlen;
+    }
+    Ok(ips)
+}
+
+/// Return the offset just past a (possibly compressed) domain name.
+fn skip_name(buf: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *buf.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            l if l & 0xC0 == 0xC0 => return Some(pos + 2),
+            l => pos += 1 + l as usize,
+        }
+    }
+}
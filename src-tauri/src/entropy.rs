@@ -0,0 +1,165 @@
+use serde::Serialize;
+
+/// Below this many bytes the entropy says too little to call the stream.
+const MIN_BYTES: usize = 64;
+/// Enough bytes for every value to be expected about five times, which the
+/// chi-square test needs to tell compressed from random data.
+const CHI_SQUARE_BYTES: usize = 1280;
+/// Upper bound of chi-square (255 degrees of freedom) for uniform data,
+/// roughly three standard deviations above the mean.
+const CHI_SQUARE_UNIFORM: f64 = 323.0;
+
+/// What a window of bytes most likely is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamClass {
+    Empty,
+    Text,
+    Compressed,
+    /// Indistinguishable from random data: encrypted, or already compressed
+    /// and then encrypted.
+    Encrypted,
+    /// High entropy, but too few bytes to tell compressed from encrypted.
+    HighEntropy,
+    /// Structured binary: headers, fields, padding.
+    Binary,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ByteDistribution {
+    pub session_id: String,
+    pub bytes: usize,
+    /// Count of each byte value, indexed by the value.
+    pub histogram: Vec<u64>,
+    pub distinct: usize,
+    /// Shannon entropy in bits per byte, 0-8.
+    pub entropy: f64,
+    /// Printable ASCII plus tab, CR and LF.
+    pub printable_ratio: f64,
+    pub zero_ratio: f64,
+    pub high_ratio: f64,
+    /// Pearson chi-square against a uniform distribution.
+    pub chi_square: f64,
+    /// "ascii", "utf-8", "utf-16le" or "utf-16be" for text.
+    pub encoding: Option<String>,
+    /// Container format recognised by its magic bytes, e.g. "gzip".
+    pub compression: Option<String>,
+    pub class: StreamClass,
+}
+
+const MAGIC: &[(&[u8], &str)] = &[
+    (&[0x1F, 0x8B], "gzip"),
+    (&[0x28, 0xB5, 0x2F, 0xFD], "zstd"),
+    (&[0x04, 0x22, 0x4D, 0x18], "lz4"),
+    (&[0xFD, b'7', b'z', b'X', b'Z', 0x00], "xz"),
+    (b"BZh", "bzip2"),
+    (b"PK\x03\x04", "zip"),
+    (&[0x78, 0x01], "zlib"),
+    (&[0x78, 0x5E], "zlib"),
+    (&[0x78, 0x9C], "zlib"),
+    (&[0x78, 0xDA], "zlib"),
+];
+
+fn is_printable(b: u8) -> bool {
+    matches!(b, 0x20..=0x7E | b'\t' | b'\r' | b'\n')
+}
+
+fn ratio(n: usize, total: usize) -> f64 {
+    if total == 0 { 0.0 } else { n as f64 / total as f64 }
+}
+
+/// UTF-16 text has a zero in every other byte for the Latin range.
+fn utf16(data: &[u8]) -> Option<&'static str> {
+    if data.len() < 4 {
+        return None;
+    }
+    let half = |start: usize| data.iter().skip(start).step_by(2);
+    let looks_like = |text: usize, zero: usize| {
+        let zeros = half(zero).filter(|&&b| b == 0).count();
+        let printable = half(text).filter(|&&b| is_printable(b)).count();
+        let n = data.len() / 2;
+        ratio(zeros, n) >= 0.9 && ratio(printable, n) >= 0.9
+    };
+    if looks_like(0, 1) {
+        Some("utf-16le")
+    } else if looks_like(1, 0) {
+        Some("utf-16be")
+    } else {
+        None
+    }
+}
+
+/// `chunks` are the window's packets in order; magic bytes are looked for at
+/// the start of each.
+pub fn analyze<'a>(session_id: &str, chunks: impl IntoIterator<Item = &'a [u8]>) -> ByteDistribution {
+    let mut data = Vec::new();
+    let mut compression = None;
+    for chunk in chunks {
+        if compression.is_none() {
+            compression = MAGIC.iter().find(|(m, _)| chunk.starts_with(m)).map(|(_, name)| name.to_string());
+        }
+        data.extend_from_slice(chunk);
+    }
+
+    let mut histogram = vec![0u64; 256];
+    for &b in &data {
+        histogram[b as usize] += 1;
+    }
+    let n = data.len();
+    let entropy = histogram.iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / n as f64;
+            -p * p.log2()
+        })
+        .sum::<f64>();
+    let expected = n as f64 / 256.0;
+    let chi_square = if n == 0 {
+        0.0
+    } else {
+        histogram.iter().map(|&c| (c as f64 - expected).powi(2) / expected).sum()
+    };
+    let printable_ratio = ratio(data.iter().filter(|&&b| is_printable(b)).count(), n);
+
+    let encoding = if n > 0 && data.is_ascii() && printable_ratio >= 0.95 {
+        Some("ascii")
+    } else if n > 0 && printable_ratio >= 0.8 && std::str::from_utf8(&data).is_ok() {
+        Some("utf-8")
+    } else {
+        utf16(&data)
+    };
+
+    // The most entropy the window could have, given its length
+    let max_entropy = (n as f64).log2().min(8.0);
+    let class = if n == 0 {
+        StreamClass::Empty
+    } else if encoding.is_some() {
+        StreamClass::Text
+    } else if n >= MIN_BYTES && entropy >= 0.9 * max_entropy {
+        match (n >= CHI_SQUARE_BYTES, chi_square <= CHI_SQUARE_UNIFORM) {
+            (true, true) => StreamClass::Encrypted,
+            (true, false) => StreamClass::Compressed,
+            (false, _) if compression.is_some() => StreamClass::Compressed,
+            (false, _) => StreamClass::HighEntropy,
+        }
+    } else if compression.is_some() && n < MIN_BYTES {
+        StreamClass::Compressed
+    } else {
+        StreamClass::Binary
+    };
+
+    ByteDistribution {
+        session_id: session_id.to_string(),
+        bytes: n,
+        distinct: histogram.iter().filter(|&&c| c > 0).count(),
+        entropy,
+        printable_ratio,
+        zero_ratio: ratio(histogram[0] as usize, n),
+        high_ratio: ratio(histogram[0x80..].iter().sum::<u64>() as usize, n),
+        chi_square,
+        encoding: encoding.map(String::from),
+        compression,
+        class,
+        histogram,
+    }
+}
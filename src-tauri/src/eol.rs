@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Cr,
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn bytes(self) -> &'static [u8] {
+        match self {
+            LineEnding::Cr => b"\r",
+            LineEnding::Lf => b"\n",
+            LineEnding::Crlf => b"\r\n",
+        }
+    }
+}
+
+/// What to append to bytes sent on a session.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TxAppend {
+    #[default]
+    None,
+    Cr,
+    Lf,
+    Crlf,
+    /// Follow the line ending detected on RX.
+    Auto,
+    /// Any terminator, e.g. ETX or a NUL for binary protocols.
+    Custom(Vec<u8>),
+}
+
+impl TxAppend {
+    /// Bytes to append given the session's detected RX line ending.
+    pub fn suffix(&self, detected: Option<LineEnding>) -> &[u8] {
+        match self {
+            TxAppend::None => b"",
+            TxAppend::Cr => b"\r",
+            TxAppend::Lf => b"\n",
+            TxAppend::Crlf => b"\r\n",
+            TxAppend::Auto => detected.map_or(b"", LineEnding::bytes),
+            TxAppend::Custom(bytes) => bytes,
+        }
+    }
+}
+
+/// Counts the line terminators seen on a stream. A CR at the end of one chunk
+/// is held until the next chunk shows whether an LF follows.
+#[derive(Debug, Clone, Default)]
+pub struct EolCounter {
+    cr: u64,
+    lf: u64,
+    crlf: u64,
+    pending_cr: bool,
+}
+
+impl EolCounter {
+    pub fn feed(&mut self, data: &[u8]) {
+        for &b in data {
+            match (self.pending_cr, b) {
+                (true, b'\n') => {
+                    self.crlf += 1;
+                    self.pending_cr = false;
+                    continue;
+                }
+                (true, _) => {
+                    self.cr += 1;
+                    self.pending_cr = false;
+                }
+                _ => {}
+            }
+            match b {
+                b'\r' => self.pending_cr = true,
+                b'\n' => self.lf += 1,
+                _ => {}
+            }
+        }
+    }
+
+    /// The most frequent terminator so far, if any line has ended yet.
+    pub fn detected(&self) -> Option<LineEnding> {
+        // Later entries win ties, so CRLF is preferred
+        [(self.cr, LineEnding::Cr), (self.lf, LineEnding::Lf), (self.crlf, LineEnding::Crlf)]
+            .into_iter()
+            .filter(|(n, _)| *n > 0)
+            .max_by_key(|(n, _)| *n)
+            .map(|(_, e)| e)
+    }
+}
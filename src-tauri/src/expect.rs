@@ -0,0 +1,66 @@
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time::Instant;
+
+/// A copy of one session's raw RX stream, for exchanges driven by the backend
+/// (AT commands, scripts) rather than the user.
+pub struct RxTap {
+    rx: UnboundedReceiver<Vec<u8>>,
+    buf: Vec<u8>,
+}
+
+impl RxTap {
+    pub fn new(rx: UnboundedReceiver<Vec<u8>>) -> Self {
+        Self { rx, buf: Vec::new() }
+    }
+
+    /// Wait until one of `needles` arrives. Returns the index of the needle and
+    /// everything received up to and including it; later bytes stay buffered.
+    /// `None` waits indefinitely.
+    pub async fn expect(&mut self, needles: &[&[u8]], timeout: Option<Duration>) -> Result<(usize, Vec<u8>), String> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let hit = needles.iter().enumerate()
+                .filter(|(_, n)| !n.is_empty())
+                .filter_map(|(i, n)| find(&self.buf, n).map(|pos| (pos + n.len(), i)))
+                .min();
+            if let Some((end, idx)) = hit {
+                return Ok((idx, self.buf.drain(..end).collect()));
+            }
+            let next = match deadline {
+                Some(d) => tokio::time::timeout_at(d, self.rx.recv()).await.map_err(|_| {
+                    let wanted: Vec<_> = needles.iter().map(|n| String::from_utf8_lossy(n)).collect();
+                    format!("Timed out waiting for {}", wanted.join(" | "))
+                })?,
+                None => self.rx.recv().await,
+            };
+            match next {
+                Some(data) => self.buf.extend_from_slice(&data),
+                None => return Err("Connection closed".into()),
+            }
+        }
+    }
+
+    /// Everything received from now until `window` has passed, plus anything
+    /// already buffered.
+    pub async fn collect(&mut self, window: Duration) -> Result<Vec<u8>, String> {
+        let deadline = Instant::now() + window;
+        loop {
+            match tokio::time::timeout_at(deadline, self.rx.recv()).await {
+                Ok(Some(data)) => self.buf.extend_from_slice(&data),
+                Ok(None) => return Err("Connection closed".into()),
+                Err(_) => return Ok(std::mem::take(&mut self.buf)),
+            }
+        }
+    }
+
+    /// Drop anything received so far.
+    pub fn clear(&mut self) {
+        while self.rx.try_recv().is_ok() {}
+        self.buf.clear();
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
@@ -0,0 +1,302 @@
+use serde::Serialize;
+use crate::decoder::{self, Protocol};
+
+/// Parsed capture filter expression, e.g.
+/// `byte[0] == 0xAA and len >= 8` or `not nmea ~ GSV`.
+///
+/// Fields: `byte[i]` (negative indexes count from the end), `len`, `text`
+/// (payload as UTF-8), and `nmea`, `modbus_rtu`, `mavlink`, `one_wire`,
+/// `dali` for the decoded message type. Numeric fields take `== != < <= > >=`, text fields
+/// `== != ~` (contains). Combine with `and`, `or`, `not` and parentheses.
+/// A comparison on a field the packet doesn't have is false.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Num(NumField, Op, i64),
+    Text(TextField, Op, String),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum NumField {
+    Byte(i64),
+    Len,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum TextField {
+    Text,
+    Decoded(Protocol),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    /// Value and the text it was written as.
+    Num(i64, String),
+    Str(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    OpenBracket,
+    CloseBracket,
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (token, len) = match c {
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => (Token::Open, 1),
+            ')' => (Token::Close, 1),
+            '[' => (Token::OpenBracket, 1),
+            ']' => (Token::CloseBracket, 1),
+            '~' => (Token::Op(Op::Contains), 1),
+            '=' if next == Some('=') => (Token::Op(Op::Eq), 2),
+            '!' if next == Some('=') => (Token::Op(Op::Ne), 2),
+            '!' => (Token::Not, 1),
+            '<' if next == Some('=') => (Token::Op(Op::Le), 2),
+            '<' => (Token::Op(Op::Lt), 1),
+            '>' if next == Some('=') => (Token::Op(Op::Ge), 2),
+            '>' => (Token::Op(Op::Gt), 1),
+            '&' if next == Some('&') => (Token::And, 2),
+            '|' if next == Some('|') => (Token::Or, 2),
+            '"' | '\'' => {
+                let end = chars[i + 1..].iter().position(|&q| q == c).ok_or("Unterminated string")?;
+                (Token::Str(chars[i + 1..i + 1 + end].iter().collect()), end + 2)
+            }
+            _ if c.is_ascii_alphanumeric() || c == '_' || c == '-' => {
+                let len = chars[i..].iter().take_while(|c| c.is_ascii_alphanumeric() || **c == '_' || **c == '-').count();
+                let word: String = chars[i..i + len].iter().collect();
+                let token = match word.to_ascii_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => match parse_num(&word) {
+                        Some(n) => Token::Num(n, word),
+                        None => Token::Word(word),
+                    },
+                };
+                (token, len)
+            }
+            _ => return Err(format!("Unexpected '{c}'")),
+        };
+        tokens.push(token);
+        i += len;
+    }
+    Ok(tokens)
+}
+
+fn parse_num(s: &str) -> Option<i64> {
+    let (neg, digits) = match s.strip_prefix('-') {
+        Some(d) => (true, d),
+        None => (false, s),
+    };
+    let n = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    Some(if neg { -n } else { n })
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn bump(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn expect(&mut self, token: Token, what: &str) -> Result<(), String> {
+        if self.bump() == Some(token) { Ok(()) } else { Err(format!("Expected {what}")) }
+    }
+
+    fn or(&mut self) -> Result<Filter, String> {
+        let mut lhs = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            lhs = Filter::Or(Box::new(lhs), Box::new(self.and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Filter, String> {
+        let mut lhs = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            lhs = Filter::And(Box::new(lhs), Box::new(self.unary()?));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Filter, String> {
+        match self.bump() {
+            Some(Token::Not) => Ok(Filter::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let inner = self.or()?;
+                self.expect(Token::Close, "')'")?;
+                Ok(inner)
+            }
+            Some(Token::Word(field)) => self.comparison(&field),
+            _ => Err("Expected a field, 'not' or '('".into()),
+        }
+    }
+
+    fn comparison(&mut self, field: &str) -> Result<Filter, String> {
+        let text = match field.to_ascii_lowercase().as_str() {
+            "byte" => {
+                self.expect(Token::OpenBracket, "'[' after byte")?;
+                let Some(Token::Num(idx, _)) = self.bump() else {
+                    return Err("Expected a byte index".into());
+                };
+                self.expect(Token::CloseBracket, "']'")?;
+                return self.num_comparison(NumField::Byte(idx));
+            }
+            "len" => return self.num_comparison(NumField::Len),
+            "text" => TextField::Text,
+            "nmea" => TextField::Decoded(Protocol::Nmea),
+            "modbus_rtu" | "modbus" => TextField::Decoded(Protocol::ModbusRtu),
+            "mavlink" => TextField::Decoded(Protocol::Mavlink),
+            "one_wire" | "onewire" => TextField::Decoded(Protocol::OneWire),
+            "dali" => TextField::Decoded(Protocol::Dali),
+            _ => return Err(format!("Unknown field '{field}'")),
+        };
+        let op = match self.bump() {
+            Some(Token::Op(op @ (Op::Eq | Op::Ne | Op::Contains))) => op,
+            _ => return Err(format!("Expected ==, != or ~ after {field}")),
+        };
+        let value = match self.bump() {
+            Some(Token::Str(s) | Token::Word(s) | Token::Num(_, s)) => s,
+            _ => return Err(format!("Expected a value after {field}")),
+        };
+        Ok(Filter::Text(text, op, value))
+    }
+
+    fn num_comparison(&mut self, field: NumField) -> Result<Filter, String> {
+        let op = match self.bump() {
+            Some(Token::Op(op)) if op != Op::Contains => op,
+            _ => return Err("Expected a numeric comparison".into()),
+        };
+        match self.bump() {
+            Some(Token::Num(n, _)) => Ok(Filter::Num(field, op, n)),
+            _ => Err("Expected a number".into()),
+        }
+    }
+}
+
+impl Filter {
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut parser = Parser { tokens: tokenize(src)?, pos: 0 };
+        let filter = parser.or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err("Unexpected input after expression".into());
+        }
+        Ok(filter)
+    }
+
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        match self {
+            Filter::And(a, b) => a.matches(bytes) && b.matches(bytes),
+            Filter::Or(a, b) => a.matches(bytes) || b.matches(bytes),
+            Filter::Not(f) => !f.matches(bytes),
+            Filter::Num(field, op, rhs) => {
+                let lhs = match *field {
+                    NumField::Len => Some(bytes.len() as i64),
+                    NumField::Byte(i) if i < 0 => bytes.len().checked_sub(i.unsigned_abs() as usize).map(|i| bytes[i] as i64),
+                    NumField::Byte(i) => bytes.get(i as usize).map(|&b| b as i64),
+                };
+                lhs.is_some_and(|lhs| match op {
+                    Op::Eq => lhs == *rhs,
+                    Op::Ne => lhs != *rhs,
+                    Op::Lt => lhs < *rhs,
+                    Op::Le => lhs <= *rhs,
+                    Op::Gt => lhs > *rhs,
+                    Op::Ge => lhs >= *rhs,
+                    Op::Contains => false,
+                })
+            }
+            Filter::Text(field, op, rhs) => {
+                let lhs = match *field {
+                    TextField::Text => Some(String::from_utf8_lossy(bytes).into_owned()),
+                    TextField::Decoded(proto) => {
+                        let decoded = decoder::identify(proto, bytes);
+                        (decoded.message != "malformed").then_some(decoded.message)
+                    }
+                };
+                lhs.is_some_and(|lhs| match op {
+                    Op::Eq => lhs == *rhs,
+                    Op::Ne => lhs != *rhs,
+                    _ => lhs.contains(rhs.as_str()),
+                })
+            }
+        }
+    }
+}
+
+/// A session's filter as configured, with how much it has held back.
+pub struct CaptureFilter {
+    pub expr: String,
+    pub filter: Filter,
+    pub passed: u64,
+    pub dropped: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureFilterInfo {
+    pub session_id: String,
+    pub expr: String,
+    pub passed: u64,
+    pub dropped: u64,
+}
+
+impl CaptureFilter {
+    pub fn new(expr: &str) -> Result<Self, String> {
+        Ok(Self { expr: expr.to_string(), filter: Filter::parse(expr)?, passed: 0, dropped: 0 })
+    }
+
+    /// Whether to keep the packet, counting the outcome.
+    pub fn admit(&mut self, bytes: &[u8]) -> bool {
+        let keep = self.filter.matches(bytes);
+        if keep { self.passed += 1 } else { self.dropped += 1 }
+        keep
+    }
+
+    pub fn info(&self, session_id: &str) -> CaptureFilterInfo {
+        CaptureFilterInfo {
+            session_id: session_id.to_string(),
+            expr: self.expr.clone(),
+            passed: self.passed,
+            dropped: self.dropped,
+        }
+    }
+}
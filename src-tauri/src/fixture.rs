@@ -0,0 +1,170 @@
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::classify::{ClassRule, Classifier};
+use crate::decoder::{self, Protocol};
+use crate::eol::EolCounter;
+use crate::payload::hex_to_bytes;
+use crate::splitter::Splitter;
+use crate::state::SplitterConfig;
+use crate::transform::{Pipeline, Stage};
+
+/// Raw RX bytes as they arrived from the transport.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chunk {
+    pub offset_ms: f64,
+    pub hex: String,
+}
+
+/// Recorded traffic plus the configuration it is to be processed with, so a
+/// replay does not depend on the current settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+    pub session_id: String,
+    pub splitter: SplitterConfig,
+    pub rules: Vec<ClassRule>,
+    pub protocol: Option<Protocol>,
+    /// RX pipeline applied after framing.
+    #[serde(default)]
+    pub stages: Vec<Stage>,
+    /// Each chunk is one datagram rather than part of a byte stream.
+    pub datagrams: bool,
+    pub chunks: Vec<Chunk>,
+}
+
+/// What processing a fixture produced for one packet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FixtureEvent {
+    pub offset_ms: f64,
+    pub hex: String,
+    pub checksum_ok: Option<bool>,
+    pub severity: Option<String>,
+    pub tags: Vec<String>,
+    /// Decoded message type, with a protocol set.
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FixtureMismatch {
+    pub index: usize,
+    pub expected: Option<FixtureEvent>,
+    pub actual: Option<FixtureEvent>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FixtureReport {
+    pub passed: bool,
+    pub events: usize,
+    /// The expectation was (re)written from this run.
+    pub updated: bool,
+    pub expected_path: String,
+    pub mismatches: Vec<FixtureMismatch>,
+}
+
+pub struct Recorder {
+    started_ms: f64,
+    fixture: Fixture,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02X}")).collect()
+}
+
+impl Recorder {
+    pub fn new(fixture: Fixture, now_ms: f64) -> Self {
+        Self { started_ms: now_ms, fixture }
+    }
+
+    pub fn push(&mut self, ts: f64, data: &[u8]) {
+        self.fixture.chunks.push(Chunk { offset_ms: ts - self.started_ms, hex: to_hex(data) });
+    }
+
+    /// When recording started, and the chunks recorded so far.
+    pub fn raw(&self) -> (f64, Vec<Chunk>) {
+        (self.started_ms, self.fixture.chunks.clone())
+    }
+
+    pub fn finish(self) -> Fixture {
+        self.fixture
+    }
+}
+
+/// Run the fixture's chunks through framing, the RX pipeline, classification
+/// and decoding.
+/// Timestamps are the recorded offsets, so the result is deterministic.
+pub fn replay(fixture: &Fixture) -> Result<Vec<FixtureEvent>, String> {
+    let classifier = Classifier::new(fixture.rules.clone())?;
+    let pipeline = Pipeline::new(fixture.stages.clone())?;
+    let mut eol = EolCounter::default();
+    let (mut buf, mut in_packet) = (Vec::new(), false);
+    let mut next_id = 1;
+    let mut events = Vec::new();
+    for chunk in &fixture.chunks {
+        let data = hex_to_bytes(&chunk.hex)?;
+        eol.feed(&data);
+        let pkts = if fixture.datagrams {
+            let splitter = Splitter::with_state(fixture.splitter.clone(), Vec::new(), false);
+            vec![splitter.whole(data, "RX", chunk.offset_ms, &fixture.session_id, &mut next_id)]
+        } else {
+            let mut splitter = Splitter::with_state(fixture.splitter.clone(), std::mem::take(&mut buf), in_packet)
+                .detected_line_ending(eol.detected());
+            let pkts = splitter.feed(&data, "RX", chunk.offset_ms, &fixture.session_id, &mut next_id);
+            (buf, in_packet) = splitter.into_state();
+            pkts
+        };
+        for pkt in pkts {
+            let out = pipeline.apply(pkt.bytes);
+            if out.dropped {
+                continue;
+            }
+            let (severity, mut tags) = classifier.classify(&out.bytes);
+            tags.extend(out.errors);
+            events.push(FixtureEvent {
+                offset_ms: pkt.timestamp_ms,
+                hex: to_hex(&out.bytes),
+                checksum_ok: out.checksum_ok.or(pkt.checksum_ok),
+                severity,
+                tags,
+                message: fixture.protocol.map(|p| decoder::identify(p, &out.bytes).message),
+            });
+        }
+    }
+    Ok(events)
+}
+
+/// Where the expectation of `fixture_path` lives unless given explicitly.
+pub fn expected_path(fixture_path: &Path) -> PathBuf {
+    fixture_path.with_extension("expected.json")
+}
+
+/// Replay the fixture at `path` and compare against the stored expectation.
+/// A missing expectation, or `update`, writes this run's output as the new one.
+pub fn test(path: &Path, expected: Option<&Path>, update: bool) -> Result<FixtureReport, String> {
+    let fixture: Fixture = serde_json::from_str(&std::fs::read_to_string(path).map_err(|e| e.to_string())?)
+        .map_err(|e| format!("Invalid fixture: {e}"))?;
+    let actual = replay(&fixture)?;
+    let expected_path = expected.map_or_else(|| expected_path(path), Path::to_path_buf);
+    let mut report = FixtureReport {
+        passed: true,
+        events: actual.len(),
+        updated: false,
+        expected_path: expected_path.to_string_lossy().into_owned(),
+        mismatches: Vec::new(),
+    };
+
+    if update || !expected_path.exists() {
+        let json = serde_json::to_string_pretty(&actual).map_err(|e| e.to_string())?;
+        std::fs::write(&expected_path, json).map_err(|e| e.to_string())?;
+        report.updated = true;
+        return Ok(report);
+    }
+    let expected: Vec<FixtureEvent> = serde_json::from_str(&std::fs::read_to_string(&expected_path).map_err(|e| e.to_string())?)
+        .map_err(|e| format!("Invalid expectation: {e}"))?;
+    for index in 0..expected.len().max(actual.len()) {
+        let (e, a) = (expected.get(index), actual.get(index));
+        if e != a {
+            report.mismatches.push(FixtureMismatch { index, expected: e.cloned(), actual: a.cloned() });
+        }
+    }
+    report.passed = report.mismatches.is_empty();
+    Ok(report)
+}
@@ -0,0 +1,168 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::expect::RxTap;
+use crate::modem::Writer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mutation {
+    /// Flip one to three random bits.
+    BitFlip,
+    /// Cut the payload short.
+    Truncate,
+    /// Random bytes, up to twice the seed's length.
+    RandomLength,
+    /// Corrupt the checksum field.
+    BadChecksum,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FuzzOptions {
+    /// Well-formed payload the cases are derived from.
+    pub seed: Vec<u8>,
+    /// Picked at random for each case.
+    pub mutations: Vec<Mutation>,
+    /// Time between cases, during which the response is collected.
+    pub interval_ms: u64,
+    /// Number of cases; `None` runs until stopped.
+    pub count: Option<u32>,
+    /// Seed of the generator, to replay a run. Random when unset.
+    pub rng_seed: Option<u64>,
+}
+
+impl Default for FuzzOptions {
+    fn default() -> Self {
+        Self {
+            seed: Vec::new(),
+            mutations: vec![Mutation::BitFlip, Mutation::Truncate, Mutation::RandomLength, Mutation::BadChecksum],
+            interval_ms: 200,
+            count: Some(100),
+            rng_seed: None,
+        }
+    }
+}
+
+/// One case sent and what came back within the interval.
+#[derive(Debug, Clone, Serialize)]
+pub struct FuzzCase {
+    pub session_id: String,
+    pub case: u32,
+    pub mutation: Mutation,
+    pub sent: Vec<u8>,
+    pub response: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FuzzSummary {
+    pub session_id: String,
+    pub rng_seed: u64,
+    pub cases: u32,
+    /// Cases the device answered.
+    pub responded: u32,
+    pub error: Option<String>,
+}
+
+/// splitmix64; small, seedable and good enough for test inputs.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform-ish value in `0..n`; `n` must be non-zero.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+pub fn random_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
+struct Mutator {
+    rng: Rng,
+    seed: Vec<u8>,
+    /// Offset and size of the checksum field, as in the splitter config.
+    checksum: (i32, usize),
+}
+
+impl Mutator {
+    fn mutate(&mut self, mutation: Mutation) -> Vec<u8> {
+        let mut data = self.seed.clone();
+        match mutation {
+            Mutation::BitFlip => {
+                for _ in 0..=self.rng.below(3) {
+                    let bit = self.rng.below(data.len() * 8);
+                    data[bit / 8] ^= 1 << (bit % 8);
+                }
+            }
+            Mutation::Truncate => data.truncate(self.rng.below(data.len())),
+            Mutation::RandomLength => {
+                let len = 1 + self.rng.below(data.len() * 2);
+                data = (0..len).map(|_| self.rng.next_u64() as u8).collect();
+            }
+            Mutation::BadChecksum => {
+                let (offset, size) = self.checksum;
+                let start = if offset < 0 {
+                    data.len().saturating_sub(offset.unsigned_abs() as usize)
+                } else {
+                    (offset as usize).min(data.len().saturating_sub(1))
+                };
+                let end = (start + size.max(1)).min(data.len());
+                for b in &mut data[start..end] {
+                    // Never XOR with zero, so the field always changes
+                    *b ^= 1 + self.rng.below(255) as u8;
+                }
+            }
+        }
+        data
+    }
+}
+
+/// Send mutated copies of `options.seed` one interval apart, reporting each
+/// case and its response through `on_case`.
+pub async fn run(
+    tap: &mut RxTap,
+    send: Writer<'_>,
+    session_id: &str,
+    options: &FuzzOptions,
+    checksum: (i32, usize),
+    on_case: impl Fn(FuzzCase),
+) -> FuzzSummary {
+    let rng_seed = options.rng_seed.unwrap_or_else(random_seed);
+    let mut mutator = Mutator { rng: Rng(rng_seed), seed: options.seed.clone(), checksum };
+    let interval = Duration::from_millis(options.interval_ms);
+    let mut summary = FuzzSummary { session_id: session_id.to_string(), rng_seed, cases: 0, responded: 0, error: None };
+
+    tap.clear();
+    while options.count.is_none_or(|n| summary.cases < n) {
+        let mutation = options.mutations[mutator.rng.below(options.mutations.len())];
+        let sent = mutator.mutate(mutation);
+        if let Err(e) = send(sent.clone()) {
+            summary.error = Some(e);
+            break;
+        }
+        let response = match tap.collect(interval).await {
+            Ok(r) => r,
+            Err(e) => {
+                summary.error = Some(e);
+                break;
+            }
+        };
+        summary.cases += 1;
+        if !response.is_empty() {
+            summary.responded += 1;
+        }
+        on_case(FuzzCase { session_id: session_id.to_string(), case: summary.cases, mutation, sent, response });
+    }
+    summary
+}
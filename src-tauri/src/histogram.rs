@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use serde::Serialize;
+
+/// Sub-buckets per power of two; bucket width stays within ~6% of its value.
+const SUB_BITS: u32 = 5;
+const SUB_COUNT: u64 = 1 << SUB_BITS;
+const HALF: u64 = SUB_COUNT / 2;
+
+/// Log-linear (HDR-style) histogram of durations at microsecond resolution.
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+    counts: BTreeMap<u64, u64>,
+    total: u64,
+    sum_us: u64,
+    min_us: u64,
+    max_us: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Bucket {
+    pub low_ms: f64,
+    pub high_ms: f64,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramSummary {
+    pub session_id: String,
+    pub count: u64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub p999_ms: f64,
+    pub buckets: Vec<Bucket>,
+}
+
+fn index(us: u64) -> u64 {
+    if us < SUB_COUNT {
+        return us;
+    }
+    let shift = 64 - us.leading_zeros() as u64 - SUB_BITS as u64;
+    shift * HALF + (us >> shift)
+}
+
+/// [low, high) in microseconds of bucket `idx`.
+fn range(idx: u64) -> (u64, u64) {
+    if idx < SUB_COUNT {
+        return (idx, idx + 1);
+    }
+    let shift = idx / HALF - 1;
+    let sub = idx - shift * HALF;
+    (sub << shift, (sub + 1) << shift)
+}
+
+impl Histogram {
+    pub fn record_ms(&mut self, ms: f64) {
+        let us = (ms.max(0.0) * 1000.0).round() as u64;
+        *self.counts.entry(index(us)).or_default() += 1;
+        self.min_us = if self.total == 0 { us } else { self.min_us.min(us) };
+        self.max_us = self.max_us.max(us);
+        self.total += 1;
+        self.sum_us += us;
+    }
+
+    /// Upper bound of the bucket holding the `q` quantile (0..=1), in ms.
+    pub fn quantile_ms(&self, q: f64) -> f64 {
+        let target = ((q * self.total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (&idx, &n) in &self.counts {
+            seen += n;
+            if seen >= target {
+                return range(idx).1.min(self.max_us) as f64 / 1000.0;
+            }
+        }
+        self.max_us as f64 / 1000.0
+    }
+
+    pub fn summary(&self, session_id: &str) -> HistogramSummary {
+        HistogramSummary {
+            session_id: session_id.to_string(),
+            count: self.total,
+            min_ms: self.min_us as f64 / 1000.0,
+            max_ms: self.max_us as f64 / 1000.0,
+            mean_ms: if self.total > 0 { self.sum_us as f64 / self.total as f64 / 1000.0 } else { 0.0 },
+            p50_ms: self.quantile_ms(0.5),
+            p90_ms: self.quantile_ms(0.9),
+            p99_ms: self.quantile_ms(0.99),
+            p999_ms: self.quantile_ms(0.999),
+            buckets: self.counts.iter()
+                .map(|(&idx, &count)| {
+                    let (low, high) = range(idx);
+                    Bucket { low_ms: low as f64 / 1000.0, high_ms: high as f64 / 1000.0, count }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// One CSV row per non-empty bucket, with the running percentile.
+pub fn to_csv(summaries: &[HistogramSummary]) -> String {
+    let mut out = String::from("session_id,low_ms,high_ms,count,cumulative_pct\n");
+    for s in summaries {
+        let mut seen = 0;
+        for b in &s.buckets {
+            seen += b.count;
+            let pct = seen as f64 * 100.0 / s.count as f64;
+            out.push_str(&format!("{},{:.3},{:.3},{},{pct:.3}\n", s.session_id, b.low_ms, b.high_ms, b.count));
+        }
+    }
+    out
+}
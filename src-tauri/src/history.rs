@@ -0,0 +1,112 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::summary::SessionSummary;
+
+/// Serialises load-modify-save of the index file across closing sessions.
+static WRITE: Mutex<()> = Mutex::new(());
+
+/// A closed session in the index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(flatten)]
+    pub summary: SessionSummary,
+}
+
+/// Filters for `SessionHistory::search`; unset fields match everything.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HistoryQuery {
+    /// Case-insensitive substring of the session id, name, label, profile,
+    /// device identity, log path or a tag.
+    pub text: Option<String>,
+    /// Every one of these tags.
+    pub tags: Vec<String>,
+    pub kind: Option<String>,
+    pub profile: Option<String>,
+    /// Sessions that were open at some point in `[from_ms, to_ms]`.
+    pub from_ms: Option<f64>,
+    pub to_ms: Option<f64>,
+}
+
+/// Index of past sessions, persisted as `sessions.json` in the app data directory.
+pub struct SessionHistory {
+    path: PathBuf,
+    entries: Vec<HistoryEntry>,
+}
+
+impl SessionHistory {
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join("sessions.json");
+        let entries = std::fs::read(&path)
+            .ok()
+            .and_then(|b| serde_json::from_slice(&b).ok())
+            .unwrap_or_default();
+        Self { path, entries }
+    }
+
+    /// Add a closed session under a new id.
+    pub fn record(dir: &Path, summary: SessionSummary) -> Result<u64, String> {
+        let _guard = WRITE.lock().unwrap_or_else(|e| e.into_inner());
+        let mut history = Self::load(dir);
+        let id = history.entries.iter().map(|e| e.id).max().map_or(1, |id| id + 1);
+        history.entries.push(HistoryEntry { id, tags: Vec::new(), summary });
+        history.save()?;
+        Ok(id)
+    }
+
+    /// Replace the tags of entry `id`.
+    pub fn tag(dir: &Path, id: u64, tags: Vec<String>) -> Result<HistoryEntry, String> {
+        let _guard = WRITE.lock().unwrap_or_else(|e| e.into_inner());
+        let mut history = Self::load(dir);
+        let entry = history.entries.iter_mut().find(|e| e.id == id).ok_or("Unknown history entry")?;
+        entry.tags = tags;
+        let entry = entry.clone();
+        history.save()?;
+        Ok(entry)
+    }
+
+    /// Remove entry `id` from the index. The log file is left alone.
+    pub fn forget(dir: &Path, id: u64) -> Result<(), String> {
+        let _guard = WRITE.lock().unwrap_or_else(|e| e.into_inner());
+        let mut history = Self::load(dir);
+        history.entries.retain(|e| e.id != id);
+        history.save()
+    }
+
+    /// Matching entries, most recently closed first.
+    pub fn search(&self, query: &HistoryQuery) -> Vec<HistoryEntry> {
+        let text = query.text.as_ref().map(|t| t.to_lowercase());
+        let mut found: Vec<HistoryEntry> = self.entries.iter()
+            .filter(|e| {
+                let s = &e.summary;
+                text.as_ref().is_none_or(|t| {
+                    [Some(&s.session_id), Some(&s.name), s.label.as_ref(), s.profile.as_ref(), s.identity.as_ref(), s.log_path.as_ref()]
+                        .into_iter()
+                        .flatten()
+                        .chain(&e.tags)
+                        .any(|f| f.to_lowercase().contains(t))
+                })
+                    && query.tags.iter().all(|t| e.tags.contains(t))
+                    && query.kind.as_ref().is_none_or(|k| *k == s.kind)
+                    && query.profile.as_ref().is_none_or(|p| s.profile.as_ref() == Some(p))
+                    && query.from_ms.is_none_or(|t| s.closed_ms >= t)
+                    && query.to_ms.is_none_or(|t| s.opened_ms.unwrap_or(s.closed_ms) <= t)
+            })
+            .cloned()
+            .collect();
+        found.sort_by(|a, b| b.summary.closed_ms.total_cmp(&a.summary.closed_ms));
+        found
+    }
+
+    fn save(&self) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_vec_pretty(&self.entries).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, json).map_err(|e| e.to_string())
+    }
+}
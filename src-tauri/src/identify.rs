@@ -0,0 +1,49 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::eol::TxAppend;
+use crate::expect::RxTap;
+use crate::modem::Writer;
+use crate::payload::PayloadFormat;
+
+/// Request sent right after a session connects to learn what device is on
+/// the other end, e.g. `*IDN?` for SCPI instruments or `ATI` for modems.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdentifyProbe {
+    pub data: String,
+    #[serde(default)]
+    pub format: PayloadFormat,
+    /// Resolve C-style escapes such as `\x1B` or `\n` in text.
+    #[serde(default)]
+    pub escape: bool,
+    /// Terminator to append; `None` uses the session's TX append mode.
+    #[serde(default)]
+    pub append: Option<TxAppend>,
+    /// Text that ends the response, e.g. "\n" or "OK". Without it, everything
+    /// received within `timeout_ms` is the response.
+    #[serde(default)]
+    pub until: Option<String>,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_timeout_ms() -> u64 {
+    1000
+}
+
+/// Send `request` and return the response as trimmed text, without an echo
+/// of the request.
+pub async fn probe(tap: &mut RxTap, send: Writer<'_>, request: Vec<u8>, probe: &IdentifyProbe) -> Result<String, String> {
+    let timeout = Duration::from_millis(probe.timeout_ms);
+    tap.clear();
+    send(request.clone())?;
+    let resp = match probe.until.as_deref().filter(|u| !u.is_empty()) {
+        Some(until) => tap.expect(&[until.as_bytes()], Some(timeout)).await?.1,
+        None => tap.collect(timeout).await?,
+    };
+    let resp = resp.strip_prefix(request.as_slice()).unwrap_or(&resp);
+    let text = String::from_utf8_lossy(resp).trim().to_string();
+    if text.is_empty() {
+        return Err("No response to the identification probe".into());
+    }
+    Ok(text)
+}
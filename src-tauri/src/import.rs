@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use regex::Regex;
+use crate::payload::hex_to_bytes;
+
+/// Payload of one captured frame or log line.
+pub struct Chunk {
+    pub ts_ms: f64,
+    /// "TX" from the side that opened the conversation, "RX" from the other.
+    pub direction: &'static str,
+    pub bytes: Vec<u8>,
+}
+
+/// One TCP/UDP conversation, or one label of a text log.
+pub struct Stream {
+    pub name: String,
+    /// "tcp" and "udp", or "log".
+    pub kind: &'static str,
+    pub chunks: Vec<Chunk>,
+}
+
+pub struct Capture {
+    pub streams: Vec<Stream>,
+    /// Frames that carried no TCP or UDP, or could not be parsed.
+    pub skipped: usize,
+}
+
+/// Parse a pcap or pcapng capture, or else a timestamped text log whose
+/// streams are named after `name` unless the lines carry labels.
+pub fn parse(data: &[u8], name: &str) -> Result<Capture, String> {
+    match data.get(..4) {
+        Some([0x0A, 0x0D, 0x0D, 0x0A]) => pcapng(data),
+        Some(&[a, b, c, d]) if pcap_magic(u32::from_le_bytes([a, b, c, d])).is_some() => pcap(data),
+        _ => Ok(text(&String::from_utf8_lossy(data), name)),
+    }
+}
+
+struct Segment<'a> {
+    tcp: bool,
+    src: SocketAddr,
+    dst: SocketAddr,
+    seq: u32,
+    syn: bool,
+    ack: bool,
+    payload: &'a [u8],
+}
+
+fn be16(b: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(b.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be32(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// The IP packet inside a frame of link type `link`.
+fn network(link: u32, frame: &[u8]) -> Option<&[u8]> {
+    let (ethertype, offset) = match link {
+        // BSD loopback: the address family, which the IP version repeats
+        0 | 108 => return frame.get(4..),
+        1 => {
+            let (mut ethertype, mut offset) = (be16(frame, 12)?, 14);
+            while matches!(ethertype, 0x8100 | 0x88A8) {
+                ethertype = be16(frame, offset + 2)?;
+                offset += 4;
+            }
+            (ethertype, offset)
+        }
+        12 | 14 | 101 | 228 | 229 => return Some(frame),
+        113 => (be16(frame, 14)?, 16),
+        276 => (be16(frame, 0)?, 20),
+        _ => return None,
+    };
+    matches!(ethertype, 0x0800 | 0x86DD).then(|| frame.get(offset..)).flatten()
+}
+
+fn transport(ip: &[u8]) -> Option<Segment<'_>> {
+    let (proto, src, dst, body) = match ip.first()? >> 4 {
+        4 => {
+            let ihl = (ip[0] & 0x0F) as usize * 4;
+            let total = (be16(ip, 2)? as usize).min(ip.len());
+            if be16(ip, 6)? & 0x1FFF != 0 {
+                return None; // Later fragments carry no port numbers
+            }
+            let addr = |at: usize| Some(IpAddr::V4(Ipv4Addr::from(be32(ip, at)?)));
+            (*ip.get(9)?, addr(12)?, addr(16)?, ip.get(ihl..total)?)
+        }
+        6 => {
+            let len = be16(ip, 4)? as usize;
+            let addr = |at: usize| Some(IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(ip.get(at..at + 16)?).ok()?)));
+            (*ip.get(6)?, addr(8)?, addr(24)?, ip.get(40..(40 + len).min(ip.len()))?)
+        }
+        _ => return None,
+    };
+    let ports = (be16(body, 0)?, be16(body, 2)?);
+    let (src, dst) = (SocketAddr::new(src, ports.0), SocketAddr::new(dst, ports.1));
+    match proto {
+        6 => {
+            let offset = (*body.get(12)? >> 4) as usize * 4;
+            let flags = *body.get(13)?;
+            Some(Segment { tcp: true, src, dst, seq: be32(body, 4)?, syn: flags & 0x02 != 0, ack: flags & 0x10 != 0, payload: body.get(offset..)? })
+        }
+        17 => {
+            let len = (be16(body, 4)? as usize).clamp(8, body.len());
+            Some(Segment { tcp: false, src, dst, seq: 0, syn: false, ack: false, payload: body.get(8..len)? })
+        }
+        _ => None,
+    }
+}
+
+struct Flow {
+    stream: usize,
+    client: SocketAddr,
+    /// Next expected TCP sequence number, client side first.
+    next_seq: [Option<u32>; 2],
+}
+
+/// Groups frames into conversations.
+#[derive(Default)]
+struct Flows {
+    flows: HashMap<(bool, SocketAddr, SocketAddr), Flow>,
+    streams: Vec<Stream>,
+    skipped: usize,
+}
+
+impl Flows {
+    fn frame(&mut self, link: u32, ts_ms: f64, frame: &[u8]) {
+        match network(link, frame).and_then(transport) {
+            Some(seg) => self.segment(ts_ms, seg),
+            None => self.skipped += 1,
+        }
+    }
+
+    fn segment(&mut self, ts_ms: f64, seg: Segment) {
+        let key = (seg.tcp, seg.src.min(seg.dst), seg.src.max(seg.dst));
+        let streams = &mut self.streams;
+        let flow = self.flows.entry(key).or_insert_with(|| {
+            // A SYN-ACK means the capture began mid-handshake, from the server
+            let (client, server) = if seg.syn && seg.ack { (seg.dst, seg.src) } else { (seg.src, seg.dst) };
+            streams.push(Stream {
+                name: format!("{client} → {server}"),
+                kind: if seg.tcp { "tcp" } else { "udp" },
+                chunks: Vec::new(),
+            });
+            Flow { stream: streams.len() - 1, client, next_seq: [None; 2] }
+        });
+        let side = usize::from(seg.src != flow.client);
+        let mut payload = seg.payload;
+        if seg.tcp {
+            if seg.syn {
+                flow.next_seq[side] = Some(seg.seq.wrapping_add(1));
+            }
+            if payload.is_empty() {
+                return;
+            }
+            let end = seg.seq.wrapping_add(payload.len() as u32);
+            if let Some(next) = flow.next_seq[side] {
+                // Retransmitted bytes are dropped, so each byte appears once
+                if end.wrapping_sub(next) as i32 <= 0 {
+                    return;
+                }
+                let overlap = next.wrapping_sub(seg.seq) as i32;
+                if overlap > 0 {
+                    payload = &payload[overlap as usize..];
+                }
+            }
+            flow.next_seq[side] = Some(end);
+        }
+        if !payload.is_empty() {
+            let direction = if side == 0 { "TX" } else { "RX" };
+            self.streams[flow.stream].chunks.push(Chunk { ts_ms, direction, bytes: payload.to_vec() });
+        }
+    }
+
+    fn finish(self) -> Capture {
+        Capture { streams: self.streams, skipped: self.skipped }
+    }
+}
+
+/// Byte order and whether fractions are nanoseconds.
+fn pcap_magic(magic: u32) -> Option<(bool, bool)> {
+    match magic {
+        0xA1B2C3D4 => Some((true, false)),
+        0xD4C3B2A1 => Some((false, false)),
+        0xA1B23C4D => Some((true, true)),
+        0x4D3CB2A1 => Some((false, true)),
+        _ => None,
+    }
+}
+
+fn read_u32(b: &[u8], at: usize, le: bool) -> Option<u32> {
+    let bytes = b.get(at..at + 4)?.try_into().ok()?;
+    Some(if le { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+}
+
+fn read_u16(b: &[u8], at: usize, le: bool) -> Option<u16> {
+    let bytes = b.get(at..at + 2)?.try_into().ok()?;
+    Some(if le { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+}
+
+fn pcap(data: &[u8]) -> Result<Capture, String> {
+    let (le, nanos) = read_u32(data, 0, true).and_then(pcap_magic).ok_or("Not a pcap file")?;
+    // The upper bits can carry FCS information
+    let link = read_u32(data, 20, le).ok_or("Truncated pcap header")? & 0xFFFF;
+    let mut flows = Flows::default();
+    let mut i = 24;
+    // A capture cut off mid-record keeps the records before it
+    while let (Some(sec), Some(frac), Some(len)) = (read_u32(data, i, le), read_u32(data, i + 4, le), read_u32(data, i + 8, le)) {
+        let Some(frame) = data.get(i + 16..i + 16 + len as usize) else {
+            break;
+        };
+        let ts_ms = sec as f64 * 1000.0 + frac as f64 / if nanos { 1e6 } else { 1e3 };
+        flows.frame(link, ts_ms, frame);
+        i += 16 + len as usize;
+    }
+    Ok(flows.finish())
+}
+
+fn pcapng(data: &[u8]) -> Result<Capture, String> {
+    let mut flows = Flows::default();
+    // Link type and timestamp units per second of each interface
+    let mut interfaces: Vec<(u32, f64)> = Vec::new();
+    let (mut le, mut last_ts) = (true, 0.0);
+    let mut i = 0;
+    while i + 12 <= data.len() {
+        if data[i..i + 4] == [0x0A, 0x0D, 0x0D, 0x0A] {
+            le = match read_u32(data, i + 8, true) {
+                Some(0x1A2B3C4D) => true,
+                Some(0x4D3C2B1A) => false,
+                _ => return Err("Invalid pcapng section header".into()),
+            };
+            interfaces.clear();
+        }
+        let kind = read_u32(data, i, le).unwrap_or(0);
+        let len = read_u32(data, i + 4, le).unwrap_or(0) as usize;
+        let Some(block) = data.get(i..i + len).filter(|_| len >= 12 && len.is_multiple_of(4)) else {
+            break;
+        };
+        match kind {
+            1 => {
+                let link = read_u16(block, 8, le).unwrap_or(0) as u32;
+                let mut units = 1e6;
+                let mut at = 16;
+                while let (Some(code), Some(olen)) = (read_u16(block, at, le), read_u16(block, at + 2, le)) {
+                    if code == 0 || at + 4 + olen as usize > len - 4 {
+                        break;
+                    }
+                    if code == 9 && olen >= 1 {
+                        let v = block[at + 4];
+                        units = if v & 0x80 != 0 { 2f64.powi((v & 0x7F) as i32) } else { 10f64.powi(v as i32) };
+                    }
+                    at += 4 + (olen as usize).div_ceil(4) * 4;
+                }
+                interfaces.push((link, units));
+            }
+            6 => {
+                let (Some(iface), Some(high), Some(low), Some(cap)) =
+                    (read_u32(block, 8, le), read_u32(block, 12, le), read_u32(block, 16, le), read_u32(block, 20, le))
+                else {
+                    break;
+                };
+                let Some(&(link, units)) = interfaces.get(iface as usize) else {
+                    flows.skipped += 1;
+                    i += len;
+                    continue;
+                };
+                last_ts = ((high as u64) << 32 | low as u64) as f64 / units * 1000.0;
+                match block.get(28..28 + cap as usize) {
+                    Some(frame) => flows.frame(link, last_ts, frame),
+                    None => flows.skipped += 1,
+                }
+            }
+            3 => {
+                // Simple packets have no timestamp; they follow the previous one
+                let orig = read_u32(block, 8, le).unwrap_or(0) as usize;
+                match (interfaces.first(), block.get(12..(12 + orig).min(len - 4))) {
+                    (Some(&(link, _)), Some(frame)) => flows.frame(link, last_ts, frame),
+                    _ => flows.skipped += 1,
+                }
+            }
+            _ => {}
+        }
+        i += len;
+    }
+    Ok(flows.finish())
+}
+
+/// Parts of a log line, each anchored at the start of what is left of it.
+struct Patterns {
+    date_time: Regex,
+    /// Unix time in seconds or, from 1e11 up, milliseconds as in wirescope logs.
+    epoch: Regex,
+    /// Seconds since start, as printed by dmesg and many terminals.
+    relative: Regex,
+    device_ts: Regex,
+    label: Regex,
+    direction: Regex,
+    class: Regex,
+    hex: Regex,
+}
+
+impl Patterns {
+    fn new() -> Self {
+        let re = |p: &str| Regex::new(p).expect("valid pattern");
+        Self {
+            date_time: re(r"^\[?(?:(\d{4})-(\d{2})-(\d{2})[T ])?(\d{2}):(\d{2}):(\d{2})(?:[.,](\d{1,9}))?(Z|[+-]\d{2}:?\d{2})?\]?\s*"),
+            epoch: re(r"^\[?(\d{9,}(?:\.\d+)?)\]?\s+"),
+            relative: re(r"^\[\s*(\d+\.\d+)\]\s*"),
+            device_ts: re(r"^@[\d.]+\s+"),
+            label: re(r"^\[([^\]]+)\]\s+"),
+            direction: re(r"^(?i:(TX|RX)|(>>|->|>)|(<<|<-|<))(?::\s*|\s+)"),
+            class: re(r"^<[^>\s]*>\s+"),
+            hex: re(r"^[0-9A-Fa-f]{2}(?: [0-9A-Fa-f]{2})*$"),
+        }
+    }
+}
+
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (m + if m > 2 { -3 } else { 9 }) + 2) / 5 + d - 1;
+    era * 146_097 + yoe * 365 + yoe / 4 - yoe / 100 + doy - 719_468
+}
+
+/// The line's leading timestamp in ms and the rest of the line. Dates
+/// without a zone are taken as UTC; times without a date count from midnight.
+fn timestamp<'a>(p: &Patterns, line: &'a str) -> Option<(f64, &'a str)> {
+    if let Some(c) = p.date_time.captures(line) {
+        let n = |i: usize| c.get(i).map_or(0, |m| m.as_str().parse::<i64>().unwrap_or(0));
+        let days = if c.get(1).is_some() { days_from_civil(n(1), n(2), n(3)) } else { 0 };
+        let frac = c.get(7).map_or(0.0, |m| format!("0.{}", m.as_str()).parse::<f64>().unwrap_or(0.0));
+        let zone_min = c.get(8).map_or(0, |m| {
+            let z = m.as_str().replace(':', "");
+            if z == "Z" { 0 } else {
+                let sign = if z.starts_with('-') { -1 } else { 1 };
+                sign * (z[1..3].parse::<i64>().unwrap_or(0) * 60 + z[3..5].parse::<i64>().unwrap_or(0))
+            }
+        });
+        let secs = days * 86_400 + n(4) * 3600 + n(5) * 60 + n(6) - zone_min * 60;
+        return Some(((secs as f64 + frac) * 1000.0, &line[c.get(0)?.end()..]));
+    }
+    if let Some(c) = p.epoch.captures(line) {
+        let v: f64 = c[1].parse().ok()?;
+        return Some((if v >= 1e11 { v } else { v * 1000.0 }, &line[c.get(0)?.end()..]));
+    }
+    let c = p.relative.captures(line)?;
+    Some((c[1].parse::<f64>().ok()? * 1000.0, &line[c.get(0)?.end()..]))
+}
+
+/// Lines of wirescope logs and timelines, or of other tools' logs: a
+/// timestamp, an optional `[label]`, an optional direction (TX/RX, `>`/`<`)
+/// and the data, as spaced hex or text. Lines without a timestamp take the
+/// previous one; `#` lines are skipped.
+fn text(contents: &str, name: &str) -> Capture {
+    let p = Patterns::new();
+    let mut streams: Vec<Stream> = Vec::new();
+    let mut last_ts = 0.0;
+    for line in contents.lines().map(str::trim_end).filter(|l| !l.trim().is_empty() && !l.starts_with('#')) {
+        let (ts, mut rest) = timestamp(&p, line).unwrap_or((last_ts, line));
+        last_ts = ts;
+        if let Some(m) = p.device_ts.find(rest) {
+            rest = &rest[m.end()..];
+        }
+        let mut label = None;
+        if let Some(c) = p.label.captures(rest) {
+            label = Some(c[1].to_string());
+            rest = &rest[c.get(0).map_or(0, |m| m.end())..];
+        }
+        let mut direction = "RX";
+        if let Some(c) = p.direction.captures(rest) {
+            let tx = c.get(2).is_some() || c.get(1).is_some_and(|m| m.as_str().eq_ignore_ascii_case("TX"));
+            direction = if tx { "TX" } else { "RX" };
+            rest = &rest[c.get(0).map_or(0, |m| m.end())..];
+        }
+        if let Some(m) = p.class.find(rest).filter(|m| p.hex.is_match(&rest[m.end()..])) {
+            rest = &rest[m.end()..];
+        }
+        let bytes = if p.hex.is_match(rest) {
+            hex_to_bytes(rest).unwrap_or_else(|_| rest.as_bytes().to_vec())
+        } else {
+            rest.as_bytes().to_vec()
+        };
+
+        let name = label.as_deref().unwrap_or(name);
+        let idx = match streams.iter().position(|s| s.name == name) {
+            Some(idx) => idx,
+            None => {
+                streams.push(Stream { name: name.to_string(), kind: "log", chunks: Vec::new() });
+                streams.len() - 1
+            }
+        };
+        streams[idx].chunks.push(Chunk { ts_ms: ts, direction, bytes });
+    }
+    Capture { streams, skipped: 0 }
+}
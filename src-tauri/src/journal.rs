@@ -0,0 +1,199 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU32, Ordering};
+use base64::Engine;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::broadcast;
+
+/// Events kept per connection for `resync`.
+pub const CAPACITY: usize = 4096;
+
+/// Events kept across all connections; the oldest go first beyond this.
+pub const TOTAL_CAPACITY: usize = 16_384;
+
+/// Events kept of a connection once its session closed.
+pub const CLOSED_CAPACITY: usize = 256;
+
+/// Newest event schema the backend can emit. Version 2 adds a `schema`
+/// field to every event and sends packet `bytes` as a base64 string.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// The shape from before schema versions existed: no `schema` field and
+/// packet `bytes` as an array of numbers. Emitted until the webview
+/// negotiates something newer, so old frontend builds keep working.
+pub const LEGACY_SCHEMA: u32 = 1;
+
+/// One emitted event as it went out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub seq: u64,
+    pub event: String,
+    pub payload: Value,
+}
+
+#[derive(Default)]
+struct Ring {
+    entries: VecDeque<Entry>,
+    /// Sequence number of the newest entry pushed out for lack of room.
+    dropped: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Sequence number of the last event emitted.
+    seq: u64,
+    /// Recent events keyed by session id; "" holds events of no session.
+    rings: HashMap<String, Ring>,
+    /// Entries across all rings.
+    total: usize,
+    /// Sequence number of the newest entry of a ring that was removed.
+    forgotten: u64,
+}
+
+impl Inner {
+    /// Drop the oldest `n` entries of `conn`, and the ring once it is empty.
+    fn drop_oldest(&mut self, conn: &str, n: usize) {
+        let Some(ring) = self.rings.get_mut(conn) else { return };
+        for entry in ring.entries.drain(..n.min(ring.entries.len())) {
+            ring.dropped = entry.seq;
+            self.total -= 1;
+        }
+        if ring.entries.is_empty() {
+            self.forgotten = self.forgotten.max(ring.dropped);
+            self.rings.remove(conn);
+        }
+    }
+
+    /// The connection whose oldest entry is the oldest of all.
+    fn oldest(&self) -> Option<String> {
+        self.rings.iter()
+            .filter_map(|(conn, ring)| ring.entries.front().map(|e| (e.seq, conn)))
+            .min()
+            .map(|(_, conn)| conn.clone())
+    }
+}
+
+/// Numbers every event sent to the webview and keeps the latest of each
+/// connection, so a reloaded or stalled webview can catch up.
+pub struct Journal {
+    inner: Mutex<Inner>,
+    /// Every event as it goes out, for listeners outside the webview.
+    live: broadcast::Sender<Entry>,
+    /// Schema the webview asked for. Entries are kept in the legacy shape
+    /// and converted on the way out.
+    schema: AtomicU32,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self { inner: Mutex::default(), live: broadcast::channel(CAPACITY).0, schema: AtomicU32::new(LEGACY_SCHEMA) }
+    }
+}
+
+/// Emit the newest schema the webview and the backend both understand from
+/// now on, and return it. A webview that never negotiates gets the legacy
+/// shape.
+pub fn negotiate(app: &AppHandle, supported: u32) -> u32 {
+    let schema = supported.clamp(LEGACY_SCHEMA, SCHEMA_VERSION);
+    app.state::<Journal>().schema.store(schema, Ordering::Relaxed);
+    schema
+}
+
+/// The schema events are emitted in.
+pub fn schema(app: &AppHandle) -> u32 {
+    app.state::<Journal>().schema.load(Ordering::Relaxed)
+}
+
+/// `payload` of `event` in the shape of `schema`.
+pub fn shape(schema: u32, event: &str, mut payload: Value) -> Value {
+    if schema < 2 {
+        return payload;
+    }
+    let Some(fields) = payload.as_object_mut() else { return payload };
+    fields.insert("schema".into(), schema.into());
+    if event == "packet" {
+        if let Some(Value::Array(bytes)) = fields.get("bytes") {
+            let bytes: Vec<u8> = bytes.iter().filter_map(Value::as_u64).map(|b| b as u8).collect();
+            let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+            fields.insert("bytes".into(), encoded.into());
+        }
+    }
+    payload
+}
+
+/// Emit `payload` as `event` with the next sequence number added as `seq`.
+/// Events are journaled under their `session_id` field.
+pub fn emit<T: Serialize>(app: &AppHandle, event: &str, payload: T) {
+    let Ok(mut value) = serde_json::to_value(payload) else { return };
+    let journal = app.state::<Journal>();
+    let mut inner = journal.inner.lock();
+    inner.seq += 1;
+    let seq = inner.seq;
+    let conn = value.get("session_id").and_then(Value::as_str).unwrap_or_default().to_string();
+    if let Some(fields) = value.as_object_mut() {
+        fields.insert("seq".into(), seq.into());
+    }
+    if inner.rings.get(&conn).is_some_and(|r| r.entries.len() == CAPACITY) {
+        inner.drop_oldest(&conn, 1);
+    } else if inner.total == TOTAL_CAPACITY {
+        if let Some(oldest) = inner.oldest() {
+            inner.drop_oldest(&oldest, 1);
+        }
+    }
+    let entry = Entry { seq, event: event.to_string(), payload: value.clone() };
+    if journal.live.receiver_count() > 0 {
+        let _ = journal.live.send(entry.clone());
+    }
+    inner.rings.entry(conn).or_default().entries.push_back(entry);
+    inner.total += 1;
+    // Emitting under the lock keeps the webview's view in sequence order
+    let _ = app.emit(event, shape(journal.schema.load(Ordering::Relaxed), event, value));
+}
+
+/// Events of a connection after `last_seq`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Resync {
+    pub events: Vec<Entry>,
+    /// Sequence number of the last event emitted on any connection.
+    pub seq: u64,
+    /// False when events after `last_seq` were already dropped from the
+    /// journal; the caller should reload its state instead.
+    pub complete: bool,
+}
+
+/// The journaled events of `conn` (a session id, or "" for events of no
+/// session) that came after `last_seq`, oldest first.
+pub fn resync(app: &AppHandle, conn: &str, last_seq: u64) -> Resync {
+    let journal = app.state::<Journal>();
+    let inner = journal.inner.lock();
+    let Some(ring) = inner.rings.get(conn) else {
+        return Resync { events: Vec::new(), seq: inner.seq, complete: inner.forgotten <= last_seq };
+    };
+    let events = ring.entries.iter().filter(|e| e.seq > last_seq).cloned().collect();
+    Resync { events, seq: inner.seq, complete: ring.dropped <= last_seq }
+}
+
+/// Keep only the newest `keep` events of `conn`, e.g. once its session closed.
+pub fn trim(app: &AppHandle, conn: &str, keep: usize) {
+    let journal = app.state::<Journal>();
+    let mut inner = journal.inner.lock();
+    let len = inner.rings.get(conn).map_or(0, |r| r.entries.len());
+    inner.drop_oldest(conn, len.saturating_sub(keep));
+}
+
+/// Forget the events of every session, keeping those of no session.
+pub fn clear_sessions(app: &AppHandle) {
+    let journal = app.state::<Journal>();
+    let mut inner = journal.inner.lock();
+    let conns: Vec<String> = inner.rings.keys().filter(|c| !c.is_empty()).cloned().collect();
+    for conn in conns {
+        inner.drop_oldest(&conn, usize::MAX);
+    }
+}
+
+/// Events emitted from now on, in sequence order.
+pub fn subscribe(app: &AppHandle) -> broadcast::Receiver<Entry> {
+    app.state::<Journal>().live.subscribe()
+}
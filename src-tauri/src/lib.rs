@@ -1,9 +1,77 @@
+mod agent;
+mod alerts;
+mod baud;
+mod bench;
+mod bridge;
+mod capabilities;
+mod chat;
 mod checksum;
+mod classify;
+mod clock;
 mod commands;
+mod compare;
+mod datagram;
+mod dali;
+mod decoder;
+mod detect;
+mod device;
+mod diagnostics;
+mod dns;
+mod entropy;
+mod eol;
+mod histogram;
+mod history;
+mod identify;
+mod import;
+mod journal;
+mod library;
+mod manifest;
+mod mdns;
+mod expect;
+mod filter;
+mod fixture;
+mod fuzz;
+mod mirror;
+mod modem;
+mod mqtt;
+mod notify;
+mod ntrip;
+mod onewire;
+mod outgoing;
+mod payload;
+mod pps;
+mod presets;
+mod priority;
+mod profile;
+mod proxy;
+mod quota;
+mod rack;
+mod reconnect;
+mod remote;
+mod rfc2217;
+mod segment;
 mod serial_port;
+mod server;
+mod session_file;
+mod session_log;
+mod sms;
+mod sniff;
 mod socket;
+mod sound;
 mod splitter;
+mod ssh;
 mod state;
+mod summary;
+mod sweep;
+mod sys_events;
+mod telnet;
+mod timeline;
+mod tls;
+mod tofu;
+mod transaction;
+mod transform;
+mod vars;
+mod ws;
 
 use commands::*;
 use state::new_state;
@@ -13,30 +81,193 @@ pub struct PendingUpdate(pub parking_lot::Mutex<Option<tauri_plugin_updater::Upd
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    clock::init();
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
+        .plugin(tauri_plugin_notification::init())
         .manage(new_state())
         .manage(PendingUpdate(parking_lot::Mutex::new(None)))
+        .manage(sys_events::SysEvents::default())
+        .manage(journal::Journal::default())
+        .setup(|app| {
+            match agent::launch_options(std::env::args().skip(1)) {
+                Ok(Some(options)) => commands::start_agent(app.handle(), options),
+                Ok(None) => {}
+                Err(e) => commands::agent_failed(app.handle(), e),
+            }
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             list_serial_ports,
             connect_serial,
+            set_serial_settings,
+            get_serial_settings,
+            get_line_errors,
+            set_baud_rate,
+            auto_baud,
+            set_baud_watch,
+            get_baud_watch,
+            sniff_serial,
+            server_start,
+            server_stop,
+            server_list,
+            server_broadcast,
+            remote_start,
+            remote_stop,
+            remote_info,
+            remote_audit,
+            device_start,
+            device_stop,
+            device_list,
             connect_tcp,
+            connect_ssh,
+            com_port_configure,
+            com_port_set_lines,
+            com_port_break,
+            com_port_purge,
+            mqtt_connect,
+            mqtt_subscribe,
+            mqtt_unsubscribe,
+            mqtt_tx,
+            discover_services,
+            agent_attach,
+            agent_detach,
+            list_agents,
+            connect_udp,
+            socket_shutdown,
+            probe_tls_fingerprint,
+            tofu_accept,
+            tofu_forget,
+            tofu_list,
+            set_udp_peer,
+            get_udp_stats,
+            set_udp_sequence,
+            reset_udp_stats,
             disconnect,
             send_bytes,
+            send_bytes_confirmed,
+            tx_raw_key,
+            set_tx_append,
             get_packets,
             clear_packets,
             get_sessions,
+            resync,
+            negotiate_schema,
+            set_session_meta,
+            set_notifications,
+            get_notifications,
+            set_alert_rules,
+            get_alert_rules,
+            play_alert_sound,
             set_splitter,
             get_splitter,
+            framing_presets,
+            apply_framing_preset,
+            save_framing_preset,
+            delete_framing_preset,
+            set_class_rules,
+            get_class_rules,
+            set_capture_filter,
+            get_capture_filters,
+            set_rx_pipeline,
+            get_rx_pipeline,
             compute_checksum,
             compute_all_checksums,
+            set_decoder,
+            detect_protocol,
+            get_protocol_stats,
+            reset_protocol_stats,
+            set_pairing,
+            get_pairing,
+            get_transaction_stats,
+            get_latency_histograms,
+            export_latency_histograms,
+            reset_transaction_stats,
             get_timing_stats,
+            analyze_bytes,
             export_packets,
+            export_timeline,
+            import_capture,
+            session_export,
+            session_import,
+            set_segmentation,
+            get_segments,
+            export_segment,
+            copy_range,
+            set_quota,
+            get_quota,
+            set_identify_probe,
+            get_identify_probe,
+            set_auto_reconnect,
+            get_auto_reconnect,
+            open_rack,
+            get_rack_status,
+            close_rack,
+            export_rack_timeline,
+            verify_log_manifest,
+            open_bench,
+            sweep_run,
+            history_list,
+            history_search,
+            history_tag,
+            history_forget,
+            sync_ntp,
+            get_clock_info,
+            clear_ntp_offset,
+            set_time_source,
+            get_time_config,
+            set_clock_offset,
+            set_device_time_offset,
+            pps_start,
+            pps_stop,
+            sms_encode_pdu,
+            sms_decode_pdu,
+            sms_send,
+            sms_list,
+            sms_read,
+            sms_watch,
+            payload_list,
+            payload_add,
+            payload_update,
+            payload_delete,
+            payload_send,
+            payload_import,
+            payload_export,
+            chat_run,
+            vars_get,
+            vars_set,
+            vars_clear,
+            set_var_captures,
+            get_var_captures,
+            benchmark_roundtrip,
+            fixture_record_start,
+            fixture_record_stop,
+            fixture_test,
+            fuzz_start,
+            fuzz_stop,
+            ntrip_source_table,
+            ntrip_connect,
+            mirror_attach,
+            mirror_detach,
+            mirror_list,
+            bridge_start,
+            bridge_stop,
+            bridge_list,
+            compare_start,
+            compare_stop,
+            compare_status,
+            get_capabilities,
+            diagnostics,
             check_update,
             install_update,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                commands::shutdown(app);
+            }
+        });
 }
@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::eol::TxAppend;
+use crate::payload::PayloadFormat;
+
+/// A named payload kept for reuse.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedPayload {
+    pub name: String,
+    pub data: String,
+    #[serde(default)]
+    pub format: PayloadFormat,
+    /// Resolve C-style escapes such as `\x1B` or `\n` in text.
+    #[serde(default)]
+    pub escape: bool,
+    /// Terminator to append; `None` uses the session's TX append mode.
+    #[serde(default)]
+    pub append: Option<TxAppend>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Saved payloads, persisted as `payloads.json` in the app data directory.
+pub struct PayloadLibrary {
+    path: PathBuf,
+    payloads: BTreeMap<String, SavedPayload>,
+}
+
+impl PayloadLibrary {
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join("payloads.json");
+        let payloads = std::fs::read(&path)
+            .ok()
+            .and_then(|b| serde_json::from_slice::<Vec<SavedPayload>>(&b).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|p| (p.name.clone(), p))
+            .collect();
+        Self { path, payloads }
+    }
+
+    pub fn list(&self) -> Vec<SavedPayload> {
+        self.payloads.values().cloned().collect()
+    }
+
+    pub fn get(&self, name: &str) -> Result<&SavedPayload, String> {
+        self.payloads.get(name).ok_or_else(|| format!("No saved payload named {name}"))
+    }
+
+    pub fn add(&mut self, payload: SavedPayload) -> Result<(), String> {
+        if payload.name.trim().is_empty() {
+            return Err("Payload name is empty".into());
+        }
+        if self.payloads.contains_key(&payload.name) {
+            return Err(format!("A payload named {} already exists", payload.name));
+        }
+        self.payloads.insert(payload.name.clone(), payload);
+        self.save()
+    }
+
+    /// Replace the payload called `name`; `payload.name` may rename it.
+    pub fn update(&mut self, name: &str, payload: SavedPayload) -> Result<(), String> {
+        self.get(name)?;
+        if payload.name != name && self.payloads.contains_key(&payload.name) {
+            return Err(format!("A payload named {} already exists", payload.name));
+        }
+        self.payloads.remove(name);
+        self.payloads.insert(payload.name.clone(), payload);
+        self.save()
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<(), String> {
+        self.payloads.remove(name).ok_or_else(|| format!("No saved payload named {name}"))?;
+        self.save()
+    }
+
+    /// Add `payloads`, replacing same-named ones only with `overwrite`.
+    /// Returns how many were added or replaced.
+    pub fn import(&mut self, payloads: Vec<SavedPayload>, overwrite: bool) -> Result<usize, String> {
+        let mut count = 0;
+        for p in payloads {
+            if overwrite || !self.payloads.contains_key(&p.name) {
+                self.payloads.insert(p.name.clone(), p);
+                count += 1;
+            }
+        }
+        self.save()?;
+        Ok(count)
+    }
+
+    fn save(&self) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_vec_pretty(&self.list()).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, json).map_err(|e| e.to_string())
+    }
+}
+
+/// Read an exported library, or a plain text file with one text payload per
+/// line (named after itself; blank lines and `#` comments are skipped).
+pub fn parse_import(contents: &str) -> Result<Vec<SavedPayload>, String> {
+    if contents.trim_start().starts_with('[') {
+        return serde_json::from_str(contents).map_err(|e| format!("Invalid payload library: {e}"));
+    }
+    let list = contents.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| SavedPayload {
+            name: l.to_string(),
+            data: l.to_string(),
+            format: PayloadFormat::Text,
+            escape: false,
+            append: None,
+            tags: Vec::new(),
+        })
+        .collect();
+    Ok(list)
+}
@@ -0,0 +1,102 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::state::now_ms;
+
+/// Kept next to the logs it describes, one JSON entry per line.
+pub const MANIFEST_FILE: &str = "manifest.jsonl";
+
+/// Serialises appends, so each entry chains onto the one written before it.
+static APPEND: Mutex<()> = Mutex::new(());
+
+/// A closed log file and its hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// File name, relative to the manifest.
+    pub file: String,
+    pub session_id: String,
+    /// Lowercase hex SHA-256 of the file.
+    pub sha256: String,
+    pub bytes: u64,
+    pub opened_ms: f64,
+    pub closed_ms: f64,
+    /// SHA-256 of the previous manifest line, empty for the first. Editing or
+    /// removing an entry breaks the chain from there on.
+    pub prev: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestCheck {
+    pub entry: ManifestEntry,
+    /// "ok", "modified" or "missing".
+    pub status: &'static str,
+    /// The entry's `prev` matches the line before it.
+    pub chain_ok: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestReport {
+    /// Every file unmodified and the chain intact.
+    pub ok: bool,
+    pub entries: Vec<ManifestCheck>,
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn hash_file(path: &Path) -> std::io::Result<(String, u64)> {
+    let mut hasher = Sha256::new();
+    let bytes = std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok((hex(&hasher.finalize()), bytes))
+}
+
+/// Hash the closed log at `log` and append it to the manifest in its directory.
+pub fn record(log: &Path, session_id: &str, opened_ms: f64) -> Result<ManifestEntry, String> {
+    let closed_ms = now_ms();
+    let (sha256, bytes) = hash_file(log).map_err(|e| e.to_string())?;
+    let dir = log.parent().ok_or("Log has no directory")?;
+    let manifest = dir.join(MANIFEST_FILE);
+
+    let _guard = APPEND.lock().unwrap_or_else(|e| e.into_inner());
+    let prev = std::fs::read_to_string(&manifest)
+        .ok()
+        .and_then(|s| s.lines().last().map(|l| hex(&Sha256::digest(l.as_bytes()))))
+        .unwrap_or_default();
+    let entry = ManifestEntry {
+        file: log.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+        session_id: session_id.to_string(),
+        sha256,
+        bytes,
+        opened_ms,
+        closed_ms,
+        prev,
+    };
+    let line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+    let mut file = OpenOptions::new().create(true).append(true).open(&manifest).map_err(|e| e.to_string())?;
+    writeln!(file, "{line}").map_err(|e| e.to_string())?;
+    Ok(entry)
+}
+
+/// Re-hash every file listed in the manifest of `dir` and check the chain.
+pub fn verify(dir: &Path) -> Result<ManifestReport, String> {
+    let contents = std::fs::read_to_string(dir.join(MANIFEST_FILE)).map_err(|e| e.to_string())?;
+    let mut entries = Vec::new();
+    let mut prev = String::new();
+    for (i, line) in contents.lines().enumerate() {
+        let entry: ManifestEntry = serde_json::from_str(line).map_err(|e| format!("Manifest line {}: {e}", i + 1))?;
+        let status = match hash_file(&dir.join(&entry.file)) {
+            Ok((sha256, _)) if sha256 == entry.sha256 => "ok",
+            Ok(_) => "modified",
+            Err(_) => "missing",
+        };
+        let chain_ok = entry.prev == prev;
+        prev = hex(&Sha256::digest(line.as_bytes()));
+        entries.push(ManifestCheck { entry, status, chain_ok });
+    }
+    let ok = entries.iter().all(|c| c.status == "ok" && c.chain_ok);
+    Ok(ManifestReport { ok, entries })
+}
@@ -0,0 +1,261 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use serde::Serialize;
+use tokio::net::UdpSocket;
+use tokio::time::Instant;
+
+const GROUP: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(224, 0, 0, 251)), 5353);
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+
+/// A service instance answering a browse.
+#[derive(Debug, Clone, Serialize)]
+pub struct Service {
+    /// Instance name, e.g. "Living Room" of "Living Room._arduino._tcp.local".
+    pub name: String,
+    /// e.g. "_arduino._tcp.local".
+    pub service_type: String,
+    /// Host the service runs on, e.g. "esp32-1a2b.local".
+    pub host: String,
+    pub port: u16,
+    pub addresses: Vec<IpAddr>,
+    /// TXT record entries; keys without `=` have an empty value.
+    pub txt: BTreeMap<String, String>,
+}
+
+enum Record {
+    Ptr(Vec<String>),
+    Srv { target: Vec<String>, port: u16 },
+    Txt(BTreeMap<String, String>),
+    Addr(IpAddr),
+}
+
+/// Records gathered from the answers so far, keyed by lowercased name.
+#[derive(Default)]
+struct Found {
+    /// Full names of the instances, in the order they answered.
+    instances: Vec<Vec<String>>,
+    srv: HashMap<String, (Vec<String>, u16)>,
+    txt: HashMap<String, BTreeMap<String, String>>,
+    addresses: HashMap<String, Vec<IpAddr>>,
+}
+
+/// `_service._proto.local` for a service type given with or without the
+/// domain and trailing dot.
+pub fn normalize(service_type: &str) -> Result<String, String> {
+    let name = service_type.trim().trim_end_matches('.');
+    let name = name.strip_suffix(".local").unwrap_or(name);
+    let valid = name.starts_with('_') && (name.ends_with("._tcp") || name.ends_with("._udp"));
+    match valid {
+        true => Ok(format!("{name}.local")),
+        false => Err(format!("Not a service type: {service_type:?} (expected e.g. _telnet._tcp)")),
+    }
+}
+
+/// Browse the local network for instances of `service_type` over multicast
+/// DNS (RFC 6762/6763) for `timeout`. A third of the way in the query is
+/// repeated, also asking for records the first answers left out.
+/// Instances whose SRV record never arrived are left out.
+pub async fn browse(service_type: &str, timeout: Duration) -> Result<Vec<Service>, String> {
+    let domain = normalize(service_type)?;
+    let domain_labels: Vec<String> = domain.split('.').map(String::from).collect();
+    // Asking from a port other than 5353 gets unicast answers (legacy
+    // unicast), so this doesn't need the mDNS port to be free
+    let sock = UdpSocket::bind("0.0.0.0:0").await.map_err(|e| e.to_string())?;
+    let _ = sock.set_multicast_ttl_v4(255);
+    let browse = [(domain_labels.clone(), TYPE_PTR)];
+    sock.send_to(&query(&browse), GROUP).await.map_err(|e| e.to_string())?;
+
+    let deadline = Instant::now() + timeout;
+    let mut retry = Some(Instant::now() + timeout / 3);
+    let mut found = Found::default();
+    let mut buf = vec![0u8; 9000];
+    loop {
+        let wake = retry.map_or(deadline, |r| r.min(deadline));
+        match tokio::time::timeout_at(wake, sock.recv(&mut buf)).await {
+            Ok(Ok(n)) => {
+                if let Some(records) = parse(&buf[..n]) {
+                    found.add(records, &domain);
+                }
+            }
+            Ok(Err(e)) => return Err(e.to_string()),
+            Err(_) => match retry.take() {
+                Some(_) => {
+                    let mut questions = browse.to_vec();
+                    questions.extend(found.missing());
+                    let _ = sock.send_to(&query(&questions), GROUP).await;
+                }
+                None => break,
+            },
+        }
+    }
+    Ok(found.services(&domain))
+}
+
+fn key(name: &[String]) -> String {
+    name.join(".").to_ascii_lowercase()
+}
+
+impl Found {
+    fn add(&mut self, records: Vec<(Vec<String>, Record)>, domain: &str) {
+        for (name, record) in records {
+            match record {
+                Record::Ptr(instance) if key(&name) == domain.to_ascii_lowercase() => {
+                    if !self.instances.iter().any(|i| key(i) == key(&instance)) {
+                        self.instances.push(instance);
+                    }
+                }
+                Record::Ptr(_) => {}
+                Record::Srv { target, port } => {
+                    self.srv.insert(key(&name), (target, port));
+                }
+                Record::Txt(entries) => {
+                    self.txt.insert(key(&name), entries);
+                }
+                Record::Addr(addr) => {
+                    let list = self.addresses.entry(key(&name)).or_default();
+                    if !list.contains(&addr) {
+                        list.push(addr);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Questions for the SRV records and host addresses not seen yet.
+    fn missing(&self) -> Vec<(Vec<String>, u16)> {
+        let mut questions = Vec::new();
+        for instance in &self.instances {
+            match self.srv.get(&key(instance)) {
+                None => questions.push((instance.clone(), TYPE_SRV)),
+                Some((target, _)) if !self.addresses.contains_key(&key(target)) => {
+                    questions.push((target.clone(), TYPE_A));
+                }
+                Some(_) => {}
+            }
+        }
+        questions
+    }
+
+    fn services(&self, domain: &str) -> Vec<Service> {
+        let mut services: Vec<Service> = self
+            .instances
+            .iter()
+            .filter_map(|instance| {
+                let (target, port) = self.srv.get(&key(instance))?;
+                Some(Service {
+                    name: instance.first().cloned().unwrap_or_default(),
+                    service_type: domain.to_string(),
+                    host: target.join("."),
+                    port: *port,
+                    addresses: self.addresses.get(&key(target)).cloned().unwrap_or_default(),
+                    txt: self.txt.get(&key(instance)).cloned().unwrap_or_default(),
+                })
+            })
+            .collect();
+        services.sort_by(|a, b| a.name.cmp(&b.name));
+        services
+    }
+}
+
+/// A query for each of `questions`, as (name, type).
+fn query(questions: &[(Vec<String>, u16)]) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(64);
+    // Id 0, no flags; RFC 6762 ignores both in queries
+    msg.extend_from_slice(&[0, 0, 0, 0]);
+    msg.extend_from_slice(&(questions.len() as u16).to_be_bytes());
+    msg.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    for (name, qtype) in questions {
+        for label in name {
+            let label = &label.as_bytes()[..label.len().min(63)];
+            msg.push(label.len() as u8);
+            msg.extend_from_slice(label);
+        }
+        msg.push(0);
+        msg.extend_from_slice(&qtype.to_be_bytes());
+        msg.extend_from_slice(&1u16.to_be_bytes()); // IN
+    }
+    msg
+}
+
+/// The records of the answer, authority and additional sections of a
+/// response; None for queries and malformed messages.
+fn parse(msg: &[u8]) -> Option<Vec<(Vec<String>, Record)>> {
+    let count = |at: usize| u16::from_be_bytes([msg[at], msg[at + 1]]) as usize;
+    if msg.len() < 12 || msg[2] & 0x80 == 0 {
+        return None;
+    }
+    let mut pos = 12;
+    for _ in 0..count(4) {
+        pos = read_name(msg, pos)?.1 + 4;
+    }
+    let mut records = Vec::new();
+    for _ in 0..count(6) + count(8) + count(10) {
+        let (name, at) = read_name(msg, pos)?;
+        let header = msg.get(at..at + 10)?;
+        let rtype = u16::from_be_bytes([header[0], header[1]]);
+        let start = at + 10;
+        let rdata = msg.get(start..start + u16::from_be_bytes([header[8], header[9]]) as usize)?;
+        let record = match rtype {
+            TYPE_PTR => Some(Record::Ptr(read_name(msg, start)?.0)),
+            TYPE_SRV if rdata.len() >= 7 => {
+                let port = u16::from_be_bytes([rdata[4], rdata[5]]);
+                Some(Record::Srv { target: read_name(msg, start + 6)?.0, port })
+            }
+            TYPE_TXT => Some(Record::Txt(parse_txt(rdata))),
+            TYPE_A if rdata.len() == 4 => Some(Record::Addr(IpAddr::V4(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3])))),
+            TYPE_AAAA if rdata.len() == 16 => {
+                let mut b = [0u8; 16];
+                b.copy_from_slice(rdata);
+                Some(Record::Addr(IpAddr::V6(Ipv6Addr::from(b))))
+            }
+            _ => None,
+        };
+        records.extend(record.map(|r| (name, r)));
+        pos = start + rdata.len();
+    }
+    Some(records)
+}
+
+/// The labels of the (possibly compressed) name at `pos`, and the offset
+/// just past it.
+fn read_name(msg: &[u8], mut pos: usize) -> Option<(Vec<String>, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // Bounded, so pointer loops in a hostile packet end
+    for _ in 0..128 {
+        let len = *msg.get(pos)? as usize;
+        match len {
+            0 => return Some((labels, end.unwrap_or(pos + 1))),
+            l if l & 0xC0 == 0xC0 => {
+                end.get_or_insert(pos + 2);
+                pos = (l & 0x3F) << 8 | *msg.get(pos + 1)? as usize;
+            }
+            l => {
+                labels.push(String::from_utf8_lossy(msg.get(pos + 1..pos + 1 + l)?).into_owned());
+                pos += 1 + l;
+            }
+        }
+    }
+    None
+}
+
+/// `key=value` strings of a TXT record.
+fn parse_txt(mut rdata: &[u8]) -> BTreeMap<String, String> {
+    let mut entries = BTreeMap::new();
+    while let Some((&len, rest)) = rdata.split_first() {
+        let Some(entry) = rest.get(..len as usize) else { break };
+        let entry = String::from_utf8_lossy(entry);
+        if !entry.is_empty() {
+            let (k, v) = entry.split_once('=').unwrap_or((&entry, ""));
+            entries.insert(k.to_string(), v.to_string());
+        }
+        rdata = &rest[len as usize..];
+    }
+    entries
+}
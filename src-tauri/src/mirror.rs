@@ -0,0 +1,195 @@
+use std::sync::Arc;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UdpSocket;
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::task::JoinHandle;
+
+/// Secondary destination for a session's byte stream.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MirrorTarget {
+    /// Append raw bytes to a file.
+    File { path: String },
+    /// Forward each received chunk as one datagram.
+    Udp { addr: String },
+    /// Emit "mirror_data" events to the window with this label.
+    Window { label: String },
+    /// Write the bytes to an external program's stdin, e.g. a protocol
+    /// analyzer or a decoder script. Its stdin closes with the session.
+    Command {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+        /// Read the program's stdout back, one annotation per line.
+        #[serde(default)]
+        annotate: bool,
+    },
+}
+
+/// Which way the mirrored bytes went.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MirrorDirection {
+    #[default]
+    Rx,
+    Tx,
+    /// Both, interleaved in the order they happened.
+    Both,
+}
+
+impl MirrorDirection {
+    pub fn rx(self) -> bool {
+        self != Self::Tx
+    }
+
+    pub fn tx(self) -> bool {
+        self != Self::Rx
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MirrorStats {
+    pub bytes: u64,
+    pub error: Option<String>,
+}
+
+pub struct Mirror {
+    pub session_id: String,
+    pub target: MirrorTarget,
+    pub direction: MirrorDirection,
+    pub stats: Arc<Mutex<MirrorStats>>,
+    pub task: JoinHandle<()>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MirrorInfo {
+    pub id: u64,
+    pub session_id: String,
+    pub target: MirrorTarget,
+    pub direction: MirrorDirection,
+    pub bytes: u64,
+    /// False once the session closed or the sink failed.
+    pub active: bool,
+    pub error: Option<String>,
+}
+
+impl Mirror {
+    pub fn info(&self, id: u64) -> MirrorInfo {
+        let stats = self.stats.lock();
+        MirrorInfo {
+            id,
+            session_id: self.session_id.clone(),
+            target: self.target.clone(),
+            direction: self.direction,
+            bytes: stats.bytes,
+            active: !self.task.is_finished(),
+            error: stats.error.clone(),
+        }
+    }
+}
+
+enum Sink {
+    File(tokio::fs::File),
+    Udp(UdpSocket),
+    Window,
+    Command(Child, ChildStdin),
+}
+
+/// Open the sink and copy everything from `rx` into it until the session
+/// closes. Window targets hand each chunk to `to_window`; command targets
+/// with `annotate` hand each line of output to `on_annotation`.
+pub async fn start(
+    session_id: String,
+    target: MirrorTarget,
+    direction: MirrorDirection,
+    mut rx: UnboundedReceiver<Vec<u8>>,
+    to_window: impl Fn(Vec<u8>) + Send + 'static,
+    on_annotation: impl Fn(String) + Send + 'static,
+) -> Result<Mirror, String> {
+    let mut sink = match &target {
+        MirrorTarget::File { path } => {
+            let file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await
+                .map_err(|e| format!("{path}: {e}"))?;
+            Sink::File(file)
+        }
+        MirrorTarget::Udp { addr } => {
+            let bind = if addr.starts_with('[') { "[::]:0" } else { "0.0.0.0:0" };
+            let sock = UdpSocket::bind(bind).await.map_err(|e| e.to_string())?;
+            sock.connect(addr).await.map_err(|e| format!("{addr}: {e}"))?;
+            Sink::Udp(sock)
+        }
+        MirrorTarget::Window { .. } => Sink::Window,
+        MirrorTarget::Command { program, args, annotate } => {
+            let mut child = Command::new(program)
+                .args(args)
+                .stdin(std::process::Stdio::piped())
+                .stdout(if *annotate { std::process::Stdio::piped() } else { std::process::Stdio::null() })
+                .kill_on_drop(true)
+                .spawn()
+                .map_err(|e| format!("{program}: {e}"))?;
+            let stdin = child.stdin.take().ok_or("No stdin")?;
+            if let Some(stdout) = child.stdout.take() {
+                tokio::spawn(async move {
+                    let mut lines = BufReader::new(stdout).lines();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        on_annotation(line);
+                    }
+                });
+            }
+            Sink::Command(child, stdin)
+        }
+    };
+
+    let stats = Arc::new(Mutex::new(MirrorStats::default()));
+    let task_stats = Arc::clone(&stats);
+    let task = tokio::spawn(async move {
+        while let Some(data) = rx.recv().await {
+            let len = data.len() as u64;
+            let result = match &mut sink {
+                Sink::File(file) => file.write_all(&data).await,
+                // A refused datagram only means nobody is listening yet
+                Sink::Udp(sock) => match sock.send(&data).await {
+                    Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => Ok(()),
+                    r => r.map(|_| ()),
+                },
+                Sink::Window => {
+                    to_window(data);
+                    Ok(())
+                }
+                // Flushed per chunk so the program sees bytes as they arrive
+                Sink::Command(_, stdin) => match stdin.write_all(&data).await {
+                    Ok(()) => stdin.flush().await,
+                    Err(e) => Err(e),
+                },
+            };
+            let mut stats = task_stats.lock();
+            match result {
+                Ok(()) => stats.bytes += len,
+                Err(e) => {
+                    stats.error = Some(e.to_string());
+                    break;
+                }
+            }
+        }
+        match sink {
+            Sink::File(mut file) => {
+                let _ = file.flush().await;
+            }
+            // Closing stdin lets the program finish its output and exit
+            Sink::Command(mut child, stdin) => {
+                drop(stdin);
+                let _ = child.wait().await;
+            }
+            Sink::Udp(_) | Sink::Window => {}
+        }
+    });
+
+    Ok(Mirror { session_id, target, direction, stats, task })
+}
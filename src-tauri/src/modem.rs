@@ -0,0 +1,36 @@
+use std::time::Duration;
+use crate::expect::RxTap;
+
+/// Writes raw bytes to the session the modem is attached to.
+pub type Writer<'a> = &'a (dyn Fn(Vec<u8>) -> Result<(), String> + Sync);
+
+/// Send one AT command and collect its response up to the final result code.
+/// Returns the response text without the final `OK`; an `ERROR`,
+/// `+CME ERROR: n` or `+CMS ERROR: n` result becomes the error string.
+pub async fn at_command(tap: &mut RxTap, send: Writer<'_>, cmd: &str, timeout: Duration) -> Result<String, String> {
+    tap.clear();
+    send(format!("{cmd}\r").into_bytes())?;
+    final_result(tap, cmd, timeout).await
+}
+
+/// Wait for the final result code of a command that was already sent
+/// (e.g. after the `> ` prompt of `AT+CMGS`).
+pub async fn final_result(tap: &mut RxTap, cmd: &str, timeout: Duration) -> Result<String, String> {
+    let (idx, resp) = tap.expect(&[b"\r\nOK\r\n", b"ERROR"], Some(timeout)).await?;
+    let text = String::from_utf8_lossy(&resp).into_owned();
+    if idx == 1 {
+        // Pick up the error code that follows e.g. "+CMS ERROR"
+        let tail = tap.expect(&[b"\r\n"], Some(Duration::from_millis(500))).await
+            .map(|(_, t)| String::from_utf8_lossy(&t).trim().to_string())
+            .unwrap_or_default();
+        let line = text.lines().last().unwrap_or("ERROR").trim();
+        return Err(format!("{cmd}: {line}{tail}"));
+    }
+    Ok(strip_echo(cmd, text.trim_end_matches("\r\nOK\r\n")).to_string())
+}
+
+/// Remove the command echo (ATE1) from the start of a response.
+fn strip_echo<'a>(cmd: &str, resp: &'a str) -> &'a str {
+    let resp = resp.trim_start();
+    resp.strip_prefix(cmd).unwrap_or(resp).trim()
+}
@@ -0,0 +1,478 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::oneshot;
+use crate::dns;
+use crate::outgoing::Outgoing;
+use crate::socket::{self, ConnectError, ErrorCategory, SocketOpenArgs};
+use crate::tls::{self, TlsOptions};
+
+const CONNECT: u8 = 1;
+const CONNACK: u8 = 2;
+const PUBLISH: u8 = 3;
+const PUBACK: u8 = 4;
+const SUBSCRIBE: u8 = 8;
+const SUBACK: u8 = 9;
+const UNSUBSCRIBE: u8 = 10;
+const UNSUBACK: u8 = 11;
+const PINGREQ: u8 = 12;
+const DISCONNECT: u8 = 14;
+
+/// How long the broker gets to answer CONNECT, SUBSCRIBE and UNSUBSCRIBE.
+const ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An MQTT 3.1.1 broker to connect to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MqttOptions {
+    pub host: String,
+    pub port: u16,
+    /// Empty lets the broker assign one, which needs `clean_session`.
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Seconds between pings; 0 turns them off.
+    pub keep_alive_s: u16,
+    pub clean_session: bool,
+    /// MQTT over TLS, usually on port 8883.
+    pub tls: Option<TlsOptions>,
+    /// Topic that plain sends (the send box, scripts) publish to; without
+    /// one only `mqtt_tx` publishes.
+    pub publish_topic: Option<String>,
+    /// QoS of plain sends, 0 or 1.
+    pub qos: u8,
+}
+
+impl Default for MqttOptions {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: 1883,
+            client_id: String::new(),
+            username: None,
+            password: None,
+            keep_alive_s: 60,
+            clean_session: true,
+            tls: None,
+            publish_topic: None,
+            qos: 0,
+        }
+    }
+}
+
+impl MqttOptions {
+    pub fn session_id(&self) -> String {
+        format!("mqtt:{}", dns::host_port(&self.host, self.port))
+    }
+}
+
+/// What waits for an acknowledgement from the broker, by packet id.
+enum Waiter {
+    /// A QoS 1 message, acknowledged on PUBACK.
+    Publish(Outgoing),
+    /// Gets the granted QoS, or 0x80 for a refusal.
+    Subscribe(oneshot::Sender<u8>),
+    Unsubscribe(oneshot::Sender<()>),
+}
+
+#[derive(Default)]
+struct Pending {
+    last_id: u16,
+    waiters: HashMap<u16, Waiter>,
+}
+
+impl Pending {
+    /// Register `waiter` under a fresh packet id.
+    fn wait(&mut self, waiter: Waiter) -> u16 {
+        // Packet ids are non-zero
+        self.last_id = self.last_id.wrapping_add(1).max(1);
+        self.waiters.insert(self.last_id, waiter);
+        self.last_id
+    }
+}
+
+/// Publishes and subscribes on a connected broker.
+#[derive(Clone)]
+pub struct MqttClient {
+    inner: UnboundedSender<Outgoing>,
+    pending: Arc<Mutex<Pending>>,
+}
+
+impl MqttClient {
+    /// Queue `out` as a message on `topic`. It is acknowledged once written
+    /// for QoS 0 and once the broker's PUBACK arrives for QoS 1. Hands `out`
+    /// back when the connection is gone.
+    pub fn publish(&self, topic: &str, qos: u8, retain: bool, mut out: Outgoing) -> Result<(), Outgoing> {
+        if qos == 0 {
+            out.data = encode_publish(topic, &out.data, 0, retain, 0);
+            return self.inner.send(out).map_err(|e| e.0);
+        }
+        let data = std::mem::take(&mut out.data);
+        let id = self.pending.lock().wait(Waiter::Publish(out));
+        if self.inner.send(Outgoing::new(encode_publish(topic, &data, qos, retain, id))).is_ok() {
+            return Ok(());
+        }
+        match self.pending.lock().waiters.remove(&id) {
+            Some(Waiter::Publish(mut out)) => {
+                out.data = data;
+                Err(out)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Subscribe to `filter` and return the QoS the broker granted.
+    pub async fn subscribe(&self, filter: &str, qos: u8) -> Result<u8, String> {
+        check_filter(filter)?;
+        check_qos(qos)?;
+        let (reply, granted) = oneshot::channel();
+        let id = self.pending.lock().wait(Waiter::Subscribe(reply));
+        let mut body = id.to_be_bytes().to_vec();
+        put_bytes(&mut body, filter.as_bytes());
+        body.push(qos);
+        match self.request(id, SUBSCRIBE, &body, granted).await? {
+            0x80 => Err(format!("Broker refused the subscription to {filter}")),
+            granted => Ok(granted),
+        }
+    }
+
+    pub async fn unsubscribe(&self, filter: &str) -> Result<(), String> {
+        check_filter(filter)?;
+        let (reply, done) = oneshot::channel();
+        let id = self.pending.lock().wait(Waiter::Unsubscribe(reply));
+        let mut body = id.to_be_bytes().to_vec();
+        put_bytes(&mut body, filter.as_bytes());
+        self.request(id, UNSUBSCRIBE, &body, done).await
+    }
+
+    /// Send a (UN)SUBSCRIBE registered as `id` and wait for its answer.
+    async fn request<T>(&self, id: u16, kind: u8, body: &[u8], answer: oneshot::Receiver<T>) -> Result<T, String> {
+        // Both carry the reserved flags 0b0010
+        let result = match self.inner.send(Outgoing::new(packet(kind << 4 | 0x02, body))) {
+            Ok(()) => tokio::time::timeout(ACK_TIMEOUT, answer).await,
+            Err(_) => return Err("Not connected".into()),
+        };
+        match result {
+            Ok(Ok(answer)) => Ok(answer),
+            Ok(Err(_)) => Err("Connection closed".into()),
+            Err(_) => {
+                self.pending.lock().waiters.remove(&id);
+                Err("Broker didn't answer in time".into())
+            }
+        }
+    }
+}
+
+pub struct MqttConnection {
+    pub client: MqttClient,
+    /// Plain sends, published to `publish_topic`.
+    pub tx: UnboundedSender<Outgoing>,
+    /// Fire to disconnect after pending writes while keeping the reader
+    /// running.
+    pub shutdown: oneshot::Sender<()>,
+}
+
+/// A topic name messages can be published to.
+pub fn check_topic(topic: &str) -> Result<(), String> {
+    if topic.is_empty() || topic.contains(['+', '#']) {
+        return Err(format!("Not a topic name: {topic:?}"));
+    }
+    check_length(topic)
+}
+
+/// A topic filter, where `+` matches one level and a trailing `#` the rest.
+fn check_filter(filter: &str) -> Result<(), String> {
+    let levels: Vec<&str> = filter.split('/').collect();
+    let bad = filter.is_empty()
+        || levels.iter().any(|l| l.len() > 1 && l.contains(['+', '#']))
+        || levels[..levels.len() - 1].contains(&"#");
+    match bad {
+        true => Err(format!("Not a topic filter: {filter:?}")),
+        false => check_length(filter),
+    }
+}
+
+fn check_length(topic: &str) -> Result<(), String> {
+    match topic.len() > u16::MAX as usize {
+        true => Err("Topic is longer than 65535 bytes".into()),
+        false => Ok(()),
+    }
+}
+
+pub fn check_qos(qos: u8) -> Result<(), String> {
+    match qos {
+        0 | 1 => Ok(()),
+        _ => Err(format!("QoS {qos} is not supported; use 0 or 1")),
+    }
+}
+
+/// Connect and log in to the broker. PUBLISH messages it sends go to
+/// `on_message` with their topic until the connection drops, then
+/// `on_close` is called.
+pub async fn connect(
+    opts: &MqttOptions,
+    on_message: impl Fn(String, Vec<u8>) + Send + 'static,
+    on_close: impl FnOnce() + Send + 'static,
+) -> Result<MqttConnection, ConnectError> {
+    let config_err = |e: String| ConnectError::new(ErrorCategory::Config, e);
+    check_qos(opts.qos).map_err(config_err)?;
+    if let Some(topic) = &opts.publish_topic {
+        check_topic(topic).map_err(config_err)?;
+    }
+    if opts.client_id.is_empty() && !opts.clean_session {
+        return Err(config_err("A persistent session needs a client id".into()));
+    }
+    let args = SocketOpenArgs { host: opts.host.clone(), port: opts.port, ..Default::default() };
+    let stream = socket::open_stream(&args).await?;
+    match &opts.tls {
+        Some(tls_opts) => {
+            let stream = tls::connect(stream, dns::bare_host(&opts.host), tls_opts).await?;
+            start(stream, opts, on_message, on_close).await
+        }
+        None => start(stream, opts, on_message, on_close).await,
+    }
+}
+
+async fn start<S>(
+    mut stream: S,
+    opts: &MqttOptions,
+    on_message: impl Fn(String, Vec<u8>) + Send + 'static,
+    on_close: impl FnOnce() + Send + 'static,
+) -> Result<MqttConnection, ConnectError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let net_err = |e: std::io::Error| ConnectError::new(ErrorCategory::Network, e);
+    stream.write_all(&encode_connect(opts)).await.map_err(net_err)?;
+    // Messages of a persistent session may follow the CONNACK right away, so
+    // whatever was read past it stays in the decoder
+    let mut decoder = Decoder::default();
+    let connack = tokio::time::timeout(ACK_TIMEOUT, async {
+        let mut buf = vec![0u8; 4096];
+        loop {
+            if let Some(packet) = decoder.next() {
+                return Ok(packet);
+            }
+            match stream.read(&mut buf).await? {
+                0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                n => decoder.push(&buf[..n]),
+            }
+        }
+    })
+    .await
+    .map_err(|_| ConnectError::new(ErrorCategory::Handshake, "Broker didn't answer CONNECT in time"))?
+    .map_err(net_err)?;
+    check_connack(&connack).map_err(|e| ConnectError::new(ErrorCategory::Handshake, e))?;
+
+    let pending = Arc::new(Mutex::new(Pending::default()));
+    let (reply_tx, mut reply_rx) = mpsc::unbounded_channel::<Vec<u8>>();
+    // Anything read past the CONNACK arrived before the reader started
+    while let Some((header, body)) = decoder.next() {
+        dispatch(header, &body, &pending, &reply_tx, &on_message);
+    }
+    let decoder = Mutex::new(decoder);
+    let pending2 = Arc::clone(&pending);
+    let on_raw = move |chunk: Vec<u8>| {
+        let mut decoder = decoder.lock();
+        decoder.push(&chunk);
+        while let Some((header, body)) = decoder.next() {
+            dispatch(header, &body, &pending2, &reply_tx, &on_message);
+        }
+    };
+    let (inner, inner_shutdown, _) = socket::spawn_io_notify(stream, on_raw, on_close);
+    let client = MqttClient { inner, pending };
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Outgoing>();
+    let (shutdown, mut shutdown_rx) = oneshot::channel::<()>();
+    let (writer, topic, qos) = (client.clone(), opts.publish_topic.clone(), opts.qos);
+    let keep_alive = opts.keep_alive_s;
+    let publish = move |out: Outgoing| match &topic {
+        Some(topic) => writer.publish(topic, qos, false, out).is_ok(),
+        None => {
+            out.done(Err("No publish topic set; publish with mqtt_tx".into()));
+            true
+        }
+    };
+    let pinger = client.inner.clone();
+    tokio::spawn(async move {
+        let mut ping = tokio::time::interval(Duration::from_secs(keep_alive.max(1) as u64));
+        ping.tick().await;
+        loop {
+            tokio::select! {
+                biased;
+                Some(reply) = reply_rx.recv() => {
+                    if pinger.send(Outgoing::new(reply)).is_err() { break }
+                }
+                out = rx.recv() => match out {
+                    Some(out) => if !publish(out) { break },
+                    None => break,
+                },
+                Ok(()) = &mut shutdown_rx => {
+                    // Publish whatever was queued before the shutdown request
+                    while let Ok(out) = rx.try_recv() {
+                        publish(out);
+                    }
+                    break;
+                }
+                _ = ping.tick(), if keep_alive > 0 => {
+                    if pinger.send(Outgoing::new(vec![PINGREQ << 4, 0])).is_err() { break }
+                }
+            }
+        }
+        let _ = pinger.send(Outgoing::new(vec![DISCONNECT << 4, 0]));
+        let _ = inner_shutdown.send(());
+    });
+
+    Ok(MqttConnection { client, tx, shutdown })
+}
+
+/// Handle one packet from the broker; `reply` takes PUBACKs to send.
+fn dispatch(
+    header: u8,
+    body: &[u8],
+    pending: &Mutex<Pending>,
+    reply: &UnboundedSender<Vec<u8>>,
+    on_message: &impl Fn(String, Vec<u8>),
+) {
+    let id = || body.get(..2).map(|b| u16::from_be_bytes([b[0], b[1]]));
+    match header >> 4 {
+        PUBLISH => {
+            let Some(topic_len) = id().map(usize::from) else { return };
+            let Some(topic) = body.get(2..2 + topic_len) else { return };
+            let qos = header >> 1 & 0x03;
+            let start = 2 + topic_len + if qos > 0 { 2 } else { 0 };
+            let Some(payload) = body.get(start..) else { return };
+            if qos > 0 {
+                // Only subscribed up to QoS 1, so this is never QoS 2
+                let id = &body[2 + topic_len..start];
+                let _ = reply.send(packet(PUBACK << 4, id));
+            }
+            on_message(String::from_utf8_lossy(topic).into_owned(), payload.to_vec());
+        }
+        PUBACK | SUBACK | UNSUBACK => {
+            let Some(waiter) = id().and_then(|id| pending.lock().waiters.remove(&id)) else { return };
+            match waiter {
+                Waiter::Publish(out) => out.done(Ok(())),
+                Waiter::Subscribe(granted) => {
+                    let _ = granted.send(body.get(2).copied().unwrap_or(0x80));
+                }
+                Waiter::Unsubscribe(done) => {
+                    let _ = done.send(());
+                }
+            }
+        }
+        // PINGRESP; nothing else is sent to a client
+        _ => {}
+    }
+}
+
+fn check_connack((header, body): &(u8, Vec<u8>)) -> Result<(), String> {
+    if header >> 4 != CONNACK || body.len() < 2 {
+        return Err("Broker didn't answer with CONNACK".into());
+    }
+    let reason = match body[1] {
+        0 => return Ok(()),
+        1 => "unacceptable protocol version",
+        2 => "client id rejected",
+        3 => "server unavailable",
+        4 => "bad user name or password",
+        5 => "not authorized",
+        _ => "unknown reason",
+    };
+    Err(format!("Broker refused the connection: {reason} ({})", body[1]))
+}
+
+/// Splits the received stream into packets, across chunk boundaries.
+#[derive(Default)]
+struct Decoder {
+    buf: Vec<u8>,
+}
+
+impl Decoder {
+    fn push(&mut self, chunk: &[u8]) {
+        self.buf.extend_from_slice(chunk);
+    }
+
+    /// The next complete packet as its first byte and body.
+    fn next(&mut self) -> Option<(u8, Vec<u8>)> {
+        let (mut len, mut used) = (0usize, 1);
+        loop {
+            let b = *self.buf.get(used)?;
+            len |= ((b & 0x7F) as usize) << (7 * (used - 1));
+            used += 1;
+            if b & 0x80 == 0 {
+                break;
+            }
+            if used > 4 {
+                // A length of more than four bytes is malformed; resync on
+                // the next chunk
+                self.buf.clear();
+                return None;
+            }
+        }
+        let body = self.buf.get(used..used + len)?.to_vec();
+        let header = self.buf[0];
+        self.buf.drain(..used + len);
+        Some((header, body))
+    }
+}
+
+/// A packet of `header` (type and flags) with its remaining length.
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![header];
+    let mut len = body.len();
+    loop {
+        let b = (len % 128) as u8;
+        len /= 128;
+        out.push(if len > 0 { b | 0x80 } else { b });
+        if len == 0 {
+            break;
+        }
+    }
+    out.extend_from_slice(body);
+    out
+}
+
+/// Append `bytes` with their u16 length in front.
+fn put_bytes(body: &mut Vec<u8>, bytes: &[u8]) {
+    body.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    body.extend_from_slice(bytes);
+}
+
+fn encode_connect(opts: &MqttOptions) -> Vec<u8> {
+    let mut body = Vec::new();
+    put_bytes(&mut body, b"MQTT");
+    body.push(4); // Protocol level of 3.1.1
+    let mut flags = 0u8;
+    if opts.username.is_some() {
+        flags |= 0x80;
+    }
+    if opts.password.is_some() {
+        flags |= 0x40;
+    }
+    if opts.clean_session {
+        flags |= 0x02;
+    }
+    body.push(flags);
+    body.extend_from_slice(&opts.keep_alive_s.to_be_bytes());
+    put_bytes(&mut body, opts.client_id.as_bytes());
+    for field in [&opts.username, &opts.password].into_iter().flatten() {
+        put_bytes(&mut body, field.as_bytes());
+    }
+    packet(CONNECT << 4, &body)
+}
+
+/// A PUBLISH; `id` is only sent for QoS 1.
+fn encode_publish(topic: &str, payload: &[u8], qos: u8, retain: bool, id: u16) -> Vec<u8> {
+    let mut body = Vec::with_capacity(topic.len() + payload.len() + 4);
+    put_bytes(&mut body, topic.as_bytes());
+    if qos > 0 {
+        body.extend_from_slice(&id.to_be_bytes());
+    }
+    body.extend_from_slice(payload);
+    packet(PUBLISH << 4 | qos << 1 | u8::from(retain), &body)
+}
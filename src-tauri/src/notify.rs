@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+/// Which events raise a system notification.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotifyConfig {
+    pub enabled: bool,
+    /// Sessions whose transport closed without being disconnected.
+    pub disconnects: bool,
+    /// Classification severities that notify when a packet matches.
+    pub severities: Vec<String>,
+    /// Quiet period per session and kind of notification.
+    pub min_interval_ms: u64,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self { enabled: true, disconnects: true, severities: vec!["error".into()], min_interval_ms: 5000 }
+    }
+}
+
+/// Applies `NotifyConfig` and rate-limits repeats, so a chatty rule doesn't
+/// bury the desktop in notifications.
+#[derive(Default)]
+pub struct Notifier {
+    pub config: NotifyConfig,
+    last_ms: HashMap<String, f64>,
+}
+
+impl Notifier {
+    fn allow(&mut self, key: String, now_ms: f64) -> bool {
+        if !self.config.enabled {
+            return false;
+        }
+        let interval = self.config.min_interval_ms as f64;
+        match self.last_ms.get(&key) {
+            Some(&last) if now_ms - last < interval => false,
+            _ => {
+                self.last_ms.insert(key, now_ms);
+                true
+            }
+        }
+    }
+
+    pub fn on_severity(&mut self, session_id: &str, severity: &str, now_ms: f64) -> bool {
+        self.config.severities.iter().any(|s| s == severity) && self.allow(format!("{session_id}\n{severity}"), now_ms)
+    }
+
+    pub fn on_disconnect(&mut self, session_id: &str, now_ms: f64) -> bool {
+        self.config.disconnects && self.allow(format!("{session_id}\nclosed"), now_ms)
+    }
+
+    pub fn on_alert(&mut self, session_id: &str, rule: &str, now_ms: f64) -> bool {
+        self.allow(format!("{session_id}\nalert\n{rule}"), now_ms)
+    }
+}
@@ -0,0 +1,135 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use crate::socket::{self, SocketOpenArgs};
+
+/// NTRIP caster to pull corrections from.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NtripOptions {
+    pub host: String,
+    pub port: u16,
+    /// Empty requests the source table.
+    pub mountpoint: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// One `STR` record of a caster source table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mountpoint {
+    pub name: String,
+    pub identifier: String,
+    pub format: String,
+    pub format_details: String,
+    pub nav_system: String,
+    pub network: String,
+    pub country: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// The caster expects GGA position reports from the client (VRS/nearest-base).
+    pub nmea: bool,
+    pub authentication: String,
+    pub bitrate: Option<u32>,
+}
+
+const USER_AGENT: &str = concat!("NTRIP WireScope/", env!("CARGO_PKG_VERSION"));
+
+/// Request `opts.mountpoint` and return the stream positioned at the first
+/// correction byte.
+pub async fn open(opts: &NtripOptions) -> Result<TcpStream, String> {
+    if opts.mountpoint.is_empty() {
+        return Err("No mountpoint selected".into());
+    }
+    let mut stream = request(opts, &opts.mountpoint).await?;
+    let status = read_line(&mut stream).await?;
+    if status.starts_with("SOURCETABLE") {
+        return Err(format!("Mountpoint not found: {}", opts.mountpoint));
+    }
+    match status_code(&status) {
+        // NTRIP 1 casters start the data right after the status line
+        Some(200) if status.starts_with("ICY") => Ok(stream),
+        Some(200) => {
+            while !read_line(&mut stream).await?.is_empty() {}
+            Ok(stream)
+        }
+        Some(401) => Err("Caster rejected credentials (401)".into()),
+        _ => Err(format!("Caster refused request: {status}")),
+    }
+}
+
+/// Fetch and parse the caster's source table.
+pub async fn source_table(opts: &NtripOptions) -> Result<Vec<Mountpoint>, String> {
+    let mut stream = request(opts, "").await?;
+    let mut body = Vec::new();
+    stream.read_to_end(&mut body).await.map_err(|e| e.to_string())?;
+    let text = String::from_utf8_lossy(&body);
+    let status = text.lines().next().unwrap_or_default();
+    if status_code(status) != Some(200) {
+        return Err(format!("Caster refused request: {status}"));
+    }
+    Ok(parse_source_table(&text))
+}
+
+async fn request(opts: &NtripOptions, mountpoint: &str) -> Result<TcpStream, String> {
+    let args = SocketOpenArgs { host: opts.host.clone(), port: opts.port, ..Default::default() };
+    let mut stream = socket::open_stream(&args).await.map_err(|e| e.to_string())?;
+    let mut req = format!("GET /{mountpoint} HTTP/1.0\r\nUser-Agent: {USER_AGENT}\r\n");
+    if let Some(user) = &opts.username {
+        let cred = format!("{user}:{}", opts.password.as_deref().unwrap_or(""));
+        let cred = base64::engine::general_purpose::STANDARD.encode(cred);
+        req.push_str(&format!("Authorization: Basic {cred}\r\n"));
+    }
+    req.push_str("\r\n");
+    stream.write_all(req.as_bytes()).await.map_err(|e| e.to_string())?;
+    Ok(stream)
+}
+
+/// Read one CRLF-terminated header line byte-wise so no stream data is consumed.
+async fn read_line(stream: &mut TcpStream) -> Result<String, String> {
+    let mut line = Vec::with_capacity(64);
+    while !line.ends_with(b"\r\n") {
+        if line.len() > 8192 {
+            return Err("Caster response header too long".into());
+        }
+        let b = stream.read_u8().await.map_err(|e| format!("Caster closed connection: {e}"))?;
+        line.push(b);
+    }
+    line.truncate(line.len() - 2);
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
+/// "ICY 200 OK", "HTTP/1.1 401 Unauthorized", "SOURCETABLE 200 OK"
+fn status_code(line: &str) -> Option<u16> {
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+fn parse_source_table(text: &str) -> Vec<Mountpoint> {
+    text.lines()
+        .filter_map(|l| l.strip_prefix("STR;"))
+        .map(|rec| {
+            let f: Vec<&str> = rec.split(';').collect();
+            let field = |i: usize| f.get(i).map_or(String::new(), |s| s.trim().to_string());
+            Mountpoint {
+                name: field(0),
+                identifier: field(1),
+                format: field(2),
+                format_details: field(3),
+                nav_system: field(5),
+                network: field(6),
+                country: field(7),
+                latitude: field(8).parse().ok(),
+                longitude: field(9).parse().ok(),
+                nmea: field(10) == "1",
+                authentication: field(14),
+                bitrate: field(16).parse().ok(),
+            }
+        })
+        .collect()
+}
+
+/// True for an NMEA GGA sentence from any talker ($GPGGA, $GNGGA, ...).
+pub fn is_gga(line: &str) -> bool {
+    line.len() > 6 && line.starts_with('$') && line.get(3..6) == Some("GGA")
+}
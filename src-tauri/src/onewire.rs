@@ -0,0 +1,104 @@
+use crc::{Crc, CRC_8_MAXIM_DOW};
+use crate::decoder::{self, Decoded};
+
+/// What a reset, sent as 0xF0 at 9600 baud, reads back as when no device
+/// answers with a presence pulse.
+const RESET_IDLE: u8 = 0xF0;
+
+const CRC8: Crc<u8> = Crc::<u8>::new(&CRC_8_MAXIM_DOW);
+
+/// Decode what a UART with TX and RX tied together read back while driving
+/// a 1-Wire bus. A packet is an optional reset followed by whole bytes of
+/// eight bit slots, least significant bit first; a slot reads back 0xFF
+/// for a 1 and anything lower for a 0.
+pub fn decode(bytes: &[u8]) -> Decoded {
+    let (reset, slots) = match bytes.len() % 8 {
+        1 => (Some(bytes[0]), &bytes[1..]),
+        0 => (None, bytes),
+        _ => return decoder::malformed(),
+    };
+    let data: Vec<u8> = slots
+        .chunks(8)
+        .map(|slot| slot.iter().enumerate().fold(0u8, |acc, (i, &s)| acc | u8::from(s == 0xFF) << i))
+        .collect();
+
+    let Some(reset) = reset else {
+        // Bytes of a transaction whose reset came in an earlier packet
+        return Decoded { message: "Data".into(), ok: true, ..Default::default() }.with("data", hex(&data));
+    };
+    let presence = reset != RESET_IDLE;
+    let mut out = Decoded { ok: presence, ..Default::default() }.with("presence", presence);
+    let Some((&rom_command, rest)) = data.split_first() else {
+        out.message = if presence { "Reset" } else { "Reset, no presence" }.into();
+        return out;
+    };
+    let (rom_name, rom_len) = match rom_command {
+        0x33 => ("Read ROM", 8),
+        0x55 => ("Match ROM", 8),
+        0xCC => ("Skip ROM", 0),
+        0xF0 => ("Search ROM", 0),
+        0xEC => ("Alarm Search", 0),
+        0x3C => ("Overdrive Skip ROM", 0),
+        0x69 => ("Overdrive Match ROM", 8),
+        0xA5 => ("Resume", 0),
+        _ => ("Unknown ROM command", 0),
+    };
+    out = out.with("rom_command", format!("{rom_command:02X}"));
+    out.message = rom_name.into();
+    if matches!(rom_command, 0xF0 | 0xEC) {
+        // The rest are the search's bit triplets, not bytes
+        return out;
+    }
+    let Some(rom) = rest.get(..rom_len) else {
+        out.ok = false;
+        return out;
+    };
+    if let [family, serial @ .., crc] = rom {
+        let crc_ok = CRC8.checksum(&rom[..7]) == *crc;
+        // Named like the Linux w1 driver does: family, then the serial most
+        // significant byte first
+        let serial: String = serial.iter().rev().map(|b| format!("{b:02x}")).collect();
+        out = out.with("device", format!("{family:02x}-{serial}")).with("rom_crc_ok", crc_ok);
+        out.ok &= crc_ok;
+    }
+    // Read ROM is a whole transaction; the other commands select devices
+    // for the function command that follows
+    let Some((&function, data)) = rest[rom_len..].split_first().filter(|_| rom_command != 0x33) else {
+        return out;
+    };
+    out.message = format!("{rom_name}, {}", function_name(function));
+    out = out.with("function", format!("{function:02X}"));
+    if !data.is_empty() {
+        out = out.with("data", hex(data));
+    }
+    // A thermometer's scratchpad ends in a CRC of the eight bytes before it
+    if function == 0xBE && data.len() == 9 {
+        let crc_ok = CRC8.checksum(&data[..8]) == data[8];
+        out = out.with("scratchpad_crc_ok", crc_ok);
+        out.ok &= crc_ok;
+    }
+    out
+}
+
+/// Function commands of the common thermometers and memories.
+fn function_name(function: u8) -> String {
+    match function {
+        0x44 => "Convert T".into(),
+        0xBE => "Read Scratchpad".into(),
+        0x4E => "Write Scratchpad".into(),
+        0x48 => "Copy Scratchpad".into(),
+        0xB8 => "Recall E2".into(),
+        0xB4 => "Read Power Supply".into(),
+        0xF0 => "Read Memory".into(),
+        0x0F => "Write Scratchpad (memory)".into(),
+        0xAA => "Read Scratchpad (memory)".into(),
+        0x55 => "Copy Scratchpad (memory)".into(),
+        0xF5 => "PIO Access Read".into(),
+        0x5A => "PIO Access Write".into(),
+        f => format!("Function {f:02X}"),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02X}")).collect()
+}
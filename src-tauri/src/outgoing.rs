@@ -0,0 +1,40 @@
+/// Called once with the outcome of writing an `Outgoing`.
+pub type Ack = Box<dyn FnOnce(Result<(), String>) + Send>;
+
+/// Bytes queued for a connection's writer.
+pub struct Outgoing {
+    pub data: Vec<u8>,
+    ack: Option<Ack>,
+}
+
+impl Outgoing {
+    /// Bytes nobody waits for, e.g. a protocol's own replies.
+    pub fn new(data: Vec<u8>) -> Self {
+        Self { data, ack: None }
+    }
+
+    /// `ack` learns whether the bytes were handed to the OS. Dropping the
+    /// message unwritten (the connection closed first) reports an error.
+    pub fn with_ack(data: Vec<u8>, ack: Ack) -> Self {
+        Self { data, ack: Some(ack) }
+    }
+
+    pub fn done(mut self, result: Result<(), String>) {
+        if let Some(ack) = self.ack.take() {
+            ack(result);
+        }
+    }
+
+    /// Drop without acknowledging, for a message that was never queued.
+    pub fn cancel(mut self) {
+        self.ack = None;
+    }
+}
+
+impl Drop for Outgoing {
+    fn drop(&mut self) {
+        if let Some(ack) = self.ack.take() {
+            ack(Err("Connection closed before the data was written".into()));
+        }
+    }
+}
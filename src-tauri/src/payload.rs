@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use crate::eol::TxAppend;
+use crate::state::SessionInfo;
+use crate::vars::Vars;
+
+/// How a payload string is turned into bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadFormat {
+    /// Sent as UTF-8.
+    #[default]
+    Text,
+    /// Hex digits, optionally separated by spaces, commas, colons or dashes
+    /// and prefixed with `0x`.
+    Hex,
+    /// Standard base64, padding optional.
+    Base64,
+}
+
+/// Why bytes could not be prepared for sending.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxError {
+    /// No session with this id.
+    UnknownSession(String),
+    /// The payload does not parse in its format.
+    Encode(String),
+    /// A template names a variable the session doesn't have.
+    UnknownVariable(String),
+}
+
+impl std::fmt::Display for TxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TxError::UnknownSession(id) => write!(f, "Unknown session: {id}"),
+            TxError::Encode(e) => write!(f, "Invalid payload: {e}"),
+            TxError::UnknownVariable(name) => write!(f, "Unknown variable: {name}"),
+        }
+    }
+}
+
+/// The bytes of `data` in `format`. With `escape`, C-style escapes in text
+/// are resolved (see `unescape`); hex and base64 can already spell out any
+/// byte, so it changes nothing for them.
+pub fn encode(data: &str, format: PayloadFormat, escape: bool) -> Result<Vec<u8>, String> {
+    match format {
+        PayloadFormat::Text if escape => unescape(data),
+        PayloadFormat::Text => Ok(data.as_bytes().to_vec()),
+        PayloadFormat::Hex => hex_to_bytes(data),
+        PayloadFormat::Base64 => {
+            let data: String = data.split_whitespace().collect();
+            base64::engine::general_purpose::STANDARD_NO_PAD
+                .decode(data.trim_end_matches('='))
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// The bytes of `text` with its backslash escapes resolved: `\x1B`, `\n`,
+/// `\r`, `\t`, `\0`, `\\` and the other single-character ones, plus `\e`
+/// for ESC.
+pub fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0u8; 4];
+            out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        let byte = match chars.next().ok_or("Trailing backslash")? {
+            'x' => {
+                let digits: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(b) if digits.len() == 2 => b,
+                    _ => return Err(format!("\\x needs two hex digits, got {digits:?}")),
+                }
+            }
+            'n' => b'\n',
+            'r' => b'\r',
+            't' => b'\t',
+            '0' => 0,
+            'a' => 0x07,
+            'b' => 0x08,
+            'e' => 0x1B,
+            'f' => 0x0C,
+            'v' => 0x0B,
+            '\\' => b'\\',
+            '\'' => b'\'',
+            '"' => b'"',
+            other => return Err(format!("Unknown escape \\{other}")),
+        };
+        out.push(byte);
+    }
+    Ok(out)
+}
+
+/// Replace each `{{name}}` in `template` with the variable's value, or with
+/// its bytes as hex digits for `{{name|hex}}`.
+pub fn expand(template: &str, vars: &Vars) -> Result<String, TxError> {
+    fill(template, vars, true)
+}
+
+/// Like `expand`, but a `{{...}}` naming no variable is left as it is, so
+/// payloads that merely contain braces are sent unchanged.
+pub fn expand_known(template: &str, vars: &Vars) -> Result<String, TxError> {
+    fill(template, vars, false)
+}
+
+fn fill(template: &str, vars: &Vars, strict: bool) -> Result<String, TxError> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else { break };
+        let inner = &rest[start + 2..start + 2 + len];
+        let (name, filter) = match inner.split_once('|') {
+            Some((name, filter)) => (name.trim(), Some(filter.trim())),
+            None => (inner.trim(), None),
+        };
+        let end = start + 2 + len + 2;
+        let Some(value) = vars.get(name) else {
+            if strict {
+                return Err(TxError::UnknownVariable(name.to_string()));
+            }
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        };
+        out.push_str(&rest[..start]);
+        match filter {
+            None => out.push_str(value),
+            Some("hex") => out.extend(value.bytes().map(|b| format!("{b:02X}"))),
+            Some(other) => return Err(TxError::Encode(format!("Unknown filter: {other}"))),
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Fill in the session's variables, encode `data` and append the terminator
+/// for `session_id`: `mode` when given, otherwise the session's TX append
+/// mode.
+pub fn apply_append_mode(
+    sessions: &HashMap<String, SessionInfo>,
+    vars: &Vars,
+    session_id: &str,
+    data: &str,
+    format: PayloadFormat,
+    escape: bool,
+    mode: Option<&TxAppend>,
+) -> Result<Vec<u8>, TxError> {
+    let sess = sessions.get(session_id).ok_or_else(|| TxError::UnknownSession(session_id.to_string()))?;
+    let data = expand_known(data, vars)?;
+    let mut bytes = encode(&data, format, escape).map_err(TxError::Encode)?;
+    bytes.extend_from_slice(mode.unwrap_or(&sess.tx_append).suffix(sess.line_ending));
+    Ok(bytes)
+}
+
+/// Parse hex bytes written as `DEADBEEF`, `DE AD BE EF`, `DE:AD`, `DE-AD` or
+/// C-style as `0xDE, 0xAD`. C-style bytes (with the `0x` prefix, or in a
+/// comma-separated list) stand alone: each has one or two digits.
+pub fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, String> {
+    let commas = hex.contains(',');
+    let mut digits = String::with_capacity(hex.len());
+    for token in hex.split(|c: char| c.is_whitespace() || matches!(c, ',' | ':' | '-')) {
+        let prefixed = token.strip_prefix("0x").or_else(|| token.strip_prefix("0X"));
+        let byte = prefixed.unwrap_or(token);
+        if prefixed.is_some() || (commas && !token.is_empty()) {
+            if !(1..=2).contains(&byte.len()) {
+                return Err(format!("{token:?} is not a single byte"));
+            }
+            if byte.len() == 1 {
+                digits.push('0');
+            }
+        }
+        digits.push_str(byte);
+    }
+    if digits.len() % 2 != 0 {
+        return Err("Odd-length hex string".into());
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            let pair = digits.get(i..i + 2).ok_or("Invalid hex digit")?;
+            u8::from_str_radix(pair, 16).map_err(|e| format!("{pair:?}: {e}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescape_resolves_c_escapes() {
+        assert_eq!(unescape(r"\x1B[0m").unwrap(), b"\x1B[0m");
+        assert_eq!(unescape(r"a\tb\r\n").unwrap(), b"a\tb\r\n");
+        assert_eq!(unescape(r"\0\\\e").unwrap(), [0x00, b'\\', 0x1B]);
+        assert_eq!(unescape(r"\xff\xFF").unwrap(), [0xFF, 0xFF]);
+        assert_eq!(unescape(r#"\"quoted\'"#).unwrap(), b"\"quoted'");
+    }
+
+    #[test]
+    fn unescape_keeps_plain_text_as_utf8() {
+        assert_eq!(unescape("AT+CSQ").unwrap(), b"AT+CSQ");
+        assert_eq!(unescape("µ").unwrap(), "µ".as_bytes());
+    }
+
+    #[test]
+    fn unescape_rejects_bad_escapes() {
+        assert!(unescape("\\").is_err());
+        assert!(unescape(r"\x1").is_err());
+        assert!(unescape(r"\xZZ").is_err());
+        assert!(unescape(r"\q").is_err());
+    }
+
+    #[test]
+    fn escapes_only_resolve_when_asked() {
+        assert_eq!(encode(r"\n", PayloadFormat::Text, false).unwrap(), b"\\n");
+        assert_eq!(encode(r"\n", PayloadFormat::Text, true).unwrap(), b"\n");
+        assert_eq!(encode("0A", PayloadFormat::Hex, true).unwrap(), b"\n");
+    }
+
+    #[test]
+    fn braces_without_a_variable_send_unchanged() {
+        let vars = Vars::from([("port".to_string(), "COM3".to_string())]);
+        assert_eq!(expand_known("{{", &vars).unwrap(), "{{");
+        assert_eq!(expand_known(r#"{{"a":1}}"#, &vars).unwrap(), r#"{{"a":1}}"#);
+        assert_eq!(expand_known("open {{port}} {{baud}}", &vars).unwrap(), "open COM3 {{baud}}");
+        assert!(expand("{{baud}}", &vars).is_err());
+    }
+
+    #[test]
+    fn hex_accepts_common_notations() {
+        let bytes = [0xDE, 0xAD, 0xBE, 0xEF];
+        for input in ["DEADBEEF", "DE AD BE EF", "de:ad:be:ef", "DE-AD-BE-EF", "0xDE,0xAD,0xBE,0xEF", "0xDE, 0xAD 0XBE,0xef"] {
+            assert_eq!(hex_to_bytes(input).unwrap(), bytes, "{input}");
+        }
+        assert_eq!(hex_to_bytes("0x1,0x0A").unwrap(), [0x01, 0x0A]);
+        assert_eq!(hex_to_bytes("1, A, ff").unwrap(), [0x01, 0x0A, 0xFF]);
+        assert!(hex_to_bytes("ABC").is_err());
+        assert!(hex_to_bytes("GG").is_err());
+        assert!(hex_to_bytes("µ1").is_err());
+    }
+
+    #[test]
+    fn hex_rejects_multi_byte_c_style_tokens() {
+        assert!(hex_to_bytes("0x123,0x456").is_err());
+        assert!(hex_to_bytes("0x1234").is_err());
+        assert!(hex_to_bytes("DEAD, BEEF").is_err());
+        assert!(hex_to_bytes("0x").is_err());
+    }
+}
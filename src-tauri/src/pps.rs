@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use crate::serial_port::{LineWatch, ModemLine};
+
+const DAY_MS: f64 = 86_400_000.0;
+
+/// Where a GNSS receiver's pulse-per-second output is wired.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PpsOptions {
+    pub line: ModemLine,
+    /// Pulse on the line becoming inactive instead of active, for inverted
+    /// outputs.
+    pub falling: bool,
+    /// How often the line is sampled; pulse times are this precise.
+    pub poll_us: u64,
+    /// A time sentence arriving later than this after a pulse isn't paired
+    /// with it.
+    pub max_latency_ms: f64,
+}
+
+impl Default for PpsOptions {
+    fn default() -> Self {
+        Self { line: ModemLine::Dcd, falling: false, poll_us: 500, max_latency_ms: 1000.0 }
+    }
+}
+
+/// A pulse paired with the first time sentence that followed it.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeMark {
+    pub session_id: String,
+    /// Host time the pulse was seen.
+    pub pulse_ms: f64,
+    /// Since the previous pulse; 1000 for a healthy receiver.
+    pub interval_ms: Option<f64>,
+    /// e.g. "GPRMC" or "UBX-NAV-PVT".
+    pub source: String,
+    /// UTC time of day the sentence reports, "hh:mm:ss.sss".
+    pub gnss_time: String,
+    /// From the pulse to the sentence's arrival.
+    pub latency_ms: f64,
+    /// Host clock minus GNSS time at the pulse, by time of day.
+    pub offset_ms: f64,
+}
+
+/// PPS correlation running on a session; dropping it stops the line watch.
+pub struct Pps {
+    pub correlator: Correlator,
+    pub _watch: LineWatch,
+}
+
+/// Pairs pulses on a modem line with the time sentences of one session.
+#[derive(Debug, Default)]
+pub struct Correlator {
+    pub options: PpsOptions,
+    /// Latest pulse, and whether a sentence was paired with it already.
+    pulse: Option<(f64, bool)>,
+    interval_ms: Option<f64>,
+}
+
+impl Correlator {
+    pub fn new(options: PpsOptions) -> Self {
+        Self { options, ..Self::default() }
+    }
+
+    /// A transition of the line to `asserted` at `ts`.
+    pub fn edge(&mut self, ts: f64, asserted: bool) {
+        if asserted == self.options.falling {
+            return;
+        }
+        self.interval_ms = self.pulse.map(|(prev, _)| ts - prev);
+        self.pulse = Some((ts, false));
+    }
+
+    /// A received packet; a time mark when it is the first time sentence
+    /// after an unpaired pulse.
+    pub fn packet(&mut self, session_id: &str, bytes: &[u8], ts: f64) -> Option<TimeMark> {
+        let (pulse_ms, paired) = self.pulse?;
+        let latency_ms = ts - pulse_ms;
+        if paired || latency_ms > self.options.max_latency_ms {
+            return None;
+        }
+        let (source, tod_ms) = nmea_time(bytes).or_else(|| ubx_time(bytes))?;
+        self.pulse = Some((pulse_ms, true));
+        // Wrap the difference into the half day either side of zero
+        let offset_ms = (pulse_ms.rem_euclid(DAY_MS) - tod_ms + DAY_MS / 2.0).rem_euclid(DAY_MS) - DAY_MS / 2.0;
+        Some(TimeMark {
+            session_id: session_id.to_string(),
+            pulse_ms,
+            interval_ms: self.interval_ms,
+            source,
+            gnss_time: format_tod(tod_ms),
+            latency_ms,
+            offset_ms,
+        })
+    }
+}
+
+fn format_tod(ms: f64) -> String {
+    let ms = ms.round() as u64;
+    format!("{:02}:{:02}:{:02}.{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000)
+}
+
+/// Talker and type (e.g. "GPRMC") and UTC time of day of an NMEA sentence
+/// that carries one.
+fn nmea_time(bytes: &[u8]) -> Option<(String, f64)> {
+    let text = String::from_utf8_lossy(bytes);
+    let sentence = &text[text.find('$')? + 1..];
+    let sentence = sentence.split(['*', '\r', '\n']).next()?;
+    let mut fields = sentence.split(',');
+    let name = fields.next()?;
+    let index = match name.get(2..)? {
+        "RMC" | "GGA" | "ZDA" | "GNS" => 0,
+        "GLL" => 4,
+        _ => return None,
+    };
+    let hhmmss = fields.nth(index)?;
+    // Line noise decodes to replacement characters, so slice ASCII only
+    if hhmmss.len() < 6 || !hhmmss.is_ascii() {
+        return None;
+    }
+    let h: f64 = hhmmss[0..2].parse().ok()?;
+    let m: f64 = hhmmss[2..4].parse().ok()?;
+    let s: f64 = hhmmss[4..].parse().ok()?;
+    Some((name.to_string(), ((h * 60.0 + m) * 60.0 + s) * 1000.0))
+}
+
+/// UTC time of day of a UBX NAV-PVT or NAV-TIMEUTC message with valid time.
+fn ubx_time(bytes: &[u8]) -> Option<(String, f64)> {
+    let start = bytes.windows(2).position(|w| w == [0xB5, 0x62])?;
+    let msg = &bytes[start..];
+    let len = u16::from_le_bytes([*msg.get(4)?, *msg.get(5)?]) as usize;
+    let payload = msg.get(6..6 + len)?;
+    let nano = |at: usize| Some(i32::from_le_bytes(payload.get(at..at + 4)?.try_into().ok()?));
+    let (name, hms, valid, nano) = match (msg[2], msg[3]) {
+        // NAV-PVT: validTime is bit 1 of `valid`
+        (0x01, 0x07) => ("UBX-NAV-PVT", payload.get(8..11)?, payload.get(11)? & 0x02 != 0, nano(16)?),
+        // NAV-TIMEUTC: validUTC is bit 2 of `valid`
+        (0x01, 0x21) => ("UBX-NAV-TIMEUTC", payload.get(12..15)?, payload.get(19)? & 0x04 != 0, nano(8)?),
+        _ => return None,
+    };
+    if !valid {
+        return None;
+    }
+    let secs = (hms[0] as f64 * 60.0 + hms[1] as f64) * 60.0 + hms[2] as f64;
+    Some((name.to_string(), secs * 1000.0 + nano as f64 / 1e6))
+}
@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::state::SplitterConfig;
+
+/// A splitter configuration selectable by name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FramingPreset {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub config: SplitterConfig,
+    /// Shipped with the app; cannot be replaced or deleted.
+    #[serde(default)]
+    pub builtin: bool,
+}
+
+fn preset(name: &str, description: &str, config: SplitterConfig) -> FramingPreset {
+    FramingPreset { name: name.into(), description: description.into(), config, builtin: true }
+}
+
+fn delimited(sof: &[u8], eof: &[u8], eof_include: bool) -> SplitterConfig {
+    SplitterConfig { method: "delimiter".into(), sof: sof.to_vec(), eof: eof.to_vec(), eof_include, ..Default::default() }
+}
+
+fn line(ending: &str) -> SplitterConfig {
+    SplitterConfig { method: "line".into(), line_ending: ending.into(), ..Default::default() }
+}
+
+pub fn builtin() -> Vec<FramingPreset> {
+    vec![
+        preset("stx_etx", "STX (0x02) … ETX (0x03)", delimited(&[0x02], &[0x03], true)),
+        preset("cr", "Lines ending in CR", line("cr")),
+        preset("lf", "Lines ending in LF", line("lf")),
+        preset("crlf", "Lines ending in CR LF", line("crlf")),
+        // Flags are shared between frames, so each frame runs to the next flag
+        preset("hdlc", "0x7E flag-delimited (HDLC, PPP)", delimited(&[0x7E], &[], true)),
+        preset("null", "Null-terminated", delimited(&[], &[0x00], false)),
+    ]
+}
+
+/// User-defined presets, persisted as `framing_presets.json` in the app data
+/// directory next to the built-in ones.
+pub struct PresetStore {
+    path: PathBuf,
+    presets: BTreeMap<String, FramingPreset>,
+}
+
+impl PresetStore {
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join("framing_presets.json");
+        let presets = std::fs::read(&path)
+            .ok()
+            .and_then(|b| serde_json::from_slice::<Vec<FramingPreset>>(&b).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|p| (p.name.clone(), FramingPreset { builtin: false, ..p }))
+            .collect();
+        Self { path, presets }
+    }
+
+    /// Built-in presets first, then the user's in name order.
+    pub fn list(&self) -> Vec<FramingPreset> {
+        builtin().into_iter().chain(self.presets.values().cloned()).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Result<FramingPreset, String> {
+        self.list().into_iter().find(|p| p.name == name).ok_or_else(|| format!("Unknown framing preset: {name}"))
+    }
+
+    /// Add or replace a user preset.
+    pub fn save_preset(&mut self, preset: FramingPreset) -> Result<(), String> {
+        if preset.name.trim().is_empty() {
+            return Err("Preset name is empty".into());
+        }
+        if builtin().iter().any(|p| p.name == preset.name) {
+            return Err(format!("{} is a built-in preset", preset.name));
+        }
+        self.presets.insert(preset.name.clone(), FramingPreset { builtin: false, ..preset });
+        self.save()
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<(), String> {
+        if builtin().iter().any(|p| p.name == name) {
+            return Err(format!("{name} is a built-in preset"));
+        }
+        self.presets.remove(name).ok_or_else(|| format!("Unknown framing preset: {name}"))?;
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let list: Vec<_> = self.presets.values().collect();
+        let json = serde_json::to_vec_pretty(&list).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, json).map_err(|e| e.to_string())
+    }
+}
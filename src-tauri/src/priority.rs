@@ -0,0 +1,182 @@
+use std::io;
+use serde::{Deserialize, Serialize};
+
+/// Scheduling class of a connection's I/O thread.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreadPriority {
+    #[default]
+    Normal,
+    /// Above other threads of normal priority. On Linux this lowers the nice
+    /// value, which needs CAP_SYS_NICE or a raised RLIMIT_NICE.
+    High,
+    /// Real-time scheduling (SCHED_FIFO, or time-critical on Windows). Needs
+    /// root or CAP_SYS_NICE on Linux; a busy device can starve the rest of
+    /// the system.
+    Realtime,
+}
+
+/// How the thread reading a connection is scheduled, for steadier RX
+/// timestamps on a busy machine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkerOptions {
+    pub priority: ThreadPriority,
+    /// CPU core to pin the thread to. Not supported on macOS.
+    pub core: Option<usize>,
+}
+
+/// Puts the thread back as it was before.
+type Restore = Box<dyn FnOnce() + Send>;
+
+/// Undoes `WorkerOptions::apply` when dropped.
+pub struct Scheduled(Vec<Restore>);
+
+impl Drop for Scheduled {
+    fn drop(&mut self) {
+        for restore in self.0.drain(..).rev() {
+            restore();
+        }
+    }
+}
+
+impl WorkerOptions {
+    /// Apply to the calling thread until the result is dropped.
+    pub fn apply(&self) -> Result<Scheduled, String> {
+        let mut scheduled = Scheduled(Vec::new());
+        if self.priority != ThreadPriority::Normal {
+            scheduled.0.push(set_priority(self.priority).map_err(|e| format!("Cannot raise thread priority: {e}"))?);
+        }
+        if let Some(core) = self.core {
+            let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+            if core >= cores {
+                return Err(format!("Core {core} does not exist; this machine has {cores}"));
+            }
+            scheduled.0.push(pin(core).map_err(|e| format!("Cannot pin thread to core {core}: {e}"))?);
+        }
+        Ok(scheduled)
+    }
+}
+
+#[cfg(unix)]
+fn sched(policy: libc::c_int, priority: libc::c_int) -> io::Result<Restore> {
+    let check = |rc: libc::c_int| if rc == 0 { Ok(()) } else { Err(io::Error::from_raw_os_error(rc)) };
+    // SAFETY: only the calling thread is touched; the params are valid for the calls
+    unsafe {
+        let (mut old_policy, mut old_param) = (0, std::mem::zeroed::<libc::sched_param>());
+        check(libc::pthread_getschedparam(libc::pthread_self(), &mut old_policy, &mut old_param))?;
+        let param = libc::sched_param { sched_priority: priority };
+        check(libc::pthread_setschedparam(libc::pthread_self(), policy, &param))?;
+        Ok(Box::new(move || {
+            libc::pthread_setschedparam(libc::pthread_self(), old_policy, &old_param);
+        }))
+    }
+}
+
+#[cfg(unix)]
+fn realtime() -> io::Result<Restore> {
+    // SAFETY: plain queries without pointers
+    let (min, max) = unsafe { (libc::sched_get_priority_min(libc::SCHED_FIFO), libc::sched_get_priority_max(libc::SCHED_FIFO)) };
+    sched(libc::SCHED_FIFO, (min + max) / 2)
+}
+
+#[cfg(target_os = "linux")]
+fn set_priority(priority: ThreadPriority) -> io::Result<Restore> {
+    match priority {
+        ThreadPriority::Normal => Ok(Box::new(|| {})),
+        // Linux nice values are per thread
+        // SAFETY: only the calling thread's nice value is changed
+        ThreadPriority::High => unsafe {
+            let tid = libc::gettid() as libc::id_t;
+            let old = libc::getpriority(libc::PRIO_PROCESS, tid);
+            if libc::setpriority(libc::PRIO_PROCESS, tid, -10) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Box::new(move || {
+                libc::setpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t, old);
+            }))
+        },
+        ThreadPriority::Realtime => realtime(),
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn set_priority(priority: ThreadPriority) -> io::Result<Restore> {
+    match priority {
+        ThreadPriority::Normal => Ok(Box::new(|| {})),
+        // SCHED_OTHER has a priority range here, unlike on Linux
+        // SAFETY: a plain query without pointers
+        ThreadPriority::High => sched(libc::SCHED_OTHER, unsafe { libc::sched_get_priority_max(libc::SCHED_OTHER) }),
+        ThreadPriority::Realtime => realtime(),
+    }
+}
+
+#[cfg(windows)]
+fn set_priority(priority: ThreadPriority) -> io::Result<Restore> {
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentThread, GetThreadPriority, SetThreadPriority, THREAD_PRIORITY_HIGHEST, THREAD_PRIORITY_TIME_CRITICAL,
+    };
+    let level = match priority {
+        ThreadPriority::Normal => return Ok(Box::new(|| {})),
+        ThreadPriority::High => THREAD_PRIORITY_HIGHEST,
+        ThreadPriority::Realtime => THREAD_PRIORITY_TIME_CRITICAL,
+    };
+    // SAFETY: the pseudo handle always refers to the calling thread
+    unsafe {
+        let old = GetThreadPriority(GetCurrentThread());
+        if SetThreadPriority(GetCurrentThread(), level) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Box::new(move || {
+            SetThreadPriority(GetCurrentThread(), old);
+        }))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn pin(core: usize) -> io::Result<Restore> {
+    // CPU_SET panics past the end of the set
+    if core >= libc::CPU_SETSIZE as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "core index out of range"));
+    }
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    // SAFETY: the sets are zeroed cpu_set_t of `size` bytes; 0 is the calling thread
+    unsafe {
+        let mut old: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, size, &mut old) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        if libc::sched_setaffinity(0, size, &set) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Box::new(move || {
+            libc::sched_setaffinity(0, size, &old);
+        }))
+    }
+}
+
+#[cfg(windows)]
+fn pin(core: usize) -> io::Result<Restore> {
+    use windows_sys::Win32::System::Threading::{GetCurrentThread, SetThreadAffinityMask};
+    let mask = u32::try_from(core)
+        .ok()
+        .and_then(|core| 1usize.checked_shl(core))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "core index out of range"))?;
+    // SAFETY: the pseudo handle always refers to the calling thread
+    unsafe {
+        let old = SetThreadAffinityMask(GetCurrentThread(), mask);
+        if old == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Box::new(move || {
+            SetThreadAffinityMask(GetCurrentThread(), old);
+        }))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn pin(_core: usize) -> io::Result<Restore> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "not supported on this platform"))
+}
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use crate::dns;
+use crate::priority::WorkerOptions;
+use crate::serial_port::Flow;
+use crate::socket::{SocketOpenArgs, UdpOptions};
+use crate::state::SessionInfo;
+
+/// One connection of a bench profile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BenchConnection {
+    Serial {
+        port: String,
+        baud: u32,
+        #[serde(default)]
+        flow: Flow,
+        /// Session name; the port name when unset.
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(default)]
+        worker: WorkerOptions,
+    },
+    /// TCP, or TLS/WebSocket when `tls`/`ws` are set, or a Unix socket.
+    Tcp(Box<SocketOpenArgs>),
+    Udp {
+        #[serde(default)]
+        host: String,
+        #[serde(default)]
+        port: u16,
+        #[serde(default)]
+        local_port: Option<u16>,
+        #[serde(default)]
+        reply_to_sender: bool,
+        #[serde(default)]
+        options: UdpOptions,
+    },
+}
+
+impl BenchConnection {
+    /// Session id the connection will be opened under.
+    pub fn session_id(&self) -> String {
+        match self {
+            Self::Serial { port, .. } => port.clone(),
+            Self::Tcp(args) => args.session_id(),
+            Self::Udp { host, port, local_port, .. } => udp_session_id(host, *port, *local_port),
+        }
+    }
+}
+
+pub fn udp_session_id(host: &str, port: u16, local_port: Option<u16>) -> String {
+    match host.is_empty() {
+        false => format!("udp:{}", dns::host_port(host, port)),
+        true => format!("udp:*:{}", local_port.unwrap_or(0)),
+    }
+}
+
+/// A set of connections opened and closed together.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchProfile {
+    pub name: String,
+    pub connections: Vec<BenchConnection>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchConnectionResult {
+    pub session_id: String,
+    /// "opened", "failed", "rolled_back" (opened, then closed again because
+    /// another connection failed) or "skipped" (not tried after a failure).
+    pub status: &'static str,
+    pub session: Option<SessionInfo>,
+    pub error: Option<String>,
+}
+
+/// Outcome of opening a bench profile. Either every connection is open or
+/// none of them is.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchOpenResult {
+    pub profile: String,
+    pub ok: bool,
+    pub connections: Vec<BenchConnectionResult>,
+}
@@ -0,0 +1,155 @@
+use std::net::IpAddr;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use crate::dns;
+use crate::socket::{ConnectError, ErrorCategory};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxyKind {
+    /// HTTP proxy using the CONNECT method.
+    #[default]
+    Http,
+    /// SOCKS5 (RFC 1928), with username/password auth (RFC 1929) when a
+    /// username is set.
+    Socks5,
+}
+
+/// Proxy to tunnel a TCP connection through.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProxyOptions {
+    pub kind: ProxyKind,
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// Ask the proxy on `stream` to open a tunnel to `target:port`.
+pub async fn handshake(stream: &mut TcpStream, target: &str, port: u16, opts: &ProxyOptions) -> Result<(), ConnectError> {
+    match opts.kind {
+        ProxyKind::Http => http_connect(stream, target, port, opts).await,
+        ProxyKind::Socks5 => socks5_connect(stream, target, port, opts).await,
+    }
+}
+
+async fn http_connect(stream: &mut TcpStream, target: &str, port: u16, opts: &ProxyOptions) -> Result<(), ConnectError> {
+    let net_err = |e: String| ConnectError::new(ErrorCategory::Network, e);
+    let authority = dns::host_port(dns::bare_host(target), port);
+    let mut req = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+    if let Some(user) = &opts.username {
+        let cred = format!("{user}:{}", opts.password.as_deref().unwrap_or(""));
+        let cred = base64::engine::general_purpose::STANDARD.encode(cred);
+        req.push_str(&format!("Proxy-Authorization: Basic {cred}\r\n"));
+    }
+    req.push_str("\r\n");
+    stream.write_all(req.as_bytes()).await.map_err(|e| net_err(e.to_string()))?;
+
+    // Read byte-wise up to the blank line so no tunnelled data is consumed
+    let mut head = Vec::with_capacity(256);
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() > 8192 {
+            return Err(net_err("Proxy response header too long".into()));
+        }
+        let b = stream.read_u8().await.map_err(|e| net_err(format!("Proxy closed connection: {e}")))?;
+        head.push(b);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let status_line = head.lines().next().unwrap_or_default();
+    let status = status_line.split_whitespace().nth(1).and_then(|c| c.parse::<u16>().ok());
+    match status {
+        Some(200..=299) => Ok(()),
+        _ => Err(net_err(format!("Proxy refused CONNECT: {status_line}"))),
+    }
+}
+
+async fn socks5_connect(stream: &mut TcpStream, target: &str, port: u16, opts: &ProxyOptions) -> Result<(), ConnectError> {
+    let net_err = |e: std::io::Error| ConnectError::new(ErrorCategory::Network, format!("Proxy closed connection: {e}"));
+    let refused = |msg: String| ConnectError::new(ErrorCategory::Network, msg);
+
+    // Offer no-auth, plus username/password when credentials are set
+    let greeting: &[u8] = match opts.username {
+        Some(_) => &[5, 2, 0x00, 0x02],
+        None => &[5, 1, 0x00],
+    };
+    stream.write_all(greeting).await.map_err(net_err)?;
+    let mut choice = [0u8; 2];
+    stream.read_exact(&mut choice).await.map_err(net_err)?;
+    if choice[0] != 5 {
+        return Err(refused("Not a SOCKS5 proxy".into()));
+    }
+    match choice[1] {
+        0x00 => {}
+        0x02 => {
+            let user = opts.username.as_deref().unwrap_or_default().as_bytes();
+            let pass = opts.password.as_deref().unwrap_or_default().as_bytes();
+            if user.len() > 255 || pass.len() > 255 {
+                return Err(ConnectError::new(ErrorCategory::Config, "SOCKS5 username and password are limited to 255 bytes"));
+            }
+            let mut auth = vec![1, user.len() as u8];
+            auth.extend_from_slice(user);
+            auth.push(pass.len() as u8);
+            auth.extend_from_slice(pass);
+            stream.write_all(&auth).await.map_err(net_err)?;
+            let mut status = [0u8; 2];
+            stream.read_exact(&mut status).await.map_err(net_err)?;
+            if status[1] != 0 {
+                return Err(refused("SOCKS5 proxy rejected the username or password".into()));
+            }
+        }
+        _ => return Err(refused("SOCKS5 proxy accepts none of the offered auth methods".into())),
+    }
+
+    let host = dns::bare_host(target);
+    let mut req = vec![5, 1, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            req.push(0x01);
+            req.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            req.push(0x04);
+            req.extend_from_slice(&ip.octets());
+        }
+        Err(_) if host.len() > 255 => return Err(ConnectError::new(ErrorCategory::Config, "Host name too long for SOCKS5")),
+        Err(_) => {
+            req.extend_from_slice(&[0x03, host.len() as u8]);
+            req.extend_from_slice(host.as_bytes());
+        }
+    }
+    req.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&req).await.map_err(net_err)?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await.map_err(net_err)?;
+    if reply[1] != 0 {
+        return Err(refused(format!("SOCKS5 proxy refused CONNECT: {}", socks5_reply(reply[1]))));
+    }
+    // Skip the bound address so no tunnelled data is consumed
+    let addr_len = match reply[3] {
+        0x01 => 4,
+        0x04 => 16,
+        0x03 => stream.read_u8().await.map_err(net_err)? as usize,
+        other => return Err(refused(format!("SOCKS5 proxy sent unknown address type {other}"))),
+    };
+    let mut bound = vec![0u8; addr_len + 2];
+    stream.read_exact(&mut bound).await.map_err(net_err)?;
+    Ok(())
+}
+
+fn socks5_reply(code: u8) -> &'static str {
+    match code {
+        0x01 => "general failure",
+        0x02 => "connection not allowed by ruleset",
+        0x03 => "network unreachable",
+        0x04 => "host unreachable",
+        0x05 => "connection refused",
+        0x06 => "TTL expired",
+        0x07 => "command not supported",
+        0x08 => "address type not supported",
+        _ => "unknown error",
+    }
+}
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+/// What happens once a session reaches its quota.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaAction {
+    /// Close the log file and keep the connection open.
+    #[default]
+    StopLogging,
+    /// Close the connection (and its log).
+    Disconnect,
+}
+
+/// Limits for one session, counted from when it connected.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Quota {
+    /// Payload bytes, both directions.
+    pub max_bytes: Option<u64>,
+    pub max_duration_s: Option<f64>,
+    pub action: QuotaAction,
+}
+
+/// A session's quota and whether it was already acted on.
+pub struct QuotaState {
+    pub quota: Quota,
+    pub reached: bool,
+    /// Fires the duration limit.
+    pub timer: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl Drop for QuotaState {
+    fn drop(&mut self) {
+        if let Some(timer) = self.timer.take() {
+            timer.abort();
+        }
+    }
+}
+
+/// Emitted once when a session reaches its quota.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaEvent {
+    pub session_id: String,
+    /// "bytes" or "duration".
+    pub limit: &'static str,
+    pub action: QuotaAction,
+    pub bytes: u64,
+    pub elapsed_s: f64,
+}
+
+impl Quota {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_bytes == Some(0) {
+            return Err("max_bytes must be positive".into());
+        }
+        if self.max_duration_s.is_some_and(|s| s <= 0.0) {
+            return Err("max_duration_s must be positive".into());
+        }
+        Ok(())
+    }
+}
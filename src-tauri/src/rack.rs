@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+use serialport::SerialPortType;
+
+/// One board in a rack definition. The port is taken from `port` when given,
+/// otherwise it is looked up by USB VID/PID/serial number.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RackBoard {
+    pub name: String,
+    #[serde(default)]
+    pub port: Option<String>,
+    #[serde(default)]
+    pub vid: Option<u16>,
+    #[serde(default)]
+    pub pid: Option<u16>,
+    #[serde(default)]
+    pub serial_number: Option<String>,
+    pub baud: u32,
+}
+
+/// A rack board together with the outcome of opening it.
+#[derive(Debug, Clone)]
+pub struct RackMember {
+    pub board: RackBoard,
+    pub session_id: Option<String>,
+    pub error: Option<String>,
+    pub log_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RackBoardStatus {
+    pub name: String,
+    pub session_id: Option<String>,
+    pub connected: bool,
+    pub error: Option<String>,
+    pub log_path: Option<String>,
+    pub tx_bytes: u64,
+    pub rx_bytes: u64,
+}
+
+/// Find the serial port for a board.
+pub fn resolve_port(board: &RackBoard) -> Result<String, String> {
+    if let Some(port) = &board.port {
+        return Ok(port.clone());
+    }
+    if board.vid.is_none() && board.pid.is_none() && board.serial_number.is_none() {
+        return Err(format!("{}: no port or USB id given", board.name));
+    }
+    let ports = serialport::available_ports().map_err(|e| e.to_string())?;
+    ports
+        .into_iter()
+        .find(|p| match &p.port_type {
+            SerialPortType::UsbPort(usb) => {
+                board.vid.is_none_or(|v| v == usb.vid)
+                    && board.pid.is_none_or(|v| v == usb.pid)
+                    && board.serial_number.as_ref().is_none_or(|sn| usb.serial_number.as_ref() == Some(sn))
+            }
+            _ => false,
+        })
+        .map(|p| p.port_name)
+        .ok_or_else(|| format!("{}: no matching USB port", board.name))
+}
+
+/// Directory-safe version of a board name.
+pub fn dir_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
@@ -0,0 +1,46 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::serial_port::SerialSettings;
+use crate::socket::SocketOpenArgs;
+use crate::ssh::SshOptions;
+
+/// Reopen a session whose device or peer went away, waiting longer after
+/// each failed attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconnectPolicy {
+    /// 0 keeps trying until the session is disconnected by hand.
+    pub max_attempts: u32,
+    /// Wait before the first attempt.
+    pub initial_ms: u64,
+    /// Longest wait between attempts.
+    pub max_interval_ms: u64,
+    /// Factor the wait grows by after each failed attempt.
+    pub multiplier: f64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self { max_attempts: 10, initial_ms: 1000, max_interval_ms: 30_000, multiplier: 2.0 }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Wait before attempt `attempt`, counting from 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let ms = self.initial_ms as f64 * self.multiplier.max(1.0).powi(attempt.saturating_sub(1) as i32);
+        Duration::from_millis(ms.min(self.max_interval_ms.max(self.initial_ms) as f64) as u64)
+    }
+
+    pub fn exhausted(&self, attempt: u32) -> bool {
+        self.max_attempts > 0 && attempt > self.max_attempts
+    }
+}
+
+/// How a session was last opened, to open it the same way again.
+#[derive(Debug, Clone)]
+pub enum Reopen {
+    Serial { port: String, baud: u32, settings: SerialSettings, name: String },
+    Tcp(Box<SocketOpenArgs>),
+    Ssh(Box<SshOptions>),
+}
@@ -0,0 +1,486 @@
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::AppHandle;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::handshake::server::{Callback, ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::{header, StatusCode};
+use tokio_tungstenite::tungstenite::Message;
+use crate::journal::{self, Entry};
+use crate::payload::PayloadFormat;
+use crate::profile::BenchConnection;
+use crate::state::now_ms;
+
+/// Audit log in the app data directory, one JSON entry per line.
+const AUDIT_FILE: &str = "remote_audit.jsonl";
+
+/// What a token lets a client do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// List sessions and stream events.
+    Read,
+    /// Send data on open sessions.
+    Tx,
+    /// Open and close connections.
+    Open,
+}
+
+impl Scope {
+    const ALL: [Scope; 3] = [Scope::Read, Scope::Tx, Scope::Open];
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    /// Recorded in the audit log in place of the secret.
+    pub name: String,
+    /// Sent by clients as `Authorization: Bearer <secret>`, or as `?token=`
+    /// where headers can't be set.
+    pub secret: String,
+    pub scopes: Vec<Scope>,
+}
+
+/// A token as reported back, without its secret.
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenInfo {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+/// Options for the WebSocket control API used by lab automation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteOptions {
+    /// Loopback unless set otherwise. Any other address needs at least one
+    /// token; without tokens, loopback clients get every scope. The API is
+    /// plain `ws://`, so off loopback tokens and traffic cross the network
+    /// unencrypted; use a trusted network or an SSH or TLS tunnel.
+    pub bind: String,
+    /// 0 picks a free port.
+    pub port: u16,
+    pub tokens: Vec<ApiToken>,
+}
+
+impl Default for RemoteOptions {
+    fn default() -> Self {
+        Self { bind: "127.0.0.1".into(), port: 0, tokens: Vec::new() }
+    }
+}
+
+/// A request from a client, as a JSON text frame: `{"id": 1, "op": "send",
+/// ...}`. The reply carries the same `id`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum RemoteOp {
+    Sessions,
+    /// This instance's clock, for a client estimating its offset.
+    Clock,
+    /// Stream every emitted event, or only those of one session, as
+    /// `{"event": ..., "seq": ..., "payload": ...}` frames.
+    Subscribe {
+        #[serde(default)]
+        session_id: Option<String>,
+    },
+    Unsubscribe,
+    /// Send `data` with the session's TX append mode.
+    Send {
+        session_id: String,
+        data: String,
+        #[serde(default)]
+        format: PayloadFormat,
+        /// Resolve C-style escapes in text.
+        #[serde(default)]
+        escape: bool,
+    },
+    Open { connection: BenchConnection },
+    Close { session_id: String },
+}
+
+impl RemoteOp {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Sessions => "sessions",
+            Self::Clock => "clock",
+            Self::Subscribe { .. } => "subscribe",
+            Self::Unsubscribe => "unsubscribe",
+            Self::Send { .. } => "send",
+            Self::Open { .. } => "open",
+            Self::Close { .. } => "close",
+        }
+    }
+
+    fn scope(&self) -> Scope {
+        match self {
+            Self::Sessions | Self::Clock | Self::Subscribe { .. } | Self::Unsubscribe => Scope::Read,
+            Self::Send { .. } => Scope::Tx,
+            Self::Open { .. } | Self::Close { .. } => Scope::Open,
+        }
+    }
+
+    fn session_id(&self) -> Option<String> {
+        match self {
+            Self::Subscribe { session_id } => session_id.clone(),
+            Self::Send { session_id, .. } | Self::Close { session_id } => Some(session_id.clone()),
+            Self::Open { connection } => Some(connection.session_id()),
+            Self::Sessions | Self::Clock | Self::Unsubscribe => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RemoteRequest {
+    #[serde(default)]
+    id: Value,
+    #[serde(flatten)]
+    op: RemoteOp,
+}
+
+/// Carries out the ops other than subscribing, with the app's state.
+pub type Dispatch = Arc<dyn Fn(RemoteOp) -> Pin<Box<dyn Future<Output = Result<Value, String>> + Send>> + Send + Sync>;
+
+/// One remote command or refused connection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp_ms: f64,
+    pub peer: String,
+    /// Name of the token used; `None` when no tokens are configured or none
+    /// was accepted.
+    pub token: Option<String>,
+    /// The op, "connect" for a refused connection or "invalid" for a request
+    /// that could not be parsed.
+    pub op: String,
+    pub session_id: Option<String>,
+    /// False when the token lacked the scope or the connection was refused.
+    pub allowed: bool,
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    fn new(peer: SocketAddr, token: Option<&str>, op: &str) -> Self {
+        Self {
+            timestamp_ms: now_ms(),
+            peer: peer.to_string(),
+            token: token.map(String::from),
+            op: op.to_string(),
+            session_id: None,
+            allowed: true,
+            error: None,
+        }
+    }
+}
+
+/// Append `entries` to the audit log in `dir`.
+fn record_audit(dir: &Path, entries: &[AuditEntry]) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(AUDIT_FILE))
+        .map_err(|e| e.to_string())?;
+    for entry in entries {
+        let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        writeln!(file, "{line}").map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Start a thread appending the entries sent to it to the audit log in
+/// `dir`, so requests don't wait on the disk. It writes what is queued and
+/// stops once every sender is gone.
+pub fn audit_writer(dir: PathBuf) -> Result<std::sync::mpsc::Sender<AuditEntry>, String> {
+    let (entries, queued) = std::sync::mpsc::channel::<AuditEntry>();
+    std::thread::Builder::new()
+        .name("remote audit".into())
+        .spawn(move || {
+            while let Ok(first) = queued.recv() {
+                let batch: Vec<AuditEntry> = std::iter::once(first).chain(queued.try_iter()).collect();
+                let _ = record_audit(&dir, &batch);
+            }
+        })
+        .map_err(|e| format!("Cannot start the audit log writer: {e}"))?;
+    Ok(entries)
+}
+
+/// The last `limit` entries of the audit log in `dir`, oldest first.
+pub fn read_audit(dir: &Path, limit: usize) -> Result<Vec<AuditEntry>, String> {
+    let file = match std::fs::File::open(dir.join(AUDIT_FILE)) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.to_string()),
+    };
+    let entries: Vec<AuditEntry> = BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect();
+    Ok(entries[entries.len().saturating_sub(limit)..].to_vec())
+}
+
+/// What an accepted client may do.
+struct Grant {
+    token: Option<String>,
+    scopes: Vec<Scope>,
+}
+
+pub struct RemoteServer {
+    pub local_addr: SocketAddr,
+    pub options: RemoteOptions,
+    clients: Arc<AtomicUsize>,
+    shutdown: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl RemoteServer {
+    pub fn clients(&self) -> usize {
+        self.clients.load(Ordering::Acquire)
+    }
+
+    pub fn tokens(&self) -> Vec<TokenInfo> {
+        self.options.tokens.iter().map(|t| TokenInfo { name: t.name.clone(), scopes: t.scopes.clone() }).collect()
+    }
+}
+
+impl Drop for RemoteServer {
+    /// Stops accepting and disconnects every client, so stopping the API
+    /// revokes access right away.
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = self.shutdown.send(true);
+    }
+}
+
+pub async fn start(
+    app: AppHandle,
+    options: RemoteOptions,
+    dispatch: Dispatch,
+    on_audit: impl Fn(AuditEntry) + Send + Sync + 'static,
+) -> Result<RemoteServer, String> {
+    if let Some(token) = options.tokens.iter().find(|t| t.secret.is_empty()) {
+        return Err(format!("Token {} has no secret", token.name));
+    }
+    let listener = TcpListener::bind((options.bind.as_str(), options.port)).await.map_err(|e| e.to_string())?;
+    let local_addr = listener.local_addr().map_err(|e| e.to_string())?;
+    if !local_addr.ip().is_loopback() && options.tokens.is_empty() {
+        return Err(format!("Listening on {local_addr} needs at least one token; use a loopback address otherwise"));
+    }
+    let clients = Arc::new(AtomicUsize::new(0));
+    let (shutdown, stop) = watch::channel(false);
+    let on_audit = Arc::new(on_audit);
+
+    let (tokens, count) = (options.tokens.clone(), Arc::clone(&clients));
+    let task = tokio::spawn(async move {
+        let tokens = Arc::new(tokens);
+        loop {
+            let Ok((stream, peer)) = listener.accept().await else {
+                // Usually out of descriptors; don't spin on it
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                continue;
+            };
+            let (app, dispatch, on_audit, tokens, count, stop) =
+                (app.clone(), Arc::clone(&dispatch), Arc::clone(&on_audit), Arc::clone(&tokens), Arc::clone(&count), stop.clone());
+            tokio::spawn(async move {
+                let (ws, grant) = match handshake(stream, &tokens).await {
+                    Ok(accepted) => accepted,
+                    Err(reason) => {
+                        on_audit(AuditEntry { allowed: false, error: Some(reason), ..AuditEntry::new(peer, None, "connect") });
+                        return;
+                    }
+                };
+                count.fetch_add(1, Ordering::AcqRel);
+                let client = Client { peer, grant, app: &app, dispatch: &dispatch, on_audit: &*on_audit, events: None, filter: None };
+                client.serve(ws, stop).await;
+                count.fetch_sub(1, Ordering::AcqRel);
+            });
+        }
+    });
+
+    Ok(RemoteServer { local_addr, options, clients, shutdown, task })
+}
+
+/// Compare without stopping at the first differing byte, so response times
+/// don't reveal how much of a guess was right.
+fn secret_eq(a: &str, b: &str) -> bool {
+    crate::server::same_secret(a.as_bytes(), b.as_bytes())
+}
+
+fn presented_token(req: &Request) -> Option<String> {
+    let bearer = req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v.trim().to_string());
+    bearer.or_else(|| {
+        req.uri().query()?.split('&').find_map(|pair| pair.strip_prefix("token=")).map(percent_decode)
+    })
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).filter(|h| bytes[i] == b'%' && h.iter().all(u8::is_ascii_hexdigit));
+        match hex.and_then(|h| u8::from_str_radix(std::str::from_utf8(h).ok()?, 16).ok()) {
+            Some(b) => {
+                out.push(b);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+type Ws = tokio_tungstenite::WebSocketStream<TcpStream>;
+
+/// Admits a client during the WebSocket handshake if it presents a known
+/// token. With no tokens configured any client is admitted except browser
+/// pages, which send an `Origin` header: otherwise every web page open in
+/// the user's browser could drive the local API.
+struct Auth<'a> {
+    tokens: &'a [ApiToken],
+    grant: &'a mut Option<Grant>,
+}
+
+impl Callback for Auth<'_> {
+    fn on_request(self, req: &Request, resp: Response) -> Result<Response, ErrorResponse> {
+        if self.tokens.is_empty() {
+            if req.headers().contains_key(header::ORIGIN) {
+                let mut refusal = ErrorResponse::new(Some("Browser clients need a token".into()));
+                *refusal.status_mut() = StatusCode::FORBIDDEN;
+                return Err(refusal);
+            }
+            *self.grant = Some(Grant { token: None, scopes: Scope::ALL.to_vec() });
+            return Ok(resp);
+        }
+        let presented = presented_token(req).unwrap_or_default();
+        match self.tokens.iter().find(|t| secret_eq(&t.secret, &presented)) {
+            Some(token) => {
+                *self.grant = Some(Grant { token: Some(token.name.clone()), scopes: token.scopes.clone() });
+                Ok(resp)
+            }
+            None => {
+                let mut refusal = ErrorResponse::new(Some("Missing or unknown token".into()));
+                *refusal.status_mut() = StatusCode::UNAUTHORIZED;
+                Err(refusal)
+            }
+        }
+    }
+}
+
+async fn handshake(stream: TcpStream, tokens: &[ApiToken]) -> Result<(Ws, Grant), String> {
+    let mut grant = None;
+    let ws = tokio_tungstenite::accept_hdr_async(stream, Auth { tokens, grant: &mut grant }).await.map_err(|e| match e {
+        tokio_tungstenite::tungstenite::Error::Http(resp) => resp.body()
+            .as_deref()
+            .map_or("Handshake refused".to_string(), |body| String::from_utf8_lossy(body).into_owned()),
+        e => e.to_string(),
+    })?;
+    Ok((ws, grant.ok_or("Handshake failed")?))
+}
+
+/// Wait for the next event, or forever when not subscribed.
+async fn next_event(events: &mut Option<broadcast::Receiver<Entry>>) -> Result<Entry, broadcast::error::RecvError> {
+    match events {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// One accepted client.
+struct Client<'a> {
+    peer: SocketAddr,
+    grant: Grant,
+    app: &'a AppHandle,
+    dispatch: &'a Dispatch,
+    on_audit: &'a (dyn Fn(AuditEntry) + Send + Sync),
+    events: Option<broadcast::Receiver<Entry>>,
+    /// Session whose events are streamed; all when unset.
+    filter: Option<String>,
+}
+
+impl Client<'_> {
+    async fn serve(mut self, mut ws: Ws, mut stop: watch::Receiver<bool>) {
+        loop {
+            let frame = tokio::select! {
+                msg = ws.next() => match msg {
+                    Some(Ok(Message::Text(text))) => self.handle(text.as_str()).await.to_string(),
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                },
+                event = next_event(&mut self.events) => match event {
+                    Ok(entry) => {
+                        let session = entry.payload.get("session_id").and_then(Value::as_str);
+                        if self.filter.as_deref().is_some_and(|f| session != Some(f)) {
+                            continue;
+                        }
+                        json!(entry).to_string()
+                    }
+                    // The client reads slower than events arrive; it can catch up with `resync`
+                    Err(broadcast::error::RecvError::Lagged(missed)) => json!({ "event": "lagged", "missed": missed }).to_string(),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = stop.changed() => break,
+            };
+            if ws.send(Message::text(frame)).await.is_err() {
+                break;
+            }
+        }
+        let _ = ws.close(None).await;
+    }
+
+    async fn handle(&mut self, text: &str) -> Value {
+        let mut audit = AuditEntry::new(self.peer, self.grant.token.as_deref(), "invalid");
+        let request: RemoteRequest = match serde_json::from_str(text) {
+            Ok(request) => request,
+            Err(e) => {
+                audit.error = Some(e.to_string());
+                (self.on_audit)(audit);
+                return json!({ "id": Value::Null, "ok": false, "error": e.to_string() });
+            }
+        };
+        audit.op = request.op.name().to_string();
+        audit.session_id = request.op.session_id();
+        let scope = request.op.scope();
+        let clock = matches!(request.op, RemoteOp::Clock);
+        let result = if !self.grant.scopes.contains(&scope) {
+            audit.allowed = false;
+            Err(format!("Token lacks the {} scope", json!(scope).as_str().unwrap_or_default()))
+        } else {
+            match request.op {
+                RemoteOp::Subscribe { session_id } => {
+                    self.events = Some(journal::subscribe(self.app));
+                    self.filter = session_id;
+                    Ok(Value::Null)
+                }
+                RemoteOp::Unsubscribe => {
+                    self.events = None;
+                    Ok(Value::Null)
+                }
+                op => (self.dispatch)(op).await,
+            }
+        };
+        audit.error = result.as_ref().err().cloned();
+        // Attached agents are polled for their clock every few seconds;
+        // only the polls that went wrong are worth keeping
+        if !clock || !audit.allowed || audit.error.is_some() {
+            (self.on_audit)(audit);
+        }
+        match result {
+            Ok(result) => json!({ "id": request.id, "ok": true, "result": result }),
+            Err(error) => json!({ "id": request.id, "ok": false, "error": error }),
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+use crate::serial_port::Flow;
+
+/// The telnet option of RFC 2217.
+pub const COM_PORT_OPTION: u8 = 44;
+
+const IAC: u8 = 255;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+const SET_BAUDRATE: u8 = 1;
+const SET_DATASIZE: u8 = 2;
+const SET_PARITY: u8 = 3;
+const SET_STOPSIZE: u8 = 4;
+const SET_CONTROL: u8 = 5;
+const NOTIFY_LINESTATE: u8 = 6;
+const NOTIFY_MODEMSTATE: u8 = 7;
+const PURGE_DATA: u8 = 12;
+/// A server answers a command with the command's code plus this.
+const SERVER_OFFSET: u8 = 100;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Parity {
+    #[default]
+    None,
+    Odd,
+    Even,
+    Mark,
+    Space,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopBits {
+    #[default]
+    One,
+    Two,
+    OnePointFive,
+}
+
+/// Line settings of a serial port behind an RFC 2217 terminal server, sent
+/// once the server agrees to COM-PORT-OPTION.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ComPortOptions {
+    pub baud: u32,
+    /// 5 to 8.
+    pub data_bits: u8,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    /// Flow control done by the server.
+    pub flow: Flow,
+}
+
+impl Default for ComPortOptions {
+    fn default() -> Self {
+        Self { baud: 115_200, data_bits: 8, parity: Parity::None, stop_bits: StopBits::One, flow: Flow::None }
+    }
+}
+
+impl ComPortOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.baud == 0 {
+            return Err("Baud rate must be positive".into());
+        }
+        if !(5..=8).contains(&self.data_bits) {
+            return Err("Data bits must be 5 to 8".into());
+        }
+        Ok(())
+    }
+
+    /// The subnegotiations that apply these settings.
+    pub fn commands(&self) -> Vec<u8> {
+        let parity = match self.parity {
+            Parity::None => 1,
+            Parity::Odd => 2,
+            Parity::Even => 3,
+            Parity::Mark => 4,
+            Parity::Space => 5,
+        };
+        let stop = match self.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+            StopBits::OnePointFive => 3,
+        };
+        let flow = match self.flow {
+            Flow::None => 1,
+            Flow::Software => 2,
+            Flow::Hardware => 3,
+        };
+        let mut out = set_baud(self.baud);
+        out.extend(command(SET_DATASIZE, &[self.data_bits]));
+        out.extend(command(SET_PARITY, &[parity]));
+        out.extend(command(SET_STOPSIZE, &[stop]));
+        out.extend(command(SET_CONTROL, &[flow]));
+        out
+    }
+}
+
+pub fn set_baud(baud: u32) -> Vec<u8> {
+    command(SET_BAUDRATE, &baud.to_be_bytes())
+}
+
+/// Drive DTR and RTS on the server's port; unset lines are left alone.
+pub fn set_lines(dtr: Option<bool>, rts: Option<bool>) -> Vec<u8> {
+    let mut out = Vec::new();
+    if let Some(on) = dtr {
+        out.extend(command(SET_CONTROL, &[if on { 8 } else { 9 }]));
+    }
+    if let Some(on) = rts {
+        out.extend(command(SET_CONTROL, &[if on { 11 } else { 12 }]));
+    }
+    out
+}
+
+pub fn set_break(on: bool) -> Vec<u8> {
+    command(SET_CONTROL, &[if on { 5 } else { 6 }])
+}
+
+/// Drop what the server has buffered in both directions.
+pub fn purge() -> Vec<u8> {
+    command(PURGE_DATA, &[3])
+}
+
+fn command(code: u8, value: &[u8]) -> Vec<u8> {
+    let mut out = vec![IAC, SB, COM_PORT_OPTION, code];
+    for &b in value {
+        out.push(b);
+        if b == IAC {
+            out.push(IAC);
+        }
+    }
+    out.extend_from_slice(&[IAC, SE]);
+    out
+}
+
+/// What the server reported in a COM-PORT-OPTION subnegotiation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notification {
+    /// The server's answer to a command, with the value it applied.
+    Setting { name: &'static str, value: String },
+    /// Errors and breaks on the line, by name.
+    LineState(Vec<&'static str>),
+    /// Modem status inputs.
+    ModemState { cts: bool, dsr: bool, ri: bool, cd: bool },
+}
+
+/// A subnegotiation's payload after the option byte, unescaped.
+pub fn parse(payload: &[u8]) -> Option<Notification> {
+    let (&code, value) = payload.split_first()?;
+    let byte = value.first().copied();
+    let setting = |name, value: String| Some(Notification::Setting { name, value });
+    match code.checked_sub(SERVER_OFFSET)? {
+        SET_BAUDRATE => setting("baud", u32::from_be_bytes(value.try_into().ok()?).to_string()),
+        SET_DATASIZE => setting("data_bits", byte?.to_string()),
+        SET_PARITY => setting("parity", ["?", "none", "odd", "even", "mark", "space"].get(byte? as usize).unwrap_or(&"?").to_string()),
+        SET_STOPSIZE => setting("stop_bits", ["?", "1", "2", "1.5"].get(byte? as usize).unwrap_or(&"?").to_string()),
+        SET_CONTROL => {
+            let (name, value) = match byte? {
+                1 => ("flow", "none"),
+                2 => ("flow", "xon/xoff"),
+                3 => ("flow", "rts/cts"),
+                5 => ("break", "on"),
+                6 => ("break", "off"),
+                8 => ("dtr", "on"),
+                9 => ("dtr", "off"),
+                11 => ("rts", "on"),
+                12 => ("rts", "off"),
+                _ => return None,
+            };
+            setting(name, value.into())
+        }
+        NOTIFY_LINESTATE => {
+            let state = byte?;
+            let flags = [(0x10, "break"), (0x08, "framing"), (0x04, "parity"), (0x02, "overrun")];
+            Some(Notification::LineState(flags.iter().filter(|(bit, _)| state & bit != 0).map(|(_, name)| *name).collect()))
+        }
+        NOTIFY_MODEMSTATE => {
+            let state = byte?;
+            Some(Notification::ModemState { cts: state & 0x10 != 0, dsr: state & 0x20 != 0, ri: state & 0x40 != 0, cd: state & 0x80 != 0 })
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::state::Packet;
+
+/// Where a session's packets are cut into segments. A segment ends as soon
+/// as either limit is reached.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SegmentConfig {
+    /// Packets (lines, with line framing) per segment.
+    pub packets: Option<u64>,
+    /// Payload bytes, both directions, per segment.
+    pub bytes: Option<u64>,
+}
+
+/// Summary of one segment.
+#[derive(Debug, Clone, Serialize)]
+pub struct Segment {
+    pub session_id: String,
+    /// 0-based, in order.
+    pub index: u64,
+    pub first_packet_id: u64,
+    pub last_packet_id: u64,
+    pub start_ms: f64,
+    pub end_ms: f64,
+    pub packets: u64,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub bad_checksums: u64,
+    /// Packet count per classification severity.
+    pub severities: BTreeMap<String, u64>,
+    /// False for the segment still being filled.
+    pub complete: bool,
+}
+
+pub struct Segmenter {
+    config: SegmentConfig,
+    done: Vec<Segment>,
+    current: Option<Segment>,
+}
+
+impl Segmenter {
+    pub fn new(config: SegmentConfig) -> Result<Self, String> {
+        if config.packets.is_none_or(|n| n == 0) && config.bytes.is_none_or(|n| n == 0) {
+            return Err("A packet or byte limit is required".into());
+        }
+        Ok(Self { config, done: Vec::new(), current: None })
+    }
+
+    /// Count `pkt` into the current segment. Returns the segment when this
+    /// packet completes it.
+    pub fn record(&mut self, pkt: &Packet) -> Option<Segment> {
+        let index = self.done.len() as u64;
+        let seg = self.current.get_or_insert_with(|| Segment {
+            session_id: pkt.session_id.clone(),
+            index,
+            first_packet_id: pkt.id,
+            last_packet_id: pkt.id,
+            start_ms: pkt.timestamp_ms,
+            end_ms: pkt.timestamp_ms,
+            packets: 0,
+            rx_bytes: 0,
+            tx_bytes: 0,
+            bad_checksums: 0,
+            severities: BTreeMap::new(),
+            complete: false,
+        });
+        seg.last_packet_id = pkt.id;
+        seg.end_ms = pkt.timestamp_ms;
+        seg.packets += 1;
+        match pkt.direction.as_str() {
+            "TX" => seg.tx_bytes += pkt.bytes.len() as u64,
+            _ => seg.rx_bytes += pkt.bytes.len() as u64,
+        }
+        if pkt.checksum_ok == Some(false) {
+            seg.bad_checksums += 1;
+        }
+        if let Some(sev) = &pkt.severity {
+            *seg.severities.entry(sev.clone()).or_default() += 1;
+        }
+
+        let full = self.config.packets.is_some_and(|n| n > 0 && seg.packets >= n)
+            || self.config.bytes.is_some_and(|n| n > 0 && seg.rx_bytes + seg.tx_bytes >= n);
+        if !full {
+            return None;
+        }
+        let mut seg = self.current.take()?;
+        seg.complete = true;
+        self.done.push(seg.clone());
+        Some(seg)
+    }
+
+    /// Completed segments followed by the one being filled, if any.
+    pub fn segments(&self) -> Vec<Segment> {
+        self.done.iter().chain(&self.current).cloned().collect()
+    }
+
+    pub fn get(&self, index: u64) -> Option<&Segment> {
+        self.done.get(index as usize).or(self.current.as_ref().filter(|s| s.index == index))
+    }
+}
@@ -1,7 +1,198 @@
-use serialport::SerialPort;
-use std::time::Duration;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serialport::{FlowControl, SerialPort};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc::{self, UnboundedSender, UnboundedReceiver};
 use tokio::task;
+use crate::outgoing::Outgoing;
+use crate::priority::WorkerOptions;
+use crate::state::now_ms;
+
+const XON: u8 = 0x11;
+const XOFF: u8 = 0x13;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Flow {
+    #[default]
+    None,
+    /// RTS/CTS, handled by the driver; CTS is polled to report stalls.
+    Hardware,
+    /// XON/XOFF, handled here so pauses can be reported. The control bytes
+    /// are removed from the RX stream.
+    Software,
+}
+
+impl Flow {
+    fn driver(self) -> FlowControl {
+        if self == Flow::Hardware { FlowControl::Hardware } else { FlowControl::None }
+    }
+}
+
+/// Settings that can be changed while the port stays open.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SerialSettings {
+    pub flow: Flow,
+    /// Hand TX bytes straight back as RX instead of writing them to the device.
+    pub loopback: bool,
+    /// How long a read waits for data; also the CTS polling interval.
+    /// Short for interactive use, long for low-CPU background logging.
+    pub read_timeout_ms: u64,
+    /// How often a writer held by XOFF checks whether it may resume.
+    pub poll_interval_ms: u64,
+    /// Scheduling of the reader thread. Fixed once the port is open.
+    pub worker: WorkerOptions,
+}
+
+impl Default for SerialSettings {
+    fn default() -> Self {
+        Self { flow: Flow::None, loopback: false, read_timeout_ms: 10, poll_interval_ms: 1, worker: WorkerOptions::default() }
+    }
+}
+
+/// Accepted `read_timeout_ms`.
+pub const READ_TIMEOUT_MS: std::ops::RangeInclusive<u64> = 1..=1000;
+/// Accepted `poll_interval_ms`.
+pub const POLL_INTERVAL_MS: std::ops::RangeInclusive<u64> = 1..=100;
+
+impl SerialSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !READ_TIMEOUT_MS.contains(&self.read_timeout_ms) {
+            return Err(format!("read_timeout_ms must be {} to {}", READ_TIMEOUT_MS.start(), READ_TIMEOUT_MS.end()));
+        }
+        if !POLL_INTERVAL_MS.contains(&self.poll_interval_ms) {
+            return Err(format!("poll_interval_ms must be {} to {}", POLL_INTERVAL_MS.start(), POLL_INTERVAL_MS.end()));
+        }
+        Ok(())
+    }
+}
+
+/// Transmission paused or resumed by the peer.
+#[derive(Debug, Clone, Serialize)]
+pub struct FlowChange {
+    /// "cts" or "xoff".
+    pub signal: &'static str,
+    pub paused: bool,
+    /// How long transmission was held, on resume.
+    pub paused_ms: Option<f64>,
+}
+
+/// A modem status input, e.g. wired to a GNSS receiver's PPS output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModemLine {
+    #[default]
+    Dcd,
+    Cts,
+    Dsr,
+    Ri,
+}
+
+impl ModemLine {
+    fn read(self, port: &mut dyn SerialPort) -> serialport::Result<bool> {
+        match self {
+            ModemLine::Dcd => port.read_carrier_detect(),
+            ModemLine::Cts => port.read_clear_to_send(),
+            ModemLine::Dsr => port.read_data_set_ready(),
+            ModemLine::Ri => port.read_ring_indicator(),
+        }
+    }
+}
+
+/// Stops watching a modem line when dropped.
+pub struct LineWatch {
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for LineWatch {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+    }
+}
+
+/// Receive errors counted by the driver since the port was opened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct LineErrors {
+    /// False where the platform or driver doesn't report the counts.
+    pub supported: bool,
+    pub framing: u64,
+    pub parity: u64,
+    pub breaks: u64,
+    /// Characters lost in the UART.
+    pub overruns: u64,
+    /// Characters lost because the driver's buffer was full.
+    pub buffer_overruns: u64,
+}
+
+/// Line error counts via TIOCGICOUNT.
+#[cfg(target_os = "linux")]
+mod icount {
+    use std::os::fd::{AsRawFd, BorrowedFd, OwnedFd};
+    use super::LineErrors;
+
+    /// `struct serial_icounter_struct` from linux/serial.h.
+    #[repr(C)]
+    #[derive(Default)]
+    struct SerialIcounter {
+        cts: i32,
+        dsr: i32,
+        rng: i32,
+        dcd: i32,
+        rx: i32,
+        tx: i32,
+        frame: i32,
+        overrun: i32,
+        parity: i32,
+        brk: i32,
+        buf_overrun: i32,
+        reserved: [i32; 9],
+    }
+
+    pub struct Counter {
+        fd: OwnedFd,
+        base: LineErrors,
+    }
+
+    fn read(fd: &OwnedFd) -> Option<LineErrors> {
+        let mut ic = SerialIcounter::default();
+        // SAFETY: the fd is open and `ic` matches the kernel's layout
+        let rc = unsafe { libc::ioctl(fd.as_raw_fd(), libc::TIOCGICOUNT, &mut ic) };
+        (rc == 0).then_some(LineErrors {
+            supported: true,
+            framing: ic.frame as u64,
+            parity: ic.parity as u64,
+            breaks: ic.brk as u64,
+            overruns: ic.overrun as u64,
+            buffer_overruns: ic.buf_overrun as u64,
+        })
+    }
+
+    impl Counter {
+        /// None if the driver doesn't keep counts.
+        pub fn new(port: &impl AsRawFd) -> Option<Self> {
+            // SAFETY: the port outlives this call; the duplicate is owned from here on
+            let fd = unsafe { BorrowedFd::borrow_raw(port.as_raw_fd()) }.try_clone_to_owned().ok()?;
+            // The kernel counts over the device's lifetime; report from now on
+            let base = read(&fd)?;
+            Some(Self { fd, base })
+        }
+
+        pub fn read(&self) -> LineErrors {
+            let Some(now) = read(&self.fd) else { return LineErrors::default() };
+            LineErrors {
+                supported: true,
+                framing: now.framing.saturating_sub(self.base.framing),
+                parity: now.parity.saturating_sub(self.base.parity),
+                breaks: now.breaks.saturating_sub(self.base.breaks),
+                overruns: now.overruns.saturating_sub(self.base.overruns),
+                buffer_overruns: now.buffer_overruns.saturating_sub(self.base.buffer_overruns),
+            }
+        }
+    }
+}
 
 pub fn list_ports() -> Vec<String> {
     serialport::available_ports()
@@ -11,34 +202,270 @@ pub fn list_ports() -> Vec<String> {
         .collect()
 }
 
+/// Keep retrying a connect until the port enumerates.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WaitForPort {
+    pub timeout_ms: u64,
+    pub interval_ms: u64,
+    /// Match a USB device instead of the port name.
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+}
+
+impl Default for WaitForPort {
+    fn default() -> Self {
+        Self { timeout_ms: 30_000, interval_ms: 200, vid: None, pid: None }
+    }
+}
+
+/// Name of the port matching `wait`'s VID/PID, or `name` itself, if it is
+/// currently enumerated.
+pub fn find_port(name: &str, wait: &WaitForPort) -> Option<String> {
+    let ports = serialport::available_ports().ok()?;
+    ports.into_iter()
+        .find(|p| match (&p.port_type, wait.vid) {
+            (serialport::SerialPortType::UsbPort(usb), Some(vid)) => {
+                usb.vid == vid && wait.pid.is_none_or(|pid| usb.pid == pid)
+            }
+            (_, Some(_)) => false,
+            (_, None) => p.port_name == name,
+        })
+        .map(|p| p.port_name)
+}
+
 pub struct SerialConnection {
-    pub tx: UnboundedSender<Vec<u8>>,
+    pub tx: UnboundedSender<Outgoing>,
+    pub control: SerialControl,
+}
+
+/// Handle for changing an open port's settings.
+pub struct SerialControl {
+    port: Mutex<Box<dyn SerialPort>>,
+    shared: Arc<Shared>,
+    #[cfg(target_os = "linux")]
+    errors: Option<icount::Counter>,
+    /// End with the reader and writer threads.
+    workers: Vec<task::JoinHandle<()>>,
+}
+
+/// Settings read by the reader and writer threads.
+struct Shared {
+    settings: Mutex<SerialSettings>,
+    /// Set when `settings` changed and the reader has not picked them up yet.
+    changed: AtomicBool,
+    /// Set when the port was closed on purpose; the threads let go of it.
+    closed: AtomicBool,
+}
+
+impl SerialControl {
+    pub fn settings(&self) -> SerialSettings {
+        *self.shared.settings.lock()
+    }
+
+    /// Apply `settings` without closing the port. The driver flow control is
+    /// switched here; the I/O threads pick up the rest on their next pass.
+    pub fn apply(&self, settings: SerialSettings) -> Result<(), String> {
+        settings.validate()?;
+        if settings.worker != self.shared.settings.lock().worker {
+            return Err("Thread priority and core can only be set when opening the port".into());
+        }
+        self.port.lock().set_flow_control(settings.flow.driver()).map_err(|e| e.to_string())?;
+        *self.shared.settings.lock() = settings;
+        self.shared.changed.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn line_errors(&self) -> LineErrors {
+        #[cfg(target_os = "linux")]
+        if let Some(counter) = &self.errors {
+            return counter.read();
+        }
+        LineErrors::default()
+    }
+
+    pub fn baud_rate(&self) -> Result<u32, String> {
+        self.port.lock().baud_rate().map_err(|e| e.to_string())
+    }
+
+    pub fn set_baud_rate(&self, baud: u32) -> Result<(), String> {
+        self.port.lock().set_baud_rate(baud).map_err(|e| e.to_string())
+    }
+
+    /// Poll `line` every `poll_us` microseconds on a thread of its own and
+    /// call `on_change(timestamp_ms, asserted)` on each transition, until the
+    /// watch is dropped or the port closes. A change is timestamped when it
+    /// is seen, up to one poll interval after it happened.
+    pub fn watch_line(&self, line: ModemLine, poll_us: u64, on_change: impl Fn(f64, bool) + Send + 'static) -> Result<LineWatch, String> {
+        let mut port = self.port.lock().try_clone().map_err(|e| e.to_string())?;
+        let mut level = line.read(&mut *port).map_err(|e| e.to_string())?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let (stopped, shared) = (Arc::clone(&stop), Arc::clone(&self.shared));
+        std::thread::spawn(move || {
+            while !stopped.load(Ordering::Acquire) && !shared.closed.load(Ordering::Acquire) {
+                std::thread::sleep(Duration::from_micros(poll_us));
+                let Ok(now) = line.read(&mut *port) else { break };
+                if now != level {
+                    on_change(now_ms(), now);
+                    level = now;
+                }
+            }
+        });
+        Ok(LineWatch { stop })
+    }
+
+    /// Take the reader and writer threads, to wait for the port to be
+    /// released. Both stop once this control and the session's sender are
+    /// dropped.
+    pub fn take_workers(&mut self) -> Vec<task::JoinHandle<()>> {
+        std::mem::take(&mut self.workers)
+    }
+}
+
+impl Drop for SerialControl {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+    }
 }
 
+/// `on_close` runs when reading fails, e.g. because the device was unplugged.
+/// Opening fails if `settings.worker` can't be applied to the reader thread.
 pub fn open(
     port_name: String,
     baud_rate: u32,
+    settings: SerialSettings,
     on_data: impl Fn(Vec<u8>) + Send + 'static,
+    on_flow: impl Fn(FlowChange) + Send + 'static,
+    on_close: impl FnOnce() + Send + 'static,
 ) -> Result<SerialConnection, String> {
-    let port = serialport::new(&port_name, baud_rate)
-        .timeout(Duration::from_millis(10))
-        .open()
-        .map_err(|e| e.to_string())?;
+    settings.validate()?;
+    let builder = serialport::new(&port_name, baud_rate)
+        .timeout(Duration::from_millis(settings.read_timeout_ms))
+        .flow_control(settings.flow.driver());
+    #[cfg(target_os = "linux")]
+    let (port, errors) = {
+        let native = builder.open_native().map_err(|e| e.to_string())?;
+        let errors = icount::Counter::new(&native);
+        (Box::new(native) as Box<dyn SerialPort>, errors)
+    };
+    #[cfg(not(target_os = "linux"))]
+    let port = builder.open().map_err(|e| e.to_string())?;
 
-    let (tx, rx): (UnboundedSender<Vec<u8>>, UnboundedReceiver<Vec<u8>>) = mpsc::unbounded_channel();
+    let (tx, rx): (UnboundedSender<Outgoing>, UnboundedReceiver<Outgoing>) = mpsc::unbounded_channel();
+    let (loop_tx, loop_rx) = std::sync::mpsc::channel();
 
     let port_clone = port.try_clone().map_err(|e| e.to_string())?;
+    let control_port = port.try_clone().map_err(|e| e.to_string())?;
+    let shared = Arc::new(Shared { settings: Mutex::new(settings), changed: AtomicBool::new(false), closed: AtomicBool::new(false) });
+    let xoff = Arc::new(AtomicBool::new(false));
+    let xoff_reader = Arc::clone(&xoff);
+    let shared_reader = Arc::clone(&shared);
+    let shared_writer = Arc::clone(&shared);
+
+    let (ready_tx, ready_rx) = std::sync::mpsc::sync_channel(1);
+    let (finished, reader_done) = tokio::sync::oneshot::channel::<()>();
+    // A thread of its own rather than tokio's blocking pool, whose threads
+    // would keep the priority and core after the port closes
+    std::thread::Builder::new().name(format!("serial reader {port_name}")).spawn(move || {
+        let _finished = finished;
+        match settings.worker.apply() {
+            Ok(_scheduled) => {
+                let _ = ready_tx.send(Ok(()));
+                read_loop(port_clone, shared_reader, xoff_reader, loop_rx, on_data, on_flow, on_close);
+            }
+            Err(e) => {
+                let _ = ready_tx.send(Err(e));
+            }
+        }
+    }).map_err(|e| e.to_string())?;
+    // Nothing has been read yet, and dropping `tx` stops the writer
+    ready_rx.recv().map_err(|e| e.to_string())??;
+    let reader = task::spawn(async move {
+        let _ = reader_done.await;
+    });
+    let workers = vec![reader, task::spawn_blocking(move || write_loop(port, rx, shared_writer, xoff, loop_tx))];
 
-    task::spawn_blocking(move || read_loop(port_clone, on_data));
-    task::spawn_blocking(move || write_loop(port, rx));
+    let control = SerialControl {
+        port: Mutex::new(control_port),
+        shared,
+        #[cfg(target_os = "linux")]
+        errors,
+        workers,
+    };
+    Ok(SerialConnection { tx, control })
+}
 
-    Ok(SerialConnection { tx })
+/// Tracks one pause signal and reports its transitions.
+struct Stall {
+    signal: &'static str,
+    since: Option<Instant>,
 }
 
-fn read_loop(mut port: Box<dyn SerialPort>, on_data: impl Fn(Vec<u8>)) {
+impl Stall {
+    fn set(&mut self, paused: bool, on_flow: &impl Fn(FlowChange)) {
+        if paused == self.since.is_some() {
+            return;
+        }
+        let paused_ms = if paused {
+            self.since = Some(Instant::now());
+            None
+        } else {
+            self.since.take().map(|t| t.elapsed().as_secs_f64() * 1000.0)
+        };
+        on_flow(FlowChange { signal: self.signal, paused, paused_ms });
+    }
+}
+
+fn read_loop(
+    mut port: Box<dyn SerialPort>,
+    shared: Arc<Shared>,
+    xoff: Arc<AtomicBool>,
+    looped: std::sync::mpsc::Receiver<Vec<u8>>,
+    on_data: impl Fn(Vec<u8>),
+    on_flow: impl Fn(FlowChange),
+    on_close: impl FnOnce(),
+) {
     let mut buf = [0u8; 4096];
-    loop {
+    let mut cts = Stall { signal: "cts", since: None };
+    let mut xoff_stall = Stall { signal: "xoff", since: None };
+    let mut flow = shared.settings.lock().flow;
+    while !shared.closed.load(Ordering::Acquire) {
+        if shared.changed.swap(false, Ordering::AcqRel) {
+            let settings = *shared.settings.lock();
+            // The timeout belongs to this handle, not the device
+            let _ = port.set_timeout(Duration::from_millis(settings.read_timeout_ms));
+            flow = settings.flow;
+            if flow != Flow::Hardware {
+                cts.set(false, &on_flow);
+            }
+            if flow != Flow::Software {
+                xoff.store(false, Ordering::Release);
+                xoff_stall.set(false, &on_flow);
+            }
+        }
+        for data in looped.try_iter() {
+            on_data(data);
+        }
+        if flow == Flow::Hardware {
+            if let Ok(clear) = port.read_clear_to_send() {
+                cts.set(!clear, &on_flow);
+            }
+        }
         match port.read(&mut buf) {
+            Ok(n) if n > 0 && flow == Flow::Software => {
+                let mut data = buf[..n].to_vec();
+                data.retain(|&b| match b {
+                    XOFF | XON => {
+                        xoff.store(b == XOFF, Ordering::Release);
+                        xoff_stall.set(b == XOFF, &on_flow);
+                        false
+                    }
+                    _ => true,
+                });
+                if !data.is_empty() {
+                    on_data(data);
+                }
+            }
             Ok(n) if n > 0 => on_data(buf[..n].to_vec()),
             Ok(_) => {}
             Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
@@ -46,11 +473,40 @@ fn read_loop(mut port: Box<dyn SerialPort>, on_data: impl Fn(Vec<u8>)) {
             Err(_) => break,
         }
     }
+    // Don't leave the writer parked on a port that is gone
+    xoff.store(false, Ordering::Release);
+    if !shared.closed.load(Ordering::Acquire) {
+        on_close();
+    }
 }
 
-fn write_loop(mut port: Box<dyn SerialPort>, mut rx: UnboundedReceiver<Vec<u8>>) {
-    while let Some(data) = rx.blocking_recv() {
-        if port.write_all(&data).is_err() {
+fn write_loop(
+    mut port: Box<dyn SerialPort>,
+    mut rx: UnboundedReceiver<Outgoing>,
+    shared: Arc<Shared>,
+    xoff: Arc<AtomicBool>,
+    looped: std::sync::mpsc::Sender<Vec<u8>>,
+) {
+    while let Some(mut out) = rx.blocking_recv() {
+        if shared.settings.lock().loopback {
+            // Delivered by the reader so RX callbacks stay on one thread
+            if looped.send(std::mem::take(&mut out.data)).is_err() {
+                break;
+            }
+            out.done(Ok(()));
+            continue;
+        }
+        // Hold queued data while the peer has sent XOFF
+        while xoff.load(Ordering::Acquire) && !shared.closed.load(Ordering::Acquire) {
+            std::thread::sleep(Duration::from_millis(shared.settings.lock().poll_interval_ms));
+        }
+        if shared.closed.load(Ordering::Acquire) {
+            break;
+        }
+        let result = port.write_all(&out.data).map_err(|e| e.to_string());
+        let failed = result.is_err();
+        out.done(result);
+        if failed {
             break;
         }
     }
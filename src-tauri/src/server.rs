@@ -0,0 +1,202 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// Longest preamble line read while waiting for the token.
+const MAX_PREAMBLE: usize = 256;
+
+/// Options for accepting TCP clients.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerOptions {
+    pub bind: String,
+    /// 0 picks a free port.
+    pub port: u16,
+    /// 0 accepts any number of clients. Clients still authenticating count.
+    pub max_clients: usize,
+    /// Addresses or CIDR blocks (`10.0.0.0/8`) allowed to connect; empty allows all.
+    pub allowlist: Vec<String>,
+    /// When set, a client's first line must be this token. The line is not
+    /// passed on as data.
+    pub token: Option<String>,
+    pub auth_timeout_ms: u64,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            bind: "0.0.0.0".into(),
+            port: 0,
+            max_clients: 0,
+            allowlist: Vec::new(),
+            token: None,
+            auth_timeout_ms: 5000,
+        }
+    }
+}
+
+/// An address block from the allowlist.
+struct IpNet {
+    addr: IpAddr,
+    prefix: u32,
+}
+
+impl IpNet {
+    fn parse(s: &str) -> Result<Self, String> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((a, p)) => (a, Some(p)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.trim().parse().map_err(|_| format!("Invalid allowlist address: {s}"))?;
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p.trim().parse().ok().filter(|&p| p <= bits).ok_or(format!("Invalid prefix length: {s}"))?,
+            None => bits,
+        };
+        Ok(Self { addr, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Holds one place under `max_clients` until dropped.
+pub struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+pub enum Admission {
+    Accepted {
+        stream: TcpStream,
+        peer: SocketAddr,
+        /// Bytes that followed the token in the same read.
+        pending: Vec<u8>,
+        slot: Slot,
+    },
+    Rejected {
+        peer: SocketAddr,
+        reason: String,
+    },
+}
+
+pub struct Listener {
+    pub local_addr: SocketAddr,
+    pub options: ServerOptions,
+    clients: Arc<AtomicUsize>,
+    task: JoinHandle<()>,
+}
+
+impl Listener {
+    pub fn clients(&self) -> usize {
+        self.clients.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for Listener {
+    /// Stops accepting; clients already admitted stay connected.
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+pub async fn start(
+    options: ServerOptions,
+    on_admission: impl Fn(SocketAddr, Admission) + Send + Sync + 'static,
+) -> Result<Listener, String> {
+    let allow = options.allowlist.iter().map(|s| IpNet::parse(s)).collect::<Result<Vec<_>, _>>()?;
+    let listener = TcpListener::bind((options.bind.as_str(), options.port)).await.map_err(|e| e.to_string())?;
+    let local_addr = listener.local_addr().map_err(|e| e.to_string())?;
+    let clients = Arc::new(AtomicUsize::new(0));
+    let on_admission = Arc::new(on_admission);
+
+    let (opts, count) = (options.clone(), Arc::clone(&clients));
+    let task = tokio::spawn(async move {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(_) => {
+                    // Usually out of descriptors; don't spin on it
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            if !allow.is_empty() && !allow.iter().any(|net| net.contains(peer.ip())) {
+                on_admission(local_addr, Admission::Rejected { peer, reason: "Address not in allowlist".into() });
+                continue;
+            }
+            if opts.max_clients > 0 && count.load(Ordering::Acquire) >= opts.max_clients {
+                on_admission(local_addr, Admission::Rejected { peer, reason: "Client limit reached".into() });
+                continue;
+            }
+            count.fetch_add(1, Ordering::AcqRel);
+            let slot = Slot(Arc::clone(&count));
+            let (on_admission, token, timeout) = (Arc::clone(&on_admission), opts.token.clone(), opts.auth_timeout_ms);
+            // Authenticate off the accept loop so a silent client can't stall it
+            tokio::spawn(async move {
+                let admission = match authenticate(stream, token.as_deref(), timeout).await {
+                    Ok((stream, pending)) => Admission::Accepted { stream, peer, pending, slot },
+                    Err(reason) => Admission::Rejected { peer, reason },
+                };
+                on_admission(local_addr, admission);
+            });
+        }
+    });
+
+    Ok(Listener { local_addr, options, clients, task })
+}
+
+/// Read the token line. Returns whatever arrived after it.
+async fn authenticate(mut stream: TcpStream, token: Option<&str>, timeout_ms: u64) -> Result<(TcpStream, Vec<u8>), String> {
+    let Some(token) = token else {
+        return Ok((stream, Vec::new()));
+    };
+    let mut buf = Vec::new();
+    let read_line = async {
+        let mut chunk = [0u8; MAX_PREAMBLE];
+        loop {
+            if let Some(end) = buf.iter().position(|&b| b == b'\n') {
+                return Ok(end);
+            }
+            if buf.len() > MAX_PREAMBLE {
+                return Err("Preamble too long".to_string());
+            }
+            match stream.read(&mut chunk).await {
+                Ok(0) | Err(_) => return Err("Closed before authenticating".to_string()),
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+    };
+    let end = tokio::time::timeout(Duration::from_millis(timeout_ms), read_line)
+        .await
+        .map_err(|_| "Authentication timed out".to_string())??;
+    let line = buf[..end].strip_suffix(b"\r").unwrap_or(&buf[..end]);
+    if !same_secret(line, token.as_bytes()) {
+        return Err("Wrong token".into());
+    }
+    Ok((stream, buf[end + 1..].to_vec()))
+}
+
+/// Compare a secret in time independent of where it first differs.
+pub fn same_secret(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
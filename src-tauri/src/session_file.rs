@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::classify::ClassRule;
+use crate::decoder::Protocol;
+use crate::fixture::Chunk;
+use crate::journal::Entry;
+use crate::state::{Packet, SessionInfo, SplitterConfig};
+use crate::transform::Stage;
+
+/// Value of `format` in every session file.
+pub const FORMAT: &str = "wirescope-session";
+/// Raised when a field changes meaning; added optional fields don't need it.
+pub const VERSION: u32 = 1;
+
+/// Processing the session's packets went through when captured.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureSettings {
+    pub splitter: SplitterConfig,
+    pub rules: Vec<ClassRule>,
+    pub protocol: Option<Protocol>,
+    /// RX pipeline applied after framing.
+    #[serde(default)]
+    pub stages: Vec<Stage>,
+}
+
+/// RX bytes exactly as the transport delivered them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawCapture {
+    pub started_ms: f64,
+    /// Offsets are relative to `started_ms`.
+    pub chunks: Vec<Chunk>,
+}
+
+/// A session with everything needed to open it on another machine: its
+/// settings, packets, events and, if it was being recorded, raw RX.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionFile {
+    pub format: String,
+    pub version: u32,
+    /// Version of wirescope that wrote the file.
+    pub app_version: String,
+    pub exported_at_ms: f64,
+    pub session: SessionInfo,
+    pub settings: CaptureSettings,
+    /// TX and RX packets as they were shown, oldest first.
+    pub packets: Vec<Packet>,
+    /// The session's events still in the journal when it was exported.
+    #[serde(default)]
+    pub events: Vec<Entry>,
+    /// Present when a fixture recording of the session was running.
+    #[serde(default)]
+    pub raw: Option<RawCapture>,
+}
+
+impl SessionFile {
+    pub fn new(session: SessionInfo, settings: CaptureSettings, packets: Vec<Packet>, exported_at_ms: f64) -> Self {
+        Self {
+            format: FORMAT.into(),
+            version: VERSION,
+            app_version: env!("CARGO_PKG_VERSION").into(),
+            exported_at_ms,
+            session,
+            settings,
+            packets,
+            events: Vec::new(),
+            raw: None,
+        }
+    }
+
+    /// Read a session file, refusing other JSON and files from a newer
+    /// format version.
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let value: Value = serde_json::from_slice(data).map_err(|e| format!("Not a session file: {e}"))?;
+        if value.get("format").and_then(Value::as_str) != Some(FORMAT) {
+            return Err("Not a wirescope session file".into());
+        }
+        let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+        if version > u64::from(VERSION) {
+            return Err(format!("Session file version {version} is newer than this wirescope reads ({VERSION}); update to open it"));
+        }
+        serde_json::from_value(value).map_err(|e| format!("Damaged session file: {e}"))
+    }
+}
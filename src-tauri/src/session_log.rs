@@ -0,0 +1,247 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use serde::{Deserialize, Serialize};
+use crate::{clock, manifest};
+use crate::state::{now_ms, Packet};
+use crate::summary::SessionSummary;
+
+/// Manifest updates of closed logs still hashing.
+static PENDING: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+/// How a log reaches the disk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum LogMode {
+    /// One file, flushed after every line and synced when closed.
+    #[default]
+    Buffered,
+    /// Write-ahead: every line carries a sequence number and goes into
+    /// segments of about `segment_bytes`. A thread of the log's own appends
+    /// and fsyncs the lines, those that queued up during an fsync together,
+    /// so a line is on disk one fsync after it was logged without the sync
+    /// holding up the session. A failed write stops the log, and the next
+    /// line logged reports the failure.
+    Wal { segment_bytes: u64 },
+}
+
+/// One open log file, recorded in the manifest once closed.
+struct Segment {
+    path: PathBuf,
+    session_id: String,
+    opened_ms: f64,
+    writer: BufWriter<File>,
+}
+
+impl Segment {
+    fn create(path: PathBuf, session_id: &str) -> io::Result<Self> {
+        let file = File::create(&path)?;
+        Ok(Self { path, session_id: session_id.to_string(), opened_ms: now_ms(), writer: BufWriter::new(file) })
+    }
+}
+
+impl Drop for Segment {
+    fn drop(&mut self) {
+        let _ = self.writer.flush();
+        let _ = self.writer.get_ref().sync_all();
+        record_manifest(self.path.clone(), std::mem::take(&mut self.session_id), self.opened_ms);
+    }
+}
+
+/// Write-ahead log fed to its writer thread: `<dir>/000001.log`, `000002.log`, ...
+struct Wal {
+    seq: u64,
+    records: Option<Sender<String>>,
+    /// Why the writer thread stopped, once it did.
+    failure: Arc<Mutex<Option<String>>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Wal {
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        if let Some(e) = self.failure.lock().unwrap_or_else(|e| e.into_inner()).clone() {
+            return Err(io::Error::other(e));
+        }
+        self.seq += 1;
+        let sent = self.records.as_ref().map(|records| records.send(format!("{} {line}\n", self.seq)));
+        match sent {
+            Some(Ok(())) => Ok(()),
+            _ => Err(io::Error::other("Log writer stopped")),
+        }
+    }
+}
+
+impl Drop for Wal {
+    /// Wait for the queued lines to reach the disk.
+    fn drop(&mut self) {
+        self.records = None;
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+enum Sink {
+    Buffered(Segment),
+    Wal(Wal),
+}
+
+/// Append-only text log of one session's packets. Closing it (dropping)
+/// records its hash in the directory's manifest; in write-ahead mode each
+/// segment is recorded as it fills up.
+pub struct SessionLog {
+    /// The log file, or the segment directory in write-ahead mode.
+    pub path: PathBuf,
+    sink: Sink,
+}
+
+impl SessionLog {
+    /// Create `<dir>/wirescope-<unix secs>.log`, or the segment directory
+    /// `<dir>/wirescope-<unix secs>.wal` in write-ahead mode, creating `dir`
+    /// if needed.
+    pub fn create(dir: &Path, session_id: &str, mode: LogMode) -> Result<Self, String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let ts = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        match mode {
+            LogMode::Buffered => {
+                let path = dir.join(format!("wirescope-{ts}.log"));
+                let segment = Segment::create(path.clone(), session_id).map_err(|e| e.to_string())?;
+                Ok(Self { path, sink: Sink::Buffered(segment) })
+            }
+            LogMode::Wal { segment_bytes } => {
+                let path = dir.join(format!("wirescope-{ts}.wal"));
+                fs::create_dir_all(&path).map_err(|e| e.to_string())?;
+                let first = Segment::create(segment_path(&path, 1), session_id).map_err(|e| e.to_string())?;
+                sync_dir(&path).map_err(|e| e.to_string())?;
+                sync_dir(dir).map_err(|e| e.to_string())?;
+                let (records, queued) = mpsc::channel();
+                let failure = Arc::new(Mutex::new(None));
+                let (wal_dir, failed) = (path.clone(), Arc::clone(&failure));
+                let writer = std::thread::spawn(move || {
+                    if let Err(e) = write_segments(&wal_dir, segment_bytes.max(1), first, queued) {
+                        *failed.lock().unwrap_or_else(|e| e.into_inner()) = Some(e.to_string());
+                    }
+                });
+                let wal = Wal { seq: 0, records: Some(records), failure, writer: Some(writer) };
+                Ok(Self { path, sink: Sink::Wal(wal) })
+            }
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        match &mut self.sink {
+            Sink::Buffered(segment) => {
+                writeln!(segment.writer, "{line}")?;
+                segment.writer.flush()
+            }
+            Sink::Wal(wal) => wal.write(line),
+        }
+    }
+
+    pub fn write_packet(&mut self, pkt: &Packet) -> io::Result<()> {
+        self.write_line(&format_line(pkt, None))
+    }
+
+    /// Write a non-packet event as a `# `-prefixed line.
+    pub fn write_note(&mut self, ts: f64, note: &str) -> io::Result<()> {
+        self.write_line(&format!("# {} {note}", clock::format_ts(ts)))
+    }
+
+    /// End the log with the connection's recap and force it to disk.
+    pub fn write_summary(&mut self, summary: &SessionSummary) -> io::Result<()> {
+        let json = serde_json::to_string(summary)?;
+        self.write_note(summary.closed_ms, &format!("summary {json}"))?;
+        match &self.sink {
+            Sink::Buffered(segment) => segment.writer.get_ref().sync_all(),
+            // The writer thread syncs every line
+            Sink::Wal(_) => Ok(()),
+        }
+    }
+}
+
+/// The write-ahead writer thread: append queued records to `segment`, one
+/// fsync per batch, starting the next segment once one is full.
+fn write_segments(dir: &Path, segment_bytes: u64, mut segment: Segment, queued: Receiver<String>) -> io::Result<()> {
+    let (mut number, mut written) = (1, 0u64);
+    while let Ok(first) = queued.recv() {
+        for record in std::iter::once(first).chain(queued.try_iter()) {
+            if written > 0 && written + record.len() as u64 > segment_bytes {
+                segment.writer.flush()?;
+                segment.writer.get_ref().sync_all()?;
+                number += 1;
+                // Dropping the full segment records it in the manifest
+                segment = Segment::create(segment_path(dir, number), &segment.session_id)?;
+                sync_dir(dir)?;
+                written = 0;
+            }
+            segment.writer.write_all(record.as_bytes())?;
+            written += record.len() as u64;
+        }
+        segment.writer.flush()?;
+        segment.writer.get_ref().sync_data()?;
+    }
+    Ok(())
+}
+
+fn segment_path(dir: &Path, segment: u32) -> PathBuf {
+    dir.join(format!("{segment:06}.log"))
+}
+
+/// Make a newly created file's directory entry durable.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+/// NTFS journals directory entries itself.
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Hash a finished log file into its directory's manifest in the background.
+fn record_manifest(path: PathBuf, session_id: String, opened_ms: f64) {
+    // Hashing a long capture shouldn't hold up whoever closed it
+    let hashing = std::thread::spawn(move || {
+        let _ = manifest::record(&path, &session_id, opened_ms);
+    });
+    let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+    pending.retain(|h| !h.is_finished());
+    pending.push(hashing);
+}
+
+/// Wait until every closed log is in its manifest.
+pub fn wait_for_manifests() {
+    let pending = std::mem::take(&mut *PENDING.lock().unwrap_or_else(|e| e.into_inner()));
+    for hashing in pending {
+        let _ = hashing.join();
+    }
+}
+
+/// One packet as a log/timeline line: `<time> [@<device_ts_ms>] [label] DIR <severity,tags> HEX`,
+/// `<time>` following the configured time source. The corrected timestamp is
+/// used when one was recorded; the device time and class fields are omitted
+/// when absent.
+pub fn format_line(pkt: &Packet, label: Option<&str>) -> String {
+    let hex = pkt.bytes.iter().map(|b| format!("{b:02X}")).collect::<Vec<_>>().join(" ");
+    let mut ts = clock::format_ts(pkt.corrected_ts_ms.unwrap_or(pkt.timestamp_ms));
+    if let Some(dev) = pkt.device_ts_ms {
+        ts.push_str(&format!(" @{dev:.3}"));
+    }
+    let class: Vec<&str> = pkt.severity.iter().chain(&pkt.tags).map(String::as_str).collect();
+    let dir = if class.is_empty() {
+        pkt.direction.clone()
+    } else {
+        format!("{} <{}>", pkt.direction, class.join(","))
+    };
+    match label {
+        Some(label) => format!("{ts} [{label}] {dir} {hex}"),
+        None => format!("{ts} {dir} {hex}"),
+    }
+}
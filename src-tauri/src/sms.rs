@@ -0,0 +1,332 @@
+use serde::{Deserialize, Serialize};
+
+/// GSM 03.38 default alphabet, indexed by septet value.
+const GSM7_BASIC: &str = "@£$¥èéùìòÇ\nØø\rÅåΔ_ΦΓΛΩΠΨΣΘΞ\u{1b}ÆæßÉ !\"#¤%&'()*+,-./0123456789:;<=>?\
+¡ABCDEFGHIJKLMNOPQRSTUVWXYZÄÖÑÜ§¿abcdefghijklmnopqrstuvwxyzäöñüà";
+
+/// Characters reached through the 0x1B escape.
+const GSM7_EXT: [(u8, char); 10] = [
+    (0x0A, '\u{0c}'), (0x14, '^'), (0x28, '{'), (0x29, '}'), (0x2F, '\\'),
+    (0x3C, '['), (0x3D, '~'), (0x3E, ']'), (0x40, '|'), (0x65, '€'),
+];
+
+/// An encoded SMS-SUBMIT ready for `AT+CMGS`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmsPdu {
+    /// Hex PDU including the (empty) SMSC prefix.
+    pub pdu: String,
+    /// TPDU length in octets, the argument to `AT+CMGS`.
+    pub tpdu_len: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmsConcat {
+    pub reference: u16,
+    pub part: u8,
+    pub total: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmsMessage {
+    /// "deliver" (received) or "submit" (outgoing/stored).
+    pub kind: String,
+    pub smsc: Option<String>,
+    /// Sender for deliver, recipient for submit.
+    pub address: String,
+    /// Service centre timestamp (deliver only), ISO 8601 with offset.
+    pub timestamp: Option<String>,
+    /// "gsm7", "ucs2" or "8bit".
+    pub encoding: String,
+    pub text: String,
+    pub concat: Option<SmsConcat>,
+}
+
+/// A message stored on the modem as reported by `AT+CMGL`/`AT+CMGR`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmsEntry {
+    pub index: Option<u32>,
+    /// 0 = received unread, 1 = received read, 2 = stored unsent, 3 = stored sent.
+    pub status: u8,
+    pub message: SmsMessage,
+}
+
+// ── Encoding ────────────────────────────────────────────────────────────────
+
+/// Encode an SMS-SUBMIT for `number`. Uses the GSM 7-bit alphabet when every
+/// character is representable, UCS-2 otherwise. Single-part messages only.
+pub fn encode_submit(number: &str, text: &str) -> Result<SmsPdu, String> {
+    let (dcs, udl, ud) = match to_septets(text) {
+        Some(septets) => {
+            if septets.len() > 160 {
+                return Err(format!("Message too long: {} of 160 GSM characters", septets.len()));
+            }
+            (0x00u8, septets.len(), pack7(&septets, 0))
+        }
+        None => {
+            let ud: Vec<u8> = text.encode_utf16().flat_map(|u| u.to_be_bytes()).collect();
+            if ud.len() > 140 {
+                return Err(format!("Message too long: {} of 70 UCS-2 characters", ud.len() / 2));
+            }
+            (0x08u8, ud.len(), ud)
+        }
+    };
+
+    let (toa, digits) = match number.strip_prefix('+') {
+        Some(rest) => (0x91u8, rest),
+        None => (0x81u8, number),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(format!("Invalid phone number: {number}"));
+    }
+
+    let mut tpdu = vec![0x01, 0x00, digits.len() as u8, toa]; // SMS-SUBMIT, TP-MR 0
+    tpdu.extend(semi_octets(digits));
+    tpdu.extend([0x00, dcs, udl as u8]); // TP-PID, TP-DCS, TP-UDL
+    tpdu.extend(ud);
+
+    let pdu = std::iter::once(0u8) // use the SMSC stored in the modem
+        .chain(tpdu.iter().copied())
+        .map(|b| format!("{b:02X}"))
+        .collect();
+    Ok(SmsPdu { pdu, tpdu_len: tpdu.len() })
+}
+
+fn to_septets(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        if let Some(i) = GSM7_BASIC.chars().position(|g| g == c && g != '\u{1b}') {
+            out.push(i as u8);
+        } else if let Some((code, _)) = GSM7_EXT.iter().find(|(_, e)| *e == c) {
+            out.extend([0x1B, *code]);
+        } else {
+            return None;
+        }
+    }
+    Some(out)
+}
+
+fn pack7(septets: &[u8], fill_bits: usize) -> Vec<u8> {
+    let mut out = vec![0u8; (fill_bits + septets.len() * 7).div_ceil(8)];
+    for (i, &s) in septets.iter().enumerate() {
+        let bit = fill_bits + i * 7;
+        let v = (s as u16 & 0x7F) << (bit % 8);
+        out[bit / 8] |= v as u8;
+        if bit % 8 > 1 {
+            out[bit / 8 + 1] |= (v >> 8) as u8;
+        }
+    }
+    out
+}
+
+fn semi_octets(digits: &str) -> Vec<u8> {
+    digits.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let lo = pair[0] - b'0';
+            let hi = pair.get(1).map_or(0x0F, |d| d - b'0');
+            (hi << 4) | lo
+        })
+        .collect()
+}
+
+// ── Decoding ────────────────────────────────────────────────────────────────
+
+/// Decode a PDU as read from the modem (with SMSC prefix).
+pub fn decode(hex: &str) -> Result<SmsMessage, String> {
+    let bytes = crate::payload::hex_to_bytes(hex)?;
+    let mut r = Reader { b: &bytes, pos: 0 };
+
+    let smsc_len = r.u8()? as usize;
+    let smsc = if smsc_len > 0 {
+        let toa = r.u8()?;
+        Some(decode_address(toa, r.take(smsc_len - 1)?, (smsc_len - 1) * 2))
+    } else {
+        None
+    };
+
+    let first = r.u8()?;
+    let udhi = first & 0x40 != 0;
+    let (kind, address, timestamp, dcs) = match first & 0x03 {
+        0x00 => {
+            let address = read_address(&mut r)?;
+            let _pid = r.u8()?;
+            let dcs = r.u8()?;
+            let ts = decode_timestamp(r.take(7)?);
+            ("deliver", address, Some(ts), dcs)
+        }
+        0x01 => {
+            let _mr = r.u8()?;
+            let address = read_address(&mut r)?;
+            let _pid = r.u8()?;
+            let dcs = r.u8()?;
+            match (first >> 3) & 0x03 {
+                0x02 => { r.take(1)?; }          // relative validity period
+                0x01 | 0x03 => { r.take(7)?; }   // enhanced / absolute
+                _ => {}
+            }
+            ("submit", address, None, dcs)
+        }
+        mti => return Err(format!("Unsupported message type {mti}")),
+    };
+
+    let udl = r.u8()? as usize;
+    let ud = r.rest();
+    let encoding = match dcs & 0x0C {
+        0x08 => "ucs2",
+        0x04 => "8bit",
+        _ => "gsm7",
+    };
+
+    let (udh, header_len) = if udhi && !ud.is_empty() {
+        let hl = ud[0] as usize + 1;
+        (ud.get(1..hl).unwrap_or_default(), hl)
+    } else {
+        (&[][..], 0)
+    };
+    let text = match encoding {
+        "gsm7" => {
+            let header_septets = (header_len * 8).div_ceil(7);
+            let fill = header_septets * 7 - header_len * 8;
+            let septets = unpack7(&ud[header_len.min(ud.len())..], udl.saturating_sub(header_septets), fill);
+            from_septets(&septets)
+        }
+        "ucs2" => {
+            let body = ud.get(header_len..udl.min(ud.len())).unwrap_or_default();
+            let units: Vec<u16> = body.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => {
+            let body = ud.get(header_len..udl.min(ud.len())).unwrap_or_default();
+            body.iter().map(|b| format!("{b:02X}")).collect()
+        }
+    };
+
+    Ok(SmsMessage {
+        kind: kind.into(),
+        smsc,
+        address,
+        timestamp,
+        encoding: encoding.into(),
+        text,
+        concat: parse_concat(udh),
+    })
+}
+
+struct Reader<'a> {
+    b: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let s = self.b.get(self.pos..self.pos + n).ok_or("Truncated PDU")?;
+        self.pos += n;
+        Ok(s)
+    }
+    fn rest(&mut self) -> &'a [u8] {
+        let s = &self.b[self.pos.min(self.b.len())..];
+        self.pos = self.b.len();
+        s
+    }
+}
+
+/// Originating/destination address: digit count, type, semi-octets.
+fn read_address(r: &mut Reader) -> Result<String, String> {
+    let digits = r.u8()? as usize;
+    let toa = r.u8()?;
+    Ok(decode_address(toa, r.take(digits.div_ceil(2))?, digits))
+}
+
+fn decode_address(toa: u8, data: &[u8], digits: usize) -> String {
+    if toa & 0x70 == 0x50 {
+        // Alphanumeric sender, GSM 7-bit packed
+        return from_septets(&unpack7(data, digits * 4 / 7, 0));
+    }
+    let num: String = data.iter()
+        .flat_map(|b| [b & 0x0F, b >> 4])
+        .take_while(|&d| d != 0x0F)
+        .take(digits)
+        .map(|d| char::from(b'0' + d.min(9)))
+        .collect();
+    if toa & 0x70 == 0x10 { format!("+{num}") } else { num }
+}
+
+fn decode_timestamp(b: &[u8]) -> String {
+    let d = |x: u8| (x & 0x0F) * 10 + (x >> 4);
+    let tz = b[6];
+    let quarters = (tz & 0x07) * 10 + (tz >> 4);
+    let sign = if tz & 0x08 != 0 { '-' } else { '+' };
+    format!(
+        "20{:02}-{:02}-{:02}T{:02}:{:02}:{:02}{sign}{:02}:{:02}",
+        d(b[0]), d(b[1]), d(b[2]), d(b[3]), d(b[4]), d(b[5]),
+        quarters / 4, (quarters % 4) * 15,
+    )
+}
+
+fn unpack7(data: &[u8], count: usize, fill_bits: usize) -> Vec<u8> {
+    (0..count)
+        .map_while(|i| {
+            let bit = fill_bits + i * 7;
+            let lo = *data.get(bit / 8)? as u16;
+            let hi = data.get(bit / 8 + 1).copied().unwrap_or(0) as u16;
+            Some((((hi << 8) | lo) >> (bit % 8)) as u8 & 0x7F)
+        })
+        .collect()
+}
+
+fn from_septets(septets: &[u8]) -> String {
+    let basic: Vec<char> = GSM7_BASIC.chars().collect();
+    let mut out = String::with_capacity(septets.len());
+    let mut it = septets.iter();
+    while let Some(&s) = it.next() {
+        if s == 0x1B {
+            if let Some(&e) = it.next() {
+                out.push(GSM7_EXT.iter().find(|(c, _)| *c == e).map_or(' ', |(_, ch)| *ch));
+            }
+        } else {
+            out.push(basic[s as usize]);
+        }
+    }
+    out
+}
+
+/// Concatenation info from a user data header (IEI 0x00 or 0x08).
+fn parse_concat(udh: &[u8]) -> Option<SmsConcat> {
+    let mut i = 0;
+    while i + 1 < udh.len() {
+        let (iei, len) = (udh[i], udh[i + 1] as usize);
+        let data = udh.get(i + 2..i + 2 + len)?;
+        match (iei, len) {
+            (0x00, 3) => return Some(SmsConcat { reference: data[0] as u16, part: data[2], total: data[1] }),
+            (0x08, 4) => {
+                return Some(SmsConcat { reference: u16::from_be_bytes([data[0], data[1]]), part: data[3], total: data[2] });
+            }
+            _ => i += 2 + len,
+        }
+    }
+    None
+}
+
+/// Parse `+CMGL:`/`+CMGR:` responses: a header line followed by the PDU line.
+pub fn parse_listing(resp: &str) -> Vec<SmsEntry> {
+    let mut entries = Vec::new();
+    let mut lines = resp.lines().map(str::trim).filter(|l| !l.is_empty());
+    while let Some(line) = lines.next() {
+        let (index, fields) = if let Some(rest) = line.strip_prefix("+CMGL:") {
+            let mut parts = rest.split(',');
+            (parts.next().and_then(|i| i.trim().parse().ok()), parts.collect::<Vec<_>>())
+        } else if let Some(rest) = line.strip_prefix("+CMGR:") {
+            (None, rest.split(',').collect())
+        } else {
+            continue;
+        };
+        let status = fields.first().and_then(|s| s.trim().parse().ok()).unwrap_or(0);
+        if let Some(Ok(message)) = lines.next().map(decode) {
+            entries.push(SmsEntry { index, status, message });
+        }
+    }
+    entries
+}
@@ -0,0 +1,173 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Which way sniffed bytes went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Written by the program that owns the port.
+    ToDevice,
+    FromDevice,
+}
+
+/// A running capture; dropping it stops the capture thread.
+pub struct Sniffer {
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for Sniffer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+    }
+}
+
+/// Watch the bulk traffic of the USB serial adapter behind `port` (e.g.
+/// /dev/ttyUSB0) while another program has it open. `on_close` runs when the
+/// capture fails, e.g. because the adapter was unplugged.
+#[cfg(target_os = "linux")]
+pub fn start(
+    port: &str,
+    on_data: impl Fn(Direction, Vec<u8>) + Send + 'static,
+    on_close: impl FnOnce() + Send + 'static,
+) -> Result<Sniffer, String> {
+    let target = usbmon::Target::resolve(port)?;
+    let file = usbmon::open(target.bus)?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::clone(&stop);
+    tokio::task::spawn_blocking(move || {
+        usbmon::capture(file, &target, &stopped, on_data);
+        if !stopped.load(Ordering::Acquire) {
+            on_close();
+        }
+    });
+    Ok(Sniffer { stop })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn start(
+    _port: &str,
+    _on_data: impl Fn(Direction, Vec<u8>) + Send + 'static,
+    _on_close: impl FnOnce() + Send + 'static,
+) -> Result<Sniffer, String> {
+    Err("Sniffing needs Linux usbmon; it is not available on this platform".into())
+}
+
+/// Capture through the kernel's binary usbmon interface (/dev/usbmonN).
+#[cfg(target_os = "linux")]
+mod usbmon {
+    use std::fs::File;
+    use std::io::{ErrorKind, Read};
+    use std::os::fd::AsRawFd;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use super::Direction;
+
+    /// read(2) returns the original 48-byte `struct mon_bin_hdr` before each
+    /// event's data; the extended 64-byte form is only used by the ioctls.
+    const HEADER: usize = 48;
+    const XFER_BULK: u8 = 3;
+    const ENDPOINT_IN: u8 = 0x80;
+    const FTDI_VENDOR: &str = "0403";
+    /// How often a blocked read checks whether the capture was stopped.
+    const POLL_MS: libc::c_int = 200;
+
+    /// The adapter behind a tty.
+    pub struct Target {
+        pub bus: u16,
+        dev: u8,
+        /// Bulk endpoint addresses of the tty's interface, so other ports of a
+        /// multi-port adapter are left out.
+        endpoints: Vec<u8>,
+        /// FTDI chips prefix every IN packet of this size with 2 status bytes.
+        ftdi_packet: Option<usize>,
+    }
+
+    fn read_attr(dir: &Path, name: &str) -> Option<String> {
+        std::fs::read_to_string(dir.join(name)).ok().map(|s| s.trim().to_string())
+    }
+
+    impl Target {
+        pub fn resolve(port: &str) -> Result<Self, String> {
+            let name = Path::new(port).file_name().and_then(|n| n.to_str()).ok_or("Invalid port name")?;
+            let device = std::fs::canonicalize(format!("/sys/class/tty/{name}/device"))
+                .map_err(|_| format!("{port} is not a USB serial adapter"))?;
+            let interface = device.ancestors()
+                .find(|d| d.join("bInterfaceNumber").exists())
+                .map(PathBuf::from)
+                .ok_or_else(|| format!("{port} is not a USB serial adapter"))?;
+            let usb = interface.parent().ok_or("USB device not found")?;
+            let number = |attr: &str| read_attr(usb, attr).and_then(|v| v.parse::<u16>().ok());
+            let (bus, dev) = number("busnum").zip(number("devnum")).ok_or("USB device not found")?;
+            let endpoints = std::fs::read_dir(&interface)
+                .map_err(|e| e.to_string())?
+                .flatten()
+                .filter(|e| read_attr(&e.path(), "type").as_deref() == Some("Bulk"))
+                .filter_map(|e| e.file_name().to_str()?.strip_prefix("ep_").and_then(|a| u8::from_str_radix(a, 16).ok()))
+                .collect();
+            let ftdi_packet = (read_attr(usb, "idVendor").as_deref() == Some(FTDI_VENDOR))
+                .then(|| if read_attr(usb, "speed").as_deref() == Some("480") { 512 } else { 64 });
+            Ok(Self { bus, dev: dev as u8, endpoints, ftdi_packet })
+        }
+    }
+
+    pub fn open(bus: u16) -> Result<File, String> {
+        let path = format!("/dev/usbmon{bus}");
+        File::open(&path).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => format!("{path} not found; load usbmon with `sudo modprobe usbmon`"),
+            ErrorKind::PermissionDenied => format!("No read access to {path}; run as root or grant access with a udev rule"),
+            _ => format!("{path}: {e}"),
+        })
+    }
+
+    /// Drop the status bytes FTDI puts at the start of every IN packet.
+    fn strip_ftdi(data: &[u8], packet: usize) -> Vec<u8> {
+        data.chunks(packet).flat_map(|p| p.get(2..).unwrap_or_default()).copied().collect()
+    }
+
+    /// Read events until `stop` is set or the device goes away.
+    pub fn capture(mut file: File, target: &Target, stop: &AtomicBool, on_data: impl Fn(Direction, Vec<u8>)) {
+        let mut buf = vec![0u8; HEADER + 64 * 1024];
+        while !stop.load(Ordering::Acquire) {
+            let mut pfd = libc::pollfd { fd: file.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+            // SAFETY: `pfd` is a valid pollfd for an open fd
+            match unsafe { libc::poll(&mut pfd, 1, POLL_MS) } {
+                0 => continue,
+                n if n < 0 && std::io::Error::last_os_error().kind() == ErrorKind::Interrupted => continue,
+                n if n < 0 => break,
+                _ => {}
+            }
+            // One event per read: the header, then as much data as was captured
+            let n = match file.read(&mut buf) {
+                Ok(n) if n >= HEADER => n,
+                Ok(_) => continue,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+            let (event, xfer, endpoint, dev, flag_data) = (buf[8], buf[9], buf[10], buf[11], buf[15]);
+            let status = i32::from_ne_bytes(buf[28..32].try_into().unwrap_or_default());
+            // Device removal fails every pending transfer with -ENODEV/-ESHUTDOWN
+            if dev == target.dev && (status == -libc::ENODEV || status == -libc::ESHUTDOWN) {
+                break;
+            }
+            if xfer != XFER_BULK || dev != target.dev || !target.endpoints.contains(&endpoint) || flag_data != 0 {
+                continue;
+            }
+            let captured = u32::from_ne_bytes(buf[36..40].try_into().unwrap_or_default()) as usize;
+            let data = &buf[HEADER..HEADER + captured.min(n - HEADER)];
+            match (event, endpoint & ENDPOINT_IN != 0) {
+                // Completed IN transfers carry what the device sent
+                (b'C', true) if status == 0 => {
+                    let data = match target.ftdi_packet {
+                        Some(packet) => strip_ftdi(data, packet),
+                        None => data.to_vec(),
+                    };
+                    if !data.is_empty() {
+                        on_data(Direction::FromDevice, data);
+                    }
+                }
+                // Submitted OUT transfers carry what the program wrote
+                (b'S', false) if !data.is_empty() => on_data(Direction::ToDevice, data.to_vec()),
+                _ => {}
+            }
+        }
+    }
+}
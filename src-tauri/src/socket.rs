@@ -1,29 +1,475 @@
-use tokio::net::TcpStream;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::datagram::SeqRule;
+use crate::dns::{self, AddressFamily, ResolverMode};
+use crate::outgoing::Outgoing;
+use crate::proxy::{self, ProxyOptions};
+use crate::telnet::{self, TelnetOptions};
+use crate::tls::{self, TlsOptions};
+use crate::ws::{self, FrameKind, WsOptions};
+use tokio::net::{TcpSocket, TcpStream, UdpSocket};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::sync::mpsc::{self, UnboundedSender, UnboundedReceiver};
+use tokio::sync::oneshot;
+use tokio::task::AbortHandle;
+
+/// Options for opening a TCP connection. Unset tuning fields keep the OS defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SocketOpenArgs {
+    pub host: String,
+    pub port: u16,
+    pub nodelay: Option<bool>,
+    /// SO_LINGER for the eventual close; 0 resets the connection instead of a graceful FIN.
+    pub linger_ms: Option<u64>,
+    pub send_buffer_size: Option<usize>,
+    pub recv_buffer_size: Option<usize>,
+    /// TCP keepalive; unset keeps the OS default (usually off).
+    pub keepalive: Option<KeepaliveOptions>,
+    /// Fixed name → IP mappings consulted before any resolver.
+    pub host_overrides: HashMap<String, IpAddr>,
+    pub resolver: ResolverMode,
+    /// Nameserver for `ResolverMode::Direct` (`ip` or `ip:port`).
+    pub dns_server: Option<String>,
+    /// Family tried first when the host has IPv4 and IPv6 addresses.
+    pub address_family: AddressFamily,
+    /// Wrap the connection in TLS when set.
+    pub tls: Option<TlsOptions>,
+    /// Speak WebSocket on top of the (TLS) stream when set.
+    pub ws: Option<WsOptions>,
+    /// Tunnel the connection through a proxy.
+    pub proxy: Option<ProxyOptions>,
+    /// Speak telnet on the (TLS) stream when set.
+    pub telnet: Option<TelnetOptions>,
+    /// Connect out, or listen on `host:port` (or `path`) for a single client.
+    pub mode: SocketMode,
+    pub proto: SocketProto,
+    /// Socket file of a Unix domain socket, e.g. /var/run/daemon.sock.
+    pub path: String,
+    /// Server mode: give up when no client connected in time; unset waits
+    /// indefinitely.
+    pub accept_timeout_ms: Option<u64>,
+    /// How long each resolved address gets to accept the connection,
+    /// instead of the OS default of often half a minute or more. Unset
+    /// allows `DEFAULT_CONNECT_TIMEOUT_MS`.
+    pub connect_timeout_ms: Option<u64>,
+    /// Local IP to connect from, e.g. the lab VLAN interface's, so the
+    /// connection leaves through that interface. Unset lets the OS choose.
+    pub local_addr: Option<String>,
+    /// Local port to connect from; unset picks an ephemeral one.
+    pub local_port: Option<u16>,
+}
+
+pub const DEFAULT_CONNECT_TIMEOUT_MS: u64 = 10_000;
+
+/// Probing of an idle TCP connection, so a peer that vanished without a FIN
+/// is noticed. Unset timings keep the OS defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeepaliveOptions {
+    pub enabled: bool,
+    /// Idle time before the first probe.
+    pub idle_ms: Option<u64>,
+    /// Time between unanswered probes.
+    pub interval_ms: Option<u64>,
+    /// Unanswered probes before the connection is dropped. Needs Windows 10
+    /// 1703 or later on Windows.
+    pub probes: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SocketMode {
+    #[default]
+    Client,
+    /// Accept one incoming connection, for devices that dial in themselves.
+    Server,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SocketProto {
+    #[default]
+    Tcp,
+    /// Unix domain stream socket at `path`; not available on Windows.
+    Unix,
+}
+
+impl SocketOpenArgs {
+    /// Session id of the connection these arguments open.
+    pub fn session_id(&self) -> String {
+        match (self.proto, self.mode) {
+            (SocketProto::Tcp, SocketMode::Client) => dns::host_port(&self.host, self.port),
+            (SocketProto::Tcp, SocketMode::Server) => format!("listen:{}", dns::host_port(&self.host, self.port)),
+            (SocketProto::Unix, SocketMode::Client) => format!("unix:{}", self.path),
+            (SocketProto::Unix, SocketMode::Server) => format!("listen:unix:{}", self.path),
+        }
+    }
+}
+
+/// Socket options as actually applied by the OS (buffer sizes are often rounded or doubled).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SocketOptionsReport {
+    pub nodelay: bool,
+    pub keepalive: bool,
+    pub linger_ms: Option<u64>,
+    pub send_buffer_size: usize,
+    pub recv_buffer_size: usize,
+}
 
 pub struct SocketConnection {
-    pub tx: UnboundedSender<Vec<u8>>,
+    pub tx: UnboundedSender<Outgoing>,
+    /// Fire to send FIN after pending writes while keeping the reader running.
+    pub shutdown: oneshot::Sender<()>,
+    /// Stops the reader once the session is closed.
+    pub reader: AbortHandle,
+    pub options: SocketOptionsReport,
+    /// Protocol agreed via TLS ALPN.
+    pub alpn: Option<String>,
+    /// WebSocket subprotocol selected by the server.
+    pub ws_protocol: Option<String>,
+    /// Remote address; in server mode, the client that connected.
+    pub peer: Option<SocketAddr>,
+    /// Raw telnet commands, e.g. RFC 2217 port settings, on telnet sessions.
+    pub control: Option<UnboundedSender<Vec<u8>>>,
+}
+
+/// Which stage of opening a connection failed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// Name resolution, routing, refused/timed-out connects.
+    Network,
+    /// Unreadable or invalid certificates/keys, or a certificate rejected by a peer.
+    Certificate,
+    /// Any other TLS or WebSocket negotiation failure.
+    Handshake,
+    /// Invalid option values (e.g. malformed header names).
+    Config,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectError {
+    pub category: ErrorCategory,
+    pub message: String,
+    /// Fingerprint of an untrusted or mismatching peer certificate (TOFU).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_fingerprint: Option<String>,
+}
+
+impl ConnectError {
+    pub fn new(category: ErrorCategory, message: impl ToString) -> Self {
+        Self { category, message: message.to_string(), peer_fingerprint: None }
+    }
+}
+
+impl std::fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tag = match self.category {
+            ErrorCategory::Network => "network",
+            ErrorCategory::Certificate => "certificate",
+            ErrorCategory::Handshake => "handshake",
+            ErrorCategory::Config => "config",
+        };
+        write!(f, "[{tag}] {}", self.message)
+    }
 }
 
+/// How far opening a connection has got, reported while it is still pending.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum OpenProgress {
+    /// Client mode: dialing, giving each address `timeout_ms`.
+    Connecting { timeout_ms: u64 },
+    /// Server mode: bound to an address or socket path and waiting for a client.
+    Listening { addr: String },
+    /// Server mode: a client connected from `peer` ("ip:port", or "pid N" on
+    /// a Unix socket when the OS reports it).
+    Accepted { peer: String },
+    /// The TLS handshake started.
+    Handshaking,
+    /// The TLS handshake completed.
+    Handshaken,
+}
+
+/// `on_data` gets the frame type along with each WebSocket message, and
+/// `None` for stream data. `on_close` runs once the peer has closed or the
+/// connection failed.
 pub async fn connect_tcp(
-    host: String,
-    port: u16,
-    on_data: impl Fn(Vec<u8>) + Send + 'static,
-) -> Result<SocketConnection, String> {
+    args: &SocketOpenArgs,
+    on_progress: impl Fn(OpenProgress) + Send + Sync,
+    on_data: impl Fn(Vec<u8>, Option<FrameKind>) + Send + 'static,
+    on_telnet: impl Fn(telnet::Event) + Send + 'static,
+    on_close: impl FnOnce() + Send + 'static,
+) -> Result<SocketConnection, ConnectError> {
+    if args.telnet.is_some() && args.ws.is_some() {
+        return Err(ConnectError::new(ErrorCategory::Config, "Telnet can't be combined with WebSocket"));
+    }
+    if args.proto == SocketProto::Unix {
+        return connect_unix(args, &on_progress, on_data, on_close).await;
+    }
+    let stream = match args.mode {
+        SocketMode::Client => {
+            on_progress(OpenProgress::Connecting { timeout_ms: args.connect_timeout_ms.unwrap_or(DEFAULT_CONNECT_TIMEOUT_MS) });
+            open_stream(args).await?
+        }
+        SocketMode::Server => accept_one(args, &on_progress).await?,
+    };
+    let peer = stream.peer_addr().ok();
+    let options = apply_options(&stream, args).map_err(|e| ConnectError::new(ErrorCategory::Network, e))?;
+
+    match &args.tls {
+        Some(tls_opts) => {
+            on_progress(OpenProgress::Handshaking);
+            let stream = match args.mode {
+                SocketMode::Client => tls::connect(stream, dns::bare_host(&args.host), tls_opts).await?,
+                SocketMode::Server => tls::accept(stream, tls_opts).await?,
+            };
+            on_progress(OpenProgress::Handshaken);
+            let alpn = tls::negotiated_alpn(&stream);
+            start(stream, args, options, alpn, on_data, on_telnet, on_close).await
+        }
+        None => start(stream, args, options, None, on_data, on_telnet, on_close).await,
+    }
+    .map(|conn| SocketConnection { peer, ..conn })
+}
+
+/// Listen on `host:port` (any interface when `host` is empty) until one client
+/// connects, then stop listening.
+async fn accept_one(args: &SocketOpenArgs, on_progress: &impl Fn(OpenProgress)) -> Result<TcpStream, ConnectError> {
+    if args.ws.is_some() || args.proxy.is_some() {
+        return Err(ConnectError::new(ErrorCategory::Config, "Server mode supports plain TCP and TLS only"));
+    }
+    let net_err = |e: std::io::Error| ConnectError::new(ErrorCategory::Network, e);
+    let host = if args.host.is_empty() { "0.0.0.0" } else { args.host.as_str() };
+    let listener = match dns::ip_literal(host, args.port).map_err(|e| ConnectError::new(ErrorCategory::Config, e))? {
+        Some(addr) => tokio::net::TcpListener::bind(addr).await,
+        None => tokio::net::TcpListener::bind((host, args.port)).await,
+    }
+    .map_err(net_err)?;
+    on_progress(OpenProgress::Listening { addr: listener.local_addr().map_err(net_err)?.to_string() });
+    let accept = listener.accept();
+    let (stream, peer) = match args.accept_timeout_ms {
+        Some(ms) => tokio::time::timeout(Duration::from_millis(ms), accept)
+            .await
+            .map_err(|_| ConnectError::new(ErrorCategory::Network, format!("No client connected within {ms} ms")))?,
+        None => accept.await,
+    }
+    .map_err(net_err)?;
+    on_progress(OpenProgress::Accepted { peer: peer.to_string() });
+    Ok(stream)
+}
+
+/// Connect to, or accept one client on, the Unix domain socket at `args.path`.
+#[cfg(unix)]
+async fn connect_unix(
+    args: &SocketOpenArgs,
+    on_progress: &impl Fn(OpenProgress),
+    on_data: impl Fn(Vec<u8>, Option<FrameKind>) + Send + 'static,
+    on_close: impl FnOnce() + Send + 'static,
+) -> Result<SocketConnection, ConnectError> {
+    if args.tls.is_some() || args.ws.is_some() || args.proxy.is_some() {
+        return Err(ConnectError::new(ErrorCategory::Config, "Unix sockets support plain streams only"));
+    }
+    let stream = match args.mode {
+        SocketMode::Client => tokio::net::UnixStream::connect(&args.path)
+            .await
+            .map_err(|e| ConnectError::new(ErrorCategory::Network, format!("{}: {e}", args.path)))?,
+        SocketMode::Server => accept_unix(args, on_progress).await?,
+    };
+    let options = apply_unix_options(&stream, args).map_err(|e| ConnectError::new(ErrorCategory::Network, e))?;
+    let (tx, shutdown, reader) = spawn_io_notify(stream, move |data| on_data(data, None), on_close);
+    Ok(SocketConnection { tx, shutdown, reader, options, alpn: None, ws_protocol: None, peer: None, control: None })
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(
+    _args: &SocketOpenArgs,
+    _on_progress: &impl Fn(OpenProgress),
+    _on_data: impl Fn(Vec<u8>, Option<FrameKind>) + Send + 'static,
+    _on_close: impl FnOnce() + Send + 'static,
+) -> Result<SocketConnection, ConnectError> {
+    Err(ConnectError::new(ErrorCategory::Config, "Unix domain sockets are not supported on this platform"))
+}
+
+/// Listen on `args.path` until one client connects, then remove the socket
+/// file again. A file left behind by a listener that has gone away is
+/// replaced; a live one is an error.
+#[cfg(unix)]
+async fn accept_unix(args: &SocketOpenArgs, on_progress: &impl Fn(OpenProgress)) -> Result<tokio::net::UnixStream, ConnectError> {
+    use std::os::unix::fs::FileTypeExt;
+    let net_err = |e: std::io::Error| ConnectError::new(ErrorCategory::Network, format!("{}: {e}", args.path));
+    let path = std::path::Path::new(&args.path);
+    let stale = std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket())
+        && std::os::unix::net::UnixStream::connect(path).is_err();
+    if stale {
+        std::fs::remove_file(path).map_err(net_err)?;
+    }
+    let listener = tokio::net::UnixListener::bind(path).map_err(net_err)?;
+    on_progress(OpenProgress::Listening { addr: args.path.clone() });
+    let accept = listener.accept();
+    let accepted = match args.accept_timeout_ms {
+        Some(ms) => tokio::time::timeout(Duration::from_millis(ms), accept)
+            .await
+            .unwrap_or_else(|_| Err(std::io::Error::new(std::io::ErrorKind::TimedOut, format!("No client connected within {ms} ms")))),
+        None => accept.await,
+    };
+    drop(listener);
+    let _ = std::fs::remove_file(path);
+    let (stream, _) = accepted.map_err(net_err)?;
+    let peer = stream.peer_cred().ok().and_then(|c| c.pid()).map_or("unknown process".into(), |pid| format!("pid {pid}"));
+    on_progress(OpenProgress::Accepted { peer });
+    Ok(stream)
+}
+
+/// Start raw, telnet or WebSocket I/O on a connected (and possibly TLS-wrapped) stream.
+async fn start<S>(
+    stream: S,
+    args: &SocketOpenArgs,
+    options: SocketOptionsReport,
+    alpn: Option<String>,
+    on_data: impl Fn(Vec<u8>, Option<FrameKind>) + Send + 'static,
+    on_telnet: impl Fn(telnet::Event) + Send + 'static,
+    on_close: impl FnOnce() + Send + 'static,
+) -> Result<SocketConnection, ConnectError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    match &args.ws {
+        Some(ws_opts) => {
+            let on_frame = move |data, kind| on_data(data, Some(kind));
+            let conn = ws::connect(stream, &args.host, args.port, args.tls.is_some(), ws_opts, on_frame, on_close).await?;
+            Ok(SocketConnection {
+                tx: conn.tx,
+                shutdown: conn.shutdown,
+                reader: conn.reader,
+                options,
+                alpn,
+                ws_protocol: conn.protocol,
+                peer: None,
+                control: None,
+            })
+        }
+        None => {
+            let (tx, shutdown, reader, control) = match &args.telnet {
+                Some(telnet_opts) => {
+                    let (tx, shutdown, reader, control) = telnet::spawn(stream, telnet_opts, move |data| on_data(data, None), on_telnet, on_close);
+                    (tx, shutdown, reader, Some(control))
+                }
+                None => {
+                    let (tx, shutdown, reader) = spawn_io_notify(stream, move |data| on_data(data, None), on_close);
+                    (tx, shutdown, reader, None)
+                }
+            };
+            Ok(SocketConnection { tx, shutdown, reader, options, alpn, ws_protocol: None, peer: None, control })
+        }
+    }
+}
+
+/// Resolve and connect the plain TCP stream, tunnelling through the proxy if one is set.
+pub async fn open_stream(args: &SocketOpenArgs) -> Result<TcpStream, ConnectError> {
+    let Some(proxy) = &args.proxy else {
+        return dial(&args.host, args.port, args).await;
+    };
+    let mut stream = dial(&proxy.host, proxy.port, args).await?;
+    // The proxy resolves the target itself unless a host override pins it
+    let target = args.host_overrides.get(&args.host).map_or(args.host.clone(), |ip| ip.to_string());
+    proxy::handshake(&mut stream, &target, args.port, proxy).await?;
+    Ok(stream)
+}
+
+async fn dial(host: &str, port: u16, args: &SocketOpenArgs) -> Result<TcpStream, ConnectError> {
+    let net_err = |e: String| ConnectError::new(ErrorCategory::Network, e);
+    let addrs = dns::resolve(host, port, &args.host_overrides, args.resolver, args.dns_server.as_deref(), args.address_family)
+        .await
+        .map_err(net_err)?;
+    let local = local_addr(args).map_err(|e| ConnectError::new(ErrorCategory::Config, e))?;
+    let timeout = Duration::from_millis(args.connect_timeout_ms.unwrap_or(DEFAULT_CONNECT_TIMEOUT_MS));
+    // Addresses are tried in turn, each with its own deadline, so one that
+    // silently drops the SYN doesn't use up the time of the others
+    let mut last_err = format!("No addresses for {host}");
+    for addr in addrs {
+        if local.is_some_and(|l| l.is_ipv4() != addr.is_ipv4()) {
+            last_err = format!("{addr}: not reachable from local address {}", args.local_addr.as_deref().unwrap_or_default());
+            continue;
+        }
+        let bind = local.or_else(|| {
+            let any: IpAddr = if addr.is_ipv4() { Ipv4Addr::UNSPECIFIED.into() } else { Ipv6Addr::UNSPECIFIED.into() };
+            args.local_port.map(|port| SocketAddr::new(any, port))
+        });
+        match tokio::time::timeout(timeout, connect(addr, bind)).await {
+            Ok(Ok(s)) => return Ok(s),
+            Ok(Err(e)) => last_err = format!("{addr}: {e}"),
+            Err(_) => last_err = format!("{addr}: no answer within {} ms", timeout.as_millis()),
+        }
+    }
+    Err(net_err(last_err))
+}
+
+/// `args.local_addr` and `local_port` as an address to bind; an IPv6
+/// address may carry a `%interface` scope.
+fn local_addr(args: &SocketOpenArgs) -> Result<Option<SocketAddr>, String> {
+    let Some(host) = args.local_addr.as_deref().filter(|h| !h.is_empty()) else {
+        return Ok(None);
+    };
+    match dns::ip_literal(host, args.local_port.unwrap_or(0))? {
+        Some(addr) => Ok(Some(addr)),
+        None => Err(format!("Local address must be an IP address: {host}")),
+    }
+}
+
+/// Connect to `addr`, from `bind` when set.
+async fn connect(addr: SocketAddr, bind: Option<SocketAddr>) -> std::io::Result<TcpStream> {
     // Retry on EINTR (macOS os error 4 — connect() interrupted by signal)
-    let stream = loop {
-        match TcpStream::connect(format!("{host}:{port}")).await {
-            Ok(s) => break Ok(s),
+    loop {
+        let result = match bind {
+            Some(local) => {
+                let sock = if addr.is_ipv4() { TcpSocket::new_v4()? } else { TcpSocket::new_v6()? };
+                // A fixed local port would otherwise be blocked while the
+                // previous connection from it lingers in TIME_WAIT
+                sock.set_reuseaddr(true)?;
+                sock.bind(local).map_err(|e| std::io::Error::new(e.kind(), format!("cannot bind {local}: {e}")))?;
+                sock.connect(addr).await
+            }
+            None => TcpStream::connect(addr).await,
+        };
+        match result {
             Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
-            Err(e) => break Err(e.to_string()),
+            result => return result,
         }
-    }?;
+    }
+}
 
+/// A stream's writer, its write-side shutdown trigger and a handle that
+/// stops its reader.
+pub type Io = (UnboundedSender<Outgoing>, oneshot::Sender<()>, AbortHandle);
+
+/// Start the reader and writer tasks for a connected byte stream.
+pub fn spawn_io<S>(stream: S, on_data: impl Fn(Vec<u8>) + Send + 'static) -> Io
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    spawn_io_notify(stream, on_data, || {})
+}
+
+/// Like `spawn_io`, calling `on_close` once the peer has closed or the read failed.
+pub fn spawn_io_notify<S>(
+    stream: S,
+    on_data: impl Fn(Vec<u8>) + Send + 'static,
+    on_close: impl FnOnce() + Send + 'static,
+) -> Io
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
     let (mut reader, mut writer) = tokio::io::split(stream);
-    let (tx, mut rx): (UnboundedSender<Vec<u8>>, UnboundedReceiver<Vec<u8>>) = mpsc::unbounded_channel();
+    let (tx, mut rx): (UnboundedSender<Outgoing>, UnboundedReceiver<Outgoing>) = mpsc::unbounded_channel();
+    let (shutdown, mut shutdown_rx) = oneshot::channel::<()>();
 
-    tokio::spawn(async move {
+    let read = tokio::spawn(async move {
         let mut buf = vec![0u8; 4096];
         loop {
             match reader.read(&mut buf).await {
@@ -31,15 +477,303 @@ pub async fn connect_tcp(
                 Ok(n) => on_data(buf[..n].to_vec()),
             }
         }
+        on_close();
     });
 
     tokio::spawn(async move {
-        while let Some(data) = rx.recv().await {
-            if writer.write_all(&data).await.is_err() {
-                break;
+        loop {
+            tokio::select! {
+                biased;
+                out = rx.recv() => match out {
+                    Some(out) => if !write(&mut writer, out).await { break },
+                    None => {
+                        // The session was closed
+                        let _ = writer.shutdown().await;
+                        break;
+                    }
+                },
+                Ok(()) = &mut shutdown_rx => {
+                    // Flush whatever was queued before the shutdown request, then FIN
+                    while let Ok(out) = rx.try_recv() {
+                        if !write(&mut writer, out).await { break; }
+                    }
+                    let _ = writer.shutdown().await;
+                    break;
+                }
             }
         }
     });
 
-    Ok(SocketConnection { tx })
+    (tx, shutdown, read.abort_handle())
+}
+
+/// Write one queued message and report the outcome to its sender.
+async fn write(writer: &mut (impl AsyncWrite + Unpin), out: Outgoing) -> bool {
+    let result = writer.write_all(&out.data).await.map_err(|e| e.to_string());
+    let ok = result.is_ok();
+    out.done(result);
+    ok
+}
+
+fn apply_options(stream: &TcpStream, args: &SocketOpenArgs) -> std::io::Result<SocketOptionsReport> {
+    let sock = socket2::SockRef::from(stream);
+    if let Some(nodelay) = args.nodelay {
+        sock.set_tcp_nodelay(nodelay)?;
+    }
+    if let Some(ms) = args.linger_ms {
+        sock.set_linger(Some(Duration::from_millis(ms)))?;
+    }
+    if let Some(size) = args.send_buffer_size {
+        sock.set_send_buffer_size(size)?;
+    }
+    if let Some(size) = args.recv_buffer_size {
+        sock.set_recv_buffer_size(size)?;
+    }
+    match args.keepalive {
+        Some(ka) if ka.enabled => {
+            let mut params = socket2::TcpKeepalive::new();
+            if let Some(ms) = ka.idle_ms {
+                params = params.with_time(Duration::from_millis(ms));
+            }
+            if let Some(ms) = ka.interval_ms {
+                params = params.with_interval(Duration::from_millis(ms));
+            }
+            if let Some(probes) = ka.probes {
+                params = params.with_retries(probes);
+            }
+            sock.set_tcp_keepalive(&params)?;
+        }
+        Some(_) => sock.set_keepalive(false)?,
+        None => {}
+    }
+    Ok(SocketOptionsReport {
+        nodelay: sock.tcp_nodelay()?,
+        keepalive: sock.keepalive()?,
+        linger_ms: sock.linger()?.map(|d| d.as_millis() as u64),
+        send_buffer_size: sock.send_buffer_size()?,
+        recv_buffer_size: sock.recv_buffer_size()?,
+    })
+}
+
+/// Buffer sizes of a Unix socket; the TCP-only options are reported as off.
+#[cfg(unix)]
+fn apply_unix_options(stream: &tokio::net::UnixStream, args: &SocketOpenArgs) -> std::io::Result<SocketOptionsReport> {
+    let sock = socket2::SockRef::from(stream);
+    if let Some(size) = args.send_buffer_size {
+        sock.set_send_buffer_size(size)?;
+    }
+    if let Some(size) = args.recv_buffer_size {
+        sock.set_recv_buffer_size(size)?;
+    }
+    Ok(SocketOptionsReport {
+        nodelay: false,
+        keepalive: false,
+        linger_ms: None,
+        send_buffer_size: sock.send_buffer_size()?,
+        recv_buffer_size: sock.recv_buffer_size()?,
+    })
+}
+
+/// Larger than any UDP payload, so nothing is ever truncated.
+pub const UDP_MAX_BUFFER: usize = 65536;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UdpOptions {
+    /// Receive buffer per datagram; longer datagrams are truncated.
+    pub recv_buffer: usize,
+    /// Where datagrams carry a sequence number, for loss and reorder counts.
+    pub sequence: Option<SeqRule>,
+    /// Local address to bind, e.g. one interface's IP. All interfaces when unset.
+    pub bind_host: Option<String>,
+    /// Group to join; needs a local port.
+    pub multicast: Option<MulticastOptions>,
+    /// Set SO_BROADCAST, to send to 255.255.255.255 or a subnet broadcast address.
+    pub broadcast: bool,
+    /// Family tried first when the remote host has IPv4 and IPv6 addresses.
+    pub address_family: AddressFamily,
+}
+
+impl Default for UdpOptions {
+    fn default() -> Self {
+        Self {
+            recv_buffer: 4096,
+            sequence: None,
+            bind_host: None,
+            multicast: None,
+            broadcast: false,
+            address_family: AddressFamily::Any,
+        }
+    }
+}
+
+/// Multicast membership of a UDP session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MulticastOptions {
+    /// Group address, e.g. 239.255.0.1 or ff02::fb.
+    pub group: String,
+    /// Interface to join on: its IPv4 address, or its index for an IPv6
+    /// group. The OS picks one when unset.
+    #[serde(default)]
+    pub interface: Option<String>,
+    /// TTL (hop limit for IPv6) of datagrams sent to the group.
+    #[serde(default)]
+    pub ttl: Option<u32>,
+}
+
+/// Bind `local_port` with address reuse, so other listeners on the host can
+/// share the group, and join `mc.group`. Returns the socket and the group.
+fn bind_multicast(mc: &MulticastOptions, bind_host: Option<&str>, local_port: Option<u16>) -> Result<(UdpSocket, IpAddr), String> {
+    let group: IpAddr = mc.group.parse().map_err(|_| format!("Invalid multicast group {}", mc.group))?;
+    if !group.is_multicast() {
+        return Err(format!("{group} is not a multicast address"));
+    }
+    let port = local_port.ok_or("Multicast needs a local port")?;
+    let ip: IpAddr = match bind_host {
+        Some(h) => h.parse().map_err(|_| format!("Invalid bind address {h}"))?,
+        None if group.is_ipv4() => Ipv4Addr::UNSPECIFIED.into(),
+        None => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let addr = SocketAddr::new(ip, port);
+    let sock = socket2::Socket::new(socket2::Domain::for_address(addr), socket2::Type::DGRAM, Some(socket2::Protocol::UDP))
+        .map_err(|e| e.to_string())?;
+    sock.set_reuse_address(true).map_err(|e| e.to_string())?;
+    sock.set_nonblocking(true).map_err(|e| e.to_string())?;
+    sock.bind(&addr.into()).map_err(|e| format!("Cannot bind {addr}: {e}"))?;
+    let joined = match group {
+        IpAddr::V4(g) => {
+            let iface = match &mc.interface {
+                Some(i) => i.parse().map_err(|_| format!("Invalid interface address {i}"))?,
+                None => Ipv4Addr::UNSPECIFIED,
+            };
+            sock.join_multicast_v4(&g, &iface)
+                .and_then(|()| mc.ttl.map_or(Ok(()), |ttl| sock.set_multicast_ttl_v4(ttl)))
+        }
+        IpAddr::V6(g) => {
+            let iface = match &mc.interface {
+                Some(i) => i.parse().map_err(|_| format!("IPv6 groups take an interface index, not {i}"))?,
+                None => 0,
+            };
+            sock.join_multicast_v6(&g, iface)
+                .and_then(|()| mc.ttl.map_or(Ok(()), |hops| sock.set_multicast_hops_v6(hops)))
+        }
+    };
+    joined.map_err(|e| format!("Cannot join {group}: {e}"))?;
+    let sock = UdpSocket::from_std(sock.into()).map_err(|e| e.to_string())?;
+    Ok((sock, group))
+}
+
+/// WSAEMSGSIZE: the datagram was larger than the buffer.
+#[cfg(windows)]
+const MSG_SIZE_ERROR: i32 = 10040;
+
+pub struct UdpConnection {
+    pub tx: UnboundedSender<Outgoing>,
+    /// Destination for outgoing datagrams; may be switched at runtime.
+    pub peer: Arc<Mutex<Option<SocketAddr>>>,
+    /// Stops the reader, releasing the socket, once the session is closed.
+    pub reader: AbortHandle,
+}
+
+/// Open a UDP socket. `remote` is the initial destination (optional when
+/// `local_port` is bound); with `follow_sender` the destination follows the
+/// most recent sender and `on_peer` is called whenever it changes.
+/// `on_data` gets each datagram, its sender if known and whether it filled
+/// the receive buffer, i.e. was probably cut short.
+pub async fn open_udp(
+    remote: Option<(&str, u16)>,
+    options: &UdpOptions,
+    local_port: Option<u16>,
+    follow_sender: bool,
+    on_data: impl Fn(Vec<u8>, Option<SocketAddr>, bool) + Send + 'static,
+    on_peer: impl Fn(SocketAddr) + Send + 'static,
+) -> Result<UdpConnection, String> {
+    let resolved = match remote {
+        Some((host, port)) => {
+            let addrs = dns::resolve(host, port, &HashMap::new(), ResolverMode::System, None, options.address_family).await?;
+            addrs.into_iter().next()
+        }
+        None => None,
+    };
+    let bind_host = options.bind_host.as_deref().filter(|h| !h.is_empty());
+    let (sock, group) = match &options.multicast {
+        Some(mc) => {
+            let (sock, group) = bind_multicast(mc, bind_host, local_port)?;
+            (sock, Some(group))
+        }
+        None => {
+            // The wildcard address must match the remote's family
+            let any = if resolved.is_some_and(|a| a.is_ipv6()) { "::" } else { "0.0.0.0" };
+            let bind_host = bind_host.unwrap_or(any);
+            let port = local_port.unwrap_or(0);
+            let bound = match dns::ip_literal(bind_host, port)? {
+                Some(addr) => UdpSocket::bind(addr).await,
+                None => UdpSocket::bind((bind_host, port)).await,
+            };
+            let sock = bound.map_err(|e| format!("Cannot bind {}: {e}", dns::host_port(bind_host, port)))?;
+            (sock, None)
+        }
+    };
+    if options.broadcast {
+        sock.set_broadcast(true).map_err(|e| format!("Cannot enable broadcast: {e}"))?;
+    }
+    let (recv_buffer, broadcast) = (options.recv_buffer, options.broadcast);
+    // Without a remote, transmissions go to the group
+    let initial = resolved.or_else(|| group.zip(local_port).map(|(g, port)| SocketAddr::new(g, port)));
+
+    let sock = Arc::new(sock);
+    let peer = Arc::new(Mutex::new(initial));
+    let (tx, mut rx): (UnboundedSender<Outgoing>, UnboundedReceiver<Outgoing>) = mpsc::unbounded_channel();
+
+    let (reader, reader_peer) = (Arc::clone(&sock), Arc::clone(&peer));
+    let read = tokio::spawn(async move {
+        let mut buf = vec![0u8; recv_buffer];
+        loop {
+            match reader.recv_from(&mut buf).await {
+                Ok((n, from)) => {
+                    if follow_sender {
+                        let changed = {
+                            let mut p = reader_peer.lock();
+                            let changed = *p != Some(from);
+                            *p = Some(from);
+                            changed
+                        };
+                        if changed {
+                            on_peer(from);
+                        }
+                    }
+                    on_data(buf[..n].to_vec(), Some(from), n == buf.len());
+                }
+                // Windows fills the buffer but reports the overflow as an error
+                #[cfg(windows)]
+                Err(e) if e.raw_os_error() == Some(MSG_SIZE_ERROR) => on_data(buf.clone(), None, true),
+                // ICMP port-unreachable surfaces as a recv error on some platforms; keep listening
+                Err(e) if e.kind() == std::io::ErrorKind::ConnectionReset => continue,
+                Err(_) => break,
+            }
+        }
+    });
+
+    let writer_peer = Arc::clone(&peer);
+    tokio::spawn(async move {
+        while let Some(out) = rx.recv().await {
+            let target = *writer_peer.lock();
+            // Nothing to send to until a peer is configured or has spoken
+            let Some(addr) = target else {
+                out.done(Err("No peer to send to yet".into()));
+                continue;
+            };
+            // A failed datagram fails only its own send; the next may get through
+            let result = sock.send_to(&out.data, addr).await.map(|_| ());
+            // The OS refuses broadcast destinations without SO_BROADCAST
+            let refused = matches!(&result, Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied);
+            out.done(result.map_err(|e| if refused && !broadcast {
+                format!("{e} (enable broadcast to send to {addr})")
+            } else {
+                e.to_string()
+            }));
+        }
+    });
+
+    Ok(UdpConnection { tx, peer, reader: read.abort_handle() })
 }
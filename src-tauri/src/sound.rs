@@ -0,0 +1,51 @@
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Upper bound on repeats, so a typo can't ring for minutes.
+const MAX_TIMES: u32 = 20;
+const GAP: Duration = Duration::from_millis(250);
+
+/// Set while a sequence is playing; requests meanwhile are dropped rather
+/// than queued behind a burst of matching packets.
+static PLAYING: AtomicBool = AtomicBool::new(false);
+
+/// Play the system alert sound `times` times in the background.
+pub fn play(times: u32) {
+    if times == 0 || PLAYING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    std::thread::spawn(move || {
+        for i in 0..times.min(MAX_TIMES) {
+            if i > 0 {
+                std::thread::sleep(GAP);
+            }
+            if !play_once() {
+                break;
+            }
+        }
+        PLAYING.store(false, Ordering::Release);
+    });
+}
+
+/// Play the alert once and wait for it to finish. False if no player worked.
+fn play_once() -> bool {
+    let ok = |cmd: &mut Command| cmd.status().is_ok_and(|s| s.success());
+    #[cfg(target_os = "macos")]
+    {
+        ok(Command::new("afplay").arg("/System/Library/Sounds/Glass.aiff"))
+    }
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        // Play() returns immediately, so wait for the sound before exiting
+        let script = "[System.Media.SystemSounds]::Exclamation.Play(); Start-Sleep -Milliseconds 600";
+        ok(Command::new("powershell").args(["-NoProfile", "-Command", script]).creation_flags(CREATE_NO_WINDOW))
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        ok(Command::new("canberra-gtk-play").arg("--id=bell"))
+            || ok(Command::new("paplay").arg("/usr/share/sounds/freedesktop/stereo/bell.oga"))
+    }
+}
@@ -1,16 +1,24 @@
 use crate::state::{Packet, SplitterConfig};
 use crate::checksum;
+use crate::eol::LineEnding;
 
 pub struct Splitter {
     config: SplitterConfig,
     buf: Vec<u8>,
     in_packet: bool,
+    detected_eol: Option<LineEnding>,
 }
 
 impl Splitter {
     /// Restore a persisted per-session splitter state.
     pub fn with_state(config: SplitterConfig, buf: Vec<u8>, in_packet: bool) -> Self {
-        Self { config, buf, in_packet }
+        Self { config, buf, in_packet, detected_eol: None }
+    }
+
+    /// Line ending detected on the session, used by the "line" method in auto mode.
+    pub fn detected_line_ending(mut self, eol: Option<LineEnding>) -> Self {
+        self.detected_eol = eol;
+        self
     }
 
     /// Extract buffer and parser state for persistence between data callbacks.
@@ -22,6 +30,7 @@ impl Splitter {
         match self.config.method.as_str() {
             "delimiter" => self.feed_delimiter(data, direction, timestamp_ms, session_id, next_id),
             "length_field" => self.feed_length_field(data, direction, timestamp_ms, session_id, next_id),
+            "line" => self.feed_line(data, direction, timestamp_ms, session_id, next_id),
             _ => {
                 let pkt = self.make_packet(data.to_vec(), direction, timestamp_ms, session_id, next_id);
                 vec![pkt]
@@ -110,6 +119,45 @@ impl Splitter {
         packets
     }
 
+    fn feed_line(&mut self, data: &[u8], direction: &str, ts: f64, sid: &str, next_id: &mut u64) -> Vec<Packet> {
+        let mut packets = Vec::new();
+        self.buf.extend_from_slice(data);
+        while let Some((end, term_len)) = self.find_line_end() {
+            let mut payload: Vec<u8> = self.buf.drain(..end + term_len).collect();
+            if !self.config.eof_include {
+                payload.truncate(end);
+            }
+            packets.push(self.make_packet(payload, direction, ts, sid, next_id));
+        }
+        packets
+    }
+
+    /// Offset and length of the first line terminator, or None if more data is needed.
+    fn find_line_end(&self) -> Option<(usize, usize)> {
+        let fixed = match self.config.line_ending.as_str() {
+            "cr" => Some(LineEnding::Cr),
+            "lf" => Some(LineEnding::Lf),
+            "crlf" => Some(LineEnding::Crlf),
+            _ => None,
+        };
+        if let Some(eol) = fixed {
+            return find_seq(&self.buf, eol.bytes()).map(|pos| (pos, eol.bytes().len()));
+        }
+
+        // Auto: CRLF, CR and LF all end a line, CRLF counting once
+        let pos = self.buf.iter().position(|&b| b == b'\r' || b == b'\n')?;
+        if self.buf[pos] == b'\n' {
+            return Some((pos, 1));
+        }
+        match self.buf.get(pos + 1) {
+            Some(b'\n') => Some((pos, 2)),
+            Some(_) => Some((pos, 1)),
+            // A trailing CR ends the line only on devices known to send bare CR
+            None if self.detected_eol == Some(LineEnding::Cr) => Some((pos, 1)),
+            None => None,
+        }
+    }
+
     fn feed_length_field(&mut self, data: &[u8], direction: &str, ts: f64, sid: &str, next_id: &mut u64) -> Vec<Packet> {
         let mut packets = Vec::new();
         self.buf.extend_from_slice(data);
@@ -133,6 +181,12 @@ impl Splitter {
         packets
     }
 
+    /// One packet for a complete message, e.g. a datagram, bypassing the
+    /// split method.
+    pub fn whole(&self, data: Vec<u8>, direction: &str, timestamp_ms: f64, session_id: &str, next_id: &mut u64) -> Packet {
+        self.make_packet(data, direction, timestamp_ms, session_id, next_id)
+    }
+
     fn make_packet(&self, payload: Vec<u8>, direction: &str, timestamp_ms: f64, session_id: &str, next_id: &mut u64) -> Packet {
         let checksum_ok = self.verify_checksum(&payload);
         let id = *next_id;
@@ -145,6 +199,14 @@ impl Splitter {
             bytes: payload,
             checksum_ok,
             session_id: session_id.to_string(),
+            corrected_ts_ms: None,
+            severity: None,
+            tags: Vec::new(),
+            transaction_id: None,
+            device_ts_ms: None,
+            time: String::new(),
+            source: None,
+            frame: None,
         }
     }
 
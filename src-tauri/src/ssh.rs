@@ -0,0 +1,209 @@
+use std::sync::Arc;
+use parking_lot::Mutex;
+use russh::client::{self, Handle};
+use russh::keys::{self, HashAlg, PrivateKeyWithHashAlg, PublicKey};
+use russh::{ChannelMsg, Disconnect};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, UnboundedSender, UnboundedReceiver};
+use tokio::sync::oneshot;
+use crate::dns;
+use crate::outgoing::Outgoing;
+use crate::socket::{self, ConnectError, ErrorCategory, SocketOpenArgs};
+
+/// An interactive shell on an SSH server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SshOptions {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: Option<String>,
+    /// Private key file (OpenSSH or PKCS#8), tried before the password.
+    pub key_path: Option<String>,
+    pub key_passphrase: Option<String>,
+    /// Terminal type and size requested for the shell's pty.
+    pub term: String,
+    pub cols: u32,
+    pub rows: u32,
+    /// SHA256 host key fingerprint as printed by OpenSSH, looked up from the
+    /// TOFU store when not given.
+    pub pinned_fingerprint: Option<String>,
+    /// Accept and pin the host key of a server seen for the first time;
+    /// otherwise an unknown key fails the connect so it can be confirmed.
+    pub accept_new_host_key: bool,
+}
+
+impl Default for SshOptions {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: 22,
+            username: String::new(),
+            password: None,
+            key_path: None,
+            key_passphrase: None,
+            term: "xterm".into(),
+            cols: 80,
+            rows: 24,
+            pinned_fingerprint: None,
+            accept_new_host_key: false,
+        }
+    }
+}
+
+impl SshOptions {
+    /// Session id of the shell these options open.
+    pub fn session_id(&self) -> String {
+        format!("ssh:{}@{}", self.username, self.endpoint())
+    }
+
+    /// `host:port`, the key of the host's pin in the TOFU store.
+    pub fn endpoint(&self) -> String {
+        dns::host_port(&self.host, self.port)
+    }
+}
+
+pub struct SshConnection {
+    pub tx: UnboundedSender<Outgoing>,
+    /// Fire to send EOF after pending writes while keeping the reader running.
+    pub shutdown: oneshot::Sender<()>,
+}
+
+/// A logged-in shell whose I/O has not started yet.
+pub struct SshShell {
+    handle: Handle<HostKey>,
+    channel: russh::Channel<client::Msg>,
+    /// Host key fingerprint; to be pinned when it was accepted as new.
+    pub fingerprint: String,
+}
+
+impl SshShell {
+    /// Start the I/O: received data (stdout and stderr) goes to `on_data`
+    /// until the shell exits or the connection drops, then `on_close` is
+    /// called.
+    pub fn start(self, on_data: impl Fn(Vec<u8>) + Send + 'static, on_close: impl FnOnce() + Send + 'static) -> SshConnection {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        tokio::spawn(run(self.handle, self.channel, rx, shutdown_rx, on_data, on_close));
+        SshConnection { tx, shutdown }
+    }
+}
+
+/// Checks the server's host key against the pin.
+struct HostKey {
+    expected: Option<String>,
+    accept_new: bool,
+    seen: Arc<Mutex<Option<String>>>,
+}
+
+impl client::Handler for HostKey {
+    type Error = russh::Error;
+
+    async fn check_server_key(&mut self, key: &PublicKey) -> Result<bool, Self::Error> {
+        let fingerprint = key.fingerprint(HashAlg::Sha256).to_string();
+        let ok = match &self.expected {
+            Some(expected) => *expected == fingerprint,
+            None => self.accept_new,
+        };
+        *self.seen.lock() = Some(fingerprint);
+        Ok(ok)
+    }
+}
+
+/// Connect, log in and open a shell, to be started with `SshShell::start`.
+pub async fn connect(opts: &SshOptions) -> Result<SshShell, ConnectError> {
+    let net_err = |e: russh::Error| ConnectError::new(ErrorCategory::Network, e);
+    let auth_err = |e: String| ConnectError::new(ErrorCategory::Handshake, e);
+    let args = SocketOpenArgs { host: opts.host.clone(), port: opts.port, ..Default::default() };
+    let stream = socket::open_stream(&args).await?;
+
+    let seen = Arc::new(Mutex::new(None));
+    let handler = HostKey { expected: opts.pinned_fingerprint.clone(), accept_new: opts.accept_new_host_key, seen: Arc::clone(&seen) };
+    let config = Arc::new(client::Config { nodelay: true, ..Default::default() });
+    let mut handle = match client::connect_stream(config, stream, handler).await {
+        Ok(handle) => handle,
+        Err(russh::Error::UnknownKey) => {
+            let fingerprint = seen.lock().take();
+            let message = match (&opts.pinned_fingerprint, &fingerprint) {
+                (Some(_), Some(fp)) => format!("Host key changed: {fp}"),
+                _ => format!("Unknown host key: {}", fingerprint.as_deref().unwrap_or("?")),
+            };
+            let mut err = ConnectError::new(ErrorCategory::Certificate, message);
+            err.peer_fingerprint = fingerprint;
+            return Err(err);
+        }
+        Err(e) => return Err(net_err(e)),
+    };
+    let fingerprint = seen.lock().take().unwrap_or_default();
+
+    authenticate(&mut handle, opts).await.map_err(auth_err)?;
+    let channel = handle.channel_open_session().await.map_err(net_err)?;
+    channel.request_pty(false, &opts.term, opts.cols, opts.rows, 0, 0, &[]).await.map_err(net_err)?;
+    channel.request_shell(false).await.map_err(net_err)?;
+    Ok(SshShell { handle, channel, fingerprint })
+}
+
+/// Log in with the key if one is given, then with the password if the key
+/// was refused or there is none.
+async fn authenticate(handle: &mut Handle<HostKey>, opts: &SshOptions) -> Result<(), String> {
+    let user = opts.username.clone();
+    if let Some(path) = &opts.key_path {
+        let key = keys::load_secret_key(path, opts.key_passphrase.as_deref()).map_err(|e| format!("{path}: {e}"))?;
+        let hash = handle.best_supported_rsa_hash().await.map_err(|e| e.to_string())?.flatten();
+        let result = handle.authenticate_publickey(user.clone(), PrivateKeyWithHashAlg::new(Arc::new(key), hash)).await;
+        if result.map_err(|e| e.to_string())?.success() {
+            return Ok(());
+        }
+    }
+    let result = match (&opts.password, &opts.key_path) {
+        (Some(password), _) => handle.authenticate_password(user, password).await,
+        (None, None) => handle.authenticate_none(user).await,
+        (None, Some(_)) => return Err(format!("SSH server rejected the key for {}", opts.username)),
+    }
+    .map_err(|e| e.to_string())?;
+    match result.success() {
+        true => Ok(()),
+        false => Err(format!("SSH server rejected the login for {}", opts.username)),
+    }
+}
+
+/// Pump queued writes into the shell and its output into `on_data`.
+async fn run(
+    handle: Handle<HostKey>,
+    mut channel: russh::Channel<client::Msg>,
+    mut rx: UnboundedReceiver<Outgoing>,
+    mut shutdown_rx: oneshot::Receiver<()>,
+    on_data: impl Fn(Vec<u8>) + Send + 'static,
+    on_close: impl FnOnce() + Send + 'static,
+) {
+    let mut writing = true;
+    loop {
+        tokio::select! {
+            out = rx.recv(), if writing => match out {
+                Some(out) => {
+                    let result = channel.data(&out.data[..]).await.map_err(|e| e.to_string());
+                    let ok = result.is_ok();
+                    out.done(result);
+                    if !ok { break }
+                }
+                None => break,
+            },
+            Ok(()) = &mut shutdown_rx, if writing => {
+                // Write whatever was queued before the shutdown request, then EOF
+                while let Ok(out) = rx.try_recv() {
+                    let result = channel.data(&out.data[..]).await.map_err(|e| e.to_string());
+                    out.done(result);
+                }
+                let _ = channel.eof().await;
+                writing = false;
+            }
+            msg = channel.wait() => match msg {
+                Some(ChannelMsg::Data { data }) | Some(ChannelMsg::ExtendedData { data, .. }) => on_data(data.to_vec()),
+                Some(ChannelMsg::Eof | ChannelMsg::Close) | None => break,
+                Some(_) => {}
+            },
+        }
+    }
+    on_close();
+    let _ = handle.disconnect(Disconnect::ByApplication, "", "en").await;
+}
@@ -1,7 +1,40 @@
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
+use crate::agent::AgentLink;
+use crate::alerts::Alerts;
+use crate::baud::BaudWatch;
+use crate::bridge::Bridge;
+use crate::classify::Classifier;
+use crate::clock::ClockInfo;
+use crate::compare::Comparator;
+use crate::datagram::DatagramCounter;
+use crate::decoder::MessageCounter;
+use crate::device::VirtualDevice;
+use crate::eol::{EolCounter, LineEnding, TxAppend};
+use crate::filter::CaptureFilter;
+use crate::identify::IdentifyProbe;
+use crate::fixture::Recorder;
+use crate::mirror::Mirror;
+use crate::notify::Notifier;
+use crate::mqtt::MqttClient;
+use crate::pps::Pps;
+use crate::outgoing::Outgoing;
+use crate::quota::QuotaState;
+use crate::rack::RackMember;
+use crate::serial_port::SerialControl;
+use crate::segment::Segmenter;
+use crate::reconnect::{ReconnectPolicy, Reopen};
+use crate::remote::RemoteServer;
+use crate::server::Listener;
+use crate::session_log::SessionLog;
+use crate::sniff::Sniffer;
+use crate::transaction::Pairer;
+use crate::transform::Pipeline;
+use crate::vars::{SessionVars, Vars};
+use crate::ws::FrameKind;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Packet {
@@ -12,11 +45,36 @@ pub struct Packet {
     pub bytes: Vec<u8>,
     pub checksum_ok: Option<bool>,
     pub session_id: String,
+    /// Timestamp with the NTP and per-session clock offsets applied.
+    #[serde(default)]
+    pub corrected_ts_ms: Option<f64>,
+    /// Severity from the first matching classification rule.
+    #[serde(default)]
+    pub severity: Option<String>,
+    /// Categories of all matching classification rules.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Request/response transaction this packet belongs to.
+    #[serde(default)]
+    pub transaction_id: Option<u64>,
+    /// Timestamp on the device's own clock, when a device offset is configured.
+    #[serde(default)]
+    pub device_ts_ms: Option<f64>,
+    /// The (corrected) timestamp as text for the configured time source.
+    #[serde(default)]
+    pub time: String,
+    /// Sender of a received datagram, as "ip:port", or the topic of an MQTT
+    /// message.
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Frame type of a received WebSocket message.
+    #[serde(default)]
+    pub frame: Option<FrameKind>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SplitterConfig {
-    pub method: String,              // "delimiter" | "length_field" | "gap"
+    pub method: String,              // "delimiter" | "length_field" | "gap" | "line"
     pub sof: Vec<u8>,
     pub eof: Vec<u8>,
     pub eof_include: bool,
@@ -29,6 +87,10 @@ pub struct SplitterConfig {
     pub checksum_size: usize,
     #[serde(default)]
     pub checksum_exclude_sof: bool,
+    /// Terminator for the "line" method: "auto" | "cr" | "lf" | "crlf".
+    /// "auto" accepts all three, using the detected ending to resolve a trailing CR.
+    #[serde(default)]
+    pub line_ending: String,
 }
 
 impl Default for SplitterConfig {
@@ -46,6 +108,7 @@ impl Default for SplitterConfig {
             checksum_offset: -2,
             checksum_size: 2,
             checksum_exclude_sof: false,
+            line_ending: "auto".into(),
         }
     }
 }
@@ -73,6 +136,34 @@ pub struct SessionInfo {
     pub connected: bool,
     pub tx_bytes: u64,
     pub rx_bytes: u64,
+    /// Manual clock correction for this session, in ms.
+    #[serde(default)]
+    pub clock_offset_ms: f64,
+    /// Line ending detected on RX.
+    #[serde(default)]
+    pub line_ending: Option<LineEnding>,
+    /// Terminator appended to every send on this session.
+    #[serde(default)]
+    pub tx_append: TxAppend,
+    /// Device clock minus host clock, in ms. Drives `Packet::device_ts_ms`.
+    #[serde(default)]
+    pub device_time_offset_ms: Option<f64>,
+    #[serde(default)]
+    pub meta: SessionMeta,
+    /// Response to the identification probe sent on connect.
+    #[serde(default)]
+    pub identity: Option<String>,
+}
+
+/// User-supplied description of a session, kept when it reconnects.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionMeta {
+    /// Display name used instead of the session name, e.g. "Motor Controller #2".
+    pub label: Option<String>,
+    /// CSS colour, e.g. "#E5484D".
+    pub color: Option<String>,
+    pub notes: String,
 }
 
 /// Per-session splitter state persisted between data callbacks.
@@ -87,24 +178,189 @@ impl Default for SessionSplitterState {
 
 pub struct AppState {
     pub sessions: HashMap<String, SessionInfo>,
+    /// When each session last connected.
+    pub opened_at: HashMap<String, f64>,
+    /// Bench profile each open session came from.
+    pub bench_profiles: HashMap<String, String>,
+    /// Identification probes sent whenever these sessions connect, kept
+    /// across reconnects.
+    pub identify_probes: HashMap<String, IdentifyProbe>,
+    /// Reconnect policies, kept across reconnects.
+    pub auto_reconnect: HashMap<String, ReconnectPolicy>,
+    /// How each session was last opened, for reconnecting.
+    pub reopen: HashMap<String, Reopen>,
+    /// Running reconnect attempts keyed by session id.
+    pub reconnecting: HashMap<String, tokio::task::JoinHandle<()>>,
+    /// Byte and duration limits keyed by session id.
+    pub quotas: HashMap<String, QuotaState>,
     pub packets: Vec<Packet>,
     pub splitter: SplitterConfig,
     pub splitter_states: HashMap<String, SessionSplitterState>,
     pub next_id: u64,
-    pub serial_tx: Option<tokio::sync::mpsc::UnboundedSender<Vec<u8>>>,
-    pub socket_tx: Option<tokio::sync::mpsc::UnboundedSender<Vec<u8>>>,
+    /// Outgoing byte channels keyed by session id.
+    pub connections: HashMap<String, tokio::sync::mpsc::UnboundedSender<Outgoing>>,
+    /// Open per-session log files (rack boards with a log directory).
+    pub logs: HashMap<String, SessionLog>,
+    pub rack: Vec<RackMember>,
+    pub clock: ClockInfo,
+    /// Current destination of each UDP session.
+    pub udp_peers: HashMap<String, Arc<Mutex<Option<SocketAddr>>>>,
+    /// Datagram size and sequence counters of UDP sessions.
+    pub datagrams: HashMap<String, DatagramCounter>,
+    /// Write-side shutdown triggers of TCP sessions.
+    pub tcp_shutdown: HashMap<String, tokio::sync::oneshot::Sender<()>>,
+    /// Reader tasks of stream and UDP sessions, stopped when the session is
+    /// closed.
+    pub readers: HashMap<String, tokio::task::AbortHandle>,
+    /// Server mode connects still waiting for their client; sending on the
+    /// trigger stops listening.
+    pub pending_accepts: HashMap<String, tokio::sync::oneshot::Sender<()>>,
+    /// Running TCP servers keyed by server id.
+    pub servers: HashMap<String, Listener>,
+    /// The WebSocket control API, while it runs.
+    pub remote_api: Option<RemoteServer>,
+    /// Running virtual devices keyed by device id.
+    pub devices: HashMap<String, VirtualDevice>,
+    /// Runtime settings handles of serial sessions.
+    pub serial_controls: HashMap<String, SerialControl>,
+    /// Passive captures of serial ports owned by other programs.
+    pub sniffers: HashMap<String, Sniffer>,
+    /// Framing-error detection on serial RX.
+    pub baud_watch: BaudWatch,
+    /// Copies of each session's raw RX bytes for backend-driven exchanges.
+    pub rx_taps: HashMap<String, Vec<tokio::sync::mpsc::UnboundedSender<Vec<u8>>>>,
+    /// Copies of each session's TX bytes, for mirrors.
+    pub tx_taps: HashMap<String, Vec<tokio::sync::mpsc::UnboundedSender<Vec<u8>>>>,
+    /// Running +CMT listeners keyed by session id.
+    pub sms_watchers: HashMap<String, tokio::task::JoinHandle<()>>,
+    /// Running fuzz runs keyed by session id.
+    pub fuzzers: HashMap<String, tokio::task::JoinHandle<()>>,
+    /// Secondary sinks copying a session's RX stream, keyed by mirror id.
+    pub mirrors: HashMap<u64, Mirror>,
+    pub next_mirror_id: u64,
+    pub classifier: Classifier,
+    pub notifier: Notifier,
+    pub alerts: Alerts,
+    /// Periodic check of the alert rules, while there are any.
+    pub alert_watch: Option<tokio::task::JoinHandle<()>>,
+    /// RX capture filters keyed by session id.
+    pub capture_filters: HashMap<String, CaptureFilter>,
+    /// Packet/byte count segmentation keyed by session id.
+    pub segmenters: HashMap<String, Segmenter>,
+    /// RX transformation pipelines keyed by session id.
+    pub pipelines: HashMap<String, Pipeline>,
+    /// Raw RX being recorded as a replayable fixture, keyed by session id.
+    pub fixture_recordings: HashMap<String, Recorder>,
+    /// RX line terminator counts per session.
+    pub eol_counters: HashMap<String, EolCounter>,
+    /// Active stream comparison between two sessions.
+    pub compare: Option<Comparator>,
+    pub pairer: Pairer,
+    /// Message-type counters of sessions with a protocol decoder selected.
+    pub decoders: HashMap<String, MessageCounter>,
+    /// Template variables and their RX captures, keyed by session id.
+    pub session_vars: HashMap<String, SessionVars>,
+    /// PPS time-mark correlation keyed by session id.
+    pub pps: HashMap<String, Pps>,
+    /// Broker clients of MQTT sessions, for subscribing and publishing.
+    pub mqtt: HashMap<String, MqttClient>,
+    /// Raw telnet command senders of RFC 2217 sessions.
+    pub com_ports: HashMap<String, tokio::sync::mpsc::UnboundedSender<Vec<u8>>>,
+    /// Sessions shared with TCP clients, keyed by session id.
+    pub bridges: HashMap<String, Bridge>,
+    /// Attached agents keyed by `host:port`.
+    pub agents: HashMap<String, AgentLink>,
+}
+
+impl AppState {
+    /// Add or replace a session, keeping the metadata of the one it replaces.
+    /// Returns the stored copy.
+    pub fn insert_session(&mut self, mut session: SessionInfo) -> SessionInfo {
+        if let Some(old) = self.sessions.get(&session.id) {
+            session.meta = old.meta.clone();
+        }
+        if session.connected {
+            self.opened_at.insert(session.id.clone(), now_ms());
+        }
+        self.sessions.insert(session.id.clone(), session.clone());
+        session
+    }
+
+    /// Template variables of `session_id`.
+    pub fn vars_of(&self, session_id: &str) -> Vars {
+        self.session_vars.get(session_id).map(|v| v.values.clone()).unwrap_or_default()
+    }
+
+    /// Absolute timestamp for `session_id` after NTP and manual offsets, if any apply.
+    pub fn corrected_ts(&self, session_id: &str, ts: f64) -> Option<f64> {
+        let manual = self.sessions.get(session_id).map_or(0.0, |s| s.clock_offset_ms);
+        if self.clock.ntp_offset_ms.is_none() && manual == 0.0 {
+            return None;
+        }
+        Some(ts + self.clock.ntp_offset_ms.unwrap_or(0.0) + manual)
+    }
+
+    /// Timestamp on `session_id`'s device clock, if a device offset is set.
+    pub fn device_ts(&self, session_id: &str, ts: f64) -> Option<f64> {
+        let offset = self.sessions.get(session_id)?.device_time_offset_ms?;
+        Some(ts + offset)
+    }
 }
 
 impl Default for AppState {
     fn default() -> Self {
         Self {
             sessions: HashMap::new(),
+            opened_at: HashMap::new(),
+            bench_profiles: HashMap::new(),
+            identify_probes: HashMap::new(),
+            auto_reconnect: HashMap::new(),
+            reopen: HashMap::new(),
+            reconnecting: HashMap::new(),
+            quotas: HashMap::new(),
             packets: Vec::new(),
             splitter: SplitterConfig::default(),
             splitter_states: HashMap::new(),
             next_id: 1,
-            serial_tx: None,
-            socket_tx: None,
+            connections: HashMap::new(),
+            logs: HashMap::new(),
+            rack: Vec::new(),
+            clock: ClockInfo::default(),
+            udp_peers: HashMap::new(),
+            tcp_shutdown: HashMap::new(),
+            readers: HashMap::new(),
+            pending_accepts: HashMap::new(),
+            servers: HashMap::new(),
+            remote_api: None,
+            devices: HashMap::new(),
+            serial_controls: HashMap::new(),
+            sniffers: HashMap::new(),
+            capture_filters: HashMap::new(),
+            pipelines: HashMap::new(),
+            segmenters: HashMap::new(),
+            fixture_recordings: HashMap::new(),
+            datagrams: HashMap::new(),
+            baud_watch: BaudWatch::default(),
+            rx_taps: HashMap::new(),
+            tx_taps: HashMap::new(),
+            sms_watchers: HashMap::new(),
+            fuzzers: HashMap::new(),
+            mirrors: HashMap::new(),
+            next_mirror_id: 1,
+            classifier: Classifier::default(),
+            notifier: Notifier::default(),
+            alerts: Alerts::default(),
+            alert_watch: None,
+            eol_counters: HashMap::new(),
+            compare: None,
+            pairer: Pairer::default(),
+            decoders: HashMap::new(),
+            session_vars: HashMap::new(),
+            pps: HashMap::new(),
+            mqtt: HashMap::new(),
+            com_ports: HashMap::new(),
+            bridges: HashMap::new(),
+            agents: HashMap::new(),
         }
     }
 }
@@ -116,9 +372,5 @@ pub fn new_state() -> SharedState {
 }
 
 pub fn now_ms() -> f64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs_f64()
-        * 1000.0
+    crate::clock::current_ms()
 }
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::serial_port::LineErrors;
+use crate::state::AppState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CloseReason {
+    /// Closed on request.
+    Disconnect,
+    /// The peer or device went away.
+    Remote,
+}
+
+/// Recap of one connection, from connect to close.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSummary {
+    pub session_id: String,
+    pub name: String,
+    pub kind: String,
+    /// User label from the session metadata.
+    pub label: Option<String>,
+    /// Bench profile the session was opened from.
+    pub profile: Option<String>,
+    /// Response to the identification probe sent on connect.
+    #[serde(default)]
+    pub identity: Option<String>,
+    pub log_path: Option<String>,
+    pub reason: CloseReason,
+    pub opened_ms: Option<f64>,
+    pub closed_ms: f64,
+    pub duration_ms: Option<f64>,
+    pub tx_bytes: u64,
+    pub rx_bytes: u64,
+    pub tx_packets: u64,
+    pub rx_packets: u64,
+    pub bad_checksums: u64,
+    /// Serial sessions only.
+    pub line_errors: Option<LineErrors>,
+    pub alerts: u64,
+    /// Packets per classification severity or tag.
+    pub class_hits: BTreeMap<String, u64>,
+}
+
+/// Summarise `session_id` as it closes. Must run before its serial control
+/// and log are dropped.
+pub fn summarize(st: &AppState, session_id: &str, reason: CloseReason, closed_ms: f64) -> Option<SessionSummary> {
+    let sess = st.sessions.get(session_id)?;
+    let opened_ms = st.opened_at.get(session_id).copied();
+    let mut summary = SessionSummary {
+        session_id: session_id.to_string(),
+        name: sess.name.clone(),
+        kind: sess.kind.clone(),
+        label: sess.meta.label.clone(),
+        profile: st.bench_profiles.get(session_id).cloned(),
+        identity: sess.identity.clone(),
+        log_path: st.logs.get(session_id).map(|l| l.path.to_string_lossy().into_owned()),
+        reason,
+        opened_ms,
+        closed_ms,
+        duration_ms: opened_ms.map(|t| closed_ms - t),
+        tx_bytes: sess.tx_bytes,
+        rx_bytes: sess.rx_bytes,
+        tx_packets: 0,
+        rx_packets: 0,
+        bad_checksums: 0,
+        line_errors: st.serial_controls.get(session_id).map(|c| c.line_errors()),
+        alerts: st.alerts.fired(session_id),
+        class_hits: BTreeMap::new(),
+    };
+    let since = opened_ms.unwrap_or(f64::MIN);
+    for pkt in st.packets.iter().filter(|p| p.session_id == session_id && p.timestamp_ms >= since) {
+        match pkt.direction.as_str() {
+            "TX" => summary.tx_packets += 1,
+            _ => summary.rx_packets += 1,
+        }
+        if pkt.checksum_ok == Some(false) {
+            summary.bad_checksums += 1;
+        }
+        for class in pkt.severity.iter().chain(&pkt.tags) {
+            *summary.class_hits.entry(class.clone()).or_default() += 1;
+        }
+    }
+    Some(summary)
+}
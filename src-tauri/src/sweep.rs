@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+use crate::baud;
+use crate::chat::{ChatResult, ChatScript};
+use crate::priority::WorkerOptions;
+use crate::profile::BenchConnection;
+use crate::serial_port::Flow;
+
+/// The settings a sweep tries, in order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SweepSpace {
+    /// One serial port at each rate.
+    Baud {
+        port: String,
+        /// Empty tries the common rates from slowest to fastest.
+        #[serde(default)]
+        bauds: Vec<u32>,
+        #[serde(default)]
+        flow: Flow,
+    },
+    /// Each connection in turn, e.g. a list of candidate TCP endpoints.
+    Connections { connections: Vec<BenchConnection> },
+}
+
+impl SweepSpace {
+    pub fn connections(&self) -> Vec<BenchConnection> {
+        match self {
+            Self::Baud { port, bauds, flow } => {
+                let bauds = match bauds.is_empty() {
+                    true => {
+                        let mut rates = baud::COMMON_RATES.to_vec();
+                        rates.sort_unstable();
+                        rates
+                    }
+                    false => bauds.clone(),
+                };
+                bauds
+                    .into_iter()
+                    .map(|baud| BenchConnection::Serial {
+                        port: port.clone(),
+                        baud,
+                        flow: *flow,
+                        name: None,
+                        worker: WorkerOptions::default(),
+                    })
+                    .collect()
+            }
+            Self::Connections { connections } => connections.clone(),
+        }
+    }
+}
+
+/// Open a device at each setting of `space`, run `script` against it and
+/// close it again, to find the settings an undocumented device answers on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepPlan {
+    pub space: SweepSpace,
+    /// A setting succeeds when every step completes; an empty script only
+    /// needs the connection to open.
+    #[serde(default)]
+    pub script: ChatScript,
+    /// End the sweep at the first setting that succeeds.
+    #[serde(default)]
+    pub stop_at_first: bool,
+    /// Wait after opening before the script starts, for devices that print
+    /// a banner or need time to wake up.
+    #[serde(default)]
+    pub settle_ms: u64,
+}
+
+/// Human-readable name of a setting, e.g. "/dev/ttyUSB0 @ 115200".
+pub fn label(conn: &BenchConnection) -> String {
+    match conn {
+        BenchConnection::Serial { port, baud, .. } => format!("{port} @ {baud}"),
+        _ => conn.session_id(),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SweepAttempt {
+    /// Position of the setting in the sweep.
+    pub index: usize,
+    pub label: String,
+    pub connection: BenchConnection,
+    pub ok: bool,
+    /// Why the connection could not be opened; the script's outcome is in
+    /// `chat` otherwise.
+    pub error: Option<String>,
+    pub chat: Option<ChatResult>,
+    pub elapsed_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SweepReport {
+    pub attempts: Vec<SweepAttempt>,
+    /// Labels of the settings that succeeded.
+    pub working: Vec<String>,
+    /// Settings not tried because `stop_at_first` ended the sweep.
+    pub skipped: usize,
+}
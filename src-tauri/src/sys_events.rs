@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tauri::{AppHandle, Manager};
+use crate::journal;
+use crate::state::now_ms;
+
+/// After an event is emitted, identical ones within this window are counted
+/// and reported together when it ends.
+pub const REPEAT_WINDOW: Duration = Duration::from_secs(2);
+
+type Key = (&'static str, String);
+
+/// Repeats of one event since it was last emitted.
+struct Burst {
+    since: Instant,
+    /// Latest suppressed payload and how many were suppressed.
+    pending: Option<(serde_json::Value, u64)>,
+}
+
+/// Collapses bursts of identical status and error events, e.g. a reconnect
+/// loop failing the same way over and over.
+#[derive(Default)]
+pub struct SysEvents(Mutex<HashMap<Key, Burst>>);
+
+/// Emit `payload` as `event`, unless one with the same `key` went out less
+/// than `REPEAT_WINDOW` ago. Such repeats are emitted once when the window
+/// ends, as the latest payload with a `repeated` count added.
+pub fn emit<T: Serialize>(app: &AppHandle, event: &'static str, key: String, payload: T) {
+    let Ok(value) = serde_json::to_value(payload) else { return };
+    let now = Instant::now();
+    let sys = app.state::<SysEvents>();
+    let mut bursts = sys.0.lock();
+    bursts.retain(|_, b| b.pending.is_some() || now.duration_since(b.since) < REPEAT_WINDOW);
+    let key = (event, key);
+    let Some(burst) = bursts.get_mut(&key) else {
+        bursts.insert(key, Burst { since: now, pending: None });
+        drop(bursts);
+        journal::emit(app, event, value);
+        return;
+    };
+    let count = burst.pending.as_ref().map_or(0, |(_, n)| *n);
+    if count == 0 {
+        let wait = REPEAT_WINDOW.saturating_sub(now.duration_since(burst.since));
+        let (app, key) = (app.clone(), key.clone());
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(wait).await;
+            flush(&app, key);
+        });
+    }
+    burst.pending = Some((value, count + 1));
+}
+
+/// Emit the repeats of `key` and start a new window, so an ongoing loop
+/// is reported once per window.
+fn flush(app: &AppHandle, key: Key) {
+    let sys = app.state::<SysEvents>();
+    let mut bursts = sys.0.lock();
+    let Some(burst) = bursts.get_mut(&key) else { return };
+    let Some((mut value, count)) = burst.pending.take() else { return };
+    burst.since = Instant::now();
+    drop(bursts);
+    if let Some(fields) = value.as_object_mut() {
+        fields.insert("repeated".into(), count.into());
+    }
+    journal::emit(app, key.0, value);
+}
+
+/// Something that happened to a session rather than data it carried, in a
+/// form tooling can match on. Emitted as "sys_event"; the session log gets
+/// the `render`ed line.
+#[derive(Debug, Clone, Serialize)]
+pub struct SysEvent {
+    pub session_id: String,
+    pub timestamp_ms: f64,
+    /// "connect", "connection", "reconnect", "flow", "alert", "segment", "quota",
+    /// "identity", "telnet", "com_port", "mqtt", "agent", "bridge" or "log".
+    pub category: &'static str,
+    /// What happened within the category, e.g. "closed" or "reached".
+    pub code: &'static str,
+    /// Fields specific to the code.
+    pub detail: Map<String, Value>,
+}
+
+impl SysEvent {
+    pub fn new(session_id: &str, category: &'static str, code: &'static str) -> Self {
+        Self { session_id: session_id.to_string(), timestamp_ms: now_ms(), category, code, detail: Map::new() }
+    }
+
+    pub fn at(mut self, timestamp_ms: f64) -> Self {
+        self.timestamp_ms = timestamp_ms;
+        self
+    }
+
+    pub fn with(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.detail.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// `category code key=value ...`, quoting strings that contain spaces.
+    pub fn render(&self) -> String {
+        let mut line = format!("{} {}", self.category, self.code);
+        for (key, value) in &self.detail {
+            let value = match value {
+                Value::String(s) if !s.is_empty() && !s.contains(char::is_whitespace) && !s.contains('"') => s.clone(),
+                v => v.to_string(),
+            };
+            line.push_str(&format!(" {key}={value}"));
+        }
+        line
+    }
+}
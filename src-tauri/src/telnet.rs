@@ -0,0 +1,328 @@
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::oneshot;
+use tokio::task::AbortHandle;
+use crate::outgoing::Outgoing;
+use crate::rfc2217::{self, ComPortOptions, Notification, COM_PORT_OPTION};
+use crate::socket;
+
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+const BINARY: u8 = 0;
+const ECHO: u8 = 1;
+const SUPPRESS_GO_AHEAD: u8 = 3;
+
+/// Longest subnegotiation kept; longer ones are cut off.
+const MAX_SUBNEGOTIATION: usize = 64;
+
+/// Speak telnet (RFC 854) on a TCP connection: answer option negotiation
+/// and keep IAC sequences out of the received data.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelnetOptions {
+    /// Report each option switched on or off as a system event.
+    pub show_negotiation: bool,
+    /// Drive a serial port on a terminal server with RFC 2217: offer
+    /// COM-PORT-OPTION and binary mode, then apply these settings.
+    pub com_port: Option<ComPortOptions>,
+}
+
+/// An option that was switched on or off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Negotiated {
+    pub option: u8,
+    /// "peer" for an option the device performs, "local" for one we do.
+    pub side: &'static str,
+    pub enabled: bool,
+}
+
+impl Negotiated {
+    pub fn name(&self) -> String {
+        option_name(self.option)
+    }
+}
+
+pub fn option_name(option: u8) -> String {
+    match option {
+        0 => "BINARY".into(),
+        1 => "ECHO".into(),
+        3 => "SUPPRESS-GO-AHEAD".into(),
+        5 => "STATUS".into(),
+        6 => "TIMING-MARK".into(),
+        24 => "TERMINAL-TYPE".into(),
+        31 => "NAWS".into(),
+        32 => "TERMINAL-SPEED".into(),
+        33 => "REMOTE-FLOW-CONTROL".into(),
+        34 => "LINEMODE".into(),
+        36 => "ENVIRON".into(),
+        39 => "NEW-ENVIRON".into(),
+        44 => "COM-PORT-OPTION".into(),
+        n => n.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+enum State {
+    #[default]
+    Data,
+    Iac,
+    /// After WILL, WONT, DO or DONT, waiting for the option.
+    Verb(u8),
+    /// Inside a subnegotiation, which is skipped unless it is RFC 2217's.
+    Sb,
+    SbIac,
+}
+
+/// Something the telnet layer reports besides data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Option(Negotiated),
+    /// What an RFC 2217 server reported about its serial port.
+    ComPort(Notification),
+}
+
+/// Received bytes split into data, negotiation replies and option changes.
+#[derive(Debug, Default)]
+pub struct Parsed {
+    pub data: Vec<u8>,
+    pub replies: Vec<u8>,
+    pub changes: Vec<Negotiated>,
+    pub com_port: Vec<Notification>,
+}
+
+/// Strips IAC sequences from the received stream, across chunk boundaries.
+/// The peer may echo and suppress go-ahead; we only suppress go-ahead.
+/// With RFC 2217 both sides may also go binary, and we do COM-PORT-OPTION.
+/// Replies are only sent when an option changes, so negotiation can't loop.
+pub struct Parser {
+    state: State,
+    /// Options the peer performs.
+    peer: [bool; 256],
+    /// Options we perform.
+    local: [bool; 256],
+    /// Options we sent WILL for and await DO on.
+    offered: [bool; 256],
+    /// Options we sent DO for and await WILL on.
+    requested: [bool; 256],
+    /// Settings sent once the peer agrees to COM-PORT-OPTION.
+    com_port: Option<ComPortOptions>,
+    /// The subnegotiation being read, starting with its option.
+    sb: Vec<u8>,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self {
+            state: State::Data,
+            peer: [false; 256],
+            local: [false; 256],
+            offered: [false; 256],
+            requested: [false; 256],
+            com_port: None,
+            sb: Vec::new(),
+        }
+    }
+}
+
+impl Parser {
+    /// A parser for RFC 2217, and the negotiation that opens it.
+    pub fn com_port(options: ComPortOptions) -> (Self, Vec<u8>) {
+        let mut parser = Self { com_port: Some(options), ..Self::default() };
+        parser.offered[COM_PORT_OPTION as usize] = true;
+        parser.offered[BINARY as usize] = true;
+        parser.requested[BINARY as usize] = true;
+        let opening = [IAC, WILL, COM_PORT_OPTION, IAC, WILL, BINARY, IAC, DO, BINARY].to_vec();
+        (parser, opening)
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) -> Parsed {
+        let mut out = Parsed::default();
+        for &b in chunk {
+            self.state = match (self.state, b) {
+                (State::Data, IAC) => State::Iac,
+                (State::Data, b) => {
+                    out.data.push(b);
+                    State::Data
+                }
+                (State::Iac, IAC) => {
+                    out.data.push(IAC);
+                    State::Data
+                }
+                (State::Iac, WILL | WONT | DO | DONT) => State::Verb(b),
+                (State::Iac, SB) => {
+                    self.sb.clear();
+                    State::Sb
+                }
+                // NOP, go-ahead and the other bare commands carry no data
+                (State::Iac, _) => State::Data,
+                (State::Verb(verb), option) => {
+                    self.negotiate(verb, option, &mut out);
+                    State::Data
+                }
+                (State::Sb, IAC) => State::SbIac,
+                (State::Sb, b) | (State::SbIac, b @ IAC) => {
+                    if self.sb.len() < MAX_SUBNEGOTIATION {
+                        self.sb.push(b);
+                    }
+                    State::Sb
+                }
+                (State::SbIac, SE) => {
+                    if let Some((&COM_PORT_OPTION, payload)) = self.sb.split_first().filter(|_| self.com_port.is_some()) {
+                        out.com_port.extend(rfc2217::parse(payload));
+                    }
+                    State::Data
+                }
+                (State::SbIac, _) => State::Sb,
+            };
+        }
+        out
+    }
+
+    fn negotiate(&mut self, verb: u8, option: u8, out: &mut Parsed) {
+        let i = option as usize;
+        let com_port = self.com_port.is_some();
+        let (side, enabled, reply) = match verb {
+            WILL if matches!(option, ECHO | SUPPRESS_GO_AHEAD) || com_port && option == BINARY => match self.peer[i] {
+                true => return,
+                false => ("peer", true, DO),
+            },
+            WILL => {
+                out.replies.extend_from_slice(&[IAC, DONT, option]);
+                return;
+            }
+            WONT if self.peer[i] => ("peer", false, DONT),
+            WONT => {
+                self.requested[i] = false;
+                return;
+            }
+            DO if option == SUPPRESS_GO_AHEAD || com_port && matches!(option, BINARY | COM_PORT_OPTION) => match self.local[i] {
+                true => return,
+                false => ("local", true, WILL),
+            },
+            DO => {
+                out.replies.extend_from_slice(&[IAC, WONT, option]);
+                return;
+            }
+            DONT if self.local[i] => ("local", false, WONT),
+            DONT => {
+                self.offered[i] = false;
+                return;
+            }
+            _ => return,
+        };
+        let asked = match side {
+            "peer" => {
+                self.peer[i] = enabled;
+                std::mem::take(&mut self.requested[i])
+            }
+            _ => {
+                self.local[i] = enabled;
+                std::mem::take(&mut self.offered[i])
+            }
+        };
+        // Agreeing to what we asked for needs no answer
+        if !(asked && enabled) {
+            out.replies.extend_from_slice(&[IAC, reply, option]);
+        }
+        if let Some(settings) = self.com_port.filter(|_| option == COM_PORT_OPTION && side == "local" && enabled) {
+            out.replies.extend(settings.commands());
+        }
+        out.changes.push(Negotiated { option, side, enabled });
+    }
+}
+
+/// Double every IAC in outgoing data so the peer reads it as a data byte.
+pub fn escape(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    for &b in data {
+        out.push(b);
+        if b == IAC {
+            out.push(IAC);
+        }
+    }
+    out
+}
+
+/// A telnet connection's writer, shutdown trigger, reader stop handle and a
+/// sender for raw commands such as RFC 2217's, written unescaped ahead of
+/// queued data.
+pub type Spawned = (UnboundedSender<Outgoing>, oneshot::Sender<()>, AbortHandle, UnboundedSender<Vec<u8>>);
+
+/// Like `socket::spawn_io_notify`, speaking telnet: `on_data` gets the data
+/// with IAC sequences removed, negotiation is answered and `on_event` gets
+/// each option change, with `show_negotiation`, and RFC 2217 notification.
+pub fn spawn<S>(
+    stream: S,
+    opts: &TelnetOptions,
+    on_data: impl Fn(Vec<u8>) + Send + 'static,
+    on_event: impl Fn(Event) + Send + 'static,
+    on_close: impl FnOnce() + Send + 'static,
+) -> Spawned
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (reply_tx, mut reply_rx) = mpsc::unbounded_channel::<Vec<u8>>();
+    let parser = match opts.com_port {
+        Some(options) => {
+            let (parser, opening) = Parser::com_port(options);
+            let _ = reply_tx.send(opening);
+            parser
+        }
+        None => Parser::default(),
+    };
+    let (parser, control) = (Mutex::new(parser), reply_tx.clone());
+    let show = opts.show_negotiation;
+    let on_raw = move |chunk: Vec<u8>| {
+        let parsed = parser.lock().feed(&chunk);
+        if !parsed.replies.is_empty() {
+            let _ = reply_tx.send(parsed.replies);
+        }
+        if show {
+            parsed.changes.into_iter().map(Event::Option).for_each(&on_event);
+        }
+        parsed.com_port.into_iter().map(Event::ComPort).for_each(&on_event);
+        if !parsed.data.is_empty() {
+            on_data(parsed.data);
+        }
+    };
+    let (inner, inner_shutdown, reader) = socket::spawn_io_notify(stream, on_raw, on_close);
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Outgoing>();
+    let (shutdown, mut shutdown_rx) = oneshot::channel::<()>();
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                biased;
+                Some(reply) = reply_rx.recv() => {
+                    if inner.send(Outgoing::new(reply)).is_err() { break }
+                }
+                out = rx.recv() => match out {
+                    Some(mut out) => {
+                        out.data = escape(&out.data);
+                        if inner.send(out).is_err() { break }
+                    }
+                    None => break,
+                },
+                Ok(()) = &mut shutdown_rx => {
+                    // Hand over whatever was queued before the shutdown request
+                    while let Ok(mut out) = rx.try_recv() {
+                        out.data = escape(&out.data);
+                        let _ = inner.send(out);
+                    }
+                    let _ = inner_shutdown.send(());
+                    break;
+                }
+            }
+        }
+    });
+
+    (tx, shutdown, reader, control)
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::{clock, session_log};
+use crate::state::Packet;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimelineFormat {
+    /// Same line format as the per-session logs.
+    #[default]
+    Text,
+    Csv,
+    /// One JSON packet per line.
+    Jsonl,
+    /// A header line per packet followed by offset/hex/ASCII rows.
+    Hexdump,
+}
+
+impl TimelineFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            TimelineFormat::Text => "log",
+            TimelineFormat::Csv => "csv",
+            TimelineFormat::Jsonl => "jsonl",
+            TimelineFormat::Hexdump => "txt",
+        }
+    }
+}
+
+/// Clock the timeline is ordered by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeBase {
+    /// Host receive time.
+    Host,
+    /// Host time with NTP and manual clock corrections.
+    #[default]
+    Corrected,
+    /// Device clock where an offset is configured, corrected host time otherwise.
+    Device,
+}
+
+fn time_of(pkt: &Packet, base: TimeBase) -> f64 {
+    let corrected = pkt.corrected_ts_ms.unwrap_or(pkt.timestamp_ms);
+    match base {
+        TimeBase::Host => pkt.timestamp_ms,
+        TimeBase::Corrected => corrected,
+        TimeBase::Device => pkt.device_ts_ms.unwrap_or(corrected),
+    }
+}
+
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    label: &'a str,
+    time_ms: f64,
+    #[serde(flatten)]
+    packet: &'a Packet,
+}
+
+/// Interleave the packets of several sessions by `base` time. `labels` maps
+/// session ids to the names written into the output.
+pub fn render(packets: &[Packet], labels: &HashMap<&str, &str>, format: TimelineFormat, base: TimeBase) -> String {
+    let mut pkts: Vec<&Packet> = packets.iter()
+        .filter(|p| labels.contains_key(p.session_id.as_str()))
+        .collect();
+    pkts.sort_by(|a, b| time_of(a, base).total_cmp(&time_of(b, base)));
+    let label = |p: &Packet| labels.get(p.session_id.as_str()).copied().unwrap_or_default();
+
+    let mut out = String::new();
+    if format == TimelineFormat::Csv {
+        out.push_str("time_ms,host_ts_ms,device_ts_ms,label,direction,severity,tags,hex\n");
+    }
+    for p in pkts {
+        match format {
+            TimelineFormat::Text => out.push_str(&session_log::format_line(p, Some(label(p)))),
+            TimelineFormat::Csv => {
+                let hex: String = p.bytes.iter().map(|b| format!("{b:02X}")).collect();
+                out.push_str(&format!(
+                    "{:.3},{:.3},{},{},{},{},{},{hex}",
+                    time_of(p, base),
+                    p.timestamp_ms,
+                    p.device_ts_ms.map(|t| format!("{t:.3}")).unwrap_or_default(),
+                    csv_field(label(p)),
+                    p.direction,
+                    p.severity.as_deref().unwrap_or_default(),
+                    csv_field(&p.tags.join(";")),
+                ));
+            }
+            TimelineFormat::Jsonl => {
+                let line = JsonLine { label: label(p), time_ms: time_of(p, base), packet: p };
+                out.push_str(&serde_json::to_string(&line).unwrap_or_default());
+            }
+            TimelineFormat::Hexdump => {
+                let ts = clock::format_ts(time_of(p, base));
+                out.push_str(&format!("{ts} [{}] {} {} bytes\n", label(p), p.direction, p.bytes.len()));
+                hexdump(&mut out, &p.bytes);
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The packets of one session timed within `[from_ms, to_ms]` on `base`.
+pub fn in_range<'a>(
+    packets: &'a [Packet],
+    session_id: &'a str,
+    from_ms: Option<f64>,
+    to_ms: Option<f64>,
+    base: TimeBase,
+) -> impl Iterator<Item = &'a Packet> {
+    packets.iter().filter(move |p| {
+        let t = time_of(p, base);
+        p.session_id == session_id && from_ms.is_none_or(|f| t >= f) && to_ms.is_none_or(|e| t <= e)
+    })
+}
+
+/// `xxd`-style rows: offset, 16 hex bytes, printable ASCII.
+fn hexdump(out: &mut String, bytes: &[u8]) {
+    for (i, row) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = row.iter().map(|b| format!("{b:02x}")).collect();
+        let ascii: String = row.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+        out.push_str(&format!("  {:04x}  {:<47}  |{ascii}|\n", i * 16, hex.join(" ")));
+    }
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
@@ -0,0 +1,183 @@
+use native_tls::{Certificate, Identity, TlsAcceptor, TlsConnector};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpStream;
+use tokio_native_tls::TlsStream;
+use crate::socket::{ConnectError, ErrorCategory};
+use crate::tofu;
+
+/// TLS settings for a TCP connection.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsOptions {
+    /// Client identity for mutual TLS as a PKCS#12 bundle (.p12/.pfx).
+    pub client_pkcs12: Option<String>,
+    pub client_pkcs12_password: Option<String>,
+    /// Client identity for mutual TLS as PEM certificate chain + PKCS#8 key.
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+    /// PEM file of CA certificates trusted in addition to the system store,
+    /// e.g. a broker's private CA.
+    pub ca_file: Option<String>,
+    /// Skip certificate and hostname validation entirely. For lab devices
+    /// with broken certificates only.
+    pub accept_invalid_certs: bool,
+    /// Accept self-signed certificates, but only when their fingerprint matches
+    /// `pinned_fingerprint` (looked up from the TOFU store when not given).
+    pub trust_on_first_use: bool,
+    pub pinned_fingerprint: Option<String>,
+    /// Name sent as SNI and checked against the certificate instead of the
+    /// connect host; an empty string disables SNI.
+    pub server_name: Option<String>,
+    /// ALPN protocol ids to offer, in preference order (e.g. "h2", "mqtt").
+    pub alpn: Vec<String>,
+    /// Server mode: PEM certificate chain and PKCS#8 key presented to the
+    /// connecting client.
+    pub server_cert: Option<String>,
+    pub server_key: Option<String>,
+}
+
+/// Run the client handshake over an established TCP stream to `host`.
+pub async fn connect(stream: TcpStream, host: &str, opts: &TlsOptions) -> Result<TlsStream<TcpStream>, ConnectError> {
+    let mut builder = TlsConnector::builder();
+    let domain = match opts.server_name.as_deref() {
+        Some("") => {
+            builder.use_sni(false);
+            host
+        }
+        Some(name) => name,
+        None => host,
+    };
+    if !opts.alpn.is_empty() {
+        let protocols: Vec<&str> = opts.alpn.iter().map(String::as_str).collect();
+        builder.request_alpns(&protocols);
+    }
+    if let Some(identity) = load_identity(opts)? {
+        builder.identity(identity);
+    }
+    if let Some(path) = &opts.ca_file {
+        for cert in load_ca_file(path)? {
+            builder.add_root_certificate(cert);
+        }
+    }
+    if opts.accept_invalid_certs {
+        builder.danger_accept_invalid_certs(true);
+        builder.danger_accept_invalid_hostnames(true);
+    }
+    if opts.trust_on_first_use {
+        // Chain validation is replaced by the fingerprint check below
+        builder.danger_accept_invalid_certs(true);
+    }
+    let connector = builder
+        .build()
+        .map_err(|e| ConnectError::new(ErrorCategory::Certificate, e))?;
+
+    let stream = tokio_native_tls::TlsConnector::from(connector)
+        .connect(domain, stream)
+        .await
+        .map_err(handshake_error)?;
+    if opts.trust_on_first_use {
+        check_pin(&stream, opts.pinned_fingerprint.as_deref())?;
+    }
+    Ok(stream)
+}
+
+/// Run the server handshake with a client that connected to us.
+pub async fn accept(stream: TcpStream, opts: &TlsOptions) -> Result<TlsStream<TcpStream>, ConnectError> {
+    let cert_err = |msg: String| ConnectError::new(ErrorCategory::Certificate, msg);
+    let (Some(cert), Some(key)) = (&opts.server_cert, &opts.server_key) else {
+        return Err(ConnectError::new(ErrorCategory::Config, "TLS server mode needs server_cert and server_key"));
+    };
+    let read = |path: &str| std::fs::read(path).map_err(|e| cert_err(format!("{path}: {e}")));
+    let identity = Identity::from_pkcs8(&read(cert)?, &read(key)?)
+        .map_err(|e| cert_err(format!("Invalid server identity: {e}")))?;
+    let acceptor = TlsAcceptor::new(identity).map_err(|e| cert_err(e.to_string()))?;
+    tokio_native_tls::TlsAcceptor::from(acceptor)
+        .accept(stream)
+        .await
+        .map_err(handshake_error)
+}
+
+/// The ALPN protocol the server selected, if any.
+pub fn negotiated_alpn(stream: &TlsStream<TcpStream>) -> Option<String> {
+    stream.get_ref()
+        .negotiated_alpn()
+        .ok()
+        .flatten()
+        .map(|p| String::from_utf8_lossy(&p).into_owned())
+}
+
+/// Compare the peer certificate against the pinned fingerprint.
+fn check_pin(stream: &TlsStream<TcpStream>, pinned: Option<&str>) -> Result<(), ConnectError> {
+    let cert_err = |e: native_tls::Error| ConnectError::new(ErrorCategory::Certificate, e);
+    let der = stream.get_ref()
+        .peer_certificate()
+        .map_err(cert_err)?
+        .ok_or_else(|| ConnectError::new(ErrorCategory::Certificate, "Peer sent no certificate"))?
+        .to_der()
+        .map_err(cert_err)?;
+    let actual = tofu::fingerprint(&der);
+    let message = match pinned {
+        Some(pin) if pin.eq_ignore_ascii_case(&actual) => return Ok(()),
+        Some(_) => "Certificate fingerprint does not match the pinned one",
+        None => "Unknown certificate; accept its fingerprint to connect",
+    };
+    let mut err = ConnectError::new(ErrorCategory::Certificate, message);
+    err.peer_fingerprint = Some(actual);
+    Err(err)
+}
+
+/// Handshake with certificate checks disabled and return the peer's fingerprint.
+pub async fn probe_fingerprint(stream: TcpStream, domain: &str) -> Result<String, ConnectError> {
+    let opts = TlsOptions { trust_on_first_use: true, ..Default::default() };
+    match connect(stream, domain, &opts).await {
+        Err(ConnectError { peer_fingerprint: Some(fp), .. }) => Ok(fp),
+        Err(e) => Err(e),
+        // No pin is given, so the check should always fail
+        Ok(_) => Err(ConnectError::new(ErrorCategory::Certificate, "Handshake succeeded without reporting a fingerprint")),
+    }
+}
+
+fn load_identity(opts: &TlsOptions) -> Result<Option<Identity>, ConnectError> {
+    let read = |path: &str| {
+        std::fs::read(path).map_err(|e| ConnectError::new(ErrorCategory::Certificate, format!("{path}: {e}")))
+    };
+    let identity = match (&opts.client_pkcs12, &opts.client_cert, &opts.client_key) {
+        (Some(p12), _, _) => {
+            let password = opts.client_pkcs12_password.as_deref().unwrap_or("");
+            Identity::from_pkcs12(&read(p12)?, password)
+        }
+        (None, Some(cert), Some(key)) => Identity::from_pkcs8(&read(cert)?, &read(key)?),
+        (None, Some(_), None) | (None, None, Some(_)) => {
+            return Err(ConnectError::new(ErrorCategory::Certificate, "client_cert and client_key must be given together"));
+        }
+        (None, None, None) => return Ok(None),
+    };
+    identity
+        .map(Some)
+        .map_err(|e| ConnectError::new(ErrorCategory::Certificate, format!("Invalid client identity: {e}")))
+}
+
+/// Every certificate in a PEM file, which may hold a whole chain.
+fn load_ca_file(path: &str) -> Result<Vec<Certificate>, ConnectError> {
+    let err = |msg: String| ConnectError::new(ErrorCategory::Certificate, msg);
+    let pem = std::fs::read_to_string(path).map_err(|e| err(format!("{path}: {e}")))?;
+    let certs = pem.split_inclusive("-----END CERTIFICATE-----")
+        .filter(|block| block.contains("-----BEGIN CERTIFICATE-----"))
+        .map(|block| Certificate::from_pem(block.as_bytes()).map_err(|e| err(format!("{path}: {e}"))))
+        .collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(err(format!("{path}: no PEM certificates found")));
+    }
+    Ok(certs)
+}
+
+/// Certificate rejections are reported separately from other handshake failures.
+fn handshake_error(e: native_tls::Error) -> ConnectError {
+    let msg = e.to_string();
+    let category = if msg.to_ascii_lowercase().contains("certificate") {
+        ErrorCategory::Certificate
+    } else {
+        ErrorCategory::Handshake
+    };
+    ConnectError::new(category, msg)
+}
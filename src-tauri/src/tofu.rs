@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// A pinned certificate fingerprint for one `host:port`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TofuPin {
+    pub endpoint: String,
+    pub fingerprint: String,
+}
+
+/// Trust-on-first-use pins, persisted as `tofu.json` in the app data directory.
+pub struct TofuStore {
+    path: PathBuf,
+    pins: BTreeMap<String, String>,
+}
+
+impl TofuStore {
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join("tofu.json");
+        let pins = std::fs::read(&path)
+            .ok()
+            .and_then(|b| serde_json::from_slice(&b).ok())
+            .unwrap_or_default();
+        Self { path, pins }
+    }
+
+    pub fn get(&self, endpoint: &str) -> Option<&String> {
+        self.pins.get(endpoint)
+    }
+
+    pub fn pin(&mut self, endpoint: String, fingerprint: String) -> Result<(), String> {
+        self.pins.insert(endpoint, fingerprint);
+        self.save()
+    }
+
+    pub fn forget(&mut self, endpoint: &str) -> Result<(), String> {
+        self.pins.remove(endpoint);
+        self.save()
+    }
+
+    pub fn list(&self) -> Vec<TofuPin> {
+        self.pins.iter()
+            .map(|(endpoint, fingerprint)| TofuPin { endpoint: endpoint.clone(), fingerprint: fingerprint.clone() })
+            .collect()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_vec_pretty(&self.pins).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, json).map_err(|e| e.to_string())
+    }
+}
+
+/// SHA-256 of a DER certificate as colon-separated upper-case hex.
+pub fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der).iter().map(|b| format!("{b:02X}")).collect::<Vec<_>>().join(":")
+}
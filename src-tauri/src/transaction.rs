@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::histogram::{Histogram, HistogramSummary};
+use crate::state::Packet;
+
+/// How RX packets are attributed to the TX that preceded them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PairingConfig {
+    pub enabled: bool,
+    /// RX arriving later than this after the TX is not part of the transaction.
+    pub timeout_ms: f64,
+    /// Close the transaction after this many RX packets; 0 waits for the timeout.
+    pub max_responses: usize,
+}
+
+impl Default for PairingConfig {
+    fn default() -> Self {
+        Self { enabled: false, timeout_ms: 1000.0, max_responses: 1 }
+    }
+}
+
+/// A TX packet and the RX packets that answered it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Transaction {
+    pub id: u64,
+    pub session_id: String,
+    pub tx_packet_id: u64,
+    pub rx_packet_ids: Vec<u64>,
+    pub started_ms: f64,
+    /// Time from the TX to the first RX; None if nothing answered.
+    pub latency_ms: Option<f64>,
+    /// Time from the TX to the last RX.
+    pub duration_ms: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TransactionStats {
+    pub session_id: String,
+    pub total: u64,
+    pub answered: u64,
+    pub unanswered: u64,
+    pub min_latency_ms: f64,
+    pub avg_latency_ms: f64,
+    pub max_latency_ms: f64,
+}
+
+#[derive(Default)]
+pub struct Pairer {
+    pub config: PairingConfig,
+    next_id: u64,
+    open: HashMap<String, Transaction>,
+    stats: HashMap<String, TransactionStats>,
+    latency: HashMap<String, Histogram>,
+}
+
+impl Pairer {
+    pub fn set_config(&mut self, config: PairingConfig) {
+        self.config = config;
+        self.open.clear();
+    }
+
+    /// Start a transaction for an outgoing packet, closing the session's
+    /// previous one.
+    pub fn on_tx(&mut self, pkt: &mut Packet) -> Option<Transaction> {
+        if !self.config.enabled {
+            return None;
+        }
+        let closed = self.close(&pkt.session_id);
+        self.next_id += 1;
+        pkt.transaction_id = Some(self.next_id);
+        self.open.insert(pkt.session_id.clone(), Transaction {
+            id: self.next_id,
+            session_id: pkt.session_id.clone(),
+            tx_packet_id: pkt.id,
+            rx_packet_ids: Vec::new(),
+            started_ms: pkt.timestamp_ms,
+            latency_ms: None,
+            duration_ms: 0.0,
+        });
+        closed
+    }
+
+    /// Attach an incoming packet to the session's open transaction if it is
+    /// still within the timeout. Returns the transaction if this closed it.
+    pub fn on_rx(&mut self, pkt: &mut Packet) -> Option<Transaction> {
+        let t = self.open.get_mut(&pkt.session_id)?;
+        let elapsed = pkt.timestamp_ms - t.started_ms;
+        if elapsed > self.config.timeout_ms {
+            return self.close(&pkt.session_id);
+        }
+        pkt.transaction_id = Some(t.id);
+        t.rx_packet_ids.push(pkt.id);
+        t.latency_ms.get_or_insert(elapsed);
+        t.duration_ms = elapsed;
+        if self.config.max_responses > 0 && t.rx_packet_ids.len() >= self.config.max_responses {
+            return self.close(&pkt.session_id);
+        }
+        None
+    }
+
+    /// Close every transaction whose timeout has passed.
+    pub fn expire(&mut self, now_ms: f64) -> Vec<Transaction> {
+        let timeout = self.config.timeout_ms;
+        let due: Vec<String> = self.open.iter()
+            .filter(|(_, t)| now_ms - t.started_ms > timeout)
+            .map(|(sid, _)| sid.clone())
+            .collect();
+        due.iter().filter_map(|sid| self.close(sid)).collect()
+    }
+
+    fn close(&mut self, session_id: &str) -> Option<Transaction> {
+        let t = self.open.remove(session_id)?;
+        let s = self.stats.entry(t.session_id.clone()).or_insert_with(|| TransactionStats {
+            session_id: t.session_id.clone(),
+            min_latency_ms: f64::INFINITY,
+            ..Default::default()
+        });
+        s.total += 1;
+        match t.latency_ms {
+            Some(lat) => {
+                self.latency.entry(t.session_id.clone()).or_default().record_ms(lat);
+                s.avg_latency_ms = (s.avg_latency_ms * s.answered as f64 + lat) / (s.answered + 1) as f64;
+                s.answered += 1;
+                s.min_latency_ms = s.min_latency_ms.min(lat);
+                s.max_latency_ms = s.max_latency_ms.max(lat);
+            }
+            None => s.unanswered += 1,
+        }
+        Some(t)
+    }
+
+    pub fn stats(&self) -> Vec<TransactionStats> {
+        let mut list: Vec<_> = self.stats.values()
+            .map(|s| TransactionStats {
+                min_latency_ms: if s.min_latency_ms.is_finite() { s.min_latency_ms } else { 0.0 },
+                ..s.clone()
+            })
+            .collect();
+        list.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+        list
+    }
+
+    /// Response latency distribution per session.
+    pub fn histograms(&self) -> Vec<HistogramSummary> {
+        let mut list: Vec<_> = self.latency.iter().map(|(sid, h)| h.summary(sid)).collect();
+        list.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+        list
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats.clear();
+        self.latency.clear();
+    }
+}
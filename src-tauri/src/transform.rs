@@ -0,0 +1,232 @@
+use std::fmt::Write;
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use crate::checksum;
+
+/// One step of a session's RX pipeline, applied to each packet in order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Stage {
+    /// Remove ANSI escape sequences (colours, cursor movement).
+    StripAnsi,
+    /// Decode a COBS frame; a trailing 0x00 delimiter is ignored.
+    CobsDecode,
+    /// Check a trailing checksum and set the packet's checksum result.
+    VerifyCrc {
+        algorithm: String,
+        /// Checksum width in bytes.
+        size: usize,
+        #[serde(default)]
+        little_endian: bool,
+        /// Remove the checksum from the packet.
+        #[serde(default)]
+        strip: bool,
+        /// Drop packets that fail instead of passing them on.
+        #[serde(default)]
+        drop_bad: bool,
+    },
+    /// Render protobuf wire format as text, without a schema.
+    Protobuf,
+    /// Regex replacement over the raw bytes; `$1` etc. refer to groups.
+    Replace { pattern: String, replacement: String },
+}
+
+/// What the pipeline made of one packet.
+pub struct Output {
+    pub bytes: Vec<u8>,
+    /// Set by a `VerifyCrc` stage.
+    pub checksum_ok: Option<bool>,
+    /// A stage asked for the packet to be dropped.
+    pub dropped: bool,
+    /// Tags for stages that could not process the packet, e.g. "cobs_error".
+    pub errors: Vec<String>,
+}
+
+enum Compiled {
+    Plain(Stage),
+    Replace(Regex, Vec<u8>),
+}
+
+/// A compiled stage chain for one session.
+pub struct Pipeline {
+    stages: Vec<Stage>,
+    compiled: Vec<Compiled>,
+}
+
+impl Pipeline {
+    pub fn new(stages: Vec<Stage>) -> Result<Self, String> {
+        let compiled = stages.iter()
+            .enumerate()
+            .map(|(i, stage)| match stage {
+                Stage::Replace { pattern, replacement } => Regex::new(pattern)
+                    .map(|re| Compiled::Replace(re, replacement.as_bytes().to_vec()))
+                    .map_err(|e| format!("Stage {}: {e}", i + 1)),
+                Stage::VerifyCrc { algorithm, .. } if !known(algorithm) => {
+                    Err(format!("Stage {}: unknown checksum algorithm {algorithm}", i + 1))
+                }
+                other => Ok(Compiled::Plain(other.clone())),
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { stages, compiled })
+    }
+
+    pub fn stages(&self) -> Vec<Stage> {
+        self.stages.clone()
+    }
+
+    pub fn apply(&self, bytes: Vec<u8>) -> Output {
+        let mut out = Output { bytes, checksum_ok: None, dropped: false, errors: Vec::new() };
+        for stage in &self.compiled {
+            match stage {
+                Compiled::Replace(re, with) => out.bytes = re.replace_all(&out.bytes, with.as_slice()).into_owned(),
+                Compiled::Plain(Stage::StripAnsi) => out.bytes = strip_ansi(&out.bytes),
+                Compiled::Plain(Stage::CobsDecode) => match cobs_decode(&out.bytes) {
+                    Some(decoded) => out.bytes = decoded,
+                    None => out.errors.push("cobs_error".into()),
+                },
+                Compiled::Plain(Stage::VerifyCrc { algorithm, size, little_endian, strip, drop_bad }) => {
+                    let Some(split) = out.bytes.len().checked_sub(*size) else {
+                        out.checksum_ok = Some(false);
+                        out.dropped |= *drop_bad;
+                        continue;
+                    };
+                    let mut expected = out.bytes[split..].to_vec();
+                    if *little_endian {
+                        expected.reverse();
+                    }
+                    let ok = checksum::verify(algorithm, &out.bytes[..split], &expected);
+                    out.checksum_ok = Some(ok);
+                    out.dropped |= !ok && *drop_bad;
+                    if *strip {
+                        out.bytes.truncate(split);
+                    }
+                }
+                Compiled::Plain(Stage::Protobuf) => match protobuf_text(&out.bytes, 0) {
+                    Some(text) => out.bytes = text.into_bytes(),
+                    None => out.errors.push("protobuf_error".into()),
+                },
+                Compiled::Plain(Stage::Replace { .. }) => {}
+            }
+            if out.dropped {
+                break;
+            }
+        }
+        out
+    }
+}
+
+fn known(algorithm: &str) -> bool {
+    matches!(algorithm, "crc16-modbus" | "crc16-ccitt" | "crc16-kermit" | "crc32" | "sum8" | "xor" | "fletcher16")
+}
+
+/// Drop CSI (`ESC [ … final`), OSC (`ESC ] … BEL/ST`) and two-byte escapes.
+fn strip_ansi(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] != 0x1B {
+            out.push(data[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+        match data.get(i) {
+            Some(b'[') => {
+                i += 1;
+                while i < data.len() && !(0x40..=0x7E).contains(&data[i]) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            Some(b']') => {
+                while i < data.len() && data[i] != 0x07 && !(data[i] == 0x1B && data.get(i + 1) == Some(&b'\\')) {
+                    i += 1;
+                }
+                i += if data.get(i) == Some(&0x1B) { 2 } else { 1 };
+            }
+            Some(_) => i += 1,
+            None => {}
+        }
+    }
+    out
+}
+
+fn cobs_decode(data: &[u8]) -> Option<Vec<u8>> {
+    let data = data.strip_suffix(&[0]).unwrap_or(data);
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        let code = data[i] as usize;
+        if code == 0 || i + code > data.len() {
+            return None;
+        }
+        out.extend_from_slice(&data[i + 1..i + code]);
+        i += code;
+        if code < 0xFF && i < data.len() {
+            out.push(0);
+        }
+    }
+    Some(out)
+}
+
+fn varint(data: &[u8], i: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let b = *data.get(*i)?;
+        *i += 1;
+        value |= ((b & 0x7F) as u64) << shift;
+        if b & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Nesting limit for length-delimited fields tried as sub-messages.
+const MAX_DEPTH: usize = 8;
+
+/// `{1: 150, 2: "abc", 3: {1: 1}}`. Length-delimited fields print as text
+/// if printable, else as a nested message if they parse as one, else as hex.
+fn protobuf_text(data: &[u8], depth: usize) -> Option<String> {
+    let mut fields = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let key = varint(data, &mut i)?;
+        let (field, wire) = (key >> 3, key & 7);
+        if field == 0 {
+            return None;
+        }
+        let value = match wire {
+            0 => varint(data, &mut i)?.to_string(),
+            1 => {
+                let b: [u8; 8] = data.get(i..i + 8)?.try_into().ok()?;
+                i += 8;
+                format!("0x{:016X}", u64::from_le_bytes(b))
+            }
+            2 => {
+                let len = varint(data, &mut i)? as usize;
+                let body = data.get(i..i.checked_add(len)?)?;
+                i += len;
+                let text = std::str::from_utf8(body).ok().filter(|s| s.chars().all(|c| !c.is_control()));
+                let nested = || (depth < MAX_DEPTH && !body.is_empty()).then(|| protobuf_text(body, depth + 1)).flatten();
+                match text {
+                    Some(s) => format!("{s:?}"),
+                    None => nested().unwrap_or_else(|| {
+                        body.iter().fold(String::from("0x"), |mut acc, b| {
+                            let _ = write!(acc, "{b:02X}");
+                            acc
+                        })
+                    }),
+                }
+            }
+            5 => {
+                let b: [u8; 4] = data.get(i..i + 4)?.try_into().ok()?;
+                i += 4;
+                format!("0x{:08X}", u32::from_le_bytes(b))
+            }
+            _ => return None,
+        };
+        fields.push(format!("{field}: {value}"));
+    }
+    Some(format!("{{{}}}", fields.join(", ")))
+}
@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Named values of one session, used as `{{name}}` in payloads.
+pub type Vars = BTreeMap<String, String>;
+
+/// Sets variable `name` from received text matching `pattern`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VarCapture {
+    pub name: String,
+    pub pattern: String,
+    /// Capture group holding the value; unset takes group 1 when the
+    /// pattern has one, else the whole match.
+    #[serde(default)]
+    pub group: Option<usize>,
+    #[serde(default)]
+    pub case_insensitive: bool,
+}
+
+/// A compiled `VarCapture`.
+#[derive(Debug, Clone)]
+pub struct Capturer {
+    capture: VarCapture,
+    re: Regex,
+}
+
+impl Capturer {
+    pub fn new(capture: VarCapture) -> Result<Self, String> {
+        if capture.name.is_empty() {
+            return Err("Variable name is empty".into());
+        }
+        let re = RegexBuilder::new(&capture.pattern)
+            .case_insensitive(capture.case_insensitive)
+            .build()
+            .map_err(|e| format!("{}: {e}", capture.name))?;
+        Ok(Self { capture, re })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.capture.name
+    }
+
+    /// The value in `bytes`, matched as lossy UTF-8.
+    pub fn extract(&self, bytes: &[u8]) -> Option<String> {
+        let text = String::from_utf8_lossy(bytes);
+        let caps = self.re.captures(&text)?;
+        let group = self.capture.group.unwrap_or(usize::from(caps.len() > 1));
+        caps.get(group).map(|m| m.as_str().to_string())
+    }
+}
+
+/// Variables of one session and the captures that set them from RX.
+#[derive(Debug, Clone, Default)]
+pub struct SessionVars {
+    pub values: Vars,
+    pub captures: Vec<Capturer>,
+}
+
+impl SessionVars {
+    pub fn set_captures(&mut self, captures: Vec<VarCapture>) -> Result<(), String> {
+        self.captures = captures.into_iter().map(Capturer::new).collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    pub fn captures(&self) -> Vec<VarCapture> {
+        self.captures.iter().map(|c| c.capture.clone()).collect()
+    }
+
+    /// Run the captures over a received packet. Returns the variables whose
+    /// value changed.
+    pub fn capture(&mut self, bytes: &[u8]) -> Vec<(String, String)> {
+        let mut changed = Vec::new();
+        for capturer in &self.captures {
+            let Some(value) = capturer.extract(bytes) else { continue };
+            if self.values.get(capturer.name()) != Some(&value) {
+                self.values.insert(capturer.name().to_string(), value.clone());
+                changed.push((capturer.name().to_string(), value));
+            }
+        }
+        changed
+    }
+}
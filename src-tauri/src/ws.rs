@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+use tokio::task::AbortHandle;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{header, HeaderName, HeaderValue};
+use tokio_tungstenite::tungstenite::Message;
+use crate::dns;
+use crate::outgoing::Outgoing;
+use crate::socket::{ConnectError, ErrorCategory};
+
+/// WebSocket handshake settings. Setting this on a TCP connection speaks
+/// WebSocket (`wss` when TLS is also enabled).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WsOptions {
+    /// Request path including any query string; defaults to "/".
+    pub path: String,
+    /// Extra handshake headers such as Authorization or Cookie.
+    pub headers: BTreeMap<String, String>,
+    /// Subprotocols offered via Sec-WebSocket-Protocol, in preference order.
+    pub subprotocols: Vec<String>,
+    /// Frame type of everything sent; text frames must be valid UTF-8.
+    pub send_as: FrameKind,
+}
+
+/// WebSocket data frame type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameKind {
+    #[default]
+    Binary,
+    Text,
+}
+
+pub struct WsConnection {
+    pub tx: UnboundedSender<Outgoing>,
+    /// Fire to start the close handshake; RX continues until the server closes.
+    pub shutdown: oneshot::Sender<()>,
+    /// Stops the frame reader once the session is closed.
+    pub reader: AbortHandle,
+    /// Subprotocol selected by the server.
+    pub protocol: Option<String>,
+}
+
+/// Run the WebSocket handshake over `stream` and start the frame pump.
+/// `on_data` gets each received message with its frame type; `on_close` runs
+/// once the peer has closed or the connection failed.
+pub async fn connect<S>(
+    stream: S,
+    host: &str,
+    port: u16,
+    secure: bool,
+    opts: &WsOptions,
+    on_data: impl Fn(Vec<u8>, FrameKind) + Send + 'static,
+    on_close: impl FnOnce() + Send + 'static,
+) -> Result<WsConnection, ConnectError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let config_err = |e: String| ConnectError::new(ErrorCategory::Config, e);
+    let scheme = if secure { "wss" } else { "ws" };
+    let path = match opts.path.as_str() {
+        "" => "/".to_string(),
+        p if p.starts_with('/') => p.to_string(),
+        p => format!("/{p}"),
+    };
+    let mut request = format!("{scheme}://{}{path}", dns::host_port(dns::bare_host(host), port))
+        .into_client_request()
+        .map_err(|e| config_err(e.to_string()))?;
+    for (name, value) in &opts.headers {
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| config_err(format!("{name}: {e}")))?;
+        let value = HeaderValue::from_str(value).map_err(|e| config_err(format!("{name}: {e}")))?;
+        request.headers_mut().insert(name, value);
+    }
+    if !opts.subprotocols.is_empty() {
+        let value = HeaderValue::from_str(&opts.subprotocols.join(", ")).map_err(|e| config_err(e.to_string()))?;
+        request.headers_mut().insert(header::SEC_WEBSOCKET_PROTOCOL, value);
+    }
+
+    let (ws, response) = tokio_tungstenite::client_async(request, stream)
+        .await
+        .map_err(|e| ConnectError::new(ErrorCategory::Handshake, e))?;
+    let protocol = response.headers()
+        .get(header::SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    let (mut sink, mut frames) = ws.split();
+    let (tx, mut rx): (UnboundedSender<Outgoing>, UnboundedReceiver<Outgoing>) = mpsc::unbounded_channel();
+    let (shutdown, mut shutdown_rx) = oneshot::channel::<()>();
+    let send_as = opts.send_as;
+
+    let reader = tokio::spawn(async move {
+        while let Some(msg) = frames.next().await {
+            match msg {
+                Ok(Message::Binary(data)) => on_data(data.to_vec(), FrameKind::Binary),
+                Ok(Message::Text(text)) => on_data(text.as_bytes().to_vec(), FrameKind::Text),
+                Ok(Message::Close(_)) | Err(_) => break,
+                Ok(_) => {} // ping/pong are answered by tungstenite
+            }
+        }
+        on_close();
+    })
+    .abort_handle();
+
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                biased;
+                out = rx.recv() => match out {
+                    Some(out) => if !send(&mut sink, out, send_as).await { break },
+                    None => break,
+                },
+                Ok(()) = &mut shutdown_rx => {
+                    while let Ok(out) = rx.try_recv() {
+                        if !send(&mut sink, out, send_as).await { break; }
+                    }
+                    let _ = sink.send(Message::Close(None)).await;
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(WsConnection { tx, shutdown, reader, protocol })
+}
+
+/// Send one queued message as a `kind` frame and report the outcome to its
+/// sender. Data that is not valid UTF-8 fails as a text frame without
+/// closing the connection.
+async fn send<S>(sink: &mut S, mut out: Outgoing, kind: FrameKind) -> bool
+where
+    S: futures_util::Sink<Message, Error = tokio_tungstenite::tungstenite::Error> + Unpin,
+{
+    let data = std::mem::take(&mut out.data);
+    let msg = match kind {
+        FrameKind::Binary => Message::binary(data),
+        FrameKind::Text => match String::from_utf8(data) {
+            Ok(text) => Message::text(text),
+            Err(_) => {
+                out.done(Err("Text frames must be valid UTF-8".into()));
+                return true;
+            }
+        },
+    };
+    let result = sink.send(msg).await.map_err(|e| e.to_string());
+    let ok = result.is_ok();
+    out.done(result);
+    ok
+}
@@ -523,7 +523,10 @@ export function AppProvider({ children }: { children: ReactNode }) {
       }
     }).then(fn => {
       if (cancelled) fn(); // StrictMode: already cleaned up, unlisten immediately
-      else cleanup = fn;
+      else {
+        cleanup = fn;
+        api.negotiateSchema().catch(() => {});
+      }
     });
 
     return () => {
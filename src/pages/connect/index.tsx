@@ -11,6 +11,7 @@ import {
   BAUD_RATES, DATA_BITS, PARITY_OPTIONS, STOP_BITS,
   FLOW_CONTROL, SERIAL_PRESETS
 } from '../../shared/config/tokens';
+import type { FlowControl } from '../../shared/types';
 
 type TcpMode = 'tcp-client' | 'tcp-server' | 'udp' | 'tls' | 'ws';
 
@@ -130,7 +131,7 @@ export function ConnectPage() {
     if (!port) return;
     setSerialLoading(true); setSerialError('');
     try {
-      const session = await api.connectSerial(port, baud);
+      const session = await api.connectSerial(port, baud, flow as FlowControl);
       dispatch({ type: 'ADD_SESSION', session });
       dispatch({ type: 'SET_ACTIVE_SESSION', id: session.id });
       dispatch({ type: 'SET_RECEIVING', id: session.id, on: true });
@@ -145,7 +146,7 @@ export function ConnectPage() {
     try {
       const portNum = parseInt(tcpPort, 10);
       if (isNaN(portNum) || portNum < 1 || portNum > 65535) { setTcpError(t('connect.portError')); setTcpLoading(false); return; }
-      const session = await api.connectTcp(host, portNum);
+      const session = await api.connectTcp(host, portNum, { nodelay: nagle === 'off' });
       dispatch({ type: 'ADD_SESSION', session });
       dispatch({ type: 'SET_ACTIVE_SESSION', id: session.id });
       dispatch({ type: 'SET_RECEIVING', id: session.id, on: true });
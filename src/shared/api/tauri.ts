@@ -1,7 +1,24 @@
 import { invoke } from '@tauri-apps/api/core';
 import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { getCurrentWindow } from '@tauri-apps/api/window';
-import type { Packet, SplitterConfig, SessionInfo, TimingStats, ChecksumResult } from '../types';
+import type {
+  Packet, WirePacket, SplitterConfig, SessionInfo, TimingStats, ChecksumResult,
+  RackBoard, RackBoardStatus, LogMode, ClockInfo, UdpPeerEvent,
+  SocketOpenArgs, SocketStatusEvent, ConnectErrorEvent, ConnectProgressEvent, SysEvent, TlsFingerprintEvent, TofuPin, SshOptions, ComPortOptions, MqttOptions, Service, AgentOptions, AgentClockEvent,
+  SmsPdu, SmsMessage, SmsEntry, SmsEvent, NtripOptions, Mountpoint,
+  MirrorTarget, MirrorDirection, MirrorInfo, MirrorDataEvent, MirrorAnnotationEvent, ClassRule, TxAppend,
+  CompareOptions, CompareDivergence, CompareStatus,
+  PairingConfig, Transaction, TransactionStats, Protocol, ProtocolStats, DecodedEvent,
+  HistogramSummary, TimelineFormat, TimeBase, FlowControl, FlowControlEvent, PpsOptions, TimeMark,
+  ChatStep, ChatScript, ChatResult, ChatResultEvent, VarCapture, SessionVarEvent, RoundtripSummary, FixtureReport,
+  FuzzOptions, FuzzCase, FuzzSummary, ByteDistribution, ProtocolDetection, SavedPayload, PayloadFormat, FramingPreset,
+  SessionMeta, SessionMetaEvent, NotifyConfig, SessionClosedEvent, AlertRule, AlertEvent,
+  SegmentConfig, Segment, DeviceScript, DeviceTransport, DeviceInfo, DeviceEvent, ImportSummary, SessionImport, ManifestReport,
+  BenchProfile, BenchOpenResult, SweepPlan, SweepAttempt, SweepReport, TxStatusEvent, SessionSummary, TimeSource, TimeConfig, Capabilities, HistoryEntry, HistoryQuery, Quota, QuotaEvent, Finding,
+  IdentifyProbe, SessionIdentityEvent, ReconnectPolicy, Resync,
+  CaptureFilterInfo, RxStage, ServerOptions, ServerInfo, ServerClientEvent, BridgeInfo, RemoteOptions, RemoteInfo, AuditEntry, SeqRule, UdpOptions, DatagramStats,
+  SerialSettings, LineErrors, LineErrorsEvent, WaitForPort, PortWaitEvent, BaudWatchConfig, BaudMismatchEvent, BaudChangedEvent,
+} from '../types';
 
 // ── Window controls ────────────────────────────────────────────
 const win = getCurrentWindow();
@@ -12,11 +29,101 @@ export const windowControls = {
 };
 
 // ── Connection ────────────────────────────────────────────────
-export const connectSerial = (port: string, baud: number) =>
-  invoke<SessionInfo>('connect_serial', { port, baud });
+export const connectSerial = (
+  port: string, baud: number, flow?: FlowControl, wait?: Partial<WaitForPort>, settings?: Partial<SerialSettings>,
+) =>
+  invoke<SessionInfo>('connect_serial', { port, baud, flow, wait, settings });
 
-export const connectTcp = (host: string, port: number) =>
-  invoke<SessionInfo>('connect_tcp', { host, port });
+export const setSerialSettings = (sessionId: string, settings: SerialSettings) =>
+  invoke<void>('set_serial_settings', { sessionId, settings });
+
+export const getSerialSettings = (sessionId: string) =>
+  invoke<SerialSettings>('get_serial_settings', { sessionId });
+
+export const getLineErrors = (sessionId: string) =>
+  invoke<LineErrors>('get_line_errors', { sessionId });
+
+export const setBaudRate = (sessionId: string, baud: number) =>
+  invoke<void>('set_baud_rate', { sessionId, baud });
+
+// RFC 2217 sessions; the server confirms with 'com_port' sys events
+export const comPortConfigure = (sessionId: string, options: ComPortOptions) =>
+  invoke<void>('com_port_configure', { sessionId, options });
+
+export const comPortSetLines = (sessionId: string, dtr?: boolean, rts?: boolean) =>
+  invoke<void>('com_port_set_lines', { sessionId, dtr, rts });
+
+export const comPortBreak = (sessionId: string, on: boolean) =>
+  invoke<void>('com_port_break', { sessionId, on });
+
+export const comPortPurge = (sessionId: string) =>
+  invoke<void>('com_port_purge', { sessionId });
+
+export const connectTcp = (host: string, port: number, opts: Partial<SocketOpenArgs> = {}) =>
+  invoke<SessionInfo>('connect_tcp', { args: { ...opts, host, port } });
+
+export const connectSsh = (opts: SshOptions) =>
+  invoke<SessionInfo>('connect_ssh', { opts });
+
+export const mqttConnect = (opts: MqttOptions) =>
+  invoke<SessionInfo>('mqtt_connect', { opts });
+
+export const mqttSubscribe = (sessionId: string, filter: string, qos?: 0 | 1) =>
+  invoke<number>('mqtt_subscribe', { sessionId, filter, qos });
+
+export const mqttUnsubscribe = (sessionId: string, filter: string) =>
+  invoke<void>('mqtt_unsubscribe', { sessionId, filter });
+
+// Resolves to the TX packet id, like `sendBytes`
+export const mqttTx = (sessionId: string, topic: string, hex: string, qos?: 0 | 1, retain?: boolean) =>
+  invoke<number>('mqtt_tx', { sessionId, topic, hex, qos, retain });
+
+// Browses mDNS, e.g. for '_telnet._tcp'; resolves after timeoutMs (default 2000)
+export const discoverServices = (serviceType: string, timeoutMs?: number) =>
+  invoke<Service[]>('discover_services', { serviceType, timeoutMs });
+
+// Attaches to a wirescope started with --agent; its sessions appear as
+// 'agent:<host:port>/<id>'
+export const agentAttach = (options: AgentOptions) =>
+  invoke<SessionInfo[]>('agent_attach', { options });
+
+export const agentDetach = (agent: string) =>
+  invoke<void>('agent_detach', { agent });
+
+export const listAgents = () =>
+  invoke<string[]>('list_agents');
+
+export const socketShutdown = (sessionId: string) =>
+  invoke<void>('socket_shutdown', { sessionId });
+
+export const connectUdp = (
+  host: string, port: number, localPort?: number, replyToSender = false, options?: Partial<UdpOptions>,
+) =>
+  invoke<SessionInfo>('connect_udp', { host, port, localPort, replyToSender, options });
+
+export const getUdpStats = (sessionId: string) =>
+  invoke<DatagramStats>('get_udp_stats', { sessionId });
+
+export const setUdpSequence = (sessionId: string, rule: SeqRule | null) =>
+  invoke<void>('set_udp_sequence', { sessionId, rule });
+
+export const resetUdpStats = (sessionId: string) =>
+  invoke<void>('reset_udp_stats', { sessionId });
+
+export const setUdpPeer = (sessionId: string, peer: string) =>
+  invoke<void>('set_udp_peer', { sessionId, peer });
+
+export const probeTlsFingerprint = (host: string, port: number) =>
+  invoke<string>('probe_tls_fingerprint', { host, port });
+
+export const tofuAccept = (host: string, port: number, fingerprint: string) =>
+  invoke<void>('tofu_accept', { host, port, fingerprint });
+
+export const tofuForget = (host: string, port: number) =>
+  invoke<void>('tofu_forget', { host, port });
+
+export const tofuList = () =>
+  invoke<TofuPin[]>('tofu_list');
 
 export const disconnect = (sessionId: string) =>
   invoke<void>('disconnect', { sessionId });
@@ -24,9 +131,85 @@ export const disconnect = (sessionId: string) =>
 export const listSerialPorts = () =>
   invoke<string[]>('list_serial_ports');
 
+// ── Capture filters ───────────────────────────────────────────
+export const setCaptureFilter = (sessionId: string, expr: string) =>
+  invoke<void>('set_capture_filter', { sessionId, expr });
+
+export const getCaptureFilters = () =>
+  invoke<CaptureFilterInfo[]>('get_capture_filters');
+
+// ── RX pipeline ───────────────────────────────────────────────
+export const setRxPipeline = (sessionId: string, stages: RxStage[]) =>
+  invoke<void>('set_rx_pipeline', { sessionId, stages });
+
+export const getRxPipeline = (sessionId: string) =>
+  invoke<RxStage[]>('get_rx_pipeline', { sessionId });
+
+// ── Serial sniffing ───────────────────────────────────────────
+export const sniffSerial = (port: string, name?: string) =>
+  invoke<SessionInfo>('sniff_serial', { port, name });
+
+// ── TCP server ────────────────────────────────────────────────
+export const serverStart = (options: Partial<ServerOptions>) =>
+  invoke<ServerInfo>('server_start', { options });
+
+export const serverStop = (serverId: string) =>
+  invoke<void>('server_stop', { serverId });
+
+export const serverList = () =>
+  invoke<ServerInfo[]>('server_list');
+
+// Sends to every client; resolves to one TX packet id per client
+export const serverBroadcast = (serverId: string, hex: string) =>
+  invoke<number[]>('server_broadcast', { serverId, hex });
+
+// ── Remote API ────────────────────────────────────────────────
+export const remoteStart = (options: Partial<RemoteOptions>) =>
+  invoke<RemoteInfo>('remote_start', { options });
+
+export const remoteStop = () =>
+  invoke<void>('remote_stop');
+
+export const remoteInfo = () =>
+  invoke<RemoteInfo | null>('remote_info');
+
+export const remoteAudit = (limit?: number) =>
+  invoke<AuditEntry[]>('remote_audit', { limit });
+
+// ── Virtual devices ───────────────────────────────────────────
+export const deviceStart = (script: Partial<DeviceScript>, transport: DeviceTransport) =>
+  invoke<DeviceInfo>('device_start', { script, transport });
+
+export const deviceStop = (deviceId: string) =>
+  invoke<void>('device_stop', { deviceId });
+
+export const deviceList = () =>
+  invoke<DeviceInfo[]>('device_list');
+
+// ── Baud detection ────────────────────────────────────────────
+export const autoBaud = (sessionId: string) =>
+  invoke<number>('auto_baud', { sessionId });
+
+export const setBaudWatch = (config: BaudWatchConfig) =>
+  invoke<void>('set_baud_watch', { config });
+
+export const getBaudWatch = () =>
+  invoke<BaudWatchConfig>('get_baud_watch');
+
 // ── Packets ───────────────────────────────────────────────────
-export const sendBytes = (hex: string, sessionId: string) =>
-  invoke<void>('send_bytes', { hex, sessionId });
+// `data` is hex unless `format` says otherwise; `escape` resolves C-style
+// escapes such as \x1B in text
+export const sendBytes = (data: string, sessionId: string, format?: PayloadFormat, escape?: boolean) =>
+  invoke<number>('send_bytes', { hex: data, sessionId, format, escape });
+
+export const sendBytesConfirmed = (data: string, sessionId: string, timeoutMs?: number, format?: PayloadFormat, escape?: boolean) =>
+  invoke<number>('send_bytes_confirmed', { hex: data, sessionId, timeoutMs, format, escape });
+
+export const txRawKey = (sessionId: string, bytes: number[]) =>
+  invoke<void>('tx_raw_key', { sessionId, bytes });
+
+export const setTxAppend = (sessionId: string, mode: TxAppend) =>
+  invoke<void>('set_tx_append', { sessionId, mode });
 
 export const getPackets = () =>
   invoke<Packet[]>('get_packets');
@@ -38,6 +221,35 @@ export const clearPackets = () =>
 export const getSessions = () =>
   invoke<SessionInfo[]>('get_sessions');
 
+export const resync = (connId: string, lastSeq: number) =>
+  invoke<Resync>('resync', { connId, lastSeq });
+
+// Newest event schema this build understands; 2 sends packet bytes as base64
+export const EVENT_SCHEMA = 2;
+
+export const negotiateSchema = (supported = EVENT_SCHEMA) =>
+  invoke<number>('negotiate_schema', { supported });
+
+export const setSessionMeta = (sessionId: string, meta: SessionMeta) =>
+  invoke<void>('set_session_meta', { sessionId, meta });
+
+// ── Notifications ─────────────────────────────────────────────
+export const setNotifications = (config: NotifyConfig) =>
+  invoke<void>('set_notifications', { config });
+
+export const getNotifications = () =>
+  invoke<NotifyConfig>('get_notifications');
+
+export const playAlertSound = (times?: number) =>
+  invoke<void>('play_alert_sound', { times });
+
+// ── Alerts ────────────────────────────────────────────────────
+export const setAlertRules = (rules: AlertRule[]) =>
+  invoke<void>('set_alert_rules', { rules });
+
+export const getAlertRules = () =>
+  invoke<AlertRule[]>('get_alert_rules');
+
 // ── Splitter ──────────────────────────────────────────────────
 export const setSplitter = (config: SplitterConfig) =>
   invoke<void>('set_splitter', { config });
@@ -45,6 +257,25 @@ export const setSplitter = (config: SplitterConfig) =>
 export const getSplitter = () =>
   invoke<SplitterConfig>('get_splitter');
 
+export const framingPresets = () =>
+  invoke<FramingPreset[]>('framing_presets');
+
+export const applyFramingPreset = (name: string) =>
+  invoke<SplitterConfig>('apply_framing_preset', { name });
+
+export const saveFramingPreset = (name: string, description?: string, config?: SplitterConfig) =>
+  invoke<void>('save_framing_preset', { name, description, config });
+
+export const deleteFramingPreset = (name: string) =>
+  invoke<void>('delete_framing_preset', { name });
+
+// ── Classification ────────────────────────────────────────────
+export const setClassRules = (rules: ClassRule[]) =>
+  invoke<void>('set_class_rules', { rules });
+
+export const getClassRules = () =>
+  invoke<ClassRule[]>('get_class_rules');
+
 // ── Checksum ──────────────────────────────────────────────────
 export const computeChecksum = (algo: string, hex: string) =>
   invoke<ChecksumResult>('compute_checksum', { algo, hex });
@@ -56,10 +287,405 @@ export const computeAllChecksums = (hex: string) =>
 export const getTimingStats = () =>
   invoke<TimingStats>('get_timing_stats');
 
+export const analyzeBytes = (sessionId: string, direction?: 'RX' | 'TX', fromMs?: number, toMs?: number) =>
+  invoke<ByteDistribution>('analyze_bytes', { sessionId, direction, fromMs, toMs });
+
+// ── Protocol statistics ───────────────────────────────────────
+export const setDecoder = (sessionId: string, protocol: Protocol | null) =>
+  invoke<void>('set_decoder', { sessionId, protocol });
+
+export const detectProtocol = (sessionId: string, sampleMs?: number, autoEnable?: boolean) =>
+  invoke<ProtocolDetection>('detect_protocol', { sessionId, sampleMs, autoEnable });
+
+export const getProtocolStats = () =>
+  invoke<ProtocolStats[]>('get_protocol_stats');
+
+export const resetProtocolStats = () =>
+  invoke<void>('reset_protocol_stats');
+
+// ── Transactions ──────────────────────────────────────────────
+export const setPairing = (config: PairingConfig) =>
+  invoke<void>('set_pairing', { config });
+
+export const getPairing = () =>
+  invoke<PairingConfig>('get_pairing');
+
+export const getTransactionStats = () =>
+  invoke<TransactionStats[]>('get_transaction_stats');
+
+export const getLatencyHistograms = () =>
+  invoke<HistogramSummary[]>('get_latency_histograms');
+
+export const exportLatencyHistograms = (format: 'csv' | 'json') =>
+  invoke<string>('export_latency_histograms', { format });
+
+export const resetTransactionStats = () =>
+  invoke<void>('reset_transaction_stats');
+
 // ── Export ────────────────────────────────────────────────────
 export const exportPackets = (json: string, ext = 'json') =>
   invoke<string>('export_packets', { json, ext });
 
+export const exportTimeline = (sessionIds: string[], format: TimelineFormat = 'text', timeBase?: TimeBase) =>
+  invoke<string>('export_timeline', { sessionIds, format, timeBase });
+
+// ── Import ────────────────────────────────────────────────────
+export const importCapture = (path: string) =>
+  invoke<ImportSummary>('import_capture', { path });
+
+// ── Session files ─────────────────────────────────────────────
+export const sessionExport = (sessionId: string) =>
+  invoke<string>('session_export', { sessionId });
+
+export const sessionImport = (path: string) =>
+  invoke<SessionImport>('session_import', { path });
+
+// ── Segmentation ──────────────────────────────────────────────
+export const setSegmentation = (sessionId: string, config: SegmentConfig | null) =>
+  invoke<void>('set_segmentation', { sessionId, config });
+
+export const getSegments = (sessionId: string) =>
+  invoke<Segment[]>('get_segments', { sessionId });
+
+export const exportSegment = (sessionId: string, index: number, format: TimelineFormat = 'text', timeBase?: TimeBase) =>
+  invoke<string>('export_segment', { sessionId, index, format, timeBase });
+
+export const copyRange = (
+  sessionId: string,
+  range: { fromMs?: number; toMs?: number; lastMs?: number },
+  format: TimelineFormat = 'text',
+  timeBase?: TimeBase,
+) =>
+  invoke<string>('copy_range', { sessionId, ...range, format, timeBase });
+
+// ── Quotas ────────────────────────────────────────────────────
+export const setQuota = (sessionId: string, quota: Quota | null) =>
+  invoke<void>('set_quota', { sessionId, quota });
+
+export const getQuota = (sessionId: string) =>
+  invoke<Quota | null>('get_quota', { sessionId });
+
+// ── Device identification ─────────────────────────────────────
+export const setIdentifyProbe = (sessionId: string, probe: IdentifyProbe | null) =>
+  invoke<void>('set_identify_probe', { sessionId, probe });
+
+export const getIdentifyProbe = (sessionId: string) =>
+  invoke<IdentifyProbe | null>('get_identify_probe', { sessionId });
+
+// ── Auto reconnect ────────────────────────────────────────────
+export const setAutoReconnect = (sessionId: string, policy: Partial<ReconnectPolicy> | null) =>
+  invoke<void>('set_auto_reconnect', { sessionId, policy });
+
+export const getAutoReconnect = (sessionId: string) =>
+  invoke<ReconnectPolicy | null>('get_auto_reconnect', { sessionId });
+
+// ── Rack ──────────────────────────────────────────────────────
+export const openRack = (boards: RackBoard[], logDir?: string, logMode?: LogMode) =>
+  invoke<RackBoardStatus[]>('open_rack', { boards, logDir, logMode });
+
+export const getRackStatus = () =>
+  invoke<RackBoardStatus[]>('get_rack_status');
+
+export const closeRack = () =>
+  invoke<void>('close_rack');
+
+export const exportRackTimeline = () =>
+  invoke<string>('export_rack_timeline');
+
+export const verifyLogManifest = (dir: string) =>
+  invoke<ManifestReport>('verify_log_manifest', { dir });
+
+// ── Session history ───────────────────────────────────────────
+export const historyList = () =>
+  invoke<HistoryEntry[]>('history_list');
+
+export const historySearch = (query: HistoryQuery) =>
+  invoke<HistoryEntry[]>('history_search', { query });
+
+export const historyTag = (id: number, tags: string[]) =>
+  invoke<HistoryEntry>('history_tag', { id, tags });
+
+export const historyForget = (id: number) =>
+  invoke<void>('history_forget', { id });
+
+// ── Bench profiles ────────────────────────────────────────────
+export const openBench = (profile: BenchProfile) =>
+  invoke<BenchOpenResult>('open_bench', { profile });
+
+// ── Parameter sweep ───────────────────────────────────────────
+export const sweepRun = (plan: SweepPlan) =>
+  invoke<SweepReport>('sweep_run', { plan });
+
+// ── Clock ─────────────────────────────────────────────────────
+export const syncNtp = (server?: string) =>
+  invoke<ClockInfo>('sync_ntp', { server });
+
+export const getClockInfo = () =>
+  invoke<ClockInfo>('get_clock_info');
+
+export const clearNtpOffset = () =>
+  invoke<void>('clear_ntp_offset');
+
+export const setTimeSource = (source: TimeSource) =>
+  invoke<TimeConfig>('set_time_source', { source });
+
+export const getTimeConfig = () =>
+  invoke<TimeConfig>('get_time_config');
+
+export const setClockOffset = (sessionId: string, offsetMs: number) =>
+  invoke<void>('set_clock_offset', { sessionId, offsetMs });
+
+export const setDeviceTimeOffset = (sessionId: string, offsetMs: number | null) =>
+  invoke<void>('set_device_time_offset', { sessionId, offsetMs });
+
+// ── PPS time marks ────────────────────────────────────────────
+export const ppsStart = (sessionId: string, options: Partial<PpsOptions> = {}) =>
+  invoke<void>('pps_start', { sessionId, options });
+
+export const ppsStop = (sessionId: string) =>
+  invoke<void>('pps_stop', { sessionId });
+
+// ── SMS ───────────────────────────────────────────────────────
+export const smsEncodePdu = (number: string, text: string) =>
+  invoke<SmsPdu>('sms_encode_pdu', { number, text });
+
+export const smsDecodePdu = (pdu: string) =>
+  invoke<SmsMessage>('sms_decode_pdu', { pdu });
+
+export const smsSend = (sessionId: string, number: string, text: string) =>
+  invoke<number | null>('sms_send', { sessionId, number, text });
+
+export const smsList = (sessionId: string) =>
+  invoke<SmsEntry[]>('sms_list', { sessionId });
+
+export const smsRead = (sessionId: string, index: number) =>
+  invoke<SmsEntry>('sms_read', { sessionId, index });
+
+export const smsWatch = (sessionId: string, enabled: boolean) =>
+  invoke<void>('sms_watch', { sessionId, enabled });
+
+// ── Payload library ───────────────────────────────────────────
+export const payloadList = () =>
+  invoke<SavedPayload[]>('payload_list');
+
+export const payloadAdd = (payload: SavedPayload) =>
+  invoke<void>('payload_add', { payload });
+
+export const payloadUpdate = (name: string, payload: SavedPayload) =>
+  invoke<void>('payload_update', { name, payload });
+
+export const payloadDelete = (name: string) =>
+  invoke<void>('payload_delete', { name });
+
+export const payloadSend = (sessionId: string, name: string) =>
+  invoke<void>('payload_send', { sessionId, name });
+
+export const payloadImport = (path: string, overwrite?: boolean) =>
+  invoke<number>('payload_import', { path, overwrite });
+
+export const payloadExport = () =>
+  invoke<string>('payload_export');
+
+// ── Chat scripts ──────────────────────────────────────────────
+export const chatRun = (sessionId: string, script: Partial<ChatScript> & { steps: ChatStep[] }) =>
+  invoke<ChatResult>('chat_run', { sessionId, script });
+
+// ── Session variables ─────────────────────────────────────────
+export const varsGet = (sessionId: string) =>
+  invoke<Record<string, string>>('vars_get', { sessionId });
+
+export const varsSet = (sessionId: string, name: string, value: string | null) =>
+  invoke<void>('vars_set', { sessionId, name, value });
+
+export const varsClear = (sessionId: string) =>
+  invoke<void>('vars_clear', { sessionId });
+
+export const setVarCaptures = (sessionId: string, captures: VarCapture[]) =>
+  invoke<void>('set_var_captures', { sessionId, captures });
+
+export const getVarCaptures = (sessionId: string) =>
+  invoke<VarCapture[]>('get_var_captures', { sessionId });
+
+// ── Benchmark ─────────────────────────────────────────────────
+export const benchmarkRoundtrip = (sessionId: string, payloadSize: number, iterations: number, timeoutMs?: number) =>
+  invoke<RoundtripSummary>('benchmark_roundtrip', { sessionId, payloadSize, iterations, timeoutMs });
+
+// ── Fixtures ──────────────────────────────────────────────────
+export const fixtureRecordStart = (sessionId: string) =>
+  invoke<void>('fixture_record_start', { sessionId });
+
+export const fixtureRecordStop = (sessionId: string) =>
+  invoke<string>('fixture_record_stop', { sessionId });
+
+export const fixtureTest = (fixturePath: string, expectedPath?: string, update?: boolean) =>
+  invoke<FixtureReport>('fixture_test', { fixturePath, expectedPath, update });
+
+// ── Fuzzing ───────────────────────────────────────────────────
+export const fuzzStart = (sessionId: string, options: Partial<FuzzOptions> & { seed: number[] }) =>
+  invoke<number>('fuzz_start', { sessionId, options });
+
+export const fuzzStop = (sessionId: string) =>
+  invoke<void>('fuzz_stop', { sessionId });
+
+// ── NTRIP ─────────────────────────────────────────────────────
+export const ntripSourceTable = (caster: NtripOptions) =>
+  invoke<Mountpoint[]>('ntrip_source_table', { caster });
+
+export const ntripConnect = (caster: NtripOptions, targetSession: string, ggaIntervalS?: number) =>
+  invoke<SessionInfo>('ntrip_connect', { caster, targetSession, ggaIntervalS });
+
+// ── Mirroring ─────────────────────────────────────────────────
+export const mirrorAttach = (sessionId: string, target: MirrorTarget, direction?: MirrorDirection) =>
+  invoke<MirrorInfo>('mirror_attach', { sessionId, target, direction });
+
+export const mirrorDetach = (id: number) =>
+  invoke<void>('mirror_detach', { id });
+
+export const mirrorList = () =>
+  invoke<MirrorInfo[]>('mirror_list');
+
+// Shares a serial port over TCP, opening it first unless it is open
+export const bridgeStart = (port: string, options: Partial<ServerOptions>, baud?: number) =>
+  invoke<BridgeInfo>('bridge_start', { port, baud, options });
+
+export const bridgeStop = (sessionId: string) =>
+  invoke<void>('bridge_stop', { sessionId });
+
+export const bridgeList = () =>
+  invoke<BridgeInfo[]>('bridge_list');
+
+// ── Compare ───────────────────────────────────────────────────
+export const compareStart = (options: CompareOptions) =>
+  invoke<void>('compare_start', { options });
+
+export const compareStop = () =>
+  invoke<CompareStatus | null>('compare_stop');
+
+export const compareStatus = () =>
+  invoke<CompareStatus | null>('compare_status');
+
+// ── Capabilities ──────────────────────────────────────────────
+export const getCapabilities = () =>
+  invoke<Capabilities>('get_capabilities');
+
+// ── Diagnostics ───────────────────────────────────────────────
+export const runDiagnostics = (logDir?: string) =>
+  invoke<Finding[]>('diagnostics', { logDir });
+
 // ── Events ────────────────────────────────────────────────────
+const decodeBytes = (bytes: number[] | string): number[] =>
+  typeof bytes === 'string' ? Array.from(atob(bytes), c => c.charCodeAt(0)) : bytes;
+
 export const onPacket = (cb: (pkt: Packet) => void): Promise<UnlistenFn> =>
-  listen<Packet>('packet', e => cb(e.payload));
+  listen<WirePacket>('packet', e => cb({ ...e.payload, bytes: decodeBytes(e.payload.bytes) }));
+
+export const onSocketStatus = (cb: (ev: SocketStatusEvent) => void): Promise<UnlistenFn> =>
+  listen<SocketStatusEvent>('socket_status', e => cb(e.payload));
+
+export const onConnectError = (cb: (ev: ConnectErrorEvent) => void): Promise<UnlistenFn> =>
+  listen<ConnectErrorEvent>('connect_error', e => cb(e.payload));
+
+export const onConnectProgress = (cb: (ev: ConnectProgressEvent) => void): Promise<UnlistenFn> =>
+  listen<ConnectProgressEvent>('connect_progress', e => cb(e.payload));
+
+export const onSysEvent = (cb: (ev: SysEvent) => void): Promise<UnlistenFn> =>
+  listen<SysEvent>('sys_event', e => cb(e.payload));
+
+export const onTlsFingerprint = (cb: (ev: TlsFingerprintEvent) => void): Promise<UnlistenFn> =>
+  listen<TlsFingerprintEvent>('tls_fingerprint', e => cb(e.payload));
+
+export const onSshHostKey = (cb: (ev: TlsFingerprintEvent) => void): Promise<UnlistenFn> =>
+  listen<TlsFingerprintEvent>('ssh_host_key', e => cb(e.payload));
+
+export const onUdpPeer = (cb: (ev: UdpPeerEvent) => void): Promise<UnlistenFn> =>
+  listen<UdpPeerEvent>('udp_peer', e => cb(e.payload));
+
+export const onSms = (cb: (ev: SmsEvent) => void): Promise<UnlistenFn> =>
+  listen<SmsEvent>('sms', e => cb(e.payload));
+
+export const onMirrorData = (cb: (ev: MirrorDataEvent) => void): Promise<UnlistenFn> =>
+  listen<MirrorDataEvent>('mirror_data', e => cb(e.payload));
+
+export const onMirrorAnnotation = (cb: (ev: MirrorAnnotationEvent) => void): Promise<UnlistenFn> =>
+  listen<MirrorAnnotationEvent>('mirror_annotation', e => cb(e.payload));
+
+export const onCompareDivergence = (cb: (ev: CompareDivergence) => void): Promise<UnlistenFn> =>
+  listen<CompareDivergence>('compare_divergence', e => cb(e.payload));
+
+export const onTransaction = (cb: (t: Transaction) => void): Promise<UnlistenFn> =>
+  listen<Transaction>('transaction', e => cb(e.payload));
+
+export const onProtocolStats = (cb: (stats: ProtocolStats) => void): Promise<UnlistenFn> =>
+  listen<ProtocolStats>('protocol_stats', e => cb(e.payload));
+
+export const onDecoded = (cb: (ev: DecodedEvent) => void): Promise<UnlistenFn> =>
+  listen<DecodedEvent>('decoded', e => cb(e.payload));
+
+export const onAgentClock = (cb: (ev: AgentClockEvent) => void): Promise<UnlistenFn> =>
+  listen<AgentClockEvent>('agent_clock', e => cb(e.payload));
+
+export const onFlowControl = (cb: (ev: FlowControlEvent) => void): Promise<UnlistenFn> =>
+  listen<FlowControlEvent>('flow_control', e => cb(e.payload));
+
+export const onTimeMark = (cb: (ev: TimeMark) => void): Promise<UnlistenFn> =>
+  listen<TimeMark>('time_mark', e => cb(e.payload));
+
+export const onPortWait = (cb: (ev: PortWaitEvent) => void): Promise<UnlistenFn> =>
+  listen<PortWaitEvent>('port_wait', e => cb(e.payload));
+
+export const onBaudMismatch = (cb: (ev: BaudMismatchEvent) => void): Promise<UnlistenFn> =>
+  listen<BaudMismatchEvent>('baud_mismatch', e => cb(e.payload));
+
+export const onBaudChanged = (cb: (ev: BaudChangedEvent) => void): Promise<UnlistenFn> =>
+  listen<BaudChangedEvent>('baud_changed', e => cb(e.payload));
+
+export const onServerClient = (cb: (ev: ServerClientEvent) => void): Promise<UnlistenFn> =>
+  listen<ServerClientEvent>('server_client', e => cb(e.payload));
+
+export const onRemoteAudit = (cb: (ev: AuditEntry) => void): Promise<UnlistenFn> =>
+  listen<AuditEntry>('remote_audit', e => cb(e.payload));
+
+export const onLineErrors = (cb: (ev: LineErrorsEvent) => void): Promise<UnlistenFn> =>
+  listen<LineErrorsEvent>('line_errors', e => cb(e.payload));
+
+export const onChatResult = (cb: (ev: ChatResultEvent) => void): Promise<UnlistenFn> =>
+  listen<ChatResultEvent>('chat_result', e => cb(e.payload));
+
+export const onSessionVar = (cb: (ev: SessionVarEvent) => void): Promise<UnlistenFn> =>
+  listen<SessionVarEvent>('session_var', e => cb(e.payload));
+
+export const onSweepAttempt = (cb: (ev: SweepAttempt) => void): Promise<UnlistenFn> =>
+  listen<SweepAttempt>('sweep_attempt', e => cb(e.payload));
+
+export const onFuzzCase = (cb: (ev: FuzzCase) => void): Promise<UnlistenFn> =>
+  listen<FuzzCase>('fuzz_case', e => cb(e.payload));
+
+export const onFuzzDone = (cb: (ev: FuzzSummary) => void): Promise<UnlistenFn> =>
+  listen<FuzzSummary>('fuzz_done', e => cb(e.payload));
+
+export const onSessionMeta = (cb: (ev: SessionMetaEvent) => void): Promise<UnlistenFn> =>
+  listen<SessionMetaEvent>('session_meta', e => cb(e.payload));
+
+export const onSessionClosed = (cb: (ev: SessionClosedEvent) => void): Promise<UnlistenFn> =>
+  listen<SessionClosedEvent>('session_closed', e => cb(e.payload));
+
+export const onAlert = (cb: (ev: AlertEvent) => void): Promise<UnlistenFn> =>
+  listen<AlertEvent>('alert', e => cb(e.payload));
+
+export const onSegment = (cb: (ev: Segment) => void): Promise<UnlistenFn> =>
+  listen<Segment>('segment', e => cb(e.payload));
+
+export const onDeviceEvent = (cb: (ev: DeviceEvent) => void): Promise<UnlistenFn> =>
+  listen<DeviceEvent>('device_event', e => cb(e.payload));
+
+export const onTxStatus = (cb: (ev: TxStatusEvent) => void): Promise<UnlistenFn> =>
+  listen<TxStatusEvent>('tx_status', e => cb(e.payload));
+
+export const onSessionSummary = (cb: (ev: SessionSummary) => void): Promise<UnlistenFn> =>
+  listen<SessionSummary>('session_summary', e => cb(e.payload));
+
+export const onSessionIdentity = (cb: (ev: SessionIdentityEvent) => void): Promise<UnlistenFn> =>
+  listen<SessionIdentityEvent>('session_identity', e => cb(e.payload));
+
+export const onQuotaReached = (cb: (ev: QuotaEvent) => void): Promise<UnlistenFn> =>
+  listen<QuotaEvent>('quota_reached', e => cb(e.payload));
@@ -4,8 +4,21 @@ export function bytesToHex(bytes: number[], sep = ' '): string {
   return bytes.map(b => b.toString(16).padStart(2, '0').toUpperCase()).join(sep);
 }
 
+// Drops C-style 0x prefixes, padding single digits: '0xA' -> '0A'. C-style
+// bytes (prefixed, or in a comma-separated list) must be one byte each.
+function stripHexPrefixes(hex: string): string {
+  const commas = hex.includes(',');
+  return hex.split(/[\s,:-]+/).map(token => {
+    const prefixed = /^0[xX]/.test(token);
+    const digits = prefixed ? token.slice(2) : token;
+    if (!prefixed && !(commas && token)) return digits;
+    if (!/^[0-9a-fA-F]{1,2}$/.test(digits)) throw new Error(`Not a single byte: ${token}`);
+    return digits.padStart(2, '0');
+  }).join('');
+}
+
 export function hexToBytes(hex: string): number[] {
-  const clean = hex.replace(/[^0-9a-fA-F]/g, '');
+  const clean = stripHexPrefixes(hex).replace(/[^0-9a-fA-F]/g, '');
   if (clean.length % 2 !== 0) throw new Error('Odd-length HEX string');
   return Array.from({ length: clean.length / 2 }, (_, i) =>
     parseInt(clean.slice(i * 2, i * 2 + 2), 16)
@@ -79,10 +92,14 @@ export function formatSize(bytes: number): string {
 // ── Hex display helpers ───────────────────────────────────────────
 
 export function parseHexInput(s: string): number[] {
-  return hexToBytes(s.replace(/[^0-9a-fA-F]/g, ''));
+  return hexToBytes(s);
 }
 
 export function isValidHex(s: string): boolean {
-  const clean = s.replace(/[^0-9a-fA-F]/g, '');
-  return clean.length > 0 && clean.length % 2 === 0;
+  try {
+    const clean = stripHexPrefixes(s).replace(/[^0-9a-fA-F]/g, '');
+    return clean.length > 0 && clean.length % 2 === 0;
+  } catch {
+    return false;
+  }
 }
@@ -8,11 +8,32 @@ export interface Packet {
   bytes:        number[];
   checksum_ok:  boolean | null;
   session_id:   string;
+  corrected_ts_ms?: number | null;
+  severity?:    string | null;
+  tags?:        string[];
+  transaction_id?: number | null;
+  device_ts_ms?: number | null;
+  time?:        string;
+  source?:      string | null;     // datagram sender or MQTT topic
+  frame?:       FrameKind | null;  // WebSocket message type
+  schema?:      number;            // absent before negotiate_schema
+}
+
+// A "packet" event as emitted: bytes are base64 from schema 2 on
+export type WirePacket = Omit<Packet, 'bytes'> & { bytes: number[] | string };
+
+export interface ClassRule {
+  pattern:           string;
+  severity?:         string | null;
+  category?:         string | null;
+  case_insensitive?: boolean;
+  sound?:            number;  // times to play the alert on RX match
 }
 
 export interface SplitterConfig {
-  method:                'delimiter' | 'length_field' | 'gap' | 'regex' | 'custom';
+  method:                'delimiter' | 'length_field' | 'gap' | 'line' | 'regex' | 'custom';
   regex_pattern?:        string;  // used when method === 'regex'
+  line_ending?:          'auto' | LineEnding;  // used when method === 'line'
   sof:                   number[];
   eof:                   number[];
   eof_include:           boolean;
@@ -35,14 +56,34 @@ export interface SplitterConfig {
 export interface SessionInfo {
   id:        string;
   name:      string;
-  kind:      'serial' | 'tcp' | 'udp' | 'tls' | 'ws';
+  kind:      'serial' | 'tcp' | 'udp' | 'tls' | 'ws' | 'telnet' | 'rfc2217' | 'ssh' | 'mqtt' | 'agent' | 'ntrip' | 'server' | 'import' | 'sniff' | 'unix';
   connected: boolean;
   tx_bytes:  number;
   rx_bytes:  number;
   baud_rate?: number;
   port_params?: string;
+  clock_offset_ms?: number;
+  line_ending?: LineEnding | null;  // detected on RX
+  tx_append?:   TxAppend;
+  device_time_offset_ms?: number | null;
+  meta?:        SessionMeta;
+  identity?:    string | null;  // response to the identification probe
+}
+
+export interface SessionMeta {
+  label: string | null;
+  color: string | null;
+  notes: string;
 }
 
+export interface SessionMetaEvent {
+  session_id: string;
+  meta:       SessionMeta;
+}
+
+export type LineEnding = 'cr' | 'lf' | 'crlf';
+export type TxAppend = 'none' | LineEnding | 'auto' | { custom: number[] };
+
 export interface TimingStats {
   total_packets:  number;
   total_bytes:    number;
@@ -64,6 +105,1073 @@ export interface ChecksumResult {
   hex:       string;
 }
 
+export type AddressFamily = 'any' | 'prefer_ipv4' | 'prefer_ipv6';
+
+export interface KeepaliveOptions {
+  enabled:      boolean;
+  idle_ms?:     number;
+  interval_ms?: number;
+  probes?:      number;
+}
+
+export interface SocketOpenArgs {
+  host:                string;
+  port:                number;
+  nodelay?:            boolean;
+  linger_ms?:          number;
+  send_buffer_size?:   number;
+  recv_buffer_size?:   number;
+  keepalive?:          KeepaliveOptions;
+  host_overrides?:     Record<string, string>;
+  resolver?:           'system' | 'direct';
+  dns_server?:         string;
+  address_family?:     AddressFamily;
+  tls?:                TlsOptions;
+  ws?:                 WsOptions;
+  proxy?:              ProxyOptions;
+  telnet?:             TelnetOptions;
+  mode?:               'client' | 'server';
+  proto?:              'tcp' | 'unix';
+  path?:               string;  // unix socket file
+  accept_timeout_ms?:  number;
+  connect_timeout_ms?: number;  // per address; default 10000
+  local_addr?:         string;  // IP to connect from, picking the interface
+  local_port?:         number;
+}
+
+export interface TelnetOptions {
+  show_negotiation?: boolean;          // option changes as 'telnet' sys events
+  com_port?:         ComPortOptions;   // RFC 2217 terminal server port
+}
+
+export interface ComPortOptions {
+  baud?:      number;                  // default 115200
+  data_bits?: 5 | 6 | 7 | 8;
+  parity?:    'none' | 'odd' | 'even' | 'mark' | 'space';
+  stop_bits?: 'one' | 'two' | 'one_point_five';
+  flow?:      FlowControl;             // done by the server
+}
+
+export interface ProxyOptions {
+  kind:      'http' | 'socks5';
+  host:      string;
+  port:      number;
+  username?: string;
+  password?: string;
+}
+
+export type FrameKind = 'binary' | 'text';
+
+export interface WsOptions {
+  path?:         string;
+  headers?:      Record<string, string>;
+  subprotocols?: string[];
+  send_as?:      FrameKind;
+}
+
+export interface TlsOptions {
+  client_pkcs12?:          string;
+  client_pkcs12_password?: string;
+  client_cert?:            string;
+  client_key?:             string;
+  ca_file?:                string;
+  accept_invalid_certs?:   boolean;
+  trust_on_first_use?:     boolean;
+  pinned_fingerprint?:     string;
+  server_name?:            string;  // '' disables SNI
+  alpn?:                   string[];
+  server_cert?:            string;  // server mode: PEM chain
+  server_key?:             string;  // server mode: PKCS#8 PEM key
+}
+
+export interface TlsFingerprintEvent {
+  session_id:  string;
+  fingerprint: string;
+  expected:    string | null;
+}
+
+export interface SshOptions {
+  host:                 string;
+  port?:                number;  // default 22
+  username:             string;
+  password?:            string;
+  key_path?:            string;  // tried before the password
+  key_passphrase?:      string;
+  term?:                string;  // default 'xterm'
+  cols?:                number;
+  rows?:                number;
+  pinned_fingerprint?:  string;  // 'SHA256:...'; else the TOFU pin
+  accept_new_host_key?: boolean;
+}
+
+export interface Service {
+  name:         string;                  // instance, e.g. 'Living Room'
+  service_type: string;                  // e.g. '_arduino._tcp.local'
+  host:         string;                  // e.g. 'esp32-1a2b.local'
+  port:         number;
+  addresses:    string[];
+  txt:          Record<string, string>;
+}
+
+export interface AgentOptions {
+  url:               string;   // e.g. 'ws://bench-pi.local:7400'
+  token?:            string;   // needs the read scope, and tx to send
+  clock_interval_s?: number;   // default 30
+}
+
+export interface AgentClockEvent {
+  agent:     string;   // 'host:port'
+  offset_ms: number;   // agent clock minus ours
+  rtt_ms:    number;
+}
+
+export interface MqttOptions {
+  host:           string;
+  port?:          number;          // default 1883
+  client_id?:     string;          // empty: assigned by the broker
+  username?:      string;
+  password?:      string;
+  keep_alive_s?:  number;          // default 60; 0 disables pings
+  clean_session?: boolean;         // default true
+  tls?:           TlsOptions;
+  publish_topic?: string;          // target of plain sends
+  qos?:           0 | 1;           // of plain sends
+}
+
+export interface TofuPin {
+  endpoint:    string;
+  fingerprint: string;
+}
+
+export interface ConnectErrorEvent {
+  session_id: string;
+  category:   'network' | 'certificate' | 'handshake' | 'config';
+  message:    string;
+  peer_fingerprint?: string;
+  repeated?:  number;
+}
+
+export type OpenProgress =
+  | { stage: 'connecting'; timeout_ms: number }
+  | { stage: 'listening'; addr: string }
+  | { stage: 'accepted'; peer: string }
+  | { stage: 'handshaking' }
+  | { stage: 'handshaken' };
+
+export type ConnectProgressEvent = OpenProgress & {
+  session_id: string;
+  repeated?:  number;
+};
+
+export interface SysEvent {
+  session_id:   string;
+  timestamp_ms: number;
+  category:     'connect' | 'connection' | 'reconnect' | 'flow' | 'alert' | 'segment' | 'quota' | 'identity' | 'telnet' | 'com_port' | 'mqtt' | 'agent' | 'bridge' | 'log';
+  code:         string;
+  detail:       Record<string, unknown>;
+  repeated?:    number;
+}
+
+export interface SocketStatusEvent {
+  session_id: string;
+  options: {
+    nodelay:          boolean;
+    keepalive:        boolean;
+    linger_ms:        number | null;
+    send_buffer_size: number;
+    recv_buffer_size: number;
+  };
+  negotiated_alpn: string | null;
+  ws_protocol:     string | null;
+}
+
+export interface UdpPeerEvent {
+  session_id: string;
+  peer:       string;
+}
+
+export interface SmsPdu {
+  pdu:      string;
+  tpdu_len: number;
+}
+
+export interface SmsMessage {
+  kind:      'deliver' | 'submit';
+  smsc:      string | null;
+  address:   string;
+  timestamp: string | null;
+  encoding:  'gsm7' | 'ucs2' | '8bit';
+  text:      string;
+  concat:    { reference: number; part: number; total: number } | null;
+}
+
+export interface SmsEntry {
+  index:   number | null;
+  status:  number;  // 0 unread, 1 read, 2 unsent, 3 sent
+  message: SmsMessage;
+}
+
+export interface SmsEvent {
+  session_id: string;
+  message:    SmsMessage;
+}
+
+export interface NtripOptions {
+  host:       string;
+  port:       number;
+  mountpoint: string;
+  username?:  string;
+  password?:  string;
+}
+
+export interface Mountpoint {
+  name:           string;
+  identifier:     string;
+  format:         string;
+  format_details: string;
+  nav_system:     string;
+  network:        string;
+  country:        string;
+  latitude:       number | null;
+  longitude:      number | null;
+  nmea:           boolean;
+  authentication: string;
+  bitrate:        number | null;
+}
+
+export type MirrorTarget =
+  | { kind: 'file';   path:  string }
+  | { kind: 'udp';    addr:  string }
+  | { kind: 'window'; label: string }
+  | { kind: 'command'; program: string; args?: string[]; annotate?: boolean };
+
+export type MirrorDirection = 'rx' | 'tx' | 'both';
+
+export interface MirrorInfo {
+  id:         number;
+  session_id: string;
+  target:     MirrorTarget;
+  direction:  MirrorDirection;
+  bytes:      number;
+  active:     boolean;
+  error:      string | null;
+}
+
+export interface MirrorDataEvent {
+  mirror_id:  number;
+  session_id: string;
+  bytes:      number[];
+}
+
+export interface MirrorAnnotationEvent {
+  mirror_id:    number;
+  session_id:   string;
+  timestamp_ms: number;
+  text:         string;
+}
+
+export interface CompareOptions {
+  a:            string;
+  b:            string;
+  max_skew_ms?: number;
+  context?:     number;
+}
+
+export interface CompareDivergence {
+  kind:       'mismatch' | 'missing_a' | 'missing_b';
+  index:      number;
+  a:          Packet | null;
+  b:          Packet | null;
+  skew_ms:    number | null;
+  first_diff: number | null;
+  context:    Packet[];
+}
+
+export interface CompareStatus {
+  a:           string;
+  b:           string;
+  matched:     number;
+  mismatched:  number;
+  missing_a:   number;
+  missing_b:   number;
+  avg_skew_ms: number;
+  max_skew_ms: number;
+}
+
+export interface PairingConfig {
+  enabled:       boolean;
+  timeout_ms:    number;
+  max_responses: number;  // 0 = until timeout
+}
+
+export interface Transaction {
+  id:            number;
+  session_id:    string;
+  tx_packet_id:  number;
+  rx_packet_ids: number[];
+  started_ms:    number;
+  latency_ms:    number | null;
+  duration_ms:   number;
+}
+
+export interface TransactionStats {
+  session_id:     string;
+  total:          number;
+  answered:       number;
+  unanswered:     number;
+  min_latency_ms: number;
+  avg_latency_ms: number;
+  max_latency_ms: number;
+}
+
+export type Protocol = 'modbus_rtu' | 'nmea' | 'mavlink' | 'one_wire' | 'dali';
+
+export interface MessageStats {
+  message:      string;
+  count:        number;
+  errors:       number;
+  bytes:        number;
+  last_seen_ms: number;
+}
+
+export interface ProtocolStats {
+  session_id: string;
+  protocol:   Protocol;
+  total:      number;
+  errors:     number;
+  messages:   MessageStats[];
+}
+
+// Fields of one packet on a 1-Wire or DALI session
+export interface DecodedEvent {
+  session_id: string;
+  packet_id:  number;
+  protocol:   Protocol;
+  message:    string;  // e.g. 'Skip ROM, Convert T' or 'QUERY STATUS'
+  ok:         boolean;
+  fields:     Record<string, string | number | boolean>;
+}
+
+export interface HistogramSummary {
+  session_id: string;
+  count:      number;
+  min_ms:     number;
+  max_ms:     number;
+  mean_ms:    number;
+  p50_ms:     number;
+  p90_ms:     number;
+  p99_ms:     number;
+  p999_ms:    number;
+  buckets:    { low_ms: number; high_ms: number; count: number }[];
+}
+
+export type TimelineFormat = 'text' | 'csv' | 'jsonl' | 'hexdump';
+export type TimeBase = 'host' | 'corrected' | 'device';
+
+export type FlowControl = 'none' | 'hardware' | 'software';
+
+export interface WaitForPort {
+  timeout_ms:  number;
+  interval_ms: number;
+  vid:         number | null;
+  pid:         number | null;
+}
+
+export interface PortWaitEvent {
+  port:       string;
+  attempt:    number;
+  elapsed_ms: number;
+  status:     'waiting' | 'opened' | 'timeout';
+  error:      string | null;
+  repeated?:  number;
+}
+
+export interface LineErrors {
+  supported:       boolean;
+  framing:         number;
+  parity:          number;
+  breaks:          number;
+  overruns:        number;
+  buffer_overruns: number;
+}
+
+export interface LineErrorsEvent extends LineErrors {
+  session_id: string;
+}
+
+export type ThreadPriority = 'normal' | 'high' | 'realtime';
+
+export interface WorkerOptions {
+  priority: ThreadPriority;
+  core:     number | null;
+}
+
+export interface SerialSettings {
+  flow:             FlowControl;
+  loopback:         boolean;
+  read_timeout_ms:  number;  // 1–1000
+  poll_interval_ms: number;  // 1–100
+  worker?:          WorkerOptions;
+}
+
+export interface ChatStep {
+  expect:     string;
+  send:       string;              // {{name}} inserts a session variable
+  timeout_ms: number | null;
+  capture?:   VarCapture | null;   // set from what arrived up to expect
+}
+
+export interface ChatScript {
+  steps:      ChatStep[];
+  abort:      string[];
+  timeout_ms: number;
+}
+
+export interface ChatResult {
+  ok:         boolean;
+  step:       number;
+  reason:     string | null;
+  connect:    string | null;
+  transcript: string;
+  captured:   Record<string, string>;
+}
+
+export interface ChatResultEvent extends ChatResult {
+  session_id: string;
+}
+
+export interface VarCapture {
+  name:              string;
+  pattern:           string;
+  group?:            number | null;  // default: group 1 if any, else the match
+  case_insensitive?: boolean;
+}
+
+export interface SessionVarEvent {
+  session_id: string;
+  name:       string;
+  value:      string | null;  // null: removed
+  source:     'rx' | 'script' | 'command';
+}
+
+export interface RoundtripSummary {
+  session_id:   string;
+  payload_size: number;
+  iterations:   number;
+  completed:    number;
+  lost:         number;
+  min_ms:       number;
+  avg_ms:       number;
+  p50_ms:       number;
+  p99_ms:       number;
+  max_ms:       number;
+}
+
+export interface FixtureEvent {
+  offset_ms:   number;
+  hex:         string;
+  checksum_ok: boolean | null;
+  severity:    string | null;
+  tags:        string[];
+  message:     string | null;
+}
+
+export interface FixtureMismatch {
+  index:    number;
+  expected: FixtureEvent | null;
+  actual:   FixtureEvent | null;
+}
+
+export interface FixtureReport {
+  passed:        boolean;
+  events:        number;
+  updated:       boolean;
+  expected_path: string;
+  mismatches:    FixtureMismatch[];
+}
+
+export type FuzzMutation = 'bit_flip' | 'truncate' | 'random_length' | 'bad_checksum';
+
+export interface FuzzOptions {
+  seed:        number[];
+  mutations:   FuzzMutation[];
+  interval_ms: number;
+  count:       number | null;
+  rng_seed:    number | null;
+}
+
+export interface FuzzCase {
+  session_id: string;
+  case:       number;
+  mutation:   FuzzMutation;
+  sent:       number[];
+  response:   number[];
+}
+
+export interface FuzzSummary {
+  session_id: string;
+  rng_seed:   number;
+  cases:      number;
+  responded:  number;
+  error:      string | null;
+}
+
+export type StreamClass = 'empty' | 'text' | 'compressed' | 'encrypted' | 'high_entropy' | 'binary';
+
+export interface ByteDistribution {
+  session_id:      string;
+  bytes:           number;
+  histogram:       number[];
+  distinct:        number;
+  entropy:         number;
+  printable_ratio: number;
+  zero_ratio:      number;
+  high_ratio:      number;
+  chi_square:      number;
+  encoding:        string | null;
+  compression:     string | null;
+  class:           StreamClass;
+}
+
+export type ProtocolGuess = 'nmea' | 'modbus_rtu' | 'mavlink' | 'ubx' | 'json_lines' | 'ascii';
+
+export interface ProtocolCandidate {
+  guess:      ProtocolGuess;
+  confidence: number;
+  frames:     number;
+  protocol:   Protocol | null;
+  framing:    string;
+}
+
+export interface ProtocolDetection {
+  session_id: string;
+  bytes:      number;
+  candidates: ProtocolCandidate[];
+  enabled:    Protocol | null;
+}
+
+export type PayloadFormat = 'text' | 'hex' | 'base64';
+
+export interface SavedPayload {
+  name:   string;
+  data:   string;
+  format: PayloadFormat;
+  escape?: boolean;  // resolve C-style escapes such as \x1B in text
+  append: TxAppend | null;
+  tags:   string[];
+}
+
+export interface FramingPreset {
+  name:        string;
+  description: string;
+  config:      SplitterConfig;
+  builtin:     boolean;
+}
+
+export interface NotifyConfig {
+  enabled:         boolean;
+  disconnects:     boolean;
+  severities:      string[];
+  min_interval_ms: number;
+}
+
+export interface SessionClosedEvent {
+  session_id: string;
+}
+
+export interface SegmentConfig {
+  packets?: number | null;
+  bytes?:   number | null;
+}
+
+export interface Segment {
+  session_id:      string;
+  index:           number;
+  first_packet_id: number;
+  last_packet_id:  number;
+  start_ms:        number;
+  end_ms:          number;
+  packets:         number;
+  rx_bytes:        number;
+  tx_bytes:        number;
+  bad_checksums:   number;
+  severities:      Record<string, number>;
+  complete:        boolean;
+}
+
+export type AlertCondition =
+  | { kind: 'idle';       seconds: number }
+  | { kind: 'rate_above'; bytes_per_sec: number; window_s: number }
+  | { kind: 'rate_below'; bytes_per_sec: number; window_s: number }
+  | { kind: 'errors' };
+
+export interface AlertRule {
+  name:        string;
+  session_id?: string | null;  // all connected sessions when unset
+  condition:   AlertCondition;
+  notify?:     boolean;
+}
+
+export interface AlertEvent {
+  rule:         string;
+  session_id:   string;
+  active:       boolean;  // false when the condition stopped holding
+  value:        number;
+  message:      string;
+  timestamp_ms: number;
+}
+
+export type RxStage =
+  | { kind: 'strip_ansi' }
+  | { kind: 'cobs_decode' }
+  | { kind: 'verify_crc'; algorithm: string; size: number; little_endian?: boolean; strip?: boolean; drop_bad?: boolean }
+  | { kind: 'protobuf' }
+  | { kind: 'replace';    pattern: string; replacement: string };
+
+export interface DeviceRule {
+  states?:   string[];  // applies in every state when empty
+  pattern:   string;    // regex matched against the whole line
+  reply?:    string;    // chat escapes, $1 for groups
+  next?:     string | null;
+  delay_ms?: number;
+}
+
+export interface DeviceScript {
+  initial_state: string;
+  greeting:      string;
+  rules:         DeviceRule[];
+  unknown:       string;
+  eol:           string;
+  echo:          boolean;
+}
+
+export type DeviceTransport =
+  | { kind: 'tcp'; bind?: string; port?: number }
+  | { kind: 'pty' };
+
+export interface DeviceInfo {
+  id:        string;
+  endpoint:  string;  // address, or pty path
+  transport: DeviceTransport;
+}
+
+export interface DeviceEvent {
+  device_id: string;
+  client:    string;
+  kind:      'connected' | 'rx' | 'tx' | 'disconnected';
+  bytes:     number[];
+  state:     string;
+}
+
+export interface ImportSummary {
+  sessions: SessionInfo[];
+  packets:  number;
+  skipped:  number;
+}
+
+export interface CaptureSettings {
+  splitter: SplitterConfig;
+  rules:    ClassRule[];
+  protocol: Protocol | null;
+  stages:   RxStage[];
+}
+
+export interface RawCapture {
+  started_ms: number;
+  chunks:     { offset_ms: number; hex: string }[];
+}
+
+export interface SessionImport {
+  session:     SessionInfo;
+  packets:     number;
+  events:      JournalEntry[];  // as emitted on the exporting machine
+  settings:    CaptureSettings;
+  raw:         RawCapture | null;
+  app_version: string;
+}
+
+export interface ManifestEntry {
+  file:       string;
+  session_id: string;
+  sha256:     string;
+  bytes:      number;
+  opened_ms:  number;
+  closed_ms:  number;
+  prev:       string;  // hash of the previous manifest line
+}
+
+export interface ManifestCheck {
+  entry:    ManifestEntry;
+  status:   'ok' | 'modified' | 'missing';
+  chain_ok: boolean;
+}
+
+export interface ManifestReport {
+  ok:      boolean;
+  entries: ManifestCheck[];
+}
+
+export type BenchConnection =
+  | { kind: 'serial'; port: string; baud: number; flow?: FlowControl; name?: string; worker?: Partial<WorkerOptions> }
+  | ({ kind: 'tcp' } & Partial<SocketOpenArgs> & { host: string; port: number })
+  | { kind: 'udp'; host?: string; port?: number; local_port?: number; reply_to_sender?: boolean; options?: Partial<UdpOptions> };
+
+export interface BenchProfile {
+  name:        string;
+  connections: BenchConnection[];
+}
+
+export interface BenchConnectionResult {
+  session_id: string;
+  status:     'opened' | 'failed' | 'rolled_back' | 'skipped';
+  session:    SessionInfo | null;
+  error:      string | null;
+}
+
+export interface BenchOpenResult {
+  profile:     string;
+  ok:          boolean;
+  connections: BenchConnectionResult[];
+}
+
+export type SweepSpace =
+  | { kind: 'baud'; port: string; bauds?: number[]; flow?: FlowControl }
+  | { kind: 'connections'; connections: BenchConnection[] };
+
+export interface SweepPlan {
+  space:          SweepSpace;
+  script?:        Partial<ChatScript>;
+  stop_at_first?: boolean;
+  settle_ms?:     number;
+}
+
+export interface SweepAttempt {
+  index:      number;
+  label:      string;  // e.g. "/dev/ttyUSB0 @ 115200"
+  connection: BenchConnection;
+  ok:         boolean;
+  error:      string | null;  // the connection could not be opened
+  chat:       ChatResult | null;
+  elapsed_ms: number;
+}
+
+export interface SweepReport {
+  attempts: SweepAttempt[];
+  working:  string[];  // labels of the settings that succeeded
+  skipped:  number;
+}
+
+export interface TxStatusEvent {
+  session_id: string;
+  packet_id:  number;
+  ok:         boolean;
+  error:      string | null;
+  elapsed_ms: number;
+}
+
+export interface SessionSummary {
+  session_id:    string;
+  name:          string;
+  kind:          string;
+  label:         string | null;
+  profile:       string | null;
+  identity:      string | null;
+  log_path:      string | null;
+  reason:        'disconnect' | 'remote';
+  opened_ms:     number | null;
+  closed_ms:     number;
+  duration_ms:   number | null;
+  tx_bytes:      number;
+  rx_bytes:      number;
+  tx_packets:    number;
+  rx_packets:    number;
+  bad_checksums: number;
+  line_errors:   LineErrors | null;
+  alerts:        number;
+  class_hits:    Record<string, number>;
+}
+
+export interface Capabilities {
+  version:            string;
+  platform:           string;
+  transports:         string[];
+  decoders:           Protocol[];
+  checksums:          string[];
+  rx_stages:          RxStage['kind'][];
+  import_formats:     string[];
+  max_connections:    number | null;
+  tls:                boolean;
+  serial_line_errors: boolean;
+  socketcan:          boolean;
+  ble:                boolean;
+}
+
+export interface HistoryEntry extends SessionSummary {
+  id:   number;
+  tags: string[];
+}
+
+export interface HistoryQuery {
+  text?:    string;
+  tags?:    string[];
+  kind?:    string;
+  profile?: string;
+  from_ms?: number;
+  to_ms?:   number;
+}
+
+export type QuotaAction = 'stop_logging' | 'disconnect';
+
+export interface Quota {
+  max_bytes?:      number | null;
+  max_duration_s?: number | null;
+  action?:         QuotaAction;
+}
+
+export interface QuotaEvent {
+  session_id: string;
+  limit:      'bytes' | 'duration';
+  action:     QuotaAction;
+  bytes:      number;
+  elapsed_s:  number;
+}
+
+export interface IdentifyProbe {
+  data:        string;
+  format?:     PayloadFormat;
+  escape?:     boolean;  // resolve C-style escapes such as \x1B in text
+  append?:     TxAppend | null;
+  until?:      string | null;
+  timeout_ms?: number;
+}
+
+export interface SessionIdentityEvent {
+  session_id: string;
+  identity:   string | null;
+  error:      string | null;
+}
+
+export interface ReconnectPolicy {
+  max_attempts:    number;  // 0 keeps trying
+  initial_ms:      number;
+  max_interval_ms: number;
+  multiplier:      number;
+}
+
+export interface Finding {
+  check:   'serial_ports' | 'serial_permissions' | 'port_grabber' | 'log_dir' | 'firewall';
+  status:  'ok' | 'info' | 'warning' | 'error';
+  message: string;
+  hint:    string | null;
+}
+
+export type Sequenced<T> = T & { seq: number };  // every event payload
+
+export interface JournalEntry {
+  seq:     number;
+  event:   string;
+  payload: Sequenced<Record<string, unknown>>;
+}
+
+export interface Resync {
+  events:   JournalEntry[];
+  seq:      number;
+  complete: boolean;  // false: events were dropped, reload instead
+}
+
+export interface CaptureFilterInfo {
+  session_id: string;
+  expr:       string;
+  passed:     number;
+  dropped:    number;
+}
+
+export interface SeqRule {
+  offset:     number;
+  width:      number;
+  big_endian: boolean;
+}
+
+export interface MulticastOptions {
+  group:      string;
+  interface?: string | null;
+  ttl?:       number | null;
+}
+
+export interface UdpOptions {
+  recv_buffer: number;
+  sequence:    SeqRule | null;
+  bind_host?:  string | null;
+  multicast?:  MulticastOptions | null;
+  broadcast?:  boolean;
+  address_family?: AddressFamily;
+}
+
+export interface DatagramStats {
+  session_id:   string;
+  datagrams:    number;
+  bytes:        number;
+  min_size:     number;
+  max_size:     number;
+  avg_size:     number;
+  truncated:    number;
+  out_of_order: number;
+  duplicates:   number;
+  missing:      number;
+  senders:      Record<string, number>;
+}
+
+export interface ServerOptions {
+  bind:            string;
+  port:            number;
+  max_clients:     number;
+  allowlist:       string[];
+  token:           string | null;
+  auth_timeout_ms: number;
+}
+
+export interface ServerInfo {
+  id:         string;
+  local_addr: string;
+  clients:    number;
+  options:    ServerOptions;
+}
+
+export interface BridgeInfo {
+  session_id: string;
+  local_addr: string;
+  clients:    number;
+  options:    ServerOptions;
+}
+
+export interface ServerClientEvent {
+  server_id: string;
+  peer:      string;
+  status:    'connected' | 'rejected' | 'disconnected';
+  reason:    string | null;
+  session:   SessionInfo | null;
+  repeated?: number;
+}
+
+export type ApiScope = 'read' | 'tx' | 'open';
+
+export interface ApiToken {
+  name:   string;
+  secret: string;
+  scopes: ApiScope[];
+}
+
+export interface RemoteOptions {
+  bind:   string;  // loopback by default; other addresses need a token, sent in plaintext
+  port:   number;
+  tokens: ApiToken[];
+}
+
+export interface RemoteInfo {
+  local_addr: string;
+  clients:    number;
+  tokens:     { name: string; scopes: ApiScope[] }[];
+}
+
+export interface AuditEntry {
+  timestamp_ms: number;
+  peer:         string;
+  token:        string | null;
+  op:           string;  // 'connect' for a refused connection
+  session_id:   string | null;
+  allowed:      boolean;
+  error:        string | null;
+}
+
+export type MismatchAction = 'hint' | 'auto_baud';
+
+export interface BaudWatchConfig {
+  enabled:   boolean;
+  window_ms: number;
+  min_bytes: number;
+  threshold: number;
+  windows:   number;
+  action:    MismatchAction;
+}
+
+export interface BaudMismatchEvent {
+  session_id:     string;
+  baud:           number;
+  bad_ratio:      number;
+  suggested_baud: number;
+  repeated?:      number;
+}
+
+export interface BaudChangedEvent {
+  session_id: string;
+  from:       number;
+  to:         number;
+}
+
+export interface FlowControlEvent {
+  session_id: string;
+  signal:     'cts' | 'xoff';
+  paused:     boolean;
+  paused_ms:  number | null;
+}
+
+export type ModemLine = 'dcd' | 'cts' | 'dsr' | 'ri';
+
+export interface PpsOptions {
+  line:           ModemLine;
+  falling:        boolean;  // pulse on the inactive edge
+  poll_us:        number;   // sampling interval, i.e. pulse precision
+  max_latency_ms: number;   // pulse to time sentence
+}
+
+export interface TimeMark {
+  session_id:  string;
+  pulse_ms:    number;
+  interval_ms: number | null;  // since the previous pulse
+  source:      string;         // e.g. 'GPRMC', 'UBX-NAV-PVT'
+  gnss_time:   string;         // hh:mm:ss.sss UTC
+  latency_ms:  number;         // pulse to sentence arrival
+  offset_ms:   number;         // host clock minus GNSS time
+}
+
+export interface ClockInfo {
+  ntp_server:    string | null;
+  ntp_offset_ms: number | null;
+  round_trip_ms: number | null;
+  synced_at_ms:  number | null;
+}
+
+export type TimeSource = 'local' | 'utc' | 'monotonic';
+
+export interface TimeConfig {
+  source:             TimeSource;
+  local_offset_s:     number;
+  local_offset_known: boolean;
+}
+
+export interface RackBoard {
+  name:           string;
+  port?:          string;
+  vid?:           number;
+  pid?:           number;
+  serial_number?: string;
+  baud:           number;
+}
+
+export type LogMode =
+  | { mode: 'buffered' }                          // flushed per line
+  | { mode: 'wal'; segment_bytes: number };       // fsynced, sequence-numbered segments
+
+export interface RackBoardStatus {
+  name:       string;
+  session_id: string | null;
+  connected:  boolean;
+  error:      string | null;
+  log_path:   string | null;
+  tx_bytes:   number;
+  rx_bytes:   number;
+}
+
 export interface SavedFilter {
   id:    string;
   label: string;